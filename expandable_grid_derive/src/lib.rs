@@ -0,0 +1,175 @@
+//! Derive macros for `expandable_grid`, re-exported by it with the `derive` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, spanned::Spanned, Data, DeriveInput, Expr, Fields, Member, Path, Type,
+};
+
+/// Implements `Subchunk`, `Index<Vector2<usize>>` and `IndexMut<Vector2<usize>>` for a struct
+/// wrapping the cells of a 2d subchunk.
+///
+/// The cells are the struct's only field, or the field marked `#[subchunk]`. This is either a 2d
+/// array of rows, `[[T; W]; H]`, where the cell at `[x, y]` is `cells[y][x]`, or a flat buffer,
+/// such as an array, `Vec` or boxed slice, with the size of the subchunk given by
+/// `#[subchunk(width = W, height = H)]` on the struct. Flat buffers are stored with `x` varying
+/// fastest, or `y` varying fastest with `#[subchunk(width = W, height = H, column_major)]`.
+///
+/// If `expandable_grid` is renamed or re-exported, its path can be given with
+/// `#[subchunk(crate = path::to::expandable_grid)]`.
+#[proc_macro_derive(Subchunk, attributes(subchunk))]
+pub fn derive_subchunk(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_subchunk(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_subchunk(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let (mut width, mut height, mut column_major) = (None, None, false);
+    let mut krate: Path = syn::parse_quote!(::expandable_grid);
+    for attribute in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("subchunk"))
+    {
+        attribute.parse_nested_meta(|meta| {
+            if meta.path.is_ident("width") {
+                width = Some(meta.value()?.parse::<Expr>()?);
+            } else if meta.path.is_ident("height") {
+                height = Some(meta.value()?.parse::<Expr>()?);
+            } else if meta.path.is_ident("column_major") {
+                column_major = true;
+            } else if meta.path.is_ident("crate") {
+                krate = meta.value()?.parse()?;
+            } else {
+                return Err(meta.error("expected `width`, `height`, `column_major` or `crate`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "`Subchunk` can only be derived for structs",
+        ));
+    };
+    let (member, field) = cells_field(&data.fields, input.ident.span())?;
+    let field_type = &field.ty;
+
+    let (width, height, output, check, cell) = match (width, height) {
+        (Some(width), Some(height)) => {
+            let linear_index = if column_major {
+                quote!(index.x * (#height) + index.y)
+            } else {
+                quote!(index.y * (#width) + index.x)
+            };
+            let check = quote! {
+                assert!(
+                    index.x < (#width) && index.y < (#height),
+                    "index should be within the subchunk",
+                );
+            };
+            (
+                quote!(#width),
+                quote!(#height),
+                quote!(<#field_type as ::core::ops::Index<usize>>::Output),
+                check,
+                quote!(self.#member[#linear_index]),
+            )
+        }
+        (None, None) => {
+            let (width, height, element) = nested_array(field_type).ok_or_else(|| {
+                syn::Error::new(
+                    field_type.span(),
+                    "expected a 2d array `[[T; W]; H]`, or `#[subchunk(width = .., height = ..)]` \
+                     on the struct for a flat buffer",
+                )
+            })?;
+            (
+                quote!(#width),
+                quote!(#height),
+                quote!(#element),
+                TokenStream2::new(),
+                quote!(self.#member[index.y][index.x]),
+            )
+        }
+        _ => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "both `width` and `height` should be given for a flat buffer",
+            ))
+        }
+    };
+
+    let name = &input.ident;
+    let vector = quote!(#krate::subchunk::__derive::Vector2<usize>);
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #krate::subchunk::Subchunk for #name #type_generics #where_clause {
+            const SUBCHUNK_SIZE: #vector = #krate::subchunk::__derive::Vector2::new(#width, #height);
+        }
+
+        impl #impl_generics ::core::ops::Index<#vector> for #name #type_generics #where_clause {
+            type Output = #output;
+
+            fn index(&self, index: #vector) -> &Self::Output {
+                #check
+                &#cell
+            }
+        }
+
+        impl #impl_generics ::core::ops::IndexMut<#vector> for #name #type_generics #where_clause {
+            fn index_mut(&mut self, index: #vector) -> &mut Self::Output {
+                #check
+                &mut #cell
+            }
+        }
+    })
+}
+
+/// Returns the field holding the cells: the only field, or the field marked `#[subchunk]`.
+fn cells_field(fields: &Fields, span: proc_macro2::Span) -> syn::Result<(Member, &syn::Field)> {
+    let members = fields.iter().enumerate().map(|(index, field)| {
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(index.into()),
+        };
+        (member, field)
+    });
+
+    let mut marked = members.clone().filter(|(_, field)| {
+        field
+            .attrs
+            .iter()
+            .any(|attr| attr.path().is_ident("subchunk"))
+    });
+    if let Some(marked) = marked.next() {
+        return Ok(marked);
+    }
+
+    let mut members = members;
+    match (members.next(), members.next()) {
+        (Some(only), None) => Ok(only),
+        _ => Err(syn::Error::new(
+            span,
+            "expected a struct with one field, or a field marked `#[subchunk]`",
+        )),
+    }
+}
+
+/// Returns the width, height and element type of a 2d array type `[[T; W]; H]`.
+fn nested_array(field_type: &Type) -> Option<(&Expr, &Expr, &Type)> {
+    let Type::Array(rows) = field_type else {
+        return None;
+    };
+    let Type::Array(row) = &*rows.elem else {
+        return None;
+    };
+
+    Some((&row.len, &rows.len, &row.elem))
+}
@@ -0,0 +1,52 @@
+/* C interface to expandable_grid, built with the `ffi` feature. See `src/ffi.rs`. */
+
+#ifndef EXPANDABLE_GRID_H
+#define EXPANDABLE_GRID_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct EgGrid EgGrid;
+
+#define EG_LAYOUT_ROW_MAJOR 0
+#define EG_LAYOUT_COLUMN_MAJOR 1
+
+typedef void (*EgCellCallback)(ptrdiff_t x, ptrdiff_t y, int64_t value, void *user_data);
+
+EgGrid *eg_grid_new(void);
+/* These return NULL if the grid would be too large, meaning its cells would not fit within
+ * PTRDIFF_MAX bytes or its corners would overflow ptrdiff_t. Those taking a layout also return
+ * NULL if it is neither EG_LAYOUT_ROW_MAJOR nor EG_LAYOUT_COLUMN_MAJOR. */
+EgGrid *eg_grid_with_size(size_t width, size_t height, ptrdiff_t x, ptrdiff_t y, int64_t fill);
+EgGrid *eg_grid_with_layout(uint32_t layout);
+EgGrid *eg_grid_with_size_and_layout(size_t width, size_t height, ptrdiff_t x, ptrdiff_t y,
+                                     int64_t fill, uint32_t layout);
+void eg_grid_free(EgGrid *grid);
+
+void eg_grid_size(const EgGrid *grid, size_t *width, size_t *height);
+void eg_grid_origin(const EgGrid *grid, ptrdiff_t *x, ptrdiff_t *y);
+
+bool eg_grid_get(const EgGrid *grid, ptrdiff_t x, ptrdiff_t y, int64_t *value);
+bool eg_grid_set(EgGrid *grid, ptrdiff_t x, ptrdiff_t y, int64_t value);
+
+/* These return false, leaving the grid unchanged, if it would become too large. */
+bool eg_grid_expand_to_fit_point(EgGrid *grid, ptrdiff_t x, ptrdiff_t y, int64_t fill);
+bool eg_grid_expand_to_fit_box(EgGrid *grid, ptrdiff_t x, ptrdiff_t y, size_t width,
+                               size_t height, int64_t fill);
+bool eg_grid_change_size(EgGrid *grid, size_t width, size_t height, ptrdiff_t offset_x,
+                         ptrdiff_t offset_y, int64_t fill);
+
+uint32_t eg_grid_layout(const EgGrid *grid);
+int64_t *eg_grid_data(EgGrid *grid, size_t *length);
+void eg_grid_for_each(const EgGrid *grid, EgCellCallback callback, void *user_data);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* EXPANDABLE_GRID_H */
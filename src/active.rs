@@ -0,0 +1,123 @@
+//! Cellular automata which only visit the cells that may change.
+//!
+//! An `ActiveGrid` wraps a grid along with a set of active cells: the cells which changed in the
+//! last step, and their neighbors. Each `step` only calls the update rule on the active cells, so
+//! simulations where most of the grid is static, such as falling sand or flowing water, cost
+//! time proportional to the cells in motion rather than to the size of the grid.
+//!
+//! Updates are applied after the rule has been called on every active cell, so the rule always
+//! reads the state of the grid from before the step, and the order the cells are visited in
+//! does not matter. The neighbors of a cell are the cells within one cell of it on every axis
+//! (the Moore neighborhood), so rules should only read cells within this distance.
+
+use crate::{coord::GridVector, util, ExpandableGridN};
+use nalgebra::SVector;
+use std::collections::HashSet;
+
+/// A grid which tracks which of its cells may change in the next step of an automaton. See the
+/// `active` module for details.
+#[derive(Clone, Debug, Default)]
+pub struct ActiveGrid<T, const D: usize = 2> {
+    grid: ExpandableGridN<T, D>,
+    active: HashSet<SVector<isize, D>>,
+}
+
+impl<T: Clone + PartialEq, const D: usize> ActiveGrid<T, D> {
+    /// Creates an active grid with every cell of `grid` active, so that the first step visits
+    /// the whole grid.
+    pub fn new(grid: ExpandableGridN<T, D>) -> Self {
+        let active = util::iter_box(SVector::zeros(), grid.size)
+            .map(|offset| grid.origin + util::usize_vec_to_isize(offset))
+            .collect();
+
+        Self { grid, active }
+    }
+
+    pub fn grid(&self) -> &ExpandableGridN<T, D> {
+        &self.grid
+    }
+
+    /// Returns the wrapped grid. Cells changed through it are not activated, so `activate`
+    /// should be called for each of them.
+    pub fn grid_mut(&mut self) -> &mut ExpandableGridN<T, D> {
+        &mut self.grid
+    }
+
+    pub fn into_inner(self) -> ExpandableGridN<T, D> {
+        self.grid
+    }
+
+    /// Returns the cells which will be visited in the next step.
+    pub fn active(&self) -> &HashSet<SVector<isize, D>> {
+        &self.active
+    }
+
+    /// Activates the cell at `index` and its neighbors, so that they are visited in the next
+    /// step.
+    pub fn activate(&mut self, index: impl GridVector<isize, D>) {
+        let index = index.to_vector();
+
+        for offset in util::iter_box(SVector::zeros(), SVector::<usize, D>::repeat(3)) {
+            self.active
+                .insert(index + util::usize_vec_to_isize(offset).add_scalar(-1));
+        }
+    }
+
+    /// Sets the cell at `index` to `value`, activating it and its neighbors if it changed.
+    /// Returns `false` if the cell is out of bounds.
+    pub fn set(&mut self, index: impl GridVector<isize, D>, value: T) -> bool {
+        let Some(cell) = self.grid.get_mut(index) else {
+            return false;
+        };
+
+        if *cell != value {
+            *cell = value;
+            self.activate(index);
+        }
+        true
+    }
+
+    /// Runs one step of the automaton, calling `rule` with the grid and the coordinates of every
+    /// active cell within its bounds. `rule` returns the new value of the cell, or `None` if it is
+    /// unchanged. Cells that change, and their neighbors, are active in the next step. Returns the
+    /// number of cells that changed.
+    pub fn step(
+        &mut self,
+        mut rule: impl FnMut(&ExpandableGridN<T, D>, SVector<isize, D>) -> Option<T>,
+    ) -> usize {
+        let active = std::mem::take(&mut self.active);
+
+        let changes: Vec<_> = active
+            .into_iter()
+            .filter_map(|index| {
+                let cell = self.grid.get(index)?;
+                let value = rule(&self.grid, index)?;
+                (value != *cell).then_some((index, value))
+            })
+            .collect();
+
+        for (index, value) in &changes {
+            self.grid[*index] = value.clone();
+            self.activate(*index);
+        }
+
+        changes.len()
+    }
+
+    /// Increases the size of the grid such that `point` is within its bounds. New cells are not
+    /// activated. See `ExpandableGridN::expand_to_fit_point`.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<isize, D>, fill: &T) {
+        self.grid.expand_to_fit_point(point, fill);
+    }
+
+    /// Increases the size of the grid such that every cell of a box is within its bounds. New
+    /// cells are not activated. See `ExpandableGridN::expand_to_fit_box`.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<isize, D>,
+        box_size: impl GridVector<usize, D>,
+        fill: &T,
+    ) {
+        self.grid.expand_to_fit_box(box_origin, box_size, fill);
+    }
+}
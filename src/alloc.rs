@@ -0,0 +1,370 @@
+//! Grids which allocate their cells through a custom allocator, such as an arena or a tracking
+//! allocator.
+//!
+//! The storage of an `ExpandableGridN` is chosen by its last type parameter, which implements
+//! `GridStorage`. It defaults to `Global`, which stores cells in a `Box<[T]>` from the global
+//! allocator. Any type implementing `GridAlloc`, a minimal allocator trait which works on stable
+//! Rust, can be used instead, in which case the cells are stored in an `AllocBuffer` allocated
+//! through it. `GridAlloc` is implemented for references to allocators, so many grids can share
+//! one arena:
+//!
+//! ```ignore
+//! let mut grid = ExpandableGrid::new_in(&arena);
+//! grid.expand_to_fit_point(vector![2, 3], &0);
+//! ```
+//!
+//! Grids can be copied between allocators with `clone_in`.
+
+use crate::{
+    coord::{self, Coordinate, GridVector},
+    ExpandableGridN, Layout,
+};
+use nalgebra::SVector;
+use std::{
+    alloc::Layout as AllocLayout,
+    fmt,
+    marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
+
+/// An allocator which the cells of an `ExpandableGridN` can be stored in.
+///
+/// # Safety
+/// Memory returned by `allocate` must be valid for reads and writes of `layout.size()` bytes,
+/// aligned to `layout.align()`, and not used by anything else until it is passed to
+/// `deallocate`.
+pub unsafe trait GridAlloc {
+    /// Allocates memory for `layout`, which never has a size of 0, or returns `None` if the
+    /// allocation failed.
+    fn allocate(&self, layout: AllocLayout) -> Option<NonNull<u8>>;
+
+    /// Frees memory returned by `allocate`.
+    ///
+    /// # Safety
+    /// `pointer` must have been returned by `allocate` on this allocator, or a copy of it, with
+    /// the same `layout`, and not already have been freed.
+    unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: AllocLayout);
+}
+
+/// The storage of an `ExpandableGridN` which keeps its cells in a `Box<[T]>` from the global
+/// allocator. This is the default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bevy", derive(bevy_reflect::TypePath))]
+pub struct Global;
+
+unsafe impl<A: GridAlloc + ?Sized> GridAlloc for &A {
+    fn allocate(&self, layout: AllocLayout) -> Option<NonNull<u8>> {
+        (**self).allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: AllocLayout) {
+        (**self).deallocate(pointer, layout);
+    }
+}
+
+/// A fixed length slice of cells allocated with a `GridAlloc`, which dereferences to `[T]`.
+pub struct AllocBuffer<T, A: GridAlloc> {
+    pointer: NonNull<T>,
+    length: usize,
+    allocator: A,
+    marker: PhantomData<T>,
+}
+
+// Safety: the buffer owns its cells, like a `Box<[T]>`
+unsafe impl<T: Send, A: GridAlloc + Send> Send for AllocBuffer<T, A> {}
+unsafe impl<T: Sync, A: GridAlloc + Sync> Sync for AllocBuffer<T, A> {}
+
+impl<T, A: GridAlloc> AllocBuffer<T, A> {
+    /// Allocates a buffer of `length` cells with `allocator`, where each cell is the result of
+    /// `f` on its index.
+    ///
+    /// # Panics
+    /// Panics if the size of the buffer overflows `isize`, or if the allocation fails.
+    pub fn from_fn(length: usize, allocator: A, f: impl FnMut(usize) -> T) -> Self {
+        Self {
+            pointer: allocate_cells(length, &allocator, f),
+            length,
+            allocator,
+            marker: PhantomData,
+        }
+    }
+
+    /// Allocates a buffer of `length` clones of `value` with `allocator`.
+    pub fn repeat(length: usize, value: &T, allocator: A) -> Self
+    where
+        T: Clone,
+    {
+        Self::from_fn(length, allocator, |_| value.clone())
+    }
+
+    pub fn allocator(&self) -> &A {
+        &self.allocator
+    }
+
+    /// Replaces the cells with `length` clones of `fill`, allocated with the same allocator, after
+    /// calling `copy` with the old and new cells.
+    fn replace(&mut self, length: usize, fill: &T, copy: impl FnOnce(&[T], &mut [T]))
+    where
+        T: Clone,
+    {
+        let pointer = allocate_cells(length, &self.allocator, |_| fill.clone());
+        // Safety: the new buffer holds `length` initialized cells, and is not used elsewhere. If
+        // `copy` panics, the new buffer is leaked
+        copy(self, unsafe {
+            std::slice::from_raw_parts_mut(pointer.as_ptr(), length)
+        });
+
+        let old_pointer = mem::replace(&mut self.pointer, pointer);
+        let old_length = mem::replace(&mut self.length, length);
+        // Safety: the old buffer is no longer referenced by `self`
+        unsafe { free_cells(old_pointer, old_length, &self.allocator) };
+    }
+}
+
+/// Allocates `length` cells with `allocator`, where each cell is the result of `f` on its index.
+fn allocate_cells<T, A: GridAlloc>(
+    length: usize,
+    allocator: &A,
+    mut f: impl FnMut(usize) -> T,
+) -> NonNull<T> {
+    let layout = AllocLayout::array::<T>(length).expect("buffer size should fit in isize");
+
+    let pointer: NonNull<T> = if layout.size() == 0 {
+        NonNull::dangling()
+    } else {
+        allocator
+            .allocate(layout)
+            .expect("allocating a grid buffer should not fail")
+            .cast()
+    };
+
+    // Frees the buffer and drops the cells written so far if `f` panics
+    struct Guard<'a, T, A: GridAlloc> {
+        pointer: NonNull<T>,
+        written: usize,
+        layout: AllocLayout,
+        allocator: &'a A,
+    }
+
+    impl<T, A: GridAlloc> Drop for Guard<'_, T, A> {
+        fn drop(&mut self) {
+            // Safety: the first `written` cells have been initialized
+            unsafe {
+                std::ptr::drop_in_place(std::ptr::slice_from_raw_parts_mut(
+                    self.pointer.as_ptr(),
+                    self.written,
+                ));
+                if self.layout.size() != 0 {
+                    self.allocator.deallocate(self.pointer.cast(), self.layout);
+                }
+            }
+        }
+    }
+
+    let mut guard = Guard {
+        pointer,
+        written: 0,
+        layout,
+        allocator,
+    };
+    while guard.written < length {
+        // Safety: the buffer has space for `length` cells
+        unsafe { pointer.as_ptr().add(guard.written).write(f(guard.written)) };
+        guard.written += 1;
+    }
+    mem::forget(guard);
+
+    pointer
+}
+
+/// Drops `length` cells at `pointer` and frees them.
+///
+/// # Safety
+/// `pointer` must have been returned by `allocate_cells` with `length` and `allocator`, and not
+/// be used again.
+unsafe fn free_cells<T, A: GridAlloc>(pointer: NonNull<T>, length: usize, allocator: &A) {
+    let layout = AllocLayout::array::<T>(length).unwrap();
+
+    std::ptr::drop_in_place(std::ptr::slice_from_raw_parts_mut(pointer.as_ptr(), length));
+    if layout.size() != 0 {
+        allocator.deallocate(pointer.cast(), layout);
+    }
+}
+
+impl<T, A: GridAlloc> Deref for AllocBuffer<T, A> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // Safety: the buffer holds `length` initialized cells
+        unsafe { std::slice::from_raw_parts(self.pointer.as_ptr(), self.length) }
+    }
+}
+
+impl<T, A: GridAlloc> DerefMut for AllocBuffer<T, A> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        // Safety: the buffer holds `length` initialized cells, and is borrowed mutably
+        unsafe { std::slice::from_raw_parts_mut(self.pointer.as_ptr(), self.length) }
+    }
+}
+
+impl<T, A: GridAlloc> Drop for AllocBuffer<T, A> {
+    fn drop(&mut self) {
+        // Safety: the buffer is never used again
+        unsafe { free_cells(self.pointer, self.length, &self.allocator) };
+    }
+}
+
+impl<T: Clone, A: GridAlloc + Clone> Clone for AllocBuffer<T, A> {
+    fn clone(&self) -> Self {
+        Self::from_fn(self.length, self.allocator.clone(), |index| {
+            self[index].clone()
+        })
+    }
+}
+
+impl<T: fmt::Debug, A: GridAlloc> fmt::Debug for AllocBuffer<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// How the cells of an `ExpandableGridN` are stored. This is implemented for `Global`, for every
+/// `GridAlloc`, which stores cells in an `AllocBuffer`, and with the `mmap` feature, for
+/// `MmapStorage`, which stores cells in a memory mapped file.
+pub trait GridStorage: Sized {
+    /// The buffer which cells of type `T` are stored in.
+    type Buffer<T>: DerefMut<Target = [T]>;
+
+    /// Creates a buffer of `length` cells, where each cell is the result of `f` on its index.
+    fn buffer_from_fn<T>(self, length: usize, f: impl FnMut(usize) -> T) -> Self::Buffer<T>;
+
+    /// Returns the storage which `buffer` was created with.
+    fn storage_of<T>(buffer: &Self::Buffer<T>) -> &Self;
+
+    /// Replaces the cells of `buffer` with `length` clones of `fill`, created with the same
+    /// storage, after calling `copy` with the old and new cells.
+    fn replace_buffer<T: Clone>(
+        buffer: &mut Self::Buffer<T>,
+        length: usize,
+        fill: &T,
+        copy: impl FnOnce(&[T], &mut [T]),
+    );
+}
+
+impl GridStorage for Global {
+    type Buffer<T> = Box<[T]>;
+
+    fn buffer_from_fn<T>(self, length: usize, f: impl FnMut(usize) -> T) -> Box<[T]> {
+        (0..length).map(f).collect()
+    }
+
+    fn storage_of<T>(_: &Box<[T]>) -> &Self {
+        &Global
+    }
+
+    fn replace_buffer<T: Clone>(
+        buffer: &mut Box<[T]>,
+        length: usize,
+        fill: &T,
+        copy: impl FnOnce(&[T], &mut [T]),
+    ) {
+        let mut data = vec![fill.clone(); length].into_boxed_slice();
+        copy(buffer, &mut data);
+        *buffer = data;
+    }
+}
+
+impl<A: GridAlloc> GridStorage for A {
+    type Buffer<T> = AllocBuffer<T, A>;
+
+    fn buffer_from_fn<T>(self, length: usize, f: impl FnMut(usize) -> T) -> AllocBuffer<T, A> {
+        AllocBuffer::from_fn(length, self, f)
+    }
+
+    fn storage_of<T>(buffer: &AllocBuffer<T, A>) -> &Self {
+        buffer.allocator()
+    }
+
+    fn replace_buffer<T: Clone>(
+        buffer: &mut AllocBuffer<T, A>,
+        length: usize,
+        fill: &T,
+        copy: impl FnOnce(&[T], &mut [T]),
+    ) {
+        buffer.replace(length, fill, copy);
+    }
+}
+
+impl<T, const D: usize, C: Coordinate, A: GridStorage> ExpandableGridN<T, D, C, A> {
+    /// Creates a new, empty grid which allocates through `allocator` once it expands.
+    pub fn new_in(allocator: A) -> Self {
+        Self::with_layout_in(Layout::RowMajor, allocator)
+    }
+
+    /// Creates a new, empty grid which stores its data in the order given by `layout`, and
+    /// allocates through `allocator` once it expands.
+    pub fn with_layout_in(layout: Layout, allocator: A) -> Self {
+        Self {
+            size: SVector::zeros(),
+            origin: coord::from_isize_vec(SVector::zeros()),
+            data: allocator.buffer_from_fn(0, |_| unreachable!()),
+            layout,
+        }
+    }
+
+    /// Creates a new grid filled with clones of `fill`, allocated through `allocator`.
+    pub fn with_size_in(
+        size: impl GridVector<usize, D>,
+        origin: impl GridVector<C, D>,
+        fill: &T,
+        allocator: A,
+    ) -> Self
+    where
+        T: Clone,
+    {
+        Self::with_size_and_layout_in(size, origin, fill, Layout::RowMajor, allocator)
+    }
+
+    /// Creates a new grid filled with clones of `fill`, which stores its data in the order given
+    /// by `layout`, allocated through `allocator`.
+    pub fn with_size_and_layout_in(
+        size: impl GridVector<usize, D>,
+        origin: impl GridVector<C, D>,
+        fill: &T,
+        layout: Layout,
+        allocator: A,
+    ) -> Self
+    where
+        T: Clone,
+    {
+        let size = size.to_vector();
+
+        Self {
+            size,
+            origin: origin.to_vector(),
+            data: allocator.buffer_from_fn(size.product(), |_| fill.clone()),
+            layout,
+        }
+    }
+
+    /// Returns the allocator which the cells of this grid are stored in.
+    pub fn allocator(&self) -> &A {
+        A::storage_of(&self.data)
+    }
+
+    /// Clones this grid into a new grid allocated through `allocator`, with the same size,
+    /// origin, and layout.
+    pub fn clone_in<B: GridStorage>(&self, allocator: B) -> ExpandableGridN<T, D, C, B>
+    where
+        T: Clone,
+    {
+        ExpandableGridN {
+            size: self.size,
+            origin: self.origin,
+            data: allocator.buffer_from_fn(self.data.len(), |index| self.data[index].clone()),
+            layout: self.layout,
+        }
+    }
+}
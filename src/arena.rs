@@ -0,0 +1,382 @@
+//! Many small grids sharing a single allocation.
+//!
+//! A `GridArena` stores the cells of every grid it holds in one `Vec`, and hands out a
+//! `GridHandle` for each grid. This avoids an allocation per grid, and keeps the cells of all
+//! grids close together in memory, which helps when iterating over all of them. Grids are
+//! always stored in `Layout::RowMajor`.
+//!
+//! Changing the size of a grid moves its cells to the end of the arena, and removing a grid
+//! leaves its cells in place, so unused cells build up over time. They are reclaimed by
+//! `compact`, which is called automatically once they outnumber the cells in use, and which
+//! keeps every handle valid. A handle to a removed grid is never reused for another grid.
+
+use crate::{coord::GridVector, util, ExpandableGridN, Layout};
+use nalgebra::SVector;
+
+/// A handle to a grid within a `GridArena`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GridHandle {
+    slot: usize,
+    generation: u64,
+}
+
+#[derive(Clone, Debug)]
+struct Slot<const D: usize> {
+    generation: u64,
+    /// The bounds of the grid in this slot, and the index of its first cell, or `None` if the
+    /// slot is free.
+    grid: Option<Entry<D>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Entry<const D: usize> {
+    size: SVector<usize, D>,
+    origin: SVector<isize, D>,
+    start: usize,
+}
+
+impl<const D: usize> Entry<D> {
+    fn range(&self) -> std::ops::Range<usize> {
+        self.start..self.start + self.size.product()
+    }
+}
+
+/// The cells of a grid within a `GridArena`.
+#[derive(Clone, Copy, Debug)]
+pub struct ArenaGrid<'a, T, const D: usize = 2> {
+    pub size: SVector<usize, D>,
+    pub origin: SVector<isize, D>,
+    /// The cells of the grid, in `Layout::RowMajor`.
+    pub data: &'a [T],
+}
+
+impl<'a, T, const D: usize> ArenaGrid<'a, T, D> {
+    pub fn get(&self, index: impl GridVector<isize, D>) -> Option<&'a T> {
+        let position = util::relative_position(self.origin, self.size, index.to_vector())?;
+        Some(&self.data[Layout::RowMajor.linear_index(position, self.size)])
+    }
+}
+
+/// Many grids stored in a single allocation. See the `arena` module for details.
+#[derive(Clone, Debug)]
+pub struct GridArena<T, const D: usize = 2> {
+    data: Vec<T>,
+    slots: Vec<Slot<D>>,
+    free_slots: Vec<usize>,
+    /// The number of cells in `data` which belong to no grid.
+    unused_cells: usize,
+}
+
+impl<T, const D: usize> Default for GridArena<T, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const D: usize> GridArena<T, D> {
+    /// Creates an arena with no grids.
+    pub fn new() -> Self {
+        Self {
+            data: Vec::new(),
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            unused_cells: 0,
+        }
+    }
+
+    /// Returns the number of grids in the arena.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free_slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of cells which belong to no grid, and will be reclaimed by `compact`.
+    pub fn unused_cells(&self) -> usize {
+        self.unused_cells
+    }
+
+    pub fn contains(&self, handle: GridHandle) -> bool {
+        self.entry(handle).is_some()
+    }
+
+    /// Adds a grid of size `size` with its lowest corner at `origin`, filled with clones of
+    /// `fill`.
+    pub fn insert(
+        &mut self,
+        size: impl GridVector<usize, D>,
+        origin: impl GridVector<isize, D>,
+        fill: &T,
+    ) -> GridHandle
+    where
+        T: Clone,
+    {
+        let size = size.to_vector();
+        let start = self.data.len();
+        self.data.resize(start + size.product(), fill.clone());
+
+        self.insert_entry(Entry {
+            size,
+            origin: origin.to_vector(),
+            start,
+        })
+    }
+
+    /// Adds a grid with the same cells as `grid`.
+    pub fn insert_grid(&mut self, grid: ExpandableGridN<T, D>) -> GridHandle {
+        let start = self.data.len();
+        let (size, origin, layout) = (grid.size, grid.origin, grid.layout);
+
+        if layout == Layout::RowMajor {
+            self.data.extend(grid.data.into_vec());
+        } else {
+            let mut cells: Vec<_> = grid.data.into_vec().into_iter().map(Some).collect();
+            self.data.extend((0..cells.len()).map(|index| {
+                let index = layout.linear_index(Layout::RowMajor.position(index, size), size);
+                cells[index].take().unwrap()
+            }));
+        }
+
+        self.insert_entry(Entry {
+            size,
+            origin,
+            start,
+        })
+    }
+
+    /// Removes the grid of `handle`, returning `false` if it was already removed. Its cells are
+    /// dropped once the arena is compacted.
+    pub fn remove(&mut self, handle: GridHandle) -> bool {
+        let Some(entry) = self.entry(handle) else {
+            return false;
+        };
+
+        self.unused_cells += entry.size.product();
+        let slot = &mut self.slots[handle.slot];
+        slot.grid = None;
+        slot.generation += 1;
+        self.free_slots.push(handle.slot);
+
+        self.compact_if_needed();
+        true
+    }
+
+    /// Returns the grid of `handle`, or `None` if it was removed.
+    pub fn get(&self, handle: GridHandle) -> Option<ArenaGrid<'_, T, D>> {
+        let entry = self.entry(handle)?;
+
+        Some(ArenaGrid {
+            size: entry.size,
+            origin: entry.origin,
+            data: &self.data[entry.range()],
+        })
+    }
+
+    /// Returns the cell at `index` of the grid of `handle`, or `None` if it is out of bounds or
+    /// the grid was removed.
+    pub fn get_cell(&self, handle: GridHandle, index: impl GridVector<isize, D>) -> Option<&T> {
+        self.get(handle)?.get(index)
+    }
+
+    /// Returns the cell at `index` of the grid of `handle`, or `None` if it is out of bounds or
+    /// the grid was removed.
+    pub fn get_cell_mut(
+        &mut self,
+        handle: GridHandle,
+        index: impl GridVector<isize, D>,
+    ) -> Option<&mut T> {
+        let entry = self.entry(handle)?;
+        let position = util::relative_position(entry.origin, entry.size, index.to_vector())?;
+
+        Some(&mut self.data[entry.start + Layout::RowMajor.linear_index(position, entry.size)])
+    }
+
+    /// Returns the cells of the grid of `handle` in `Layout::RowMajor`, or `None` if it was
+    /// removed.
+    pub fn data_mut(&mut self, handle: GridHandle) -> Option<&mut [T]> {
+        let entry = self.entry(handle)?;
+        Some(&mut self.data[entry.range()])
+    }
+
+    /// Iterates over every grid in the arena, in the order their cells are stored.
+    pub fn iter(&self) -> impl Iterator<Item = (GridHandle, ArenaGrid<'_, T, D>)> {
+        let mut grids: Vec<_> = (self.slots.iter().enumerate())
+            .filter_map(|(slot, Slot { generation, grid })| {
+                let handle = GridHandle {
+                    slot,
+                    generation: *generation,
+                };
+                Some((handle, (*grid)?))
+            })
+            .collect();
+        grids.sort_by_key(|(_, entry)| entry.start);
+
+        grids.into_iter().map(|(handle, entry)| {
+            let grid = ArenaGrid {
+                size: entry.size,
+                origin: entry.origin,
+                data: &self.data[entry.range()],
+            };
+            (handle, grid)
+        })
+    }
+
+    /// Copies the grid of `handle` into a new `ExpandableGridN`, or returns `None` if it was
+    /// removed.
+    pub fn to_grid(&self, handle: GridHandle) -> Option<ExpandableGridN<T, D>>
+    where
+        T: Clone,
+    {
+        let grid = self.get(handle)?;
+
+        Some(ExpandableGridN {
+            size: grid.size,
+            origin: grid.origin,
+            data: grid.data.into(),
+            layout: Layout::RowMajor,
+        })
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_point`. Returns `false` if the grid was removed.
+    pub fn expand_to_fit_point(
+        &mut self,
+        handle: GridHandle,
+        point: impl GridVector<isize, D>,
+        fill: &T,
+    ) -> bool
+    where
+        T: Clone,
+    {
+        self.expand_to_fit_box(handle, point, SVector::<usize, D>::repeat(1), fill)
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_box`. Returns `false` if the grid was removed.
+    pub fn expand_to_fit_box(
+        &mut self,
+        handle: GridHandle,
+        box_origin: impl GridVector<isize, D>,
+        box_size: impl GridVector<usize, D>,
+        fill: &T,
+    ) -> bool
+    where
+        T: Clone,
+    {
+        let Some(entry) = self.entry(handle) else {
+            return false;
+        };
+        let (box_origin, box_size) = (box_origin.to_vector(), box_size.to_vector());
+
+        if entry.size.product() == 0 {
+            let offset = box_origin - entry.origin;
+            self.change_size(handle, box_size, offset, fill);
+        } else if let Some((new_size, offset)) =
+            util::expansion_to_fit_box(entry.size, entry.origin, box_origin, box_size)
+        {
+            self.change_size(handle, new_size, offset, fill);
+        }
+
+        true
+    }
+
+    /// Changes the size of the grid of `handle`, shifting its origin by `offset`, as with
+    /// `ExpandableGridN::change_size`. Its cells are moved to the end of the arena. Returns
+    /// `false` if the grid was removed.
+    pub fn change_size(
+        &mut self,
+        handle: GridHandle,
+        new_size: impl GridVector<usize, D>,
+        offset: impl GridVector<isize, D>,
+        fill: &T,
+    ) -> bool
+    where
+        T: Clone,
+    {
+        let Some(entry) = self.entry(handle) else {
+            return false;
+        };
+        let (new_size, offset) = (new_size.to_vector(), offset.to_vector());
+
+        let start = self.data.len();
+        self.data.resize(start + new_size.product(), fill.clone());
+        for (new_index, old_index, length) in
+            util::kept_runs(Layout::RowMajor, entry.size, new_size, offset)
+        {
+            // The old cells are always before the new ones
+            let (old, new) = self.data.split_at_mut(start);
+            let old_index = entry.start + old_index;
+            new[new_index..new_index + length]
+                .clone_from_slice(&old[old_index..old_index + length]);
+        }
+
+        self.unused_cells += entry.size.product();
+        self.slots[handle.slot].grid = Some(Entry {
+            size: new_size,
+            origin: entry.origin + offset,
+            start,
+        });
+
+        self.compact_if_needed();
+        true
+    }
+
+    /// Drops every cell which belongs to no grid, moving the cells of the remaining grids
+    /// together. Handles stay valid.
+    pub fn compact(&mut self) {
+        if self.unused_cells == 0 {
+            return;
+        }
+
+        let mut used = vec![false; self.data.len()];
+        for entry in self.slots.iter().filter_map(|slot| slot.grid) {
+            used[entry.range()].fill(true);
+        }
+
+        // Each cell moves back by the number of unused cells before it
+        let mut removed_before = Vec::with_capacity(self.data.len() + 1);
+        removed_before.push(0);
+        for &used in &used {
+            removed_before.push(removed_before.last().unwrap() + usize::from(!used));
+        }
+        for entry in self.slots.iter_mut().filter_map(|slot| slot.grid.as_mut()) {
+            entry.start -= removed_before[entry.start];
+        }
+
+        let mut used = used.into_iter();
+        self.data.retain(|_| used.next().unwrap());
+        self.unused_cells = 0;
+    }
+
+    fn compact_if_needed(&mut self) {
+        if self.unused_cells > self.data.len() - self.unused_cells {
+            self.compact();
+        }
+    }
+
+    fn entry(&self, handle: GridHandle) -> Option<Entry<D>> {
+        let slot = self.slots.get(handle.slot)?;
+        (slot.generation == handle.generation).then_some(slot.grid?)
+    }
+
+    fn insert_entry(&mut self, entry: Entry<D>) -> GridHandle {
+        let slot = match self.free_slots.pop() {
+            Some(slot) => {
+                self.slots[slot].grid = Some(entry);
+                slot
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    grid: Some(entry),
+                });
+                self.slots.len() - 1
+            }
+        };
+
+        GridHandle {
+            slot,
+            generation: self.slots[slot].generation,
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Converting an `ExpandableGrid` to and from ASCII maps, mostly useful for test fixtures and
+//! debugging.
+//!
+//! Each line of a map is a row of the grid, and each character is a cell. The first character of
+//! the first line is the cell at the grid's origin, `x` increases to the right along each line,
+//! and `y` increases downwards from one line to the next. This matches the order of `rows`, so a
+//! map reads the same way as the data of a row major grid.
+
+use crate::{coord::Coordinate, ExpandableGrid};
+use nalgebra::{vector, Vector2};
+
+impl<T, C: Coordinate> ExpandableGrid<T, C> {
+    /// Creates a grid from the ASCII map `map`, with its first character at `origin`. Each
+    /// character is converted to a cell by `decode`.
+    ///
+    /// Lines containing only whitespace at the start and end of the map are ignored, so maps can
+    /// be written as multiline string literals. Lines shorter than the longest line are padded
+    /// with `decode(' ')`.
+    pub fn from_ascii(map: &str, origin: Vector2<C>, decode: impl Fn(char) -> T) -> Self {
+        let mut lines = map.lines().collect::<Vec<_>>();
+        let is_blank = |line: &&str| line.trim().is_empty();
+        let start = lines.iter().position(|line| !is_blank(line)).unwrap_or(0);
+        let end = lines
+            .iter()
+            .rposition(|line| !is_blank(line))
+            .map_or(0, |end| end + 1);
+        lines.truncate(end);
+        lines.drain(..start.min(end));
+
+        let width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        let size = vector![width, lines.len()];
+
+        if size.product() == 0 {
+            return Self::new();
+        }
+
+        let mut data = Vec::with_capacity(size.product());
+        for line in &lines {
+            let length = data.len();
+            data.extend(line.chars().map(&decode));
+            data.extend((data.len() - length..width).map(|_| decode(' ')));
+        }
+
+        Self {
+            size,
+            origin,
+            data: data.into_boxed_slice(),
+            layout: Default::default(),
+        }
+    }
+
+    /// Formats this grid as an ASCII map in the format read by `from_ascii`, converting each cell
+    /// to a character with `encode`. Rows are separated by newlines, with no newline after the
+    /// last row.
+    pub fn to_ascii(&self, encode: impl Fn(&T) -> char) -> String {
+        let mut map = String::with_capacity((self.size.x + 1) * self.size.y);
+
+        for y in 0..self.size.y {
+            if y > 0 {
+                map.push('\n');
+            }
+            for x in 0..self.size.x {
+                let index = self.layout.linear_index(vector![x, y], self.size);
+                map.push(encode(&self.data[index]));
+            }
+        }
+
+        map
+    }
+}
@@ -0,0 +1,208 @@
+//! Grids of atomic integers, for accumulating into a grid from many threads at once.
+//!
+//! An `AtomicGrid<A>` is an `ExpandableGridN` of `AtomicCell<A>`, where `A` is one of the atomic
+//! integer types from `std::sync::atomic`. Its cells can be loaded, stored, and modified with
+//! fetch operations through a shared reference, with the memory ordering given to each accessor.
+//! `AtomicCell` is `Clone`, loading the value with `Ordering::Relaxed`, so the grid can be
+//! expanded and resized with the usual methods while it is not shared.
+
+use crate::{
+    coord::{Coordinate, GridVector},
+    ExpandableGridN,
+};
+use std::sync::atomic::{self, Ordering};
+
+/// An atomic integer type which can be stored in an `AtomicGrid`.
+pub trait AtomicValue: Send + Sync {
+    type Value: Copy;
+
+    fn new(value: Self::Value) -> Self;
+    fn load(&self, ordering: Ordering) -> Self::Value;
+    fn store(&self, value: Self::Value, ordering: Ordering);
+    fn swap(&self, value: Self::Value, ordering: Ordering) -> Self::Value;
+    fn compare_exchange(
+        &self,
+        current: Self::Value,
+        new: Self::Value,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Self::Value, Self::Value>;
+    fn fetch_add(&self, value: Self::Value, ordering: Ordering) -> Self::Value;
+    fn fetch_sub(&self, value: Self::Value, ordering: Ordering) -> Self::Value;
+    fn fetch_max(&self, value: Self::Value, ordering: Ordering) -> Self::Value;
+    fn fetch_min(&self, value: Self::Value, ordering: Ordering) -> Self::Value;
+    fn fetch_and(&self, value: Self::Value, ordering: Ordering) -> Self::Value;
+    fn fetch_or(&self, value: Self::Value, ordering: Ordering) -> Self::Value;
+    fn fetch_xor(&self, value: Self::Value, ordering: Ordering) -> Self::Value;
+}
+
+macro_rules! impl_atomic_value {
+    ($($atomic:ident: $value:ty),* $(,)?) => {
+        $(
+            impl AtomicValue for atomic::$atomic {
+                type Value = $value;
+
+                fn new(value: $value) -> Self {
+                    atomic::$atomic::new(value)
+                }
+
+                fn load(&self, ordering: Ordering) -> $value {
+                    self.load(ordering)
+                }
+
+                fn store(&self, value: $value, ordering: Ordering) {
+                    self.store(value, ordering)
+                }
+
+                fn swap(&self, value: $value, ordering: Ordering) -> $value {
+                    self.swap(value, ordering)
+                }
+
+                fn compare_exchange(
+                    &self,
+                    current: $value,
+                    new: $value,
+                    success: Ordering,
+                    failure: Ordering,
+                ) -> Result<$value, $value> {
+                    self.compare_exchange(current, new, success, failure)
+                }
+
+                fn fetch_add(&self, value: $value, ordering: Ordering) -> $value {
+                    self.fetch_add(value, ordering)
+                }
+
+                fn fetch_sub(&self, value: $value, ordering: Ordering) -> $value {
+                    self.fetch_sub(value, ordering)
+                }
+
+                fn fetch_max(&self, value: $value, ordering: Ordering) -> $value {
+                    self.fetch_max(value, ordering)
+                }
+
+                fn fetch_min(&self, value: $value, ordering: Ordering) -> $value {
+                    self.fetch_min(value, ordering)
+                }
+
+                fn fetch_and(&self, value: $value, ordering: Ordering) -> $value {
+                    self.fetch_and(value, ordering)
+                }
+
+                fn fetch_or(&self, value: $value, ordering: Ordering) -> $value {
+                    self.fetch_or(value, ordering)
+                }
+
+                fn fetch_xor(&self, value: $value, ordering: Ordering) -> $value {
+                    self.fetch_xor(value, ordering)
+                }
+            }
+        )*
+    };
+}
+
+impl_atomic_value!(
+    AtomicU8: u8,
+    AtomicU16: u16,
+    AtomicU32: u32,
+    AtomicU64: u64,
+    AtomicUsize: usize,
+    AtomicI8: i8,
+    AtomicI16: i16,
+    AtomicI32: i32,
+    AtomicI64: i64,
+    AtomicIsize: isize,
+);
+
+/// A cell of an `AtomicGrid`. Cloning it loads its value with `Ordering::Relaxed`.
+#[derive(Debug, Default)]
+pub struct AtomicCell<A>(pub A);
+
+impl<A: AtomicValue> AtomicCell<A> {
+    pub fn new(value: A::Value) -> Self {
+        Self(A::new(value))
+    }
+}
+
+impl<A: AtomicValue> Clone for AtomicCell<A> {
+    fn clone(&self) -> Self {
+        Self::new(self.0.load(Ordering::Relaxed))
+    }
+}
+
+/// A `D` dimensional grid of atomic integers. See the `atomic` module for details.
+pub type AtomicGrid<A, const D: usize = 2, C = isize> = ExpandableGridN<AtomicCell<A>, D, C>;
+
+macro_rules! fetch_op {
+    ($($name:ident),*) => {
+        $(
+            #[doc = concat!(
+                "Calls `", stringify!($name), "` on the cell at `index`, returning its previous ",
+                "value, or `None` if it is out of bounds."
+            )]
+            pub fn $name(
+                &self,
+                index: impl GridVector<C, D>,
+                value: A::Value,
+                ordering: Ordering,
+            ) -> Option<A::Value> {
+                Some(self.get(index)?.0.$name(value, ordering))
+            }
+        )*
+    };
+}
+
+impl<A: AtomicValue, const D: usize, C: Coordinate> ExpandableGridN<AtomicCell<A>, D, C> {
+    /// Creates a grid with the same values as `grid`.
+    pub fn from_values(grid: &ExpandableGridN<A::Value, D, C>) -> Self {
+        ExpandableGridN {
+            size: grid.size,
+            origin: grid.origin,
+            data: grid
+                .data
+                .iter()
+                .map(|&value| AtomicCell::new(value))
+                .collect(),
+            layout: grid.layout,
+        }
+    }
+
+    /// Loads every cell of this grid with `ordering` into a new grid.
+    pub fn to_values(&self, ordering: Ordering) -> ExpandableGridN<A::Value, D, C> {
+        ExpandableGridN {
+            size: self.size,
+            origin: self.origin,
+            data: self.data.iter().map(|cell| cell.0.load(ordering)).collect(),
+            layout: self.layout,
+        }
+    }
+
+    /// Loads the cell at `index`, or returns `None` if it is out of bounds.
+    pub fn load(&self, index: impl GridVector<C, D>, ordering: Ordering) -> Option<A::Value> {
+        Some(self.get(index)?.0.load(ordering))
+    }
+
+    /// Stores `value` in the cell at `index`, returning whether it is within bounds.
+    pub fn store(&self, index: impl GridVector<C, D>, value: A::Value, ordering: Ordering) -> bool {
+        self.get(index)
+            .map(|cell| cell.0.store(value, ordering))
+            .is_some()
+    }
+
+    /// Calls `compare_exchange` on the cell at `index`, or returns `None` if it is out of bounds.
+    pub fn compare_exchange(
+        &self,
+        index: impl GridVector<C, D>,
+        current: A::Value,
+        new: A::Value,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Option<Result<A::Value, A::Value>> {
+        Some(
+            self.get(index)?
+                .0
+                .compare_exchange(current, new, success, failure),
+        )
+    }
+
+    fetch_op!(swap, fetch_add, fetch_sub, fetch_max, fetch_min, fetch_and, fetch_or, fetch_xor);
+}
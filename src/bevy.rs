@@ -0,0 +1,184 @@
+//! Integration with the Bevy game engine.
+//!
+//! A `GridComponent` wraps an `ExpandableGrid` so that it can be used as a component or a
+//! resource, and is reflected so that it shows up in inspectors. Since `bevy_reflect` doesn't
+//! support `nalgebra` vectors or boxed slices, the grid is reflected as an opaque value, so
+//! inspectors show its `Debug` output rather than editable fields. Cloning the component through
+//! reflection and `FromReflect` keep the whole grid. Scenes can only serialize it once
+//! `ReflectSerialize` and `ReflectDeserialize` are registered for the grid type, which needs the
+//! `serde` feature.
+//!
+//! Reading the grid through `Deref` never triggers change detection, and the mutation methods on
+//! `GridComponent` only mark it as changed when a cell or the bounds of the grid actually change,
+//! so systems using `Changed<GridComponent<T>>` are not run needlessly.
+
+use crate::{alloc::GridStorage, coord::GridVector, ExpandableGrid, ExpandableGridN};
+use bevy_ecs::{
+    change_detection::DetectChangesMut,
+    component::Component,
+    reflect::{ReflectComponent, ReflectResource},
+    system::Resource,
+};
+use bevy_reflect::{std_traits::ReflectDefault, utility::GenericTypePathCell, Reflect, TypePath};
+use std::ops::Deref;
+
+/// An `ExpandableGrid` which can be used as a Bevy component or resource.
+#[derive(Component, Resource, Reflect, Clone, Debug)]
+#[reflect(Component, Resource, Default, Debug)]
+#[reflect(where T: std::fmt::Debug)]
+pub struct GridComponent<T> {
+    grid: ExpandableGrid<T>,
+}
+
+impl<T> GridComponent<T> {
+    pub fn new(grid: ExpandableGrid<T>) -> Self {
+        Self { grid }
+    }
+
+    /// Returns the wrapped grid.
+    pub fn into_inner(self) -> ExpandableGrid<T> {
+        self.grid
+    }
+
+    /// Returns a mutable reference to the grid, always marking it as changed.
+    pub fn grid_mut(this: &mut impl DetectChangesMut<Inner = Self>) -> &mut ExpandableGrid<T> {
+        this.set_changed();
+        &mut this.bypass_change_detection().grid
+    }
+
+    /// Sets the cell at `index` to `value`, returning whether it changed. The grid is only marked
+    /// as changed if the cell held a different value.
+    pub fn set(
+        this: &mut impl DetectChangesMut<Inner = Self>,
+        index: impl GridVector<isize, 2>,
+        value: T,
+    ) -> bool
+    where
+        T: PartialEq,
+    {
+        let Some(cell) = this.bypass_change_detection().grid.get_mut(index) else {
+            return false;
+        };
+        if *cell == value {
+            return false;
+        }
+
+        *cell = value;
+        this.set_changed();
+        true
+    }
+
+    /// Calls `f` with a mutable reference to the cell at `index`, returning its result. The grid
+    /// is only marked as changed if the cell holds a different value afterwards.
+    pub fn modify<R>(
+        this: &mut impl DetectChangesMut<Inner = Self>,
+        index: impl GridVector<isize, 2>,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Option<R>
+    where
+        T: PartialEq + Clone,
+    {
+        let cell = this.bypass_change_detection().grid.get_mut(index)?;
+        let old = cell.clone();
+        let result = f(cell);
+
+        if *cell != old {
+            this.set_changed();
+        }
+        Some(result)
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_point`. The grid is only marked as changed if it
+    /// expands.
+    pub fn expand_to_fit_point(
+        this: &mut impl DetectChangesMut<Inner = Self>,
+        point: impl GridVector<isize, 2>,
+        fill: &T,
+    ) where
+        T: Clone,
+    {
+        Self::expand_to_fit_box(this, point, [1, 1], fill);
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_box`. The grid is only marked as changed if it
+    /// expands.
+    pub fn expand_to_fit_box(
+        this: &mut impl DetectChangesMut<Inner = Self>,
+        box_origin: impl GridVector<isize, 2>,
+        box_size: impl GridVector<usize, 2>,
+        fill: &T,
+    ) where
+        T: Clone,
+    {
+        let grid = &mut this.bypass_change_detection().grid;
+        let (old_origin, old_size) = (grid.origin, grid.size);
+
+        grid.expand_to_fit_box(box_origin, box_size, fill);
+
+        if (grid.origin, grid.size) != (old_origin, old_size) {
+            this.set_changed();
+        }
+    }
+}
+
+impl<T> Default for GridComponent<T> {
+    fn default() -> Self {
+        Self::new(ExpandableGrid::new())
+    }
+}
+
+impl<T> Deref for GridComponent<T> {
+    type Target = ExpandableGrid<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.grid
+    }
+}
+
+impl<T> From<ExpandableGrid<T>> for GridComponent<T> {
+    fn from(grid: ExpandableGrid<T>) -> Self {
+        Self::new(grid)
+    }
+}
+
+// Written by hand since the derived implementation doesn't compile for const generics when another
+// crate adds an `Add` implementation to `String`, as `smartstring` does for the `scripting` feature
+impl<T: TypePath, const D: usize, C: TypePath, A: GridStorage + TypePath> TypePath
+    for ExpandableGridN<T, D, C, A>
+{
+    fn type_path() -> &'static str {
+        static CELL: GenericTypePathCell = GenericTypePathCell::new();
+        CELL.get_or_insert::<Self, _>(|| {
+            format!(
+                "expandable_grid::ExpandableGridN<{}, {D}, {}, {}>",
+                T::type_path(),
+                C::type_path(),
+                A::type_path(),
+            )
+        })
+    }
+
+    fn short_type_path() -> &'static str {
+        static CELL: GenericTypePathCell = GenericTypePathCell::new();
+        CELL.get_or_insert::<Self, _>(|| {
+            format!(
+                "ExpandableGridN<{}, {D}, {}, {}>",
+                T::short_type_path(),
+                C::short_type_path(),
+                A::short_type_path(),
+            )
+        })
+    }
+
+    fn type_ident() -> Option<&'static str> {
+        Some("ExpandableGridN")
+    }
+
+    fn crate_name() -> Option<&'static str> {
+        Some("expandable_grid")
+    }
+
+    fn module_path() -> Option<&'static str> {
+        Some("expandable_grid")
+    }
+}
@@ -0,0 +1,649 @@
+//! A compact, portable binary format for grids, independent of serde.
+//!
+//! Grids are written with `ExpandableGridN::write_binary` and read back with
+//! `ExpandableGridN::read_binary`, for any cell type implementing `BinaryCell`. All integers are
+//! stored in little endian byte order regardless of platform, and sizes are always stored as 64
+//! bit integers, so files can be shared between platforms.
+//!
+//! # Layout
+//!
+//! | Field      | Encoding                   | Notes                                     |
+//! |------------|----------------------------|-------------------------------------------|
+//! | magic      | 4 bytes                    | Always `b"EXGR"`                          |
+//! | version    | `u16`                      | Currently `4`                             |
+//! | dimensions | `u8`                       | `D`                                       |
+//! | layout     | `u8`                       | `0` for row major, `1` for column major   |
+//! | encoding   | `u8`                       | An `Encoding`                             |
+//! | cell size  | `u32`                      | `T::SIZE`                                 |
+//! | data ver.  | `u32`                      | Set by the application                    |
+//! | size       | `D` × `u64`                | One per axis, starting with `x`           |
+//! | origin     | `D` × `i64`                | One per axis, starting with `x`           |
+//! | data       | depends on encoding        | See below                                 |
+//! | checksum   | `u32`                      |                                           |
+//!
+//! With `Encoding::Raw`, the data is every cell as written by `BinaryCell`, in the order given by
+//! layout. With `Encoding::RunLength`, the data is a sequence of runs of equal cells in the same
+//! order, each stored as its length in cells (an unsigned LEB128 integer) followed by the cell.
+//! The lengths of the runs always add up to the area of the grid, and are never 0.
+//!
+//! The checksum is the CRC-32 (see the `checksum` module) of every byte before it, and is checked
+//! when reading the grid. Only the current version of the format can be read, so data written by
+//! an older version of this crate must be read with that version and written again.
+//!
+//! The data version is not used by this crate. Applications can store the version of their cell
+//! type in it when writing with `write_binary_with_version`, and upgrade grids written with older
+//! versions when reading with `read_binary_migrated` (see the `migration` module).
+
+use crate::{
+    checksum::{ChecksumReader, ChecksumWriter},
+    coord::{self, Coordinate},
+    rle, util, ExpandableGridN, Layout,
+};
+use nalgebra::SVector;
+use std::io::{self, Read, Write};
+
+/// The bytes every file in this format starts with.
+pub const MAGIC: [u8; 4] = *b"EXGR";
+
+/// The version of the format written by this version of the crate.
+pub const VERSION: u16 = 4;
+
+/// The number of cells which are encoded or decoded at once.
+const BUFFER_CELLS: usize = 4096;
+
+/// How the cells of a grid are stored after the header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Encoding {
+    /// Every cell is stored individually.
+    Raw = 0,
+    /// Runs of equal cells are stored as a length and a single cell.
+    RunLength = 1,
+}
+
+impl Encoding {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Encoding::Raw),
+            1 => Some(Encoding::RunLength),
+            _ => None,
+        }
+    }
+}
+
+/// A type which can be converted to and from a fixed size representation in bytes, allowing it
+/// to be stored in the binary format.
+pub trait BinaryCell: Sized {
+    /// The number of bytes each value takes up.
+    const SIZE: usize;
+
+    /// Writes this value into `bytes`, which is exactly `SIZE` bytes long.
+    fn write_bytes(&self, bytes: &mut [u8]);
+
+    /// Reads a value from `bytes`, which is exactly `SIZE` bytes long. Returns `None` if the
+    /// bytes do not represent a valid value.
+    fn read_bytes(bytes: &[u8]) -> Option<Self>;
+}
+
+macro_rules! impl_binary_cell_for_numbers {
+    ($($number:ty),*) => {
+        $(
+            impl BinaryCell for $number {
+                const SIZE: usize = std::mem::size_of::<$number>();
+
+                fn write_bytes(&self, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&self.to_le_bytes());
+                }
+
+                fn read_bytes(bytes: &[u8]) -> Option<Self> {
+                    Some(<$number>::from_le_bytes(bytes.try_into().ok()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_binary_cell_for_numbers!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+impl BinaryCell for bool {
+    const SIZE: usize = 1;
+
+    fn write_bytes(&self, bytes: &mut [u8]) {
+        bytes[0] = *self as u8;
+    }
+
+    fn read_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes[0] {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl<T: BinaryCell, const N: usize> BinaryCell for [T; N] {
+    const SIZE: usize = T::SIZE * N;
+
+    fn write_bytes(&self, bytes: &mut [u8]) {
+        for (value, bytes) in self.iter().zip(bytes.chunks_exact_mut(T::SIZE.max(1))) {
+            value.write_bytes(bytes);
+        }
+    }
+
+    fn read_bytes(bytes: &[u8]) -> Option<Self> {
+        let values = (0..N)
+            .map(|i| T::read_bytes(&bytes[i * T::SIZE..(i + 1) * T::SIZE]))
+            .collect::<Option<Vec<_>>>()?;
+
+        values.try_into().ok()
+    }
+}
+
+/// An error encountered while reading a grid in the binary format.
+#[derive(Debug)]
+pub enum BinaryError {
+    /// The underlying reader failed, or ended before the grid was complete.
+    Io(io::Error),
+    /// The data does not start with `MAGIC`, so is likely not a grid.
+    InvalidMagic([u8; 4]),
+    /// The data was written with a version of the format this crate cannot read.
+    UnsupportedVersion(u16),
+    /// The grid has a different number of dimensions than the grid being read.
+    DimensionMismatch { expected: usize, found: usize },
+    /// The layout byte does not correspond to any `Layout`.
+    InvalidLayout(u8),
+    /// The encoding byte does not correspond to any `Encoding`.
+    InvalidEncoding(u8),
+    /// The cells of the grid have a different size than the type being read.
+    CellSizeMismatch { expected: usize, found: usize },
+    /// The size of the grid does not fit within the address space of this platform.
+    SizeOverflow,
+    /// The tile size of a region file is 0 on some axis.
+    InvalidTileSize,
+    /// The bytes of the cell at this index within the data could not be read as a value.
+    InvalidCell { index: usize },
+    /// The tag of the edit at this index within an edit log does not correspond to any `Edit`.
+    InvalidEdit { index: usize },
+    /// The run starting at this index within the data is empty or extends past the end of the
+    /// grid.
+    InvalidRun { index: usize },
+    /// The run starting at this index within the data could not be allocated, as the grid is too
+    /// large to fit in memory.
+    OutOfMemory { index: usize },
+    /// The checksum stored with the data does not match the data, so it has been corrupted.
+    ChecksumMismatch { expected: u32, found: u32 },
+    /// The data version of the grid is newer than the current version of the `Migrator` it was
+    /// read with.
+    UnsupportedDataVersion { current: u32, found: u32 },
+    /// The `Migrator` used to read the grid has no migration from this data version.
+    MissingMigration { version: u32 },
+    /// Migrating the grid from this data version failed for the given reason.
+    MigrationFailed { version: u32, reason: String },
+    /// Reading the tile of a region with these coordinates failed with `error`.
+    InTile {
+        tile: Vec<isize>,
+        error: Box<BinaryError>,
+    },
+}
+
+impl std::fmt::Display for BinaryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryError::Io(error) => write!(f, "io error: {error}"),
+            BinaryError::InvalidMagic(magic) => write!(f, "invalid magic bytes {magic:?}"),
+            BinaryError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {version}")
+            }
+            BinaryError::DimensionMismatch { expected, found } => {
+                write!(
+                    f,
+                    "expected a grid with {expected} dimensions, found {found}"
+                )
+            }
+            BinaryError::InvalidLayout(layout) => write!(f, "invalid layout {layout}"),
+            BinaryError::InvalidEncoding(encoding) => write!(f, "invalid encoding {encoding}"),
+            BinaryError::CellSizeMismatch { expected, found } => {
+                write!(f, "expected cells of {expected} bytes, found {found}")
+            }
+            BinaryError::SizeOverflow => write!(f, "grid size overflows usize"),
+            BinaryError::InvalidTileSize => write!(f, "tile size is 0"),
+            BinaryError::InvalidCell { index } => write!(f, "invalid cell at index {index}"),
+            BinaryError::InvalidEdit { index } => write!(f, "invalid edit at index {index}"),
+            BinaryError::InvalidRun { index } => write!(f, "invalid run at index {index}"),
+            BinaryError::OutOfMemory { index } => {
+                write!(f, "out of memory for the run at index {index}")
+            }
+            BinaryError::ChecksumMismatch { expected, found } => write!(
+                f,
+                "checksum mismatch, expected {expected:#010x} but found {found:#010x}",
+            ),
+            BinaryError::UnsupportedDataVersion { current, found } => {
+                write!(
+                    f,
+                    "data version {found} is newer than the current version {current}"
+                )
+            }
+            BinaryError::MissingMigration { version } => {
+                write!(f, "no migration from data version {version}")
+            }
+            BinaryError::MigrationFailed { version, reason } => {
+                write!(f, "migration from data version {version} failed: {reason}")
+            }
+            BinaryError::InTile { tile, error } => write!(f, "in tile {tile:?}: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for BinaryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BinaryError::Io(error) => Some(error),
+            BinaryError::InTile { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl BinaryError {
+    /// Wraps this error in `BinaryError::InTile`, identifying the tile it was encountered in.
+    pub fn in_tile<const D: usize>(self, tile: SVector<isize, D>) -> Self {
+        BinaryError::InTile {
+            tile: tile.iter().copied().collect(),
+            error: Box::new(self),
+        }
+    }
+}
+
+impl From<io::Error> for BinaryError {
+    fn from(error: io::Error) -> Self {
+        BinaryError::Io(error)
+    }
+}
+
+impl<T: BinaryCell, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Writes this grid to `writer` in the binary format described in the `binary` module, with
+    /// every cell stored individually.
+    pub fn write_binary(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.write_binary_with_version(writer, 0)
+    }
+
+    /// Same as `write_binary`, but records `data_version` as the version of the cells, so that
+    /// `read_binary_migrated` can upgrade the grid if the cell type changes in the future.
+    pub fn write_binary_with_version(
+        &self,
+        writer: &mut impl Write,
+        data_version: u32,
+    ) -> io::Result<()> {
+        let writer = &mut ChecksumWriter::new(writer);
+        self.write_header(writer, Encoding::Raw, data_version)?;
+
+        let mut buffer = vec![0; T::SIZE * BUFFER_CELLS.min(self.data.len())];
+        for cells in self.data.chunks(BUFFER_CELLS) {
+            let bytes = &mut buffer[..cells.len() * T::SIZE];
+            for (cell, bytes) in cells.iter().zip(bytes.chunks_exact_mut(T::SIZE.max(1))) {
+                cell.write_bytes(bytes);
+            }
+            writer.write_all(bytes)?;
+        }
+
+        writer.write_checksum()
+    }
+
+    /// Writes this grid to `writer` in the binary format described in the `binary` module, with
+    /// runs of equal cells stored as a single cell and a count. This is much smaller than
+    /// `write_binary` for grids with large areas of the same value.
+    pub fn write_binary_rle(&self, writer: &mut impl Write) -> io::Result<()>
+    where
+        T: PartialEq,
+    {
+        self.write_binary_rle_with_version(writer, 0)
+    }
+
+    /// Same as `write_binary_rle`, but records `data_version` as the version of the cells, so
+    /// that `read_binary_migrated` can upgrade the grid if the cell type changes in the future.
+    pub fn write_binary_rle_with_version(
+        &self,
+        writer: &mut impl Write,
+        data_version: u32,
+    ) -> io::Result<()>
+    where
+        T: PartialEq,
+    {
+        let writer = &mut ChecksumWriter::new(writer);
+        self.write_header(writer, Encoding::RunLength, data_version)?;
+
+        let mut buffer = vec![0; T::SIZE];
+        for (length, cell) in rle::runs(&self.data) {
+            write_varint(writer, length as u64)?;
+            cell.write_bytes(&mut buffer);
+            writer.write_all(&buffer)?;
+        }
+
+        writer.write_checksum()
+    }
+
+    fn write_header(
+        &self,
+        writer: &mut impl Write,
+        encoding: Encoding,
+        data_version: u32,
+    ) -> io::Result<()> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&[D as u8, layout_to_byte(self.layout), encoding as u8])?;
+        writer.write_all(&(T::SIZE as u32).to_le_bytes())?;
+        writer.write_all(&data_version.to_le_bytes())?;
+
+        write_size(writer, self.size)?;
+        write_position(writer, coord::to_isize_vec(self.origin))?;
+
+        Ok(())
+    }
+
+    /// Reads a grid from `reader` in the binary format described in the `binary` module. Grids
+    /// written by both `write_binary` and `write_binary_rle` can be read.
+    ///
+    /// The data version of the grid is ignored, see `read_binary_migrated` to upgrade grids
+    /// written with older versions of the cell type.
+    pub fn read_binary(reader: &mut impl Read) -> Result<Self, BinaryError> {
+        let reader = &mut ChecksumReader::new(reader);
+
+        let header = Header::read(reader)?;
+        header.check_cell_size(T::SIZE)?;
+        let origin = header.checked_origin()?;
+        let data = header.read_cells(reader)?;
+        header.verify_checksum(reader)?;
+
+        Ok(Self {
+            size: header.size,
+            origin,
+            data: data.into_boxed_slice(),
+            layout: header.layout,
+        })
+    }
+}
+
+/// The header of a grid in the binary format, after it has been checked for consistency.
+pub(crate) struct Header<const D: usize> {
+    pub layout: Layout,
+    pub encoding: Encoding,
+    pub cell_size: usize,
+    pub data_version: u32,
+    pub size: SVector<usize, D>,
+    pub origin: SVector<isize, D>,
+    pub area: usize,
+}
+
+impl<const D: usize> Header<D> {
+    pub fn read(reader: &mut impl Read) -> Result<Self, BinaryError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(BinaryError::InvalidMagic(magic));
+        }
+
+        let version = u16::from_le_bytes(read_array(reader)?);
+        if version != VERSION {
+            return Err(BinaryError::UnsupportedVersion(version));
+        }
+
+        let [dimensions, layout] = read_array(reader)?;
+        if dimensions as usize != D {
+            return Err(BinaryError::DimensionMismatch {
+                expected: D,
+                found: dimensions as usize,
+            });
+        }
+        let layout = layout_from_byte(layout).ok_or(BinaryError::InvalidLayout(layout))?;
+
+        let [encoding] = read_array(reader)?;
+        let encoding =
+            Encoding::from_byte(encoding).ok_or(BinaryError::InvalidEncoding(encoding))?;
+
+        let cell_size = u32::from_le_bytes(read_array(reader)?) as usize;
+        let data_version = u32::from_le_bytes(read_array(reader)?);
+
+        let size = read_size(reader)?;
+        let origin = read_position(reader)?;
+
+        let area = util::checked_area(size)
+            .filter(|area| area.checked_mul(cell_size).is_some())
+            .ok_or(BinaryError::SizeOverflow)?;
+
+        Ok(Self {
+            layout,
+            encoding,
+            cell_size,
+            data_version,
+            size,
+            origin,
+            area,
+        })
+    }
+
+    /// Returns the origin of the grid as coordinates of type `C`, or `BinaryError::SizeOverflow`
+    /// if the grid does not fit within the range of `C`.
+    pub fn checked_origin<C: Coordinate>(&self) -> Result<SVector<C, D>, BinaryError> {
+        coord::checked_origin(self.origin, self.size).ok_or(BinaryError::SizeOverflow)
+    }
+
+    pub fn check_cell_size(&self, expected: usize) -> Result<(), BinaryError> {
+        if self.cell_size != expected {
+            return Err(BinaryError::CellSizeMismatch {
+                expected,
+                found: self.cell_size,
+            });
+        }
+        Ok(())
+    }
+
+    /// Reads the cells following the header. The cell size must already have been checked.
+    pub fn read_cells<T: BinaryCell>(&self, reader: &mut impl Read) -> Result<Vec<T>, BinaryError> {
+        match self.encoding {
+            Encoding::Raw => read_raw_cells(reader, self.area),
+            Encoding::RunLength => read_run_length_cells(reader, self.area),
+        }
+    }
+
+    /// Reads the cells following the header without decoding them, as `cell_size` bytes per cell.
+    pub fn read_cell_bytes(&self, reader: &mut impl Read) -> Result<Vec<u8>, BinaryError> {
+        let length = self.area * self.cell_size;
+
+        match self.encoding {
+            Encoding::Raw => {
+                // Avoid trusting the header with a large allocation before any data has been read
+                let mut bytes = Vec::new();
+                reader.take(length as u64).read_to_end(&mut bytes)?;
+                if bytes.len() < length {
+                    return Err(BinaryError::Io(io::ErrorKind::UnexpectedEof.into()));
+                }
+                Ok(bytes)
+            }
+            Encoding::RunLength => {
+                let mut bytes = Vec::with_capacity(length.min(BUFFER_CELLS * self.cell_size));
+                let mut buffer = vec![0; self.cell_size];
+                let mut index = 0;
+                while index < self.area {
+                    let length = read_varint(reader)?
+                        .and_then(|length| usize::try_from(length).ok())
+                        .filter(|&length| length > 0 && length <= self.area - index)
+                        .ok_or(BinaryError::InvalidRun { index })?;
+
+                    reader.read_exact(&mut buffer)?;
+                    // The run has been read, but its length may still be far more than fits in
+                    // memory, so allocate it fallibly
+                    bytes
+                        .try_reserve(length * self.cell_size)
+                        .map_err(|_| BinaryError::OutOfMemory { index })?;
+                    for _ in 0..length {
+                        bytes.extend_from_slice(&buffer);
+                    }
+                    index += length;
+                }
+                Ok(bytes)
+            }
+        }
+    }
+
+    pub fn verify_checksum<R: Read>(
+        &self,
+        reader: &mut ChecksumReader<R>,
+    ) -> Result<(), BinaryError> {
+        let expected = reader.checksum();
+        let found = reader.read_stored_checksum()?;
+        if found != expected {
+            return Err(BinaryError::ChecksumMismatch { expected, found });
+        }
+        Ok(())
+    }
+}
+
+fn read_raw_cells<T: BinaryCell>(
+    reader: &mut impl Read,
+    area: usize,
+) -> Result<Vec<T>, BinaryError> {
+    // Avoid trusting the header with a large allocation before any data has been read
+    let mut data = Vec::with_capacity(area.min(BUFFER_CELLS));
+    let mut buffer = vec![0; T::SIZE * BUFFER_CELLS.min(area)];
+    while data.len() < area {
+        let cells = (area - data.len()).min(BUFFER_CELLS);
+        let bytes = &mut buffer[..cells * T::SIZE];
+        reader.read_exact(bytes)?;
+
+        for i in 0..cells {
+            let index = data.len();
+            let cell = T::read_bytes(&bytes[i * T::SIZE..(i + 1) * T::SIZE])
+                .ok_or(BinaryError::InvalidCell { index })?;
+            data.push(cell);
+        }
+    }
+
+    Ok(data)
+}
+
+fn read_run_length_cells<T: BinaryCell>(
+    reader: &mut impl Read,
+    area: usize,
+) -> Result<Vec<T>, BinaryError> {
+    let mut data = Vec::with_capacity(area.min(BUFFER_CELLS));
+    let mut buffer = vec![0; T::SIZE];
+    while data.len() < area {
+        let index = data.len();
+
+        let length = read_varint(reader)?
+            .and_then(|length| usize::try_from(length).ok())
+            .filter(|&length| length > 0 && length <= area - index)
+            .ok_or(BinaryError::InvalidRun { index })?;
+
+        reader.read_exact(&mut buffer)?;
+        // The run has been read, but its length may still be far more than fits in memory, so
+        // allocate it fallibly
+        data.try_reserve(length)
+            .map_err(|_| BinaryError::OutOfMemory { index })?;
+        for _ in 0..length {
+            // Reading the bytes again for each cell avoids requiring `T: Clone`
+            let cell = T::read_bytes(&buffer).ok_or(BinaryError::InvalidCell { index })?;
+            data.push(cell);
+        }
+    }
+
+    Ok(data)
+}
+
+/// Writes `value` as an unsigned LEB128 variable length integer.
+pub(crate) fn write_varint(writer: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+/// Reads an unsigned LEB128 variable length integer, or `None` if it does not fit in a `u64`.
+pub(crate) fn read_varint(reader: &mut impl Read) -> io::Result<Option<u64>> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let [byte] = read_array(reader)?;
+
+        let bits = (byte & 0x7f) as u64;
+        if bits << shift >> shift != bits {
+            return Ok(None);
+        }
+        value |= bits << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+
+    Ok(None)
+}
+
+pub(crate) fn write_size<const D: usize>(
+    writer: &mut impl Write,
+    size: SVector<usize, D>,
+) -> io::Result<()> {
+    for &length in size.iter() {
+        writer.write_all(&(length as u64).to_le_bytes())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn write_position<const D: usize>(
+    writer: &mut impl Write,
+    position: SVector<isize, D>,
+) -> io::Result<()> {
+    for &component in position.iter() {
+        writer.write_all(&(component as i64).to_le_bytes())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_size<const D: usize>(
+    reader: &mut impl Read,
+) -> Result<SVector<usize, D>, BinaryError> {
+    let mut size = SVector::<usize, D>::zeros();
+    for length in size.iter_mut() {
+        *length = u64::from_le_bytes(read_array(reader)?)
+            .try_into()
+            .map_err(|_| BinaryError::SizeOverflow)?;
+    }
+    Ok(size)
+}
+
+pub(crate) fn read_position<const D: usize>(
+    reader: &mut impl Read,
+) -> Result<SVector<isize, D>, BinaryError> {
+    let mut position = SVector::<isize, D>::zeros();
+    for component in position.iter_mut() {
+        *component = i64::from_le_bytes(read_array(reader)?)
+            .try_into()
+            .map_err(|_| BinaryError::SizeOverflow)?;
+    }
+    Ok(position)
+}
+
+pub(crate) fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+pub(crate) fn layout_to_byte(layout: Layout) -> u8 {
+    match layout {
+        Layout::RowMajor => 0,
+        Layout::ColumnMajor => 1,
+    }
+}
+
+pub(crate) fn layout_from_byte(byte: u8) -> Option<Layout> {
+    match byte {
+        0 => Some(Layout::RowMajor),
+        1 => Some(Layout::ColumnMajor),
+        _ => None,
+    }
+}
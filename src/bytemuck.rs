@@ -0,0 +1,51 @@
+//! Zero-copy access to the cells of a grid as bytes, for cells which implement `bytemuck::Pod`.
+//!
+//! This is useful for uploading grids to the GPU or hashing their contents. The bytes are in the
+//! native byte order of the platform, so they should not be used as a portable file format; see
+//! the `binary` module for that.
+
+use crate::{
+    coord::{Coordinate, GridVector},
+    util, ExpandableGridN, Layout,
+};
+use bytemuck::Pod;
+
+impl<T: Pod, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Returns the cells of this grid as bytes, in the order given by `layout`.
+    pub fn as_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.data)
+    }
+
+    /// Mutable version of `as_bytes`.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        bytemuck::cast_slice_mut(&mut self.data)
+    }
+
+    /// Creates a row major grid from the bytes of its cells, as returned by `as_bytes`. Returns
+    /// `None` if `bytes` does not contain exactly the cells of a grid of size `size`.
+    ///
+    /// `bytes` does not need to be aligned for `T`.
+    pub fn from_bytes(
+        size: impl GridVector<usize, D>,
+        origin: impl GridVector<C, D>,
+        bytes: &[u8],
+    ) -> Option<Self> {
+        let size = size.to_vector();
+
+        let length = util::checked_area(size)?.checked_mul(std::mem::size_of::<T>())?;
+        if bytes.len() != length {
+            return None;
+        }
+
+        // Copy into a zeroed allocation, since `bytes` may not be aligned for `T`
+        let mut data: Box<[T]> = std::iter::repeat_n(T::zeroed(), size.product()).collect();
+        bytemuck::cast_slice_mut(&mut data).copy_from_slice(bytes);
+
+        Some(Self {
+            size,
+            origin: origin.to_vector(),
+            data,
+            layout: Layout::RowMajor,
+        })
+    }
+}
@@ -0,0 +1,53 @@
+//! Grids which can be modified through shared references, for single threaded code where many
+//! systems need to update the same grid.
+//!
+//! A `CellGrid<T>` is an `ExpandableGridN` of `Cell<T>`, so its cells can be read and written
+//! through a `&CellGrid<T>`, with `get_value`, `set_value`, `replace` and `update`. Changing the
+//! size of the grid still requires a mutable reference, and uses the usual methods.
+
+use crate::{
+    coord::{Coordinate, GridVector},
+    ExpandableGridN,
+};
+use std::cell::Cell;
+
+/// A `D` dimensional grid of `Cell`s. See the `cell` module for details.
+pub type CellGrid<T, const D: usize = 2, C = isize> = ExpandableGridN<Cell<T>, D, C>;
+
+impl<T: Copy, const D: usize, C: Coordinate> ExpandableGridN<Cell<T>, D, C> {
+    /// Creates a grid with the same values as `grid`.
+    pub fn from_values(grid: &ExpandableGridN<T, D, C>) -> Self {
+        grid.map(|&value| Cell::new(value))
+    }
+
+    /// Copies the value of every cell of this grid into a new grid.
+    pub fn to_values(&self) -> ExpandableGridN<T, D, C> {
+        self.map(Cell::get)
+    }
+
+    /// Returns the value of the cell at `index`, or `None` if it is out of bounds.
+    pub fn get_value(&self, index: impl GridVector<C, D>) -> Option<T> {
+        Some(self.get(index)?.get())
+    }
+
+    /// Sets the value of the cell at `index`, returning whether it is within bounds.
+    pub fn set_value(&self, index: impl GridVector<C, D>, value: T) -> bool {
+        self.get(index).map(|cell| cell.set(value)).is_some()
+    }
+
+    /// Sets the value of the cell at `index`, returning its previous value, or `None` if it is out
+    /// of bounds.
+    pub fn replace(&self, index: impl GridVector<C, D>, value: T) -> Option<T> {
+        Some(self.get(index)?.replace(value))
+    }
+
+    /// Sets the value of the cell at `index` to the result of `f` on its current value, returning
+    /// the new value, or `None` if it is out of bounds.
+    pub fn update(&self, index: impl GridVector<C, D>, f: impl FnOnce(T) -> T) -> Option<T> {
+        let cell = self.get(index)?;
+        let value = f(cell.get());
+        cell.set(value);
+
+        Some(value)
+    }
+}
@@ -0,0 +1,228 @@
+//! Cellular automata, which compute the next state of every cell from its current state and the
+//! states of its neighbors, such as Conway's Game of Life or cave generation.
+//!
+//! An `Automaton` combines a `Rule` with the `Neighborhood` of cells passed to it and the
+//! `Boundary` used for neighbors outside the bounds of the grid. `BirthSurvival` implements the
+//! rules of life-like automata, such as `B3/S23`, and any `Fn(&T, &[&T]) -> T` closure is a rule
+//! which is given a cell and its neighbors.
+//!
+//! `Automaton::step` creates a new grid for the next state, while `Automaton::step_buffered`
+//! writes it to the back grid of a `DoubleBufferedGrid`, which avoids allocating every step.
+//! Either way, every cell is computed from the state before the step. To only update the cells
+//! which may change, see the `active` module.
+
+use crate::{double_buffer::DoubleBufferedGrid, util, ExpandableGridN};
+use nalgebra::SVector;
+
+/// Computes the next state of a cell from its current state and the states of its neighbors.
+pub trait Rule<T> {
+    fn next(&self, cell: &T, neighbors: &[&T]) -> T;
+}
+
+impl<T, F: Fn(&T, &[&T]) -> T> Rule<T> for F {
+    fn next(&self, cell: &T, neighbors: &[&T]) -> T {
+        self(cell, neighbors)
+    }
+}
+
+/// The rule of a life-like automaton, where dead cells become alive if their number of living
+/// neighbors is one of the birth counts, and living cells stay alive if it is one of the
+/// survival counts. Counts of 64 or more are not supported.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BirthSurvival {
+    birth: u64,
+    survival: u64,
+}
+
+impl BirthSurvival {
+    pub fn new(birth: &[u32], survival: &[u32]) -> Self {
+        let mask = |counts: &[u32]| counts.iter().fold(0, |mask, count| mask | 1 << count);
+
+        Self {
+            birth: mask(birth),
+            survival: mask(survival),
+        }
+    }
+
+    /// Conway's Game of Life, `B3/S23`.
+    pub fn life() -> Self {
+        Self::new(&[3], &[2, 3])
+    }
+
+    /// Parses a rule in the notation `B3/S23`, where the digits after `B` are the birth counts
+    /// and the digits after `S` are the survival counts. Returns `None` if the notation is
+    /// invalid.
+    pub fn parse(notation: &str) -> Option<Self> {
+        let (birth, survival) = notation.split_once('/')?;
+        let counts = |counts: &str, prefix: char| -> Option<Vec<u32>> {
+            let counts = counts.strip_prefix(prefix)?;
+            counts.chars().map(|digit| digit.to_digit(10)).collect()
+        };
+
+        Some(Self::new(&counts(birth, 'B')?, &counts(survival, 'S')?))
+    }
+
+    pub fn is_birth(&self, count: u32) -> bool {
+        count < 64 && self.birth & 1 << count != 0
+    }
+
+    pub fn is_survival(&self, count: u32) -> bool {
+        count < 64 && self.survival & 1 << count != 0
+    }
+}
+
+impl Rule<bool> for BirthSurvival {
+    fn next(&self, &alive: &bool, neighbors: &[&bool]) -> bool {
+        let count = neighbors.iter().filter(|&&&neighbor| neighbor).count() as u32;
+
+        if alive {
+            self.is_survival(count)
+        } else {
+            self.is_birth(count)
+        }
+    }
+}
+
+/// Which cells around a cell are its neighbors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Neighborhood {
+    /// Every cell within one cell on every axis, 8 cells in 2d.
+    #[default]
+    Moore,
+    /// The cells one cell away along a single axis, 4 cells in 2d.
+    VonNeumann,
+}
+
+impl Neighborhood {
+    /// Returns the offsets of the neighbors of a cell, in the order they are passed to rules.
+    pub fn offsets<const D: usize>(self) -> Vec<SVector<isize, D>> {
+        match self {
+            Neighborhood::Moore => util::iter_box(SVector::zeros(), SVector::<usize, D>::repeat(3))
+                .map(|offset| util::usize_vec_to_isize(offset).add_scalar(-1))
+                .filter(|offset| *offset != SVector::<isize, D>::zeros())
+                .collect(),
+            Neighborhood::VonNeumann => (0..D)
+                .flat_map(|axis| {
+                    [-1, 1].map(|direction| {
+                        let mut offset = SVector::zeros();
+                        offset[axis] = direction;
+                        offset
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+/// How the neighbors of cells outside the bounds of the grid are read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Boundary<T> {
+    /// Every cell outside the grid has this value.
+    Fixed(T),
+    /// The grid wraps around on every axis, like a torus.
+    Wrap,
+    /// Cells outside the grid have the value of the nearest cell within it.
+    Clamp,
+}
+
+/// A rule, along with the neighborhood and boundary it is applied with. See the `cellular`
+/// module for details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Automaton<T, R> {
+    pub rule: R,
+    pub neighborhood: Neighborhood,
+    pub boundary: Boundary<T>,
+}
+
+impl<T, R: Rule<T>> Automaton<T, R> {
+    /// Creates an automaton with the Moore neighborhood.
+    pub fn new(rule: R, boundary: Boundary<T>) -> Self {
+        Self {
+            rule,
+            neighborhood: Neighborhood::Moore,
+            boundary,
+        }
+    }
+
+    pub fn with_neighborhood(self, neighborhood: Neighborhood) -> Self {
+        Self {
+            neighborhood,
+            ..self
+        }
+    }
+
+    /// Returns the next state of `grid`, with the same bounds and layout.
+    pub fn step<const D: usize>(&self, grid: &ExpandableGridN<T, D>) -> ExpandableGridN<T, D> {
+        let offsets = self.neighborhood.offsets();
+        let mut neighbors = Vec::with_capacity(offsets.len());
+
+        ExpandableGridN {
+            size: grid.size,
+            origin: grid.origin,
+            data: (0..grid.data.len())
+                .map(|index| self.next(grid, index, &offsets, &mut neighbors))
+                .collect(),
+            layout: grid.layout,
+        }
+    }
+
+    /// Writes the next state of the front grid of `grid` to its back grid, then swaps them.
+    pub fn step_buffered<const D: usize>(&self, grid: &mut DoubleBufferedGrid<T, D>)
+    where
+        T: Clone,
+    {
+        let offsets = self.neighborhood.offsets();
+
+        grid.step(|front, back| {
+            let mut neighbors = Vec::with_capacity(offsets.len());
+            for index in 0..front.data.len() {
+                back.data[index] = self.next(front, index, &offsets, &mut neighbors);
+            }
+        });
+    }
+
+    /// Returns the next state of the cell stored at `index` within the data of `grid`.
+    fn next<'a, const D: usize>(
+        &'a self,
+        grid: &'a ExpandableGridN<T, D>,
+        index: usize,
+        offsets: &[SVector<isize, D>],
+        neighbors: &mut Vec<&'a T>,
+    ) -> T {
+        let position = util::usize_vec_to_isize(grid.layout.position(index, grid.size));
+
+        neighbors.clear();
+        neighbors.extend(
+            offsets
+                .iter()
+                .map(|offset| self.neighbor(grid, position + offset)),
+        );
+        self.rule.next(&grid.data[index], neighbors)
+    }
+
+    /// Returns the cell at `position`, relative to the origin of `grid`, or the value given by
+    /// the boundary if it is outside of the grid.
+    fn neighbor<'a, const D: usize>(
+        &'a self,
+        grid: &'a ExpandableGridN<T, D>,
+        position: SVector<isize, D>,
+    ) -> &'a T {
+        let size = util::usize_vec_to_isize(grid.size);
+        let position = match &self.boundary {
+            Boundary::Fixed(value) => {
+                if (0..D).any(|axis| position[axis] < 0 || position[axis] >= size[axis]) {
+                    return value;
+                }
+                position
+            }
+            Boundary::Wrap => position.zip_map(&size, isize::rem_euclid),
+            Boundary::Clamp => {
+                position.zip_map(&size, |position, length| position.clamp(0, length - 1))
+            }
+        };
+
+        &grid.data[grid
+            .layout
+            .linear_index(position.map(|length| length as usize), grid.size)]
+    }
+}
@@ -0,0 +1,140 @@
+//! CRC-32 checksums, used by the binary and region formats to detect corrupted data.
+//!
+//! This is the common CRC-32 (ISO-HDLC) used by zip, png, and gzip, so checksums can be checked
+//! with other tools.
+
+use std::io::{self, Read, Write};
+
+const TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+
+    let mut i = 0;
+    while i < 256 {
+        let mut value = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 == 1 {
+                (value >> 1) ^ 0xedb8_8320
+            } else {
+                value >> 1
+            };
+            bit += 1;
+        }
+        table[i] = value;
+        i += 1;
+    }
+
+    table
+};
+
+/// An in progress CRC-32 checksum.
+#[derive(Clone, Copy, Debug)]
+pub struct Crc32(u32);
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Self(0xffff_ffff)
+    }
+
+    /// Adds `bytes` to the data being checksummed.
+    pub fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = TABLE[((self.0 ^ byte as u32) & 0xff) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    /// Returns the checksum of all data added so far.
+    pub fn finish(self) -> u32 {
+        !self.0
+    }
+}
+
+/// Returns the CRC-32 checksum of `bytes`.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(bytes);
+    crc.finish()
+}
+
+/// A writer which computes the checksum of everything written through it.
+#[derive(Debug)]
+pub struct ChecksumWriter<W> {
+    pub inner: W,
+    crc: Crc32,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            crc: Crc32::new(),
+        }
+    }
+
+    /// Returns the checksum of everything written so far.
+    pub fn checksum(&self) -> u32 {
+        self.crc.finish()
+    }
+
+    /// Writes the checksum of everything written so far to the inner writer, as a little endian
+    /// `u32`.
+    pub fn write_checksum(&mut self) -> io::Result<()> {
+        let checksum = self.checksum();
+        self.inner.write_all(&checksum.to_le_bytes())
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(bytes)?;
+        self.crc.update(&bytes[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A reader which computes the checksum of everything read through it.
+#[derive(Debug)]
+pub struct ChecksumReader<R> {
+    pub inner: R,
+    crc: Crc32,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            crc: Crc32::new(),
+        }
+    }
+
+    /// Returns the checksum of everything read so far.
+    pub fn checksum(&self) -> u32 {
+        self.crc.finish()
+    }
+
+    /// Reads a checksum stored as a little endian `u32` from the inner reader, without including
+    /// it in the checksum of everything read so far.
+    pub fn read_stored_checksum(&mut self) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        self.inner.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, bytes: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(bytes)?;
+        self.crc.update(&bytes[..read]);
+        Ok(read)
+    }
+}
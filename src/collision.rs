@@ -0,0 +1,179 @@
+//! Collision queries between floating point boxes and the solid cells of a grid.
+//!
+//! As with `GridSegment`, the cell at `[x, y]` covers the square from `[x, y]` to
+//! `[x + 1, y + 1]`. Boxes only overlap cells they share some area with, so a box resting exactly
+//! on top of a solid cell does not collide with it. Which cells are solid is decided by a
+//! predicate, and cells outside the bounds of the grid are never solid.
+//!
+//! `ExpandableGridN::sweep_rect` moves a box until it first touches a solid cell. Since touching
+//! is not overlapping, a box can slide along a row of solid cells without catching on the seams
+//! between them.
+
+use crate::{
+    coord::{self, Coordinate, GridVector},
+    rect::GridRect,
+    ExpandableGridN,
+};
+use nalgebra::SVector;
+
+/// An axis aligned bounding box from `min` to `max`, in the same units as grid coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb<const D: usize = 2> {
+    pub min: SVector<f32, D>,
+    pub max: SVector<f32, D>,
+}
+
+impl<const D: usize> Aabb<D> {
+    pub fn new(min: impl GridVector<f32, D>, max: impl GridVector<f32, D>) -> Self {
+        Self {
+            min: min.to_vector(),
+            max: max.to_vector(),
+        }
+    }
+
+    /// Creates a box with its lowest corner at `origin` and size `size`.
+    pub fn with_size(origin: impl GridVector<f32, D>, size: impl GridVector<f32, D>) -> Self {
+        let origin = origin.to_vector();
+        Self::new(origin, origin + size.to_vector())
+    }
+
+    /// Returns the size of this box on every axis.
+    pub fn size(&self) -> SVector<f32, D> {
+        self.max - self.min
+    }
+
+    /// Returns this box moved by `offset`.
+    pub fn translated(&self, offset: impl GridVector<f32, D>) -> Self {
+        let offset = offset.to_vector();
+        Self::new(self.min + offset, self.max + offset)
+    }
+
+    /// Returns the rect of cells this box overlaps, which is empty if the box has no area.
+    pub fn cells(&self) -> GridRect<D> {
+        let low = self.min.map(|component| component.floor() as isize);
+        let high = self.max.map(|component| component.ceil() as isize);
+
+        GridRect::new(low, (high - low).map(|length| length.max(0) as usize))
+    }
+}
+
+/// The result of moving a box with `ExpandableGridN::sweep_rect`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SweepResult<const D: usize = 2, C: Coordinate = isize> {
+    /// The fraction of the velocity the box moved before touching a solid cell, from 0 to 1.
+    pub time: f32,
+    /// The direction from the solid cell to the box along the axis they touched on, or zero if
+    /// the box did not touch a solid cell.
+    pub normal: SVector<f32, D>,
+    /// The box at its resolved position, moved by `time * velocity`.
+    pub rect: Aabb<D>,
+    /// The coordinates of the solid cell the box touched, or `None` if it moved freely.
+    pub cell: Option<SVector<C, D>>,
+}
+
+impl<const D: usize, C: Coordinate> SweepResult<D, C> {
+    /// Returns `true` if the box touched a solid cell.
+    pub fn hit(&self) -> bool {
+        self.cell.is_some()
+    }
+}
+
+impl<T, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Moves `rect` by `velocity` until it touches a cell for which `is_solid` returns `true`,
+    /// returning when and where it stopped. Solid cells which `rect` already overlaps are
+    /// ignored, so a box which starts inside a wall can still move out of it.
+    pub fn sweep_rect(
+        &self,
+        rect: Aabb<D>,
+        velocity: impl GridVector<f32, D>,
+        is_solid: impl Fn(&T) -> bool,
+    ) -> SweepResult<D, C> {
+        let velocity = velocity.to_vector();
+        let moved = rect.translated(velocity);
+        let swept = Aabb::new(rect.min.inf(&moved.min), rect.max.sup(&moved.max));
+
+        let mut result = SweepResult {
+            time: 1.0,
+            normal: SVector::zeros(),
+            rect: moved,
+            cell: None,
+        };
+        for (position, _) in self.overlapping_solid_cells(swept, is_solid) {
+            let Some((time, axis)) = time_of_impact(&rect, velocity, coord::to_isize_vec(position))
+            else {
+                continue;
+            };
+            if result.cell.is_none() || time < result.time {
+                result.time = time;
+                result.normal = SVector::zeros();
+                result.normal[axis] = -velocity[axis].signum();
+                result.cell = Some(position);
+            }
+        }
+
+        result.rect = rect.translated(velocity * result.time);
+        result
+    }
+
+    /// Returns `true` if `rect` overlaps any cell for which `is_solid` returns `true`.
+    pub fn collides_rect(&self, rect: Aabb<D>, is_solid: impl Fn(&T) -> bool) -> bool {
+        self.overlapping_solid_cells(rect, is_solid)
+            .next()
+            .is_some()
+    }
+
+    /// Iterates over the coordinates and values of every cell overlapped by `rect` for which
+    /// `is_solid` returns `true`.
+    pub fn overlapping_solid_cells(
+        &self,
+        rect: Aabb<D>,
+        is_solid: impl Fn(&T) -> bool,
+    ) -> impl Iterator<Item = (SVector<C, D>, &T)> {
+        // Clip the cells to the grid first, since they may not fit in `C`
+        let bounds = GridRect::new(coord::to_isize_vec(self.origin), self.size);
+        let cells = (bounds.intersection(&rect.cells()))
+            .map(|cells| GridRect::new(coord::from_isize_vec(cells.origin), cells.size))
+            .unwrap_or(GridRect::new(self.origin, SVector::zeros()));
+
+        self.view(cells)
+            .iter()
+            .filter(move |(_, cell)| is_solid(cell))
+    }
+}
+
+/// Returns the fraction of `velocity` `rect` moves before touching the cell at `cell`, and the
+/// axis it touches on, or `None` if it never touches the cell or already overlaps it.
+fn time_of_impact<const D: usize>(
+    rect: &Aabb<D>,
+    velocity: SVector<f32, D>,
+    cell: SVector<isize, D>,
+) -> Option<(f32, usize)> {
+    let (mut entry, mut exit) = (f32::NEG_INFINITY, f32::INFINITY);
+    let mut entry_axis = None;
+
+    for axis in 0..D {
+        let (low, high) = (cell[axis] as f32, cell[axis] as f32 + 1.0);
+
+        if velocity[axis] == 0.0 {
+            // The box never moves on this axis, so it must already be between the cell's sides
+            if rect.max[axis] <= low || rect.min[axis] >= high {
+                return None;
+            }
+            continue;
+        }
+
+        let a = (low - rect.max[axis]) / velocity[axis];
+        let b = (high - rect.min[axis]) / velocity[axis];
+        let (axis_entry, axis_exit) = (a.min(b), a.max(b));
+
+        if axis_entry > entry {
+            entry = axis_entry;
+            entry_axis = Some(axis);
+        }
+        exit = exit.min(axis_exit);
+    }
+
+    // A box which never moves has no entry axis, and never touches a cell it doesn't overlap
+    let axis = entry_axis?;
+    (entry < exit && (0.0..=1.0).contains(&entry)).then_some((entry, axis))
+}
@@ -0,0 +1,43 @@
+//! LZ4 compression of grids in the binary format, enabled with the `compression` feature.
+//!
+//! Compressed grids are a standard LZ4 frame containing a grid in the format described in the
+//! `binary` module, so they can also be decompressed with any LZ4 implementation.
+
+use crate::{
+    binary::{BinaryCell, BinaryError},
+    coord::Coordinate,
+    ExpandableGridN,
+};
+use lz4_flex::frame::{FrameDecoder, FrameEncoder};
+use std::io::{self, Read, Write};
+
+impl<T: BinaryCell, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Writes this grid to `writer` in the binary format, compressed with LZ4.
+    pub fn write_compressed(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut encoder = FrameEncoder::new(writer);
+        self.write_binary(&mut encoder)?;
+        encoder.finish().map_err(io::Error::other)?;
+
+        Ok(())
+    }
+
+    /// Reads a grid written by `write_compressed` from `reader`.
+    pub fn read_compressed(reader: &mut impl Read) -> Result<Self, BinaryError> {
+        Self::read_binary(&mut FrameDecoder::new(reader))
+    }
+}
+
+/// Compresses `bytes` into an LZ4 frame. This can be used for data which is not a single grid,
+/// such as a collection of separately serialized chunks.
+pub fn compress(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = FrameEncoder::new(Vec::new());
+    encoder.write_all(bytes)?;
+    encoder.finish().map_err(io::Error::other)
+}
+
+/// Decompresses an LZ4 frame created by `compress`.
+pub fn decompress(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    FrameDecoder::new(bytes).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
@@ -0,0 +1,254 @@
+//! A grid which can be read, written, and expanded from many threads at once.
+//!
+//! A `ConcurrentGrid` stores its cells in chunks of a fixed size, each behind its own `RwLock`,
+//! so threads working on different chunks never wait for each other. The chunks themselves are
+//! indexed by an `ExpandableGridN` behind another `RwLock`. To expand the grid, a copy of this
+//! index is expanded and its new chunks are allocated and filled without holding any lock; the
+//! index is then only locked for writing long enough to swap in the copy. If another thread
+//! expanded the grid in the meantime, the expansion starts over from its new index. The contents
+//! of existing chunks are never locked or copied by expansion.
+
+use crate::{coord::GridVector, memory::MemoryUsage, util, ExpandableGridN, Layout};
+use nalgebra::SVector;
+use std::{
+    mem::size_of,
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+type Chunk<T> = Arc<RwLock<Box<[T]>>>;
+
+/// A `D` dimensional grid which can be shared between threads, with each fixed size chunk of
+/// cells locked separately. See the `concurrent` module for details.
+#[derive(Debug)]
+pub struct ConcurrentGrid<T, const D: usize = 2> {
+    chunk_size: SVector<usize, D>,
+    fill: T,
+    /// Every chunk within the bounds of this grid is `Some`.
+    chunks: RwLock<ExpandableGridN<Option<Chunk<T>>, D>>,
+}
+
+impl<T: Clone, const D: usize> ConcurrentGrid<T, D> {
+    /// Creates a new, empty grid, which is expanded by chunks of size `chunk_size` filled with
+    /// clones of `fill`.
+    ///
+    /// # Panics
+    /// Panics if `chunk_size` is 0 on any axis.
+    pub fn new(chunk_size: impl GridVector<usize, D>, fill: T) -> Self {
+        let chunk_size = chunk_size.to_vector();
+        assert!(
+            chunk_size.iter().all(|&length| length > 0),
+            "chunk size should not be 0 on any axis",
+        );
+
+        Self {
+            chunk_size,
+            fill,
+            chunks: RwLock::new(ExpandableGridN::new()),
+        }
+    }
+
+    /// Creates a grid with the same cells as `grid`, expanded by chunks of size `chunk_size`
+    /// filled with clones of `fill`. Cells outside the bounds of `grid` but within its chunks
+    /// are also filled with `fill`.
+    ///
+    /// # Panics
+    /// Panics if `chunk_size` is 0 on any axis.
+    pub fn from_grid(
+        grid: &ExpandableGridN<T, D>,
+        chunk_size: impl GridVector<usize, D>,
+        fill: T,
+    ) -> Self {
+        let concurrent = Self::new(chunk_size, fill);
+        concurrent.expand_to_fit_box(grid.origin, grid.size);
+
+        for offset in util::iter_box(SVector::zeros(), grid.size) {
+            let index = grid.origin + util::usize_vec_to_isize(offset);
+            concurrent.set(index, grid[index].clone());
+        }
+
+        concurrent
+    }
+
+    /// Returns the size of each chunk of this grid.
+    pub fn chunk_size(&self) -> SVector<usize, D> {
+        self.chunk_size
+    }
+
+    /// Returns the number of cells on each axis of this grid, which is always a multiple of the
+    /// chunk size.
+    pub fn size(&self) -> SVector<usize, D> {
+        read(&self.chunks).size.component_mul(&self.chunk_size)
+    }
+
+    /// Returns the coordinates of the lowest corner of this grid, which is always a multiple of
+    /// the chunk size.
+    pub fn origin(&self) -> SVector<isize, D> {
+        read(&self.chunks)
+            .origin
+            .component_mul(&util::usize_vec_to_isize(self.chunk_size))
+    }
+
+    /// Returns a clone of the cell at `index`, or `None` if it is out of bounds.
+    pub fn get(&self, index: impl GridVector<isize, D>) -> Option<T> {
+        self.read(index, T::clone)
+    }
+
+    /// Sets the cell at `index` to `value`, returning the previous value, or `None` if it is out
+    /// of bounds.
+    pub fn set(&self, index: impl GridVector<isize, D>, value: T) -> Option<T> {
+        self.modify(index, |cell| std::mem::replace(cell, value))
+    }
+
+    /// Calls `f` with the cell at `index` while its chunk is locked for reading, returning its
+    /// result, or `None` if the cell is out of bounds.
+    pub fn read<R>(&self, index: impl GridVector<isize, D>, f: impl FnOnce(&T) -> R) -> Option<R> {
+        let (chunk, index) = self.chunk_of(index.to_vector())?;
+        let chunk = read(&chunk);
+        Some(f(&chunk[index]))
+    }
+
+    /// Calls `f` with the cell at `index` while its chunk is locked for writing, returning its
+    /// result, or `None` if the cell is out of bounds.
+    pub fn modify<R>(
+        &self,
+        index: impl GridVector<isize, D>,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Option<R> {
+        let (chunk, index) = self.chunk_of(index.to_vector())?;
+        let mut chunk = write(&chunk);
+        Some(f(&mut chunk[index]))
+    }
+
+    /// Increases the size of the grid such that `point` is within its bounds. See
+    /// `ExpandableGridN::expand_to_fit_point`.
+    pub fn expand_to_fit_point(&self, point: impl GridVector<isize, D>) {
+        self.expand_to_fit_box(point, SVector::<usize, D>::repeat(1));
+    }
+
+    /// Increases the size of the grid such that every cell of the box with its lowest corner at
+    /// `box_origin` and size `box_size` is within its bounds. New chunks are filled with clones of
+    /// the fill value of this grid. See `ExpandableGridN::expand_to_fit_box`.
+    pub fn expand_to_fit_box(
+        &self,
+        box_origin: impl GridVector<isize, D>,
+        box_size: impl GridVector<usize, D>,
+    ) {
+        let Some((first_chunk, last_chunk)) = util::chunk_range(
+            box_origin.to_vector(),
+            box_size.to_vector(),
+            self.chunk_size,
+        ) else {
+            return;
+        };
+        let chunk_box_size = (last_chunk - first_chunk).map(|length| length as usize + 1);
+
+        let chunk_area = self.chunk_size.product();
+
+        loop {
+            // Copy the index of chunks, which only clones their handles
+            let mut expanded = {
+                let chunks = read(&self.chunks);
+                if chunks.index_of(first_chunk).is_some() && chunks.index_of(last_chunk).is_some() {
+                    return;
+                }
+                chunks.clone()
+            };
+            let (size, origin) = (expanded.size, expanded.origin);
+
+            // Allocate the new chunks while no lock is held
+            expanded.expand_to_fit_box(first_chunk, chunk_box_size, &None);
+            for chunk in expanded.data.iter_mut().filter(|chunk| chunk.is_none()) {
+                let cells = std::iter::repeat_n(self.fill.clone(), chunk_area).collect();
+                *chunk = Some(Arc::new(RwLock::new(cells)));
+            }
+
+            let mut chunks = write(&self.chunks);
+            // Another thread may have expanded the grid since it was copied, in which case the
+            // copy is missing its chunks
+            if chunks.size == size && chunks.origin == origin {
+                let previous = std::mem::replace(&mut *chunks, expanded);
+                drop(chunks);
+                drop(previous);
+                return;
+            }
+        }
+    }
+
+    /// Copies every cell of this grid into a new `ExpandableGridN`. Each chunk is locked for
+    /// reading while it is copied, so the copy is only consistent if no other thread is writing to
+    /// the grid.
+    pub fn to_grid(&self) -> ExpandableGridN<T, D> {
+        let chunks = read(&self.chunks);
+        let chunk_origin = chunks.origin;
+        let size = chunks.size.component_mul(&self.chunk_size);
+        let origin = chunk_origin.component_mul(&util::usize_vec_to_isize(self.chunk_size));
+
+        let mut grid = ExpandableGridN::with_size(size, origin, &self.fill);
+        for chunk_offset in util::iter_box(SVector::zeros(), chunks.size) {
+            let chunk_index = chunk_origin + util::usize_vec_to_isize(chunk_offset);
+            let chunk = read(chunks[chunk_index].as_ref().unwrap());
+            let chunk_start =
+                origin + util::usize_vec_to_isize(chunk_offset.component_mul(&self.chunk_size));
+
+            for offset in util::iter_box(SVector::zeros(), self.chunk_size) {
+                let cell = &chunk[Self::linear_index(offset, self.chunk_size)];
+                grid[chunk_start + util::usize_vec_to_isize(offset)] = cell.clone();
+            }
+        }
+
+        grid
+    }
+
+    /// Returns the memory used by this grid, not counting memory owned by cells. See the `memory`
+    /// module for details.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.memory_usage_with(|_| 0)
+    }
+
+    /// Returns the memory used by this grid, where `heap_bytes` returns the bytes on the heap
+    /// owned by each cell. Each chunk is locked for reading while it is measured.
+    pub fn memory_usage_with(&self, mut heap_bytes: impl FnMut(&T) -> usize) -> MemoryUsage {
+        let chunks = read(&self.chunks);
+        let chunk_area = self.chunk_size.product();
+        // The reference counts and lock stored alongside each chunk
+        let chunk_overhead = 2 * size_of::<usize>() + size_of::<RwLock<Box<[T]>>>();
+
+        let mut usage = MemoryUsage {
+            cells: chunks.data.len() * chunk_area,
+            chunk_overhead_bytes: chunks.data.len() * size_of::<Option<Chunk<T>>>(),
+            ..MemoryUsage::default()
+        };
+        usage.used_bytes = usage.cells * size_of::<T>();
+
+        for chunk in chunks.data.iter().flatten() {
+            usage.chunks += 1;
+            usage.buffer_bytes += chunk_area * size_of::<T>();
+            usage.chunk_overhead_bytes += chunk_overhead;
+            usage.heap_bytes += read(chunk).iter().map(&mut heap_bytes).sum::<usize>();
+        }
+
+        usage
+    }
+
+    /// Returns the chunk containing the cell at `index` and the index of the cell within it.
+    fn chunk_of(&self, index: SVector<isize, D>) -> Option<(Chunk<T>, usize)> {
+        let (chunk, offset) = util::split_chunk_index(index, self.chunk_size);
+        let chunk = read(&self.chunks).get(chunk)?.clone()?;
+
+        Some((chunk, Self::linear_index(offset, self.chunk_size)))
+    }
+
+    fn linear_index(offset: SVector<usize, D>, chunk_size: SVector<usize, D>) -> usize {
+        Layout::RowMajor.linear_index(offset, chunk_size)
+    }
+}
+
+/// Locks `lock` for reading. Poisoning is ignored, as cells are always left valid.
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Locks `lock` for writing. Poisoning is ignored, as cells are always left valid.
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
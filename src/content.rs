@@ -0,0 +1,225 @@
+//! Tracking the bounds of the content of a grid, ignoring cells with an "empty" value.
+//!
+//! A `ContentGrid` wraps a grid, keeping a count of the cells which are not equal to its empty
+//! value within each row, column and so on, along each axis. These counts are updated by every
+//! write made through it, so `content_bounds` only needs to look past the empty rows and columns
+//! at the edges of the grid, rather than at every cell. The counts are rebuilt from scratch when
+//! the bounds of the grid change, unless it only expanded with empty cells.
+
+use crate::{coord::GridVector, rect::GridRect, util, ExpandableGridN};
+use nalgebra::SVector;
+use std::ops::Deref;
+
+/// A grid which tracks the bounds of its cells which are not empty. See the `content` module for
+/// details.
+#[derive(Clone, Debug)]
+pub struct ContentGrid<T, const D: usize = 2> {
+    grid: ExpandableGridN<T, D>,
+    empty: T,
+    /// For each axis, the number of cells which are not empty at each coordinate along it,
+    /// relative to the origin of the grid.
+    counts: [Vec<usize>; D],
+}
+
+impl<T: PartialEq + Clone, const D: usize> ContentGrid<T, D> {
+    /// Wraps `grid`, where cells equal to `empty` are ignored. This takes time proportional to
+    /// the area of the grid.
+    pub fn new(grid: ExpandableGridN<T, D>, empty: T) -> Self {
+        let mut content = Self {
+            grid,
+            empty,
+            counts: std::array::from_fn(|_| Vec::new()),
+        };
+        content.recount();
+        content
+    }
+
+    pub fn into_inner(self) -> ExpandableGridN<T, D> {
+        self.grid
+    }
+
+    /// Returns the value of cells which are ignored.
+    pub fn empty(&self) -> &T {
+        &self.empty
+    }
+
+    /// Returns the smallest rect containing every cell which is not empty, or `None` if every
+    /// cell is empty.
+    pub fn content_bounds(&self) -> Option<GridRect<D>> {
+        let mut rect = self.grid.bounds();
+
+        for (axis, counts) in self.counts.iter().enumerate() {
+            let first = counts.iter().position(|&count| count > 0)?;
+            let last = counts.iter().rposition(|&count| count > 0)?;
+
+            rect.origin[axis] += first as isize;
+            rect.size[axis] = last - first + 1;
+        }
+        Some(rect)
+    }
+
+    /// Changes the bounds of the grid to exactly its content bounds, or to an empty grid if every
+    /// cell is empty.
+    pub fn shrink_to_content(&mut self) {
+        let bounds = self
+            .content_bounds()
+            .unwrap_or(GridRect::new(self.grid.origin, SVector::zeros()));
+
+        let empty = self.empty.clone();
+        self.change_size(bounds.size, bounds.origin - self.grid.origin, &empty);
+    }
+
+    /// Sets the cell at `index` to `value`, returning the previous value, or `None` if it is out
+    /// of bounds.
+    pub fn set(&mut self, index: impl GridVector<isize, D>, value: T) -> Option<T> {
+        self.modify(index, |cell| std::mem::replace(cell, value))
+    }
+
+    /// Calls `f` with a mutable reference to the cell at `index`, returning its result, or `None`
+    /// if it is out of bounds.
+    pub fn modify<R>(
+        &mut self,
+        index: impl GridVector<isize, D>,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Option<R> {
+        let index = index.to_vector();
+        let position = util::relative_position(self.grid.origin, self.grid.size, index)?;
+        let cell = &mut self.grid[index];
+
+        let was_empty = *cell == self.empty;
+        let result = f(cell);
+        let is_empty = *cell == self.empty;
+
+        if was_empty != is_empty {
+            self.count(position, !is_empty);
+        }
+        Some(result)
+    }
+
+    /// Sets every cell of `rect` within the bounds of the grid to clones of `value`.
+    pub fn fill_rect(&mut self, rect: GridRect<D>, value: &T) {
+        let Some((origin, size)) =
+            util::intersect_boxes(self.grid.origin, self.grid.size, rect.origin, rect.size)
+        else {
+            return;
+        };
+
+        for offset in util::iter_box(SVector::zeros(), size) {
+            self.set(origin + util::usize_vec_to_isize(offset), value.clone());
+        }
+    }
+
+    /// See `ExpandableGridN::blit`.
+    pub fn blit(&mut self, source: &ExpandableGridN<T, D>) {
+        let Some((origin, size)) =
+            util::intersect_boxes(self.grid.origin, self.grid.size, source.origin, source.size)
+        else {
+            return;
+        };
+
+        for offset in util::iter_box(SVector::zeros(), size) {
+            let position = origin + util::usize_vec_to_isize(offset);
+            self.set(position, source[position].clone());
+        }
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_point`.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<isize, D>, fill: &T) {
+        self.expand_to_fit_box(point, SVector::<usize, D>::repeat(1), fill);
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_box`.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<isize, D>,
+        box_size: impl GridVector<usize, D>,
+        fill: &T,
+    ) {
+        let old_origin = self.grid.origin;
+        self.grid.expand_to_fit_box(box_origin, box_size, fill);
+        self.update_counts(old_origin, fill);
+    }
+
+    /// See `ExpandableGridN::change_size`.
+    pub fn change_size(
+        &mut self,
+        new_size: impl GridVector<usize, D>,
+        offset: impl GridVector<isize, D>,
+        fill: &T,
+    ) {
+        let old_origin = self.grid.origin;
+        self.grid.change_size(new_size, offset, fill);
+        self.update_counts(old_origin, fill);
+    }
+
+    /// Updates the counts after the bounds of the grid change from having their origin at
+    /// `old_origin`.
+    fn update_counts(&mut self, old_origin: SVector<isize, D>, fill: &T) {
+        let old_size = SVector::<usize, D>::from_fn(|axis, _| self.counts[axis].len());
+        let old_rect = GridRect::new(old_origin, old_size);
+        let rect = self.grid.bounds();
+
+        if rect == old_rect {
+            return;
+        }
+
+        // Only expanding with empty cells leaves every count the same, just moved
+        let expanded = (0..D).all(|axis| {
+            rect.origin[axis] <= old_rect.origin[axis] && old_rect.end()[axis] <= rect.end()[axis]
+        });
+        if !(expanded && *fill == self.empty && old_size.product() > 0) {
+            self.recount();
+            return;
+        }
+
+        for (axis, counts) in self.counts.iter_mut().enumerate() {
+            let before = (old_rect.origin[axis] - rect.origin[axis]) as usize;
+            let after = (rect.end()[axis] - old_rect.end()[axis]) as usize;
+
+            counts.splice(0..0, std::iter::repeat_n(0, before));
+            counts.extend(std::iter::repeat_n(0, after));
+        }
+    }
+
+    fn recount(&mut self) {
+        for (axis, counts) in self.counts.iter_mut().enumerate() {
+            counts.clear();
+            counts.resize(self.grid.size[axis], 0);
+        }
+
+        for position in util::iter_box(SVector::zeros(), self.grid.size) {
+            let index = self.grid.origin + util::usize_vec_to_isize(position);
+            if self.grid[index] != self.empty {
+                self.count(position, true);
+            }
+        }
+    }
+
+    /// Adds or removes a cell which is not empty at `position`, relative to the origin.
+    fn count(&mut self, position: SVector<usize, D>, add: bool) {
+        for (axis, counts) in self.counts.iter_mut().enumerate() {
+            let count = &mut counts[position[axis]];
+            if add {
+                *count += 1;
+            } else {
+                *count -= 1;
+            }
+        }
+    }
+}
+
+impl<T, const D: usize> Deref for ContentGrid<T, D> {
+    type Target = ExpandableGridN<T, D>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.grid
+    }
+}
+
+impl<T, I: GridVector<isize, D>, const D: usize> std::ops::Index<I> for ContentGrid<T, D> {
+    type Output = T;
+
+    fn index(&self, index: I) -> &Self::Output {
+        &self.grid[index]
+    }
+}
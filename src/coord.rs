@@ -0,0 +1,161 @@
+//! The `GridVector` trait, which lets coordinates and sizes be given to grids in types other than
+//! `nalgebra::SVector`.
+//!
+//! Grids store their size and origin as `nalgebra` vectors, but every method which takes a
+//! coordinate or size accepts any `GridVector`, so application code using another math crate
+//! can pass its own vector types directly. `GridVector` is implemented for `nalgebra` vectors and
+//! points, arrays, and tuples of 2 or 3 components, and with the `mint` and `glam` features, for
+//! their vector types. This means that `grid[(x, y)]` and `grid[[x, y]]` work as well as
+//! `grid[vector![x, y]]`.
+//!
+//! The scalar type of a grid's coordinates can be chosen with its last type parameter, which is
+//! any `Coordinate` and defaults to `isize`. Grids always calculate with `isize` internally, so
+//! this only affects how coordinates are stored and passed to the grid, which lets applications
+//! use `i32` coordinates to save memory, or `i64` to be explicit about the range of their world.
+//!
+//! The methods added to grids by the other modules, such as searching, views, handles, and the
+//! binary and readable forms, work with any `Coordinate`, as do `GridRect`, `DirtyGrid`,
+//! `GridHistory` and `DoubleBufferedGrid`, and the `glam` vector types. Other types which wrap or
+//! own a grid of their own, such as `ConcurrentGrid`, `RegionGrid` and the region readers of
+//! `persistence`, as well as the hex helpers and the conversions to and from other crates' types
+//! (`image`, `bevy`, `SmallGrid`, the FFI and scripting bindings, and the `Arbitrary`
+//! implementations), still only use `isize` coordinates.
+
+use nalgebra::{Point, SVector, Scalar};
+use std::{fmt::Debug, hash::Hash};
+
+/// A `D` dimensional vector with components of type `N` that can be converted to and from
+/// `nalgebra::SVector<N, D>`. Coordinates are `GridVector<isize, D>`, and sizes are
+/// `GridVector<usize, D>`.
+pub trait GridVector<N, const D: usize>: Copy {
+    /// Converts this vector to the vector type used within grids.
+    fn to_vector(self) -> SVector<N, D>;
+
+    /// Converts a vector used within grids to this type.
+    ///
+    /// # Panics
+    /// May panic if the components of `vector` cannot be represented by this type.
+    fn from_vector(vector: SVector<N, D>) -> Self;
+}
+
+impl<N: Copy, const D: usize> GridVector<N, D> for SVector<N, D> {
+    fn to_vector(self) -> SVector<N, D> {
+        self
+    }
+
+    fn from_vector(vector: SVector<N, D>) -> Self {
+        vector
+    }
+}
+
+impl<N: nalgebra::Scalar + Copy, const D: usize> GridVector<N, D> for Point<N, D> {
+    fn to_vector(self) -> SVector<N, D> {
+        self.coords
+    }
+
+    fn from_vector(vector: SVector<N, D>) -> Self {
+        Point::from(vector)
+    }
+}
+
+impl<N: Scalar + Copy, const D: usize> GridVector<N, D> for [N; D] {
+    fn to_vector(self) -> SVector<N, D> {
+        SVector::from(self)
+    }
+
+    fn from_vector(vector: SVector<N, D>) -> Self {
+        vector.into()
+    }
+}
+
+impl<N: Scalar + Copy> GridVector<N, 2> for (N, N) {
+    fn to_vector(self) -> SVector<N, 2> {
+        SVector::from([self.0, self.1])
+    }
+
+    fn from_vector(vector: SVector<N, 2>) -> Self {
+        (vector[0], vector[1])
+    }
+}
+
+impl<N: Scalar + Copy> GridVector<N, 3> for (N, N, N) {
+    fn to_vector(self) -> SVector<N, 3> {
+        SVector::from([self.0, self.1, self.2])
+    }
+
+    fn from_vector(vector: SVector<N, 3>) -> Self {
+        (vector[0], vector[1], vector[2])
+    }
+}
+
+/// A signed integer type which can be used for the coordinates of a grid.
+pub trait Coordinate: Scalar + Copy + Ord + Hash + Debug + Send + Sync {
+    /// Converts this coordinate to the type grids calculate with.
+    ///
+    /// # Panics
+    /// Panics if this coordinate does not fit in an `isize`.
+    fn to_isize(self) -> isize;
+
+    /// Converts a coordinate from the type grids calculate with.
+    ///
+    /// # Panics
+    /// Panics if `value` does not fit in this type.
+    fn from_isize(value: isize) -> Self;
+
+    /// Converts a coordinate from the type grids calculate with, or returns `None` if `value`
+    /// does not fit in this type.
+    fn checked_from_isize(value: isize) -> Option<Self>;
+}
+
+macro_rules! impl_coordinate {
+    ($($scalar:ty),*) => {
+        $(
+            impl Coordinate for $scalar {
+                fn to_isize(self) -> isize {
+                    self.try_into().expect("coordinate should fit in an isize")
+                }
+
+                fn from_isize(value: isize) -> Self {
+                    Self::checked_from_isize(value)
+                        .expect(concat!("coordinate should fit in an ", stringify!($scalar)))
+                }
+
+                fn checked_from_isize(value: isize) -> Option<Self> {
+                    value.try_into().ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_coordinate!(i8, i16, i32, i64, isize);
+
+/// Converts a vector of coordinates to the type grids calculate with.
+pub(crate) fn to_isize_vec<C: Coordinate, const D: usize>(
+    vector: SVector<C, D>,
+) -> SVector<isize, D> {
+    vector.map(C::to_isize)
+}
+
+/// Converts a vector from the type grids calculate with to coordinates.
+pub(crate) fn from_isize_vec<C: Coordinate, const D: usize>(
+    vector: SVector<isize, D>,
+) -> SVector<C, D> {
+    vector.map(C::from_isize)
+}
+
+/// Converts the origin of a grid of size `size` from the type grids calculate with, or returns
+/// `None` if the coordinates of any cell of the grid, or of its corner, do not fit in `C`.
+pub(crate) fn checked_origin<C: Coordinate, const D: usize>(
+    origin: SVector<isize, D>,
+    size: SVector<usize, D>,
+) -> Option<SVector<C, D>> {
+    (0..D)
+        .all(|axis| {
+            let corner = (isize::try_from(size[axis]).ok())
+                .and_then(|length| origin[axis].checked_add(length));
+            C::checked_from_isize(origin[axis]).is_some()
+                && corner.and_then(C::checked_from_isize).is_some()
+        })
+        .then(|| from_isize_vec(origin))
+}
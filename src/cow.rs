@@ -0,0 +1,213 @@
+//! A grid which can be snapshotted cheaply, by sharing its chunks between copies.
+//!
+//! A `CowGrid` stores its cells in chunks of a fixed size, each behind an `Arc`. Cloning the grid
+//! with `snapshot` only clones the `Arc` of each chunk, and a chunk shared with a snapshot is only
+//! copied when one of its cells is first written to afterwards. Snapshots can be sent to another
+//! thread, such as one saving or rendering the world, while the original grid keeps changing.
+//!
+//! New chunks created by expansion share a single chunk of the fill value until they are written
+//! to, so expanding a grid far beyond the cells in use costs little memory.
+
+use crate::{coord::GridVector, memory::MemoryUsage, util, ExpandableGridN, Layout};
+use nalgebra::SVector;
+use std::{collections::HashSet, mem::size_of, sync::Arc};
+
+type Chunk<T> = Arc<Box<[T]>>;
+
+/// A `D` dimensional grid which shares its chunks with its snapshots. See the `cow` module for
+/// details.
+#[derive(Clone, Debug)]
+pub struct CowGrid<T, const D: usize = 2> {
+    chunk_size: SVector<usize, D>,
+    /// The chunk every new chunk starts as.
+    fill: Chunk<T>,
+    chunks: ExpandableGridN<Chunk<T>, D>,
+}
+
+impl<T: Clone, const D: usize> CowGrid<T, D> {
+    /// Creates a new, empty grid, which is expanded by chunks of size `chunk_size` filled with
+    /// clones of `fill`.
+    ///
+    /// # Panics
+    /// Panics if `chunk_size` is 0 on any axis.
+    pub fn new(chunk_size: impl GridVector<usize, D>, fill: T) -> Self {
+        let chunk_size = chunk_size.to_vector();
+        assert!(
+            chunk_size.iter().all(|&length| length > 0),
+            "chunk size should not be 0 on any axis",
+        );
+
+        Self {
+            chunk_size,
+            fill: Arc::new(std::iter::repeat_n(fill, chunk_size.product()).collect()),
+            chunks: ExpandableGridN::new(),
+        }
+    }
+
+    /// Creates a grid with the same cells as `grid`, expanded by chunks of size `chunk_size`
+    /// filled with clones of `fill`. Cells outside the bounds of `grid` but within its chunks
+    /// are also filled with `fill`.
+    ///
+    /// # Panics
+    /// Panics if `chunk_size` is 0 on any axis.
+    pub fn from_grid(
+        grid: &ExpandableGridN<T, D>,
+        chunk_size: impl GridVector<usize, D>,
+        fill: T,
+    ) -> Self {
+        let mut cow = Self::new(chunk_size, fill);
+        cow.expand_to_fit_box(grid.origin, grid.size);
+
+        for offset in util::iter_box(SVector::zeros(), grid.size) {
+            let index = grid.origin + util::usize_vec_to_isize(offset);
+            cow[index] = grid[index].clone();
+        }
+
+        cow
+    }
+
+    /// Returns a copy of this grid which shares all of its chunks with this grid. This takes time
+    /// proportional to the number of chunks, rather than the number of cells.
+    pub fn snapshot(&self) -> Self {
+        self.clone()
+    }
+
+    /// Returns the size of each chunk of this grid.
+    pub fn chunk_size(&self) -> SVector<usize, D> {
+        self.chunk_size
+    }
+
+    /// Returns the number of cells on each axis of this grid, which is always a multiple of the
+    /// chunk size.
+    pub fn size(&self) -> SVector<usize, D> {
+        self.chunks.size.component_mul(&self.chunk_size)
+    }
+
+    /// Returns the coordinates of the lowest corner of this grid, which is always a multiple of
+    /// the chunk size.
+    pub fn origin(&self) -> SVector<isize, D> {
+        self.chunks
+            .origin
+            .component_mul(&util::usize_vec_to_isize(self.chunk_size))
+    }
+
+    pub fn get(&self, index: impl GridVector<isize, D>) -> Option<&T> {
+        let (chunk, offset) = util::split_chunk_index(index.to_vector(), self.chunk_size);
+
+        Some(&self.chunks.get(chunk)?[Layout::RowMajor.linear_index(offset, self.chunk_size)])
+    }
+
+    /// Returns a mutable reference to the cell at `index`, or `None` if it is out of bounds. If
+    /// the chunk of the cell is shared with a snapshot, it is copied first.
+    pub fn get_mut(&mut self, index: impl GridVector<isize, D>) -> Option<&mut T> {
+        let (chunk, offset) = util::split_chunk_index(index.to_vector(), self.chunk_size);
+        let chunk = Arc::make_mut(self.chunks.get_mut(chunk)?);
+
+        Some(&mut chunk[Layout::RowMajor.linear_index(offset, self.chunk_size)])
+    }
+
+    /// Returns whether the chunk containing the cell at `index` is shared with a snapshot or
+    /// another unwritten chunk, or `None` if it is out of bounds.
+    pub fn is_shared(&self, index: impl GridVector<isize, D>) -> Option<bool> {
+        let (chunk, _) = util::split_chunk_index(index.to_vector(), self.chunk_size);
+
+        Some(Arc::strong_count(self.chunks.get(chunk)?) > 1)
+    }
+
+    /// Increases the size of the grid such that `point` is within its bounds. See
+    /// `ExpandableGridN::expand_to_fit_point`.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<isize, D>) {
+        self.expand_to_fit_box(point, SVector::<usize, D>::repeat(1));
+    }
+
+    /// Increases the size of the grid such that every cell of the box with its lowest corner at
+    /// `box_origin` and size `box_size` is within its bounds. See
+    /// `ExpandableGridN::expand_to_fit_box`.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<isize, D>,
+        box_size: impl GridVector<usize, D>,
+    ) {
+        let Some((first_chunk, last_chunk)) = util::chunk_range(
+            box_origin.to_vector(),
+            box_size.to_vector(),
+            self.chunk_size,
+        ) else {
+            return;
+        };
+        let chunk_box_size = (last_chunk - first_chunk).map(|length| length as usize + 1);
+
+        self.chunks
+            .expand_to_fit_box(first_chunk, chunk_box_size, &self.fill);
+    }
+
+    /// Returns the memory used by this grid, not counting memory owned by cells. See the `memory`
+    /// module for details.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.memory_usage_with(|_| 0)
+    }
+
+    /// Returns the memory used by this grid, where `heap_bytes` returns the bytes on the heap
+    /// owned by each cell. Chunks shared between several cells of this grid are counted once,
+    /// but chunks shared with snapshots are counted in full by each grid.
+    pub fn memory_usage_with(&self, mut heap_bytes: impl FnMut(&T) -> usize) -> MemoryUsage {
+        let chunk_area = self.chunk_size.product();
+        // The reference counts and pointer stored alongside each chunk
+        let chunk_overhead = 2 * size_of::<usize>() + size_of::<Box<[T]>>();
+
+        let mut usage = MemoryUsage {
+            cells: self.chunks.data.len() * chunk_area,
+            chunk_overhead_bytes: self.chunks.data.len() * size_of::<Chunk<T>>(),
+            ..MemoryUsage::default()
+        };
+        usage.used_bytes = usage.cells * size_of::<T>();
+
+        let mut seen = HashSet::new();
+        for chunk in std::iter::once(&self.fill).chain(self.chunks.data.iter()) {
+            if !seen.insert(Arc::as_ptr(chunk)) {
+                continue;
+            }
+
+            usage.chunks += 1;
+            usage.buffer_bytes += chunk_area * size_of::<T>();
+            usage.chunk_overhead_bytes += chunk_overhead;
+            usage.heap_bytes += chunk.iter().map(&mut heap_bytes).sum::<usize>();
+        }
+
+        usage
+    }
+
+    /// Copies every cell of this grid into a new `ExpandableGridN`.
+    pub fn to_grid(&self) -> ExpandableGridN<T, D> {
+        let chunk_origin = self.chunks.origin;
+        let origin = self.origin();
+        let mut grid = ExpandableGridN::with_size(self.size(), origin, &self.fill[0]);
+
+        for chunk_offset in util::iter_box(SVector::zeros(), self.chunks.size) {
+            let chunk = &self.chunks[chunk_origin + util::usize_vec_to_isize(chunk_offset)];
+            let chunk_start =
+                origin + util::usize_vec_to_isize(chunk_offset.component_mul(&self.chunk_size));
+
+            for offset in util::iter_box(SVector::zeros(), self.chunk_size) {
+                grid[chunk_start + util::usize_vec_to_isize(offset)] =
+                    chunk[Layout::RowMajor.linear_index(offset, self.chunk_size)].clone();
+            }
+        }
+
+        grid
+    }
+}
+
+impl<T: Clone, I: GridVector<isize, D>, const D: usize> std::ops::Index<I> for CowGrid<T, D> {
+    type Output = T;
+
+    fn index(&self, index: I) -> &Self::Output {
+        self.get(index).unwrap()
+    }
+}
+
+impl<T: Clone, I: GridVector<isize, D>, const D: usize> std::ops::IndexMut<I> for CowGrid<T, D> {
+    fn index_mut(&mut self, index: I) -> &mut Self::Output {
+        self.get_mut(index).unwrap()
+    }
+}
@@ -0,0 +1,218 @@
+//! Comparing two grids, and finding the changes that turn one into the other.
+//!
+//! `ExpandableGridN::diff` returns a `GridDiff` with the bounds of both grids and a patch for
+//! every rect of cells which differ, including cells outside the bounds of the old grid. Cells
+//! which are only in the old grid are not recorded, since they are removed by the change of
+//! bounds.
+//!
+//! `ExpandableGridN::apply_diff` makes the changes of a diff to a grid, changing its bounds to
+//! match and copying in the patches, so that a grid equal to the old grid becomes equal to the
+//! new one. Together these allow keeping a copy of a grid in sync by sending only its changes.
+//!
+//! Rects are found by grouping changed cells into runs along the `x` axis, and then merging runs
+//! which line up exactly along the `y` axis, so a diff is compact for changes in solid blocks but
+//! does not find the fewest possible rects in general.
+
+use crate::{
+    coord::{self, Coordinate},
+    rect::GridRect,
+    util, ExpandableGridN,
+};
+use nalgebra::SVector;
+use std::collections::HashMap;
+
+/// The changes which turn one grid into another. See the `diff` module for details.
+#[derive(Clone, Debug)]
+pub struct GridDiff<T, const D: usize = 2, C = isize> {
+    pub(crate) old_bounds: GridRect<D>,
+    pub(crate) new_bounds: GridRect<D>,
+    pub(crate) patches: Vec<ExpandableGridN<T, D, C>>,
+}
+
+impl<T, const D: usize, C: Coordinate> GridDiff<T, D, C> {
+    /// Creates a diff from a grid which had the bounds `old_bounds` to `grid`, where only the
+    /// cells within `rects` may have changed, such as the rects recorded by a `DirtyGrid`. Parts
+    /// of the rects outside the bounds of `grid` are ignored.
+    ///
+    /// If the bounds of the grid changed, the whole grid is included, since the cells within
+    /// `rects` may not cover every new cell.
+    pub fn from_rects(
+        old_bounds: GridRect<D, C>,
+        grid: &ExpandableGridN<T, D, C>,
+        rects: impl IntoIterator<Item = GridRect<D, C>>,
+    ) -> Self
+    where
+        T: Clone,
+    {
+        let (old_bounds, new_bounds) = (old_bounds.to_isize(), grid.bounds().to_isize());
+        let patches = if old_bounds != new_bounds {
+            (!grid.data.is_empty())
+                .then(|| grid.clone())
+                .into_iter()
+                .collect()
+        } else {
+            (rects.into_iter())
+                .filter_map(|rect| {
+                    let (origin, size) = util::intersect_boxes(
+                        coord::to_isize_vec(grid.origin),
+                        grid.size,
+                        coord::to_isize_vec(rect.origin),
+                        rect.size,
+                    )?;
+                    let origin = coord::from_isize_vec::<C, D>(origin);
+                    Some(grid.copy_box(origin, size, &grid[origin]))
+                })
+                .collect()
+        };
+
+        Self {
+            old_bounds,
+            new_bounds,
+            patches,
+        }
+    }
+
+    /// Returns the bounds of the grid the diff was computed from.
+    pub fn old_bounds(&self) -> GridRect<D, C> {
+        GridRect::from_isize(self.old_bounds)
+    }
+
+    /// Returns the bounds of the grid the diff was computed to.
+    pub fn new_bounds(&self) -> GridRect<D, C> {
+        GridRect::from_isize(self.new_bounds)
+    }
+
+    /// Returns a grid for each rect of changed cells, holding their new values. The patches of a
+    /// diff from `ExpandableGridN::diff` never overlap.
+    pub fn patches(&self) -> &[ExpandableGridN<T, D, C>] {
+        &self.patches
+    }
+
+    /// Returns the rect covered by each patch.
+    pub fn changed_rects(&self) -> impl Iterator<Item = GridRect<D, C>> + '_ {
+        self.patches.iter().map(|patch| patch.bounds())
+    }
+
+    /// Returns the number of cells which changed.
+    pub fn changed_cells(&self) -> usize {
+        self.patches.iter().map(|patch| patch.data.len()).sum()
+    }
+
+    /// Returns `true` if both grids have the same bounds and cells.
+    pub fn is_empty(&self) -> bool {
+        self.old_bounds == self.new_bounds && self.patches.is_empty()
+    }
+}
+
+impl<T: PartialEq + Clone, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Returns the changes which turn this grid into `other`. See the `diff` module for details.
+    pub fn diff(&self, other: &Self) -> GridDiff<T, D, C> {
+        let mut rects = Vec::new();
+        // Maps each rect by the origin the next run would need to extend it along `y`, and the
+        // length of its runs
+        let mut open_rects = HashMap::new();
+
+        let mut add_run = |start: SVector<isize, D>, length: usize| {
+            let mut size = SVector::repeat(1);
+            size[0] = length;
+
+            let index = if D > 1 {
+                open_rects.remove(&(start, length)).inspect(|&index| {
+                    let rect: &mut GridRect<D> = &mut rects[index];
+                    rect.size[1] += 1;
+                })
+            } else {
+                None
+            }
+            .unwrap_or_else(|| {
+                rects.push(GridRect::new(start, size));
+                rects.len() - 1
+            });
+
+            if D > 1 {
+                let mut next = start;
+                next[1] += 1;
+                open_rects.insert((next, length), index);
+            }
+        };
+
+        let mut run: Option<(SVector<isize, D>, usize)> = None;
+        for offset in util::iter_box(SVector::zeros(), other.size) {
+            // Runs end at the end of each row
+            if offset[0] == 0 {
+                if let Some((start, length)) = run.take() {
+                    add_run(start, length);
+                }
+            }
+
+            let position = coord::to_isize_vec(other.origin) + util::usize_vec_to_isize(offset);
+            let cell = &other.data[other.layout.linear_index(offset, other.size)];
+            if self.get_isize(position) == Some(cell) {
+                if let Some((start, length)) = run.take() {
+                    add_run(start, length);
+                }
+            } else {
+                match &mut run {
+                    Some((_, length)) => *length += 1,
+                    None => run = Some((position, 1)),
+                }
+            }
+        }
+        if let Some((start, length)) = run {
+            add_run(start, length);
+        }
+
+        GridDiff {
+            old_bounds: self.bounds().to_isize(),
+            new_bounds: other.bounds().to_isize(),
+            patches: (rects.into_iter())
+                .map(|rect| {
+                    let origin = coord::from_isize_vec::<C, D>(rect.origin);
+                    other.copy_box(origin, rect.size, &other[origin])
+                })
+                .collect(),
+        }
+    }
+
+    /// Makes the changes of `diff` to this grid, changing its bounds to the bounds of the new
+    /// grid and copying in every patch. If this grid is equal to the grid the diff was computed
+    /// from, it will be equal to the grid it was computed to afterwards.
+    ///
+    /// Applying a diff to any other grid still changes its bounds, expanding it as needed, but
+    /// cells added to the grid which are not covered by a patch are filled with clones of some
+    /// other cell.
+    ///
+    /// # Panics
+    /// Panics if the grid needs to expand, but both it and the diff have no cells to fill it with.
+    pub fn apply_diff(&mut self, diff: &GridDiff<T, D, C>) {
+        let bounds = diff.new_bounds;
+        let origin = coord::from_isize_vec::<C, D>(bounds.origin);
+
+        if self.bounds().to_isize() != bounds {
+            if bounds.size.product() == 0 {
+                *self = ExpandableGridN {
+                    size: bounds.size,
+                    origin,
+                    data: Box::new([]),
+                    layout: self.layout,
+                };
+            } else {
+                let fill = (diff.patches.iter().find_map(|patch| patch.data.first()))
+                    .or(self.data.first())
+                    .expect("grid should have a cell to fill new cells with")
+                    .clone();
+
+                if self.data.is_empty() {
+                    *self = Self::with_size_and_layout(bounds.size, origin, &fill, self.layout);
+                } else {
+                    let offset = bounds.origin - coord::to_isize_vec(self.origin);
+                    self.change_size(bounds.size, coord::from_isize_vec::<C, D>(offset), &fill);
+                }
+            }
+        }
+
+        for patch in &diff.patches {
+            self.blit(patch);
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! Tracking which cells of a grid have changed, for renderers and savers which only need to
+//! process what changed.
+//!
+//! A `DirtyGrid` wraps a grid, recording the cells written to through its mutating methods:
+//! `get_mut`, `IndexMut`, `set`, `fill_rect`, `blit`, and the methods which change its size.
+//! `take_dirty` returns the recorded cells as rects and starts recording again. Reading through
+//! `Deref` records nothing.
+//!
+//! Cells are recorded in a bitmap with one bit per cell, so recording a write takes constant
+//! time however many cells have been written, and `take_dirty` only scans the smallest box
+//! containing every recorded cell.
+//!
+//! Cells are recorded as written whether or not their value actually changed. When the bounds of
+//! the grid change, the whole grid is recorded, since every cell may have moved within its
+//! data.
+
+use crate::{
+    coord::{self, Coordinate, GridVector},
+    rect::GridRect,
+    util, ExpandableGridN, Layout,
+};
+use nalgebra::SVector;
+use std::ops::{Deref, Range};
+
+/// A grid which records which of its cells have been written. See the `dirty` module for
+/// details.
+#[derive(Clone, Debug)]
+pub struct DirtyGrid<T, const D: usize = 2, C = isize> {
+    grid: ExpandableGridN<T, D, C>,
+    /// The bounds of the grid when the bitmap was created.
+    tracked: GridRect<D>,
+    /// One bit for each cell of `tracked` in row major order, set if the cell has been written.
+    dirty: Vec<u64>,
+    /// The smallest rect containing every recorded cell, or `None` if there are none.
+    dirty_bounds: Option<GridRect<D>>,
+}
+
+impl<T, const D: usize, C: Coordinate> DirtyGrid<T, D, C> {
+    /// Wraps `grid`, with no cells recorded as dirty.
+    pub fn new(grid: ExpandableGridN<T, D, C>) -> Self {
+        let area = util::checked_area(grid.size).expect("the area of a grid should fit in usize");
+        Self {
+            tracked: grid.bounds().to_isize(),
+            dirty: vec![0; area.div_ceil(64)],
+            dirty_bounds: None,
+            grid,
+        }
+    }
+
+    pub fn into_inner(self) -> ExpandableGridN<T, D, C> {
+        self.grid
+    }
+
+    /// Returns a mutable reference to the grid without recording anything. `mark_dirty` should be
+    /// called for any cells changed through it.
+    pub fn untracked_mut(&mut self) -> &mut ExpandableGridN<T, D, C> {
+        &mut self.grid
+    }
+
+    /// Returns whether any cells have been recorded since the last call to `take_dirty`.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty_bounds.is_some()
+    }
+
+    /// Records every cell of `rect` within the bounds of the grid as dirty.
+    pub fn mark_dirty(&mut self, rect: GridRect<D, C>) {
+        self.track_bounds(|_| ());
+        self.mark_isize_rect_dirty(rect.to_isize());
+    }
+
+    /// Same as `mark_dirty`, for a rect in the type grids calculate with.
+    fn mark_isize_rect_dirty(&mut self, rect: GridRect<D>) {
+        let Some(rect) = self.tracked.intersection(&rect) else {
+            return;
+        };
+
+        // Set the run of bits of each row of the rect
+        let mut row_size = rect.size;
+        row_size[0] = 1;
+        for row in util::iter_box(SVector::zeros(), row_size) {
+            let start = self.bit_index(rect.origin + util::usize_vec_to_isize(row));
+            set_bits(&mut self.dirty, start..start + rect.size[0], true);
+        }
+
+        self.include_in_bounds(rect);
+    }
+
+    /// Returns the recorded cells as rects, and stops recording them. Each rect is a run of
+    /// recorded cells along the `x` axis, merged with identical runs in the following rows along
+    /// the `y` axis. The returned rects never overlap.
+    pub fn take_dirty(&mut self) -> Vec<GridRect<D, C>> {
+        self.track_bounds(|_| ());
+
+        let Some(bounds) = self.dirty_bounds.take() else {
+            return Vec::new();
+        };
+
+        let mut rects = Vec::<GridRect<D>>::new();
+        // The runs of the previous row, along with the index of the rect each belongs to
+        let mut previous = Vec::<(Range<usize>, usize)>::new();
+        let mut current = Vec::new();
+
+        let mut row_size = bounds.size;
+        row_size[0] = 1;
+        for row in util::iter_box(SVector::zeros(), row_size) {
+            let origin = bounds.origin + util::usize_vec_to_isize(row);
+            let start = self.bit_index(origin);
+            let range = start..start + bounds.size[0];
+
+            // Rows only continue the previous row if they follow it along the `y` axis
+            if D < 2 || row[1] == 0 {
+                previous.clear();
+            }
+
+            let mut candidates = previous.iter().peekable();
+            for_each_run(&self.dirty, range.clone(), |run| {
+                let run = run.start - start..run.end - start;
+                while candidates
+                    .next_if(|(other, _)| other.start < run.start)
+                    .is_some()
+                {}
+
+                let index = match candidates.next_if(|(other, _)| *other == run) {
+                    Some(&(_, index)) => {
+                        rects[index].size[1] += 1;
+                        index
+                    }
+                    None => {
+                        let mut rect_origin = origin;
+                        rect_origin[0] += run.start as isize;
+                        let mut size = SVector::repeat(1);
+                        size[0] = run.len();
+                        rects.push(GridRect::new(rect_origin, size));
+                        rects.len() - 1
+                    }
+                };
+                current.push((run, index));
+            });
+            std::mem::swap(&mut previous, &mut current);
+            current.clear();
+
+            set_bits(&mut self.dirty, range, false);
+        }
+
+        rects.into_iter().map(GridRect::from_isize).collect()
+    }
+
+    /// Returns a mutable reference to the cell at `index`, recording it as dirty.
+    pub fn get_mut(&mut self, index: impl GridVector<C, D>) -> Option<&mut T> {
+        self.track_bounds(|_| ());
+
+        let index = coord::to_isize_vec(index.to_vector());
+        let data_index = self.grid.index_of_isize(index)?;
+
+        let bit = self.bit_index(index);
+        self.dirty[bit / 64] |= 1 << (bit % 64);
+        self.include_in_bounds(GridRect::new(index, SVector::<usize, D>::repeat(1)));
+
+        Some(&mut self.grid.data[data_index])
+    }
+
+    /// Sets the cell at `index` to `value`, returning `false` if it is out of bounds.
+    pub fn set(&mut self, index: impl GridVector<C, D>, value: T) -> bool {
+        self.get_mut(index).map(|cell| *cell = value).is_some()
+    }
+
+    /// Sets every cell of `rect` within the bounds of the grid to clones of `value`.
+    pub fn fill_rect(&mut self, rect: GridRect<D, C>, value: &T)
+    where
+        T: Clone,
+    {
+        let Some(rect) = self.grid.bounds().intersection(&rect) else {
+            return;
+        };
+
+        self.grid.fill_rect(rect, value);
+        self.mark_dirty(rect);
+    }
+
+    /// See `ExpandableGridN::blit`. Records the cells copied from `source`.
+    pub fn blit(&mut self, source: &ExpandableGridN<T, D, C>)
+    where
+        T: Clone,
+    {
+        self.grid.blit(source);
+        self.mark_dirty(source.bounds());
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_point`.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<C, D>, fill: &T)
+    where
+        T: Clone,
+    {
+        self.track_bounds(|grid| grid.expand_to_fit_point(point, fill));
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_box`.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<C, D>,
+        box_size: impl GridVector<usize, D>,
+        fill: &T,
+    ) where
+        T: Clone,
+    {
+        self.track_bounds(|grid| grid.expand_to_fit_box(box_origin, box_size, fill));
+    }
+
+    /// See `ExpandableGridN::change_size`.
+    pub fn change_size(
+        &mut self,
+        new_size: impl GridVector<usize, D>,
+        offset: impl GridVector<C, D>,
+        fill: &T,
+    ) where
+        T: Clone,
+    {
+        self.track_bounds(|grid| grid.change_size(new_size, offset, fill));
+    }
+
+    /// Calls `f` with the grid, recording the whole grid as dirty if its bounds changed, either
+    /// within `f` or through `untracked_mut`.
+    fn track_bounds(&mut self, f: impl FnOnce(&mut ExpandableGridN<T, D, C>)) {
+        f(&mut self.grid);
+
+        let bounds = self.grid.bounds().to_isize();
+        if bounds != self.tracked {
+            *self = Self::new(std::mem::take(&mut self.grid));
+            self.mark_isize_rect_dirty(bounds);
+        }
+    }
+
+    /// Returns the index within the bitmap of the bit for the cell at `position`, which must be
+    /// within the grid.
+    fn bit_index(&self, position: SVector<isize, D>) -> usize {
+        let relative = (position - self.tracked.origin).map(|component| component as usize);
+        Layout::RowMajor.linear_index(relative, self.tracked.size)
+    }
+
+    fn include_in_bounds(&mut self, rect: GridRect<D>) {
+        self.dirty_bounds = Some(match self.dirty_bounds {
+            Some(bounds) => bounds.union(&rect),
+            None => rect,
+        });
+    }
+}
+
+impl<T, const D: usize, C: Coordinate> Default for DirtyGrid<T, D, C> {
+    fn default() -> Self {
+        Self::new(ExpandableGridN::default())
+    }
+}
+
+impl<T, const D: usize, C> Deref for DirtyGrid<T, D, C> {
+    type Target = ExpandableGridN<T, D, C>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.grid
+    }
+}
+
+impl<T, I: GridVector<C, D>, const D: usize, C: Coordinate> std::ops::Index<I>
+    for DirtyGrid<T, D, C>
+{
+    type Output = T;
+
+    fn index(&self, index: I) -> &Self::Output {
+        &self.grid[index]
+    }
+}
+
+impl<T, I: GridVector<C, D>, const D: usize, C: Coordinate> std::ops::IndexMut<I>
+    for DirtyGrid<T, D, C>
+{
+    fn index_mut(&mut self, index: I) -> &mut Self::Output {
+        self.get_mut(index).unwrap()
+    }
+}
+
+/// Sets every bit of `bits` within `range` to `value`.
+fn set_bits(bits: &mut [u64], range: Range<usize>, value: bool) {
+    let mut index = range.start;
+    while index < range.end {
+        let offset = index % 64;
+        let length = (64 - offset).min(range.end - index);
+        let mask = (u64::MAX >> (64 - length)) << offset;
+
+        if value {
+            bits[index / 64] |= mask;
+        } else {
+            bits[index / 64] &= !mask;
+        }
+        index += length;
+    }
+}
+
+/// Calls `f` with each run of set bits of `bits` within `range`, in order.
+fn for_each_run(bits: &[u64], range: Range<usize>, mut f: impl FnMut(Range<usize>)) {
+    let mut index = range.start;
+    while index < range.end {
+        // Skip to the next set bit, a whole word at a time where possible
+        let word = bits[index / 64] >> (index % 64);
+        if word == 0 {
+            index = (index / 64 + 1) * 64;
+            continue;
+        }
+        index += word.trailing_zeros() as usize;
+        if index >= range.end {
+            return;
+        }
+
+        // Then to the next unset bit
+        let start = index;
+        loop {
+            let word = !bits[index / 64] >> (index % 64);
+            if word == 0 {
+                index = (index / 64 + 1) * 64;
+            } else {
+                index += word.trailing_zeros() as usize;
+            }
+            if word != 0 || index >= range.end {
+                break;
+            }
+        }
+
+        f(start..index.min(range.end));
+    }
+}
@@ -0,0 +1,126 @@
+//! A pair of grids for simulations which compute each state from the previous one, such as
+//! cellular automata.
+//!
+//! A `DoubleBufferedGrid` holds a front grid, with the current state, and a back grid, which
+//! the next state is written to before the two are swapped. Both grids always have the same
+//! size, origin and layout: expanding or resizing the double buffered grid expands both, so
+//! coordinates read from the front grid are always valid in the back grid.
+
+use crate::{
+    coord::{self, Coordinate, GridVector},
+    ExpandableGridN,
+};
+
+/// A front and back grid which always have the same bounds. See the `double_buffer` module for
+/// details.
+#[derive(Clone, Debug, Default)]
+pub struct DoubleBufferedGrid<T, const D: usize = 2, C: Coordinate = isize> {
+    front: ExpandableGridN<T, D, C>,
+    back: ExpandableGridN<T, D, C>,
+}
+
+impl<T: Clone, const D: usize, C: Coordinate> DoubleBufferedGrid<T, D, C> {
+    /// Creates a double buffered grid with `grid` as the front grid, and a copy of it as the
+    /// back grid.
+    pub fn new(grid: ExpandableGridN<T, D, C>) -> Self {
+        Self {
+            back: grid.clone(),
+            front: grid,
+        }
+    }
+
+    /// Returns the grid holding the current state.
+    pub fn front(&self) -> &ExpandableGridN<T, D, C> {
+        &self.front
+    }
+
+    /// Returns the grid holding the current state. Its size must not be changed, use the methods
+    /// of `DoubleBufferedGrid` for that.
+    pub fn front_mut(&mut self) -> &mut ExpandableGridN<T, D, C> {
+        &mut self.front
+    }
+
+    /// Returns the grid the next state is written to, which holds the state before the last
+    /// swap.
+    pub fn back(&self) -> &ExpandableGridN<T, D, C> {
+        &self.back
+    }
+
+    /// Returns the front grid, discarding the back grid.
+    pub fn into_front(self) -> ExpandableGridN<T, D, C> {
+        self.front
+    }
+
+    /// Calls `f` with the front grid to read from and the back grid to write to, then swaps them
+    /// so that the front grid holds the result. The back grid still holds the state before the
+    /// last step, so `f` should write every cell, or call `copy_front_to_back` first.
+    ///
+    /// # Panics
+    /// Panics if `f` changes the size, origin or layout of the back grid.
+    pub fn step<R>(
+        &mut self,
+        f: impl FnOnce(&ExpandableGridN<T, D, C>, &mut ExpandableGridN<T, D, C>) -> R,
+    ) -> R {
+        let result = f(&self.front, &mut self.back);
+        assert!(
+            self.back.size == self.front.size
+                && self.back.origin == self.front.origin
+                && self.back.layout == self.front.layout,
+            "the bounds of the back grid should not be changed",
+        );
+
+        self.swap();
+        result
+    }
+
+    /// Swaps the front and back grids.
+    pub fn swap(&mut self) {
+        std::mem::swap(&mut self.front, &mut self.back);
+    }
+
+    /// Copies every cell of the front grid to the back grid.
+    pub fn copy_front_to_back(&mut self) {
+        self.back.data.clone_from_slice(&self.front.data);
+    }
+
+    /// Increases the size of both grids such that `point` is within their bounds. See
+    /// `ExpandableGridN::expand_to_fit_point`.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<C, D>, fill: &T) {
+        self.front.expand_to_fit_point(point, fill);
+        self.match_back(fill);
+    }
+
+    /// Increases the size of both grids such that every cell of a box is within their bounds.
+    /// See `ExpandableGridN::expand_to_fit_box`.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<C, D>,
+        box_size: impl GridVector<usize, D>,
+        fill: &T,
+    ) {
+        self.front.expand_to_fit_box(box_origin, box_size, fill);
+        self.match_back(fill);
+    }
+
+    /// Changes the size of both grids. See `ExpandableGridN::change_size`.
+    pub fn change_size(
+        &mut self,
+        new_size: impl GridVector<usize, D>,
+        offset: impl GridVector<C, D>,
+        fill: &T,
+    ) {
+        self.front.change_size(new_size, offset, fill);
+        self.match_back(fill);
+    }
+
+    /// Changes the bounds of the back grid to match the front grid, keeping the cells of the back
+    /// grid which remain within bounds.
+    fn match_back(&mut self, fill: &T) {
+        if (self.back.size, self.back.origin) == (self.front.size, self.front.origin) {
+            return;
+        }
+
+        let offset = coord::to_isize_vec(self.front.origin) - coord::to_isize_vec(self.back.origin);
+        self.back.change_size_isize(self.front.size, offset, fill);
+    }
+}
@@ -0,0 +1,341 @@
+//! An append-only log of the operations made to a grid, which can be replayed to reconstruct it.
+//!
+//! Each `Edit` is a high level operation, such as setting a cell or filling a box, rather than
+//! the cells it changed, so a log is usually much smaller than a snapshot of the grid. Replaying
+//! the same log onto the same starting grid always produces the same grid, which allows for
+//! deterministic replays, and saving only the edits made since the last save.
+//!
+//! Logs can be stored with serde when the `serde` feature is enabled, or in the binary format
+//! below with `EditLog::write_binary`. Edits can be appended to an existing file with
+//! `EditLog::write_binary_edits`, since the format has no footer.
+//!
+//! # Layout
+//!
+//! All integers are little endian.
+//!
+//! | Field      | Encoding    | Notes                         |
+//! |------------|-------------|-------------------------------|
+//! | magic      | 4 bytes     | Always `b"EXLG"`              |
+//! | version    | `u16`       | Currently `1`                 |
+//! | dimensions | `u8`        | `D`                           |
+//! | cell size  | `u32`       | `T::SIZE`                     |
+//! | edits      | see below   | Continue until the end        |
+//!
+//! Each edit is a tag byte followed by its fields, where positions are `D` × `i64`, sizes are
+//! `D` × `u64`, and cells are written by `BinaryCell`:
+//!
+//! | Tag | Edit              | Fields                                          |
+//! |-----|-------------------|-------------------------------------------------|
+//! | `0` | `Set`             | index, value                                    |
+//! | `1` | `FillBox`         | box origin, box size, value                     |
+//! | `2` | `Blit`            | the source grid, as written by `write_binary`   |
+//! | `3` | `ChangeSize`      | new size, offset, fill                          |
+//! | `4` | `ExpandToFitBox`  | box origin, box size, fill                      |
+
+use crate::{
+    binary::{self, BinaryCell, BinaryError},
+    util, ExpandableGridN,
+};
+use nalgebra::SVector;
+use std::io::{self, Read, Write};
+
+/// The bytes every edit log starts with.
+pub const MAGIC: [u8; 4] = *b"EXLG";
+
+/// The version of the edit log format written by this version of the crate.
+pub const VERSION: u16 = 1;
+
+/// An operation on a grid, which can be recorded in an `EditLog`.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Edit<T, const D: usize = 2> {
+    /// Sets the cell at `index` to `value`, doing nothing if it is out of bounds.
+    Set { index: SVector<isize, D>, value: T },
+    /// Sets every cell of the box within the bounds of the grid to clones of `value`.
+    FillBox {
+        box_origin: SVector<isize, D>,
+        box_size: SVector<usize, D>,
+        value: T,
+    },
+    /// See `ExpandableGridN::blit`.
+    Blit(ExpandableGridN<T, D>),
+    /// See `ExpandableGridN::change_size`.
+    ChangeSize {
+        new_size: SVector<usize, D>,
+        offset: SVector<isize, D>,
+        fill: T,
+    },
+    /// See `ExpandableGridN::expand_to_fit_box`.
+    ExpandToFitBox {
+        box_origin: SVector<isize, D>,
+        box_size: SVector<usize, D>,
+        fill: T,
+    },
+}
+
+impl<T: Clone, const D: usize> Edit<T, D> {
+    /// Makes this edit to `grid`.
+    pub fn apply_to(&self, grid: &mut ExpandableGridN<T, D>) {
+        match self {
+            Edit::Set { index, value } => {
+                if let Some(cell) = grid.get_mut(*index) {
+                    *cell = value.clone();
+                }
+            }
+            Edit::FillBox {
+                box_origin,
+                box_size,
+                value,
+            } => {
+                let Some((origin, size)) =
+                    util::intersect_boxes(grid.origin, grid.size, *box_origin, *box_size)
+                else {
+                    return;
+                };
+
+                for offset in util::iter_box(SVector::zeros(), size) {
+                    grid[origin + util::usize_vec_to_isize(offset)] = value.clone();
+                }
+            }
+            Edit::Blit(source) => grid.blit(source),
+            Edit::ChangeSize {
+                new_size,
+                offset,
+                fill,
+            } => grid.change_size(*new_size, *offset, fill),
+            Edit::ExpandToFitBox {
+                box_origin,
+                box_size,
+                fill,
+            } => grid.expand_to_fit_box(*box_origin, *box_size, fill),
+        }
+    }
+}
+
+/// An append-only list of edits. See the `edit_log` module for details.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EditLog<T, const D: usize = 2> {
+    edits: Vec<Edit<T, D>>,
+}
+
+impl<T, const D: usize> Default for EditLog<T, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const D: usize> EditLog<T, D> {
+    pub fn new() -> Self {
+        Self { edits: Vec::new() }
+    }
+
+    pub fn edits(&self) -> &[Edit<T, D>] {
+        &self.edits
+    }
+
+    pub fn len(&self) -> usize {
+        self.edits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// Appends `edit` to the log without making it to any grid.
+    pub fn push(&mut self, edit: Edit<T, D>) {
+        self.edits.push(edit);
+    }
+}
+
+impl<T: Clone, const D: usize> EditLog<T, D> {
+    /// Makes `edit` to `grid` and appends it to the log.
+    pub fn apply(&mut self, grid: &mut ExpandableGridN<T, D>, edit: Edit<T, D>) {
+        edit.apply_to(grid);
+        self.edits.push(edit);
+    }
+
+    /// Makes every edit in the log to `grid`, in order.
+    pub fn replay(&self, grid: &mut ExpandableGridN<T, D>) {
+        self.replay_from(0, grid);
+    }
+
+    /// Makes every edit in the log starting at index `start` to `grid`, in order.
+    pub fn replay_from(&self, start: usize, grid: &mut ExpandableGridN<T, D>) {
+        for edit in &self.edits[start..] {
+            edit.apply_to(grid);
+        }
+    }
+
+    /// Returns the grid created by replaying the log onto an empty grid.
+    pub fn to_grid(&self) -> ExpandableGridN<T, D> {
+        let mut grid = ExpandableGridN::new();
+        self.replay(&mut grid);
+        grid
+    }
+}
+
+impl<T: BinaryCell, const D: usize> EditLog<T, D> {
+    /// Writes this log to `writer` in the format described in the `edit_log` module.
+    pub fn write_binary(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&[D as u8])?;
+        writer.write_all(&(T::SIZE as u32).to_le_bytes())?;
+
+        self.write_binary_edits(0, writer)
+    }
+
+    /// Writes the edits starting at index `start` to `writer` without a header, so that they can
+    /// be appended to a log which already has the earlier edits.
+    pub fn write_binary_edits(&self, start: usize, writer: &mut impl Write) -> io::Result<()> {
+        for edit in &self.edits[start..] {
+            write_edit(writer, edit)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a log from `reader` in the format described in the `edit_log` module, reading edits
+    /// until the end of `reader`.
+    pub fn read_binary(reader: &mut impl Read) -> Result<Self, BinaryError> {
+        let magic = binary::read_array(reader)?;
+        if magic != MAGIC {
+            return Err(BinaryError::InvalidMagic(magic));
+        }
+
+        let version = u16::from_le_bytes(binary::read_array(reader)?);
+        if version != VERSION {
+            return Err(BinaryError::UnsupportedVersion(version));
+        }
+
+        let [dimensions] = binary::read_array(reader)?;
+        if dimensions as usize != D {
+            return Err(BinaryError::DimensionMismatch {
+                expected: D,
+                found: dimensions as usize,
+            });
+        }
+
+        let cell_size = u32::from_le_bytes(binary::read_array(reader)?) as usize;
+        if cell_size != T::SIZE {
+            return Err(BinaryError::CellSizeMismatch {
+                expected: T::SIZE,
+                found: cell_size,
+            });
+        }
+
+        let mut edits = Vec::new();
+        while let Some(tag) = read_tag(reader)? {
+            let index = edits.len();
+            edits.push(read_edit(reader, tag, index)?);
+        }
+
+        Ok(Self { edits })
+    }
+}
+
+fn write_edit<T: BinaryCell, const D: usize>(
+    writer: &mut impl Write,
+    edit: &Edit<T, D>,
+) -> io::Result<()> {
+    match edit {
+        Edit::Set { index, value } => {
+            writer.write_all(&[0])?;
+            binary::write_position(writer, *index)?;
+            write_cell(writer, value)
+        }
+        Edit::FillBox {
+            box_origin,
+            box_size,
+            value,
+        } => {
+            writer.write_all(&[1])?;
+            binary::write_position(writer, *box_origin)?;
+            binary::write_size(writer, *box_size)?;
+            write_cell(writer, value)
+        }
+        Edit::Blit(source) => {
+            writer.write_all(&[2])?;
+            source.write_binary(writer)
+        }
+        Edit::ChangeSize {
+            new_size,
+            offset,
+            fill,
+        } => {
+            writer.write_all(&[3])?;
+            binary::write_size(writer, *new_size)?;
+            binary::write_position(writer, *offset)?;
+            write_cell(writer, fill)
+        }
+        Edit::ExpandToFitBox {
+            box_origin,
+            box_size,
+            fill,
+        } => {
+            writer.write_all(&[4])?;
+            binary::write_position(writer, *box_origin)?;
+            binary::write_size(writer, *box_size)?;
+            write_cell(writer, fill)
+        }
+    }
+}
+
+/// Reads the fields of the edit with tag `tag`, which is at `index` within the log.
+fn read_edit<T: BinaryCell, const D: usize>(
+    reader: &mut impl Read,
+    tag: u8,
+    index: usize,
+) -> Result<Edit<T, D>, BinaryError> {
+    let read_cell = |reader: &mut _| read_cell(reader, index);
+
+    Ok(match tag {
+        0 => Edit::Set {
+            index: binary::read_position(reader)?,
+            value: read_cell(reader)?,
+        },
+        1 => Edit::FillBox {
+            box_origin: binary::read_position(reader)?,
+            box_size: binary::read_size(reader)?,
+            value: read_cell(reader)?,
+        },
+        2 => Edit::Blit(ExpandableGridN::read_binary(reader)?),
+        3 => Edit::ChangeSize {
+            new_size: binary::read_size(reader)?,
+            offset: binary::read_position(reader)?,
+            fill: read_cell(reader)?,
+        },
+        4 => Edit::ExpandToFitBox {
+            box_origin: binary::read_position(reader)?,
+            box_size: binary::read_size(reader)?,
+            fill: read_cell(reader)?,
+        },
+        _ => return Err(BinaryError::InvalidEdit { index }),
+    })
+}
+
+/// Reads the tag of the next edit, or `None` if the reader has ended.
+fn read_tag(reader: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut tag = [0];
+    loop {
+        match reader.read(&mut tag) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(tag[0])),
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => (),
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+fn write_cell<T: BinaryCell>(writer: &mut impl Write, cell: &T) -> io::Result<()> {
+    let mut bytes = vec![0; T::SIZE];
+    cell.write_bytes(&mut bytes);
+    writer.write_all(&bytes)
+}
+
+/// Reads a cell of the edit at `index` within the log.
+fn read_cell<T: BinaryCell>(reader: &mut impl Read, index: usize) -> Result<T, BinaryError> {
+    let mut bytes = vec![0; T::SIZE];
+    reader.read_exact(&mut bytes)?;
+    T::read_bytes(&bytes).ok_or(BinaryError::InvalidCell { index })
+}
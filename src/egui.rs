@@ -0,0 +1,190 @@
+//! A debugging widget for `egui` which draws a grid as a heatmap.
+//!
+//! `GridInspector` draws each cell as a square colored by a heat closure, which can be panned by
+//! dragging and zoomed by scrolling. Hovering a cell shows its coordinates and the text given by
+//! a formatter closure, and clicking a cell selects it so that it stays highlighted. The pan, zoom
+//! and selection are kept in egui's memory, so the widget can be recreated every frame.
+
+use crate::ExpandableGrid;
+use egui::{Color32, Id, Pos2, Rect, Response, Sense, Stroke, Ui, Vec2, Widget};
+use nalgebra::{vector, Vector2};
+
+/// A widget that draws an `ExpandableGrid` as a pannable and zoomable heatmap.
+pub struct GridInspector<'a, T> {
+    grid: &'a ExpandableGrid<T>,
+    id_source: Id,
+    heat: Box<dyn Fn(&T) -> f32 + 'a>,
+    format: Box<dyn Fn(&T) -> String + 'a>,
+    cell_size: f32,
+    desired_size: Vec2,
+    cold: Color32,
+    hot: Color32,
+}
+
+/// The state of a `GridInspector` which is kept between frames.
+#[derive(Clone, Copy, Debug)]
+struct InspectorState {
+    pan: Vec2,
+    zoom: f32,
+    selected: Option<Vector2<isize>>,
+}
+
+impl Default for InspectorState {
+    fn default() -> Self {
+        Self {
+            pan: Vec2::ZERO,
+            zoom: 1.0,
+            selected: None,
+        }
+    }
+}
+
+impl<'a, T> GridInspector<'a, T> {
+    /// Creates an inspector for `grid`. Each cell is colored by `heat`, which should return a
+    /// value from `0.0` (cold) to `1.0` (hot), and described by `format` when hovered or
+    /// selected.
+    pub fn new(
+        grid: &'a ExpandableGrid<T>,
+        heat: impl Fn(&T) -> f32 + 'a,
+        format: impl Fn(&T) -> String + 'a,
+    ) -> Self {
+        Self {
+            grid,
+            id_source: Id::new("expandable_grid_inspector"),
+            heat: Box::new(heat),
+            format: Box::new(format),
+            cell_size: 8.0,
+            desired_size: Vec2::new(320.0, 240.0),
+            cold: Color32::from_rgb(16, 16, 48),
+            hot: Color32::from_rgb(255, 200, 64),
+        }
+    }
+
+    /// Sets the source of the id the state of the inspector is stored under. This must be unique
+    /// if multiple inspectors are shown at once.
+    pub fn id_source(mut self, id_source: impl std::hash::Hash) -> Self {
+        self.id_source = Id::new(id_source);
+        self
+    }
+
+    /// Sets the size of each cell in points before zooming.
+    pub fn cell_size(mut self, cell_size: f32) -> Self {
+        self.cell_size = cell_size;
+        self
+    }
+
+    /// Sets the size of the area the grid is drawn in.
+    pub fn desired_size(mut self, desired_size: Vec2) -> Self {
+        self.desired_size = desired_size;
+        self
+    }
+
+    /// Sets the colors used for cells with a heat of `0.0` and `1.0`.
+    pub fn colors(mut self, cold: Color32, hot: Color32) -> Self {
+        self.cold = cold;
+        self.hot = hot;
+        self
+    }
+
+    /// Returns the coordinates of the cell selected in the inspector with the id source
+    /// `id_source`, if any.
+    pub fn selected(ui: &Ui, id_source: impl std::hash::Hash) -> Option<Vector2<isize>> {
+        let id = ui.make_persistent_id(Id::new(id_source));
+        ui.data(|data| data.get_temp::<InspectorState>(id))
+            .and_then(|state| state.selected)
+    }
+}
+
+impl<T> Widget for GridInspector<'_, T> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let id = ui.make_persistent_id(self.id_source);
+        let mut state = ui
+            .data(|data| data.get_temp::<InspectorState>(id))
+            .unwrap_or_default();
+
+        let (rect, mut response) =
+            ui.allocate_exact_size(self.desired_size, Sense::click_and_drag());
+
+        // Pan by dragging, and zoom around the pointer by scrolling
+        if response.dragged() {
+            state.pan += response.drag_delta();
+        }
+        if let Some(pointer) = response.hover_pos() {
+            let scroll = ui.input(|input| input.smooth_scroll_delta.y);
+            let zoom = (state.zoom * (scroll * 0.002).exp()).clamp(0.05, 50.0);
+
+            let anchor = pointer - rect.min - state.pan;
+            state.pan -= anchor * (zoom / state.zoom - 1.0);
+            state.zoom = zoom;
+        }
+
+        let cell_size = self.cell_size * state.zoom;
+        let grid_min = rect.min + state.pan;
+        let cell_at = |position: Pos2| -> Vector2<isize> {
+            let offset = (position - grid_min) / cell_size;
+            self.grid.origin + vector![offset.x.floor() as isize, offset.y.floor() as isize]
+        };
+        let cell_rect = |cell: Vector2<isize>| -> Rect {
+            let offset = cell - self.grid.origin;
+            let min = grid_min + Vec2::new(offset.x as f32, offset.y as f32) * cell_size;
+            Rect::from_min_size(min, Vec2::splat(cell_size))
+        };
+
+        if response.clicked() {
+            if let Some(pointer) = response.interact_pointer_pos() {
+                let cell = cell_at(pointer);
+                state.selected = self.grid.get(cell).is_some().then_some(cell);
+            }
+        }
+
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+
+        // Only draw the cells which are visible
+        let (min, max) = (cell_at(rect.min), cell_at(rect.max));
+        let grid_max = self.grid.origin + self.grid.size.map(|length| length as isize);
+        for y in min.y.max(self.grid.origin.y)..=max.y.min(grid_max.y - 1) {
+            for x in min.x.max(self.grid.origin.x)..=max.x.min(grid_max.x - 1) {
+                let cell = vector![x, y];
+                let heat = (self.heat)(&self.grid[cell]).clamp(0.0, 1.0);
+                painter.rect_filled(cell_rect(cell), 0.0, lerp_color(self.cold, self.hot, heat));
+            }
+        }
+
+        if let Some(selected) = state.selected {
+            let stroke = Stroke::new(2.0, ui.visuals().selection.stroke.color);
+            painter.rect_stroke(cell_rect(selected), 0.0, stroke);
+
+            if let Some(cell) = self.grid.get(selected) {
+                let text = format!("{:?}: {}", selected.as_slice(), (self.format)(cell));
+                painter.text(
+                    rect.left_bottom() + Vec2::new(4.0, -4.0),
+                    egui::Align2::LEFT_BOTTOM,
+                    text,
+                    egui::FontId::monospace(12.0),
+                    ui.visuals().strong_text_color(),
+                );
+            }
+        }
+
+        if let Some(pointer) = response.hover_pos() {
+            let hovered = cell_at(pointer);
+            if let Some(cell) = self.grid.get(hovered) {
+                let text = format!("{:?}: {}", hovered.as_slice(), (self.format)(cell));
+                response = response.on_hover_text_at_pointer(text);
+            }
+        }
+
+        ui.data_mut(|data| data.insert_temp(id, state));
+        response
+    }
+}
+
+fn lerp_color(from: Color32, to: Color32, t: f32) -> Color32 {
+    let lerp = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * t).round() as u8;
+    Color32::from_rgb(
+        lerp(from.r(), to.r()),
+        lerp(from.g(), to.g()),
+        lerp(from.b(), to.b()),
+    )
+}
@@ -0,0 +1,250 @@
+//! Keeping only the most recently used chunks of a grid of subchunks in memory.
+//!
+//! An `EvictingGrid` wraps a grid of chunks implementing `Subchunk`, where each chunk is either
+//! resident in memory or held by a `ChunkStore`. When more chunks than the budget are resident,
+//! the least recently accessed chunks are handed to `ChunkStore::store` and evicted, and they are
+//! loaded again with `ChunkStore::load` the next time they are accessed. Chunks the store has
+//! never seen start as clones of the fill chunk.
+//!
+//! Resident chunks are boxed and kept by their chunk coordinates, so the memory used is
+//! proportional to the budget rather than the bounds of the grid, and evicting a chunk frees its
+//! memory even when the chunk stores its cells inline. Evicted chunks are not dropped, but kept
+//! in a pool, and chunks are loaded into a chunk from the pool where possible. This way neither
+//! the boxes nor any heap memory the chunks own need to be allocated and freed as the area in
+//! use moves around the world. `shrink_pool` drops the chunks in the pool.
+//!
+//! Every access takes `&mut self`, since it may load or evict chunks. Evicted chunks are always
+//! stored, whether or not they were modified, and `flush` stores every resident chunk, such as
+//! before exiting.
+
+use crate::{coord::GridVector, rect::GridRect, subchunk::Subchunk, util};
+use nalgebra::SVector;
+use std::collections::{BTreeMap, HashMap};
+
+/// Where chunks evicted from an `EvictingGrid` are kept, such as files on disk.
+pub trait ChunkStore<C, const D: usize = 2> {
+    /// Keeps a copy of `chunk`, which has the chunk coordinates `coordinates`, until it is loaded
+    /// again.
+    fn store(&mut self, coordinates: SVector<isize, D>, chunk: &C);
+
+    /// Overwrites `chunk` with the chunk with the chunk coordinates `coordinates`, returning
+    /// `false` without changing it if that chunk has never been stored. `chunk` is reused from a
+    /// previously evicted chunk where possible, so it should be overwritten in place.
+    fn load(&mut self, coordinates: SVector<isize, D>, chunk: &mut C) -> bool;
+}
+
+/// Keeps chunks in memory, which is mostly useful for testing.
+impl<C: Clone, const D: usize> ChunkStore<C, D> for HashMap<SVector<isize, D>, C> {
+    fn store(&mut self, coordinates: SVector<isize, D>, chunk: &C) {
+        self.insert(coordinates, chunk.clone());
+    }
+
+    fn load(&mut self, coordinates: SVector<isize, D>, chunk: &mut C) -> bool {
+        self.remove(&coordinates)
+            .map(|stored| *chunk = stored)
+            .is_some()
+    }
+}
+
+/// A grid of subchunks which keeps at most a fixed number of chunks in memory. See the `eviction`
+/// module for details.
+#[derive(Clone, Debug)]
+pub struct EvictingGrid<C, S, const D: usize = 2> {
+    /// The bounds of the grid of chunks, in chunk coordinates.
+    bounds: GridRect<D>,
+    /// Each resident chunk by its chunk coordinates, with the time it was last accessed.
+    chunks: HashMap<SVector<isize, D>, (Box<C>, u64)>,
+    /// The coordinates of each resident chunk, by the time it was last accessed.
+    accessed: BTreeMap<u64, SVector<isize, D>>,
+    time: u64,
+    budget: usize,
+    fill: C,
+    store: S,
+    /// Evicted chunks, which are reused when loading chunks.
+    pool: Vec<Box<C>>,
+}
+
+impl<C: Subchunk<D> + Clone, S: ChunkStore<C, D>, const D: usize> EvictingGrid<C, S, D>
+where
+    C::Output: Sized,
+{
+    /// Creates an empty grid which keeps at most `budget` chunks in memory, evicting chunks to
+    /// `store`. New chunks start as clones of `fill`.
+    ///
+    /// # Panics
+    /// Panics if `budget` is 0.
+    pub fn new(budget: usize, fill: C, store: S) -> Self {
+        assert!(budget > 0, "at least one chunk should be kept in memory");
+
+        Self {
+            bounds: GridRect::new(SVector::zeros(), SVector::zeros()),
+            chunks: HashMap::new(),
+            accessed: BTreeMap::new(),
+            time: 0,
+            budget,
+            fill,
+            store,
+            pool: Vec::new(),
+        }
+    }
+
+    /// Returns the maximum number of chunks kept in memory.
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Sets the maximum number of chunks kept in memory, evicting chunks if there are more.
+    ///
+    /// # Panics
+    /// Panics if `budget` is 0.
+    pub fn set_budget(&mut self, budget: usize) {
+        assert!(budget > 0, "at least one chunk should be kept in memory");
+        self.budget = budget;
+        self.evict();
+    }
+
+    /// Returns the number of chunks currently in memory.
+    pub fn resident_chunks(&self) -> usize {
+        self.accessed.len()
+    }
+
+    /// Returns `true` if the chunk with the chunk coordinates `coordinates` is in memory.
+    pub fn is_resident(&self, coordinates: impl GridVector<isize, D>) -> bool {
+        self.chunks.contains_key(&coordinates.to_vector())
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn store_mut(&mut self) -> &mut S {
+        &mut self.store
+    }
+
+    /// Stores every resident chunk, and returns the store.
+    pub fn into_store(mut self) -> S {
+        self.flush();
+        self.store
+    }
+
+    /// Returns the number of evicted chunks kept for reuse.
+    pub fn pooled_chunks(&self) -> usize {
+        self.pool.len()
+    }
+
+    /// Drops every evicted chunk kept for reuse.
+    pub fn shrink_pool(&mut self) {
+        self.pool = Vec::new();
+    }
+
+    /// Returns the size of the grid of chunks.
+    pub fn chunk_grid_size(&self) -> SVector<usize, D> {
+        self.bounds.size
+    }
+
+    /// Returns the chunk coordinates of the lowest corner of the grid of chunks.
+    pub fn chunk_grid_origin(&self) -> SVector<isize, D> {
+        self.bounds.origin
+    }
+
+    /// Returns the chunk with the chunk coordinates `coordinates`, loading it if needed, or
+    /// `None` if it is out of bounds.
+    pub fn chunk(&mut self, coordinates: impl GridVector<isize, D>) -> Option<&C> {
+        self.chunk_mut(coordinates).map(|chunk| &*chunk)
+    }
+
+    /// Returns the chunk with the chunk coordinates `coordinates`, loading it if needed, or
+    /// `None` if it is out of bounds.
+    pub fn chunk_mut(&mut self, coordinates: impl GridVector<isize, D>) -> Option<&mut C> {
+        let coordinates = coordinates.to_vector();
+        if !self.bounds.contains(coordinates) {
+            return None;
+        }
+
+        let time = self.time;
+        self.time += 1;
+
+        match self.chunks.get_mut(&coordinates) {
+            Some((_, accessed)) => {
+                self.accessed.remove(accessed);
+                *accessed = time;
+            }
+            None => {
+                let mut chunk = self
+                    .pool
+                    .pop()
+                    .unwrap_or_else(|| Box::new(self.fill.clone()));
+                if !self.store.load(coordinates, &mut chunk) {
+                    chunk.as_mut().clone_from(&self.fill);
+                }
+                self.chunks.insert(coordinates, (chunk, time));
+            }
+        }
+        self.accessed.insert(time, coordinates);
+
+        // The chunk was just accessed, so it is never evicted
+        self.evict();
+        self.chunks
+            .get_mut(&coordinates)
+            .map(|(chunk, _)| &mut **chunk)
+    }
+
+    /// Returns the cell at `index`, loading its chunk if needed, or `None` if it is out of bounds.
+    pub fn get(&mut self, index: impl GridVector<isize, D>) -> Option<&C::Output> {
+        let (chunk, subchunk) = util::split_chunk_index(index.to_vector(), C::SUBCHUNK_SIZE);
+        Some(&self.chunk(chunk)?[subchunk])
+    }
+
+    /// Returns the cell at `index`, loading its chunk if needed, or `None` if it is out of bounds.
+    pub fn get_mut(&mut self, index: impl GridVector<isize, D>) -> Option<&mut C::Output> {
+        let (chunk, subchunk) = util::split_chunk_index(index.to_vector(), C::SUBCHUNK_SIZE);
+        Some(&mut self.chunk_mut(chunk)?[subchunk])
+    }
+
+    /// Increases the size of the grid of chunks such that the cell at `point` is within its
+    /// bounds. New chunks are not loaded until they are accessed. See
+    /// `ExpandableGridN::expand_to_fit_point`.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<isize, D>) {
+        self.expand_to_fit_box(point, SVector::<usize, D>::repeat(1));
+    }
+
+    /// Increases the size of the grid of chunks such that every cell of a box is within its
+    /// bounds. New chunks are not loaded until they are accessed. See
+    /// `ExpandableGridN::expand_to_fit_box`.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<isize, D>,
+        box_size: impl GridVector<usize, D>,
+    ) {
+        let Some((first_chunk, last_chunk)) = util::chunk_range(
+            box_origin.to_vector(),
+            box_size.to_vector(),
+            C::SUBCHUNK_SIZE,
+        ) else {
+            return;
+        };
+
+        let chunk_box_size = (last_chunk - first_chunk).map(|length| length as usize + 1);
+        self.bounds = self
+            .bounds
+            .union(&GridRect::new(first_chunk, chunk_box_size));
+    }
+
+    /// Stores every resident chunk, keeping them in memory.
+    pub fn flush(&mut self) {
+        for (&coordinates, (chunk, _)) in &self.chunks {
+            self.store.store(coordinates, chunk);
+        }
+    }
+
+    /// Stores and evicts the least recently accessed chunks until no more than the budget are
+    /// resident.
+    fn evict(&mut self) {
+        while self.accessed.len() > self.budget {
+            let (_, coordinates) = self.accessed.pop_first().unwrap();
+            let (chunk, _) = self.chunks.remove(&coordinates).unwrap();
+            self.store.store(coordinates, &chunk);
+            self.pool.push(chunk);
+        }
+    }
+}
@@ -1,53 +1,157 @@
-use crate::util;
-use nalgebra::{vector, Vector2};
+use crate::{
+    alloc::{Global, GridStorage},
+    coord::{self, Coordinate, GridVector},
+    util,
+};
+use nalgebra::SVector;
+use std::slice::{ChunksExact, ChunksExactMut};
 
-/// Represents a 2d grid that can be expanded in any direction. It can be expanded to fit a point
-/// or box with `expand_to_fit_point` and `expand_to_fit_box`, as well as set to a specific size
-/// with `change_size`.
+/// Represents a `D` dimensional grid that can be expanded in any direction. It can be expanded to
+/// fit a point or box with `expand_to_fit_point` and `expand_to_fit_box`, as well as set to a
+/// specific size with `change_size`.
 ///
-/// Values are accessed with signed 2d coordinates stored as a `nalgebra::Vector2<isize>`.
+/// Values are accessed with signed coordinates stored as a `nalgebra::SVector<C, D>`, where `C`
+/// is `isize` unless another `Coordinate` type is chosen. Most code will want to use one of the
+/// aliases `ExpandableGrid` (2d) or `ExpandableGrid3` (3d).
+///
+/// The order of the values within `data` is determined by `layout`, which is kept when the grid
+/// changes size. `data` is a `Box<[T]>` unless another `GridStorage` is chosen as `A`, such as a
+/// custom allocator (see the `alloc` module).
 #[derive(Clone, Debug)]
-pub struct ExpandableGrid<T> {
-    pub size: Vector2<usize>,
-    pub origin: Vector2<isize>,
-    pub data: Box<[T]>,
+#[cfg_attr(
+    feature = "bevy",
+    derive(bevy_reflect::Reflect),
+    reflect_value(
+        Debug,
+        type_path = false,
+        where T: Clone + std::fmt::Debug + bevy_reflect::TypePath,
+        C: Clone + std::fmt::Debug + bevy_reflect::TypePath,
+        A: Clone + std::fmt::Debug + bevy_reflect::TypePath,
+        A::Buffer<T>: Clone + std::fmt::Debug
+    )
+)]
+pub struct ExpandableGridN<T, const D: usize, C = isize, A: GridStorage = Global> {
+    pub size: SVector<usize, D>,
+    pub origin: SVector<C, D>,
+    pub data: A::Buffer<T>,
+    pub layout: Layout,
+}
+
+/// A 2d grid that can be expanded in any direction, accessed with `nalgebra::Vector2<isize>`
+/// coordinates.
+pub type ExpandableGrid<T, C = isize, A = Global> = ExpandableGridN<T, 2, C, A>;
+
+/// A 3d grid that can be expanded in any direction, accessed with `nalgebra::Vector3<isize>`
+/// coordinates.
+pub type ExpandableGrid3<T, C = isize, A = Global> = ExpandableGridN<T, 3, C, A>;
+
+/// The order in which the cells of an `ExpandableGridN` are stored within its `data`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Layout {
+    /// Each row is stored contiguously, so `x` varies fastest and the last axis varies slowest.
+    #[default]
+    RowMajor,
+    /// Each column is stored contiguously, so the last axis varies fastest and `x` varies
+    /// slowest. In 2d, this means that `y` varies fastest.
+    ColumnMajor,
+}
+
+impl Layout {
+    /// Returns the index within the data of a grid of size `size` that the cell at `index`
+    /// (relative to the grid's origin) is stored at.
+    pub fn linear_index<const D: usize>(
+        self,
+        index: SVector<usize, D>,
+        size: SVector<usize, D>,
+    ) -> usize {
+        let accumulate = |linear_index: usize, axis: usize| linear_index * size[axis] + index[axis];
+
+        match self {
+            Layout::RowMajor => (0..D).rev().fold(0, accumulate),
+            Layout::ColumnMajor => (0..D).fold(0, accumulate),
+        }
+    }
+
+    /// Returns the position (relative to the grid's origin) of the cell stored at `linear_index`
+    /// within the data of a grid of size `size`. This is the inverse of `linear_index`.
+    pub fn position<const D: usize>(
+        self,
+        linear_index: usize,
+        size: SVector<usize, D>,
+    ) -> SVector<usize, D> {
+        let mut position = SVector::zeros();
+        let mut remaining = linear_index;
+        let mut take_axis = |axis: usize| {
+            position[axis] = remaining % size[axis];
+            remaining /= size[axis];
+        };
+
+        match self {
+            Layout::RowMajor => (0..D).for_each(&mut take_axis),
+            Layout::ColumnMajor => (0..D).rev().for_each(&mut take_axis),
+        }
+
+        position
+    }
+}
+
+impl<T, const D: usize, C: Coordinate> Default for ExpandableGridN<T, D, C> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl<T> ExpandableGrid<T> {
+impl<T, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
     /// Creates a new, empty grid
     pub fn new() -> Self {
-        Self {
-            size: vector![0, 0],
-            origin: vector![0, 0],
-            data: Box::new([]),
-        }
+        Self::with_layout(Layout::RowMajor)
+    }
+
+    /// Creates a new, empty grid which stores its data in the order given by `layout`
+    pub fn with_layout(layout: Layout) -> Self {
+        Self::with_layout_in(layout, Global)
     }
 
     /// Creates a new grid filled with clones of `fill`
-    pub fn with_size(size: Vector2<usize>, origin: Vector2<isize>, fill: &T) -> Self
+    pub fn with_size(
+        size: impl GridVector<usize, D>,
+        origin: impl GridVector<C, D>,
+        fill: &T,
+    ) -> Self
     where
         T: Clone,
     {
-        Self {
-            size,
-            origin,
-            data: std::iter::repeat(fill.clone())
-                .take(size.x * size.y)
-                .collect(),
-        }
+        Self::with_size_and_layout(size, origin, fill, Layout::RowMajor)
+    }
+
+    /// Creates a new grid filled with clones of `fill`, which stores its data in the order given
+    /// by `layout`
+    pub fn with_size_and_layout(
+        size: impl GridVector<usize, D>,
+        origin: impl GridVector<C, D>,
+        fill: &T,
+        layout: Layout,
+    ) -> Self
+    where
+        T: Clone,
+    {
+        Self::with_size_and_layout_in(size, origin, fill, layout, Global)
     }
+}
 
+impl<T, const D: usize, C: Coordinate, A: GridStorage> ExpandableGridN<T, D, C, A> {
     /// Increases the size of the grid such that `point` is included within the bounds of the grid.
     /// The newly created space is filled with clones of `fill`.
     ///
     /// Note that this is not guarenteed to expand exactly as much as is needed, rather, this
     /// method will first expand by doubling the width or height of the grid in each direction as
     /// nececary, and will expand further if this is not enough.
-    pub fn expand_to_fit_point(&mut self, point: Vector2<isize>, fill: &T)
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<C, D>, fill: &T)
     where
         T: Clone,
     {
-        self.expand_to_fit_box(point, vector![1, 1], fill);
+        self.expand_to_fit_box(point, SVector::<usize, D>::repeat(1), fill);
     }
 
     /// Increases the size of the grid such that all elements of a box with its bottom right corner at
@@ -59,163 +163,427 @@ impl<T> ExpandableGrid<T> {
     /// nececary, and will expand further if this is not enough.
     pub fn expand_to_fit_box(
         &mut self,
-        box_origin: Vector2<isize>,
-        box_size: Vector2<usize>,
+        box_origin: impl GridVector<C, D>,
+        box_size: impl GridVector<usize, D>,
         fill: &T,
     ) where
         T: Clone,
     {
-        if self.size == vector![0, 0] {
+        let (box_origin, box_size) = (box_origin.to_vector(), box_size.to_vector());
+
+        if self.size == SVector::<usize, D>::zeros() {
             self.size = box_size;
             self.origin = box_origin;
-            self.data = std::iter::repeat(fill.clone())
-                .take(box_size.x * box_size.y)
-                .collect();
-        } else {
-            let area_corner = util::usize_vec_to_isize(self.size) + self.origin;
-            let box_corner = util::usize_vec_to_isize(box_size) + box_origin;
-
-            let mut new_size = self.size;
-            let mut offset = vector![0, 0];
-            let mut expanded = false;
-
-            // Expand on x-axis
-            if box_origin.x < self.origin.x {
-                let distance = self.origin.x - box_origin.x;
-                let distance = util::calculate_exponential_distance(distance, self.size.x);
-                offset.x = -(distance as isize);
-                new_size.x += distance;
-                expanded = true;
-            }
-            if box_corner.x > area_corner.x {
-                let distance = box_corner.x - area_corner.x;
-                let distance = util::calculate_exponential_distance(distance, self.size.x);
-                new_size.x += distance;
-                expanded = true;
-            }
+            A::replace_buffer(&mut self.data, box_size.product(), fill, |_, _| {});
+        } else if let Some((new_size, offset)) = util::expansion_to_fit_box(
+            self.size,
+            coord::to_isize_vec(self.origin),
+            coord::to_isize_vec(box_origin),
+            box_size,
+        ) {
+            self.change_size(new_size, coord::from_isize_vec::<C, D>(offset), fill);
+        }
+    }
 
-            // Expand on y-axis
-            if box_origin.y < self.origin.y {
-                let distance = self.origin.y - box_origin.y;
-                let distance = util::calculate_exponential_distance(distance, self.size.y);
-                offset.y = -(distance as isize);
-                new_size.y += distance;
-                expanded = true;
-            }
-            if box_corner.y > area_corner.y {
-                let distance = box_corner.y - area_corner.y;
-                let distance = util::calculate_exponential_distance(distance, self.size.y);
-                new_size.y += distance;
-                expanded = true;
+    /// Changes the size of this grid, shifting the origin of the grid by `offset`. Any grid cells that
+    /// become out of bounds due to this are removed, and any new cells are cloned values of fill.
+    pub fn change_size(
+        &mut self,
+        new_size: impl GridVector<usize, D>,
+        offset: impl GridVector<C, D>,
+        fill: &T,
+    ) where
+        T: Clone,
+    {
+        let (new_size, offset) = (new_size.to_vector(), offset.to_vector());
+        self.change_size_isize(new_size, coord::to_isize_vec(offset), fill);
+    }
+
+    /// Same as `change_size`, with an offset in the type grids calculate with, which may not fit
+    /// in `C` even when the new origin does.
+    pub(crate) fn change_size_isize(
+        &mut self,
+        new_size: SVector<usize, D>,
+        isize_offset: SVector<isize, D>,
+        fill: &T,
+    ) where
+        T: Clone,
+    {
+        // Allocate and fill array with `fill`, then copy the old data to the new array, a
+        // contiguous run at a time
+        let (layout, size) = (self.layout, self.size);
+        A::replace_buffer(&mut self.data, new_size.product(), fill, |old, new| {
+            // Maintain consistant behavior if the grid is empty
+            if old.is_empty() {
+                return;
             }
 
-            if expanded {
-                self.change_size(new_size, offset, fill);
+            for (new_index, old_index, length) in
+                util::kept_runs(layout, size, new_size, isize_offset)
+            {
+                new[new_index..new_index + length]
+                    .clone_from_slice(&old[old_index..old_index + length]);
             }
+        });
+
+        // Update `self` with new values
+        self.size = new_size;
+        self.origin = coord::from_isize_vec(coord::to_isize_vec(self.origin) + isize_offset);
+    }
+
+    /// Sets every cell of the box with its lowest corner at `box_origin` and size `box_size`
+    /// which is within the bounds of this grid to clones of `value`. Cells are filled a
+    /// contiguous run at a time, which becomes a `memset` for `Copy` types.
+    pub fn fill_box(
+        &mut self,
+        box_origin: impl GridVector<C, D>,
+        box_size: impl GridVector<usize, D>,
+        value: &T,
+    ) where
+        T: Clone,
+    {
+        let origin = coord::to_isize_vec(self.origin);
+        let Some((box_origin, box_size)) = util::intersect_boxes(
+            origin,
+            self.size,
+            coord::to_isize_vec(box_origin.to_vector()),
+            box_size.to_vector(),
+        ) else {
+            return;
+        };
+        let start = (box_origin - origin).map(|component| component as usize);
+
+        for (index, length) in util::box_runs(self.layout, self.size, start, box_size) {
+            self.data[index..index + length].fill(value.clone());
         }
     }
 
-    /// Changes the size of this grid, shifting the origin of the grid by `offset`. Any grid cells that
-    /// become out of bounds due to this are removed, and any new cells are cloned values of fill.
-    pub fn change_size(&mut self, new_size: Vector2<usize>, offset: Vector2<isize>, fill: &T)
+    /// Creates a new grid covering the box with its lowest corner at `box_origin` and size
+    /// `box_size`, with the same layout as this grid. Cells of the box within the bounds of this
+    /// grid are cloned from it, and the rest are clones of `fill`.
+    pub fn copy_box(
+        &self,
+        box_origin: impl GridVector<C, D>,
+        box_size: impl GridVector<usize, D>,
+        fill: &T,
+    ) -> Self
+    where
+        T: Clone,
+        A: Clone,
+    {
+        let mut grid = Self::with_size_and_layout_in(
+            box_size,
+            box_origin,
+            fill,
+            self.layout,
+            self.allocator().clone(),
+        );
+        grid.blit(self);
+        grid
+    }
+
+    /// Copies every cell of `source` which is within the bounds of this grid to the same
+    /// coordinates within this grid. Cells of `source` outside the bounds of this grid are
+    /// ignored.
+    pub fn blit<B: GridStorage>(&mut self, source: &ExpandableGridN<T, D, C, B>)
     where
         T: Clone,
     {
-        // Maintain consistant behavior if the grid is empty
-        if self.data.len() == 0 {
-            *self = ExpandableGrid::with_size(new_size, offset, fill);
+        let (self_origin, source_origin) = (
+            coord::to_isize_vec(self.origin),
+            coord::to_isize_vec(source.origin),
+        );
+        let Some((origin, size)) =
+            util::intersect_boxes(self_origin, self.size, source_origin, source.size)
+        else {
+            return;
+        };
+
+        for offset in util::iter_box(SVector::zeros(), size) {
+            let position = origin + util::usize_vec_to_isize(offset);
+
+            // Safety: `position` is within the bounds of both grids
+            let (index, source_index) = unsafe {
+                (
+                    self.vector_to_1d_index(
+                        (position - self_origin).map(|component| component as usize),
+                    ),
+                    source.vector_to_1d_index(
+                        (position - source_origin).map(|component| component as usize),
+                    ),
+                )
+            };
+            self.data[index] = source.data[source_index].clone();
         }
+    }
 
-        // Calculate the offsets of size and size + origin
-        let relative_size =
-            util::usize_vec_to_isize(new_size) - util::usize_vec_to_isize(self.size);
-        let corner_offset = offset + relative_size;
-
-        // Allocate and fill array with `fill`
-        let mut data: Box<_> = std::iter::repeat(fill.clone())
-            .take(new_size.x * new_size.y)
-            .collect();
-
-        // Calculate bounds of the old size in the coordinate space of the new size
-        let start = util::isize_vec_to_usize_saturating(-offset);
-        let end = new_size - util::isize_vec_to_usize_saturating(corner_offset);
-
-        // Copy the old data to the new array
-        for y in start.y..end.y {
-            let new_line_start = y * new_size.x;
-            let old_line_start = (y.checked_add_signed(offset.y))
-                .expect("offset.y should never be less than -y")
-                * self.size.x;
-
-            for x in start.x..end.x {
-                let new_index = x + new_line_start;
-                let old_index = (x.checked_add_signed(offset.x))
-                    .expect("offset.x should never be less than -x")
-                    + old_line_start;
-
-                data[new_index] = self.data[old_index].clone();
-            }
+    /// Expands this grid to fit every cell of `other`, then combines the two grids. Where a cell
+    /// of `other` overlaps a cell within the previous bounds of this grid, it becomes the result
+    /// of `resolve` on this grid's cell and `other`'s cell. Other cells of `other` are cloned,
+    /// and any remaining new space is filled with clones of `fill`.
+    pub fn merge<B: GridStorage>(
+        &mut self,
+        other: &ExpandableGridN<T, D, C, B>,
+        mut resolve: impl FnMut(&T, &T) -> T,
+        fill: &T,
+    ) where
+        T: Clone,
+    {
+        if other.data.is_empty() {
+            return;
         }
 
-        // Update `self` with new values
-        self.data = data;
-        self.size = new_size;
-        self.origin += offset;
+        let (old_origin, old_size) = (coord::to_isize_vec(self.origin), self.size);
+        self.expand_to_fit_box(other.origin, other.size, fill);
+
+        let (self_origin, other_origin) = (
+            coord::to_isize_vec(self.origin),
+            coord::to_isize_vec(other.origin),
+        );
+        for offset in util::iter_box(SVector::zeros(), other.size) {
+            let position = other_origin + util::usize_vec_to_isize(offset);
+
+            // Safety: `position` is within the bounds of both grids after expanding
+            let (index, other_index) = unsafe {
+                (
+                    self.vector_to_1d_index(
+                        (position - self_origin).map(|component| component as usize),
+                    ),
+                    other.vector_to_1d_index(offset),
+                )
+            };
+
+            let other_cell = &other.data[other_index];
+            self.data[index] = if util::relative_position(old_origin, old_size, position).is_some()
+            {
+                resolve(&self.data[index], other_cell)
+            } else {
+                other_cell.clone()
+            };
+        }
+    }
+
+    /// Returns the number of cells within the bounds of the grid.
+    pub fn area(&self) -> usize {
+        self.data.len()
     }
 
-    pub fn get(&self, index: Vector2<isize>) -> Option<&T> {
+    /// Returns `true` if the grid has no cells, which is the case if its size is 0 on any axis.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns `true` if `point` is within the bounds of the grid.
+    pub fn contains(&self, point: impl GridVector<C, D>) -> bool {
+        self.index_of(point).is_some()
+    }
+
+    /// Returns the coordinates just past the highest corner of the grid on every axis, or
+    /// `origin + size`. The highest cell within the bounds of the grid is one less on every axis.
+    pub fn corner(&self) -> SVector<C, D> {
+        coord::from_isize_vec(
+            coord::to_isize_vec(self.origin) + util::usize_vec_to_isize(self.size),
+        )
+    }
+
+    /// Returns the size of the grid as any [`GridVector`], such as a tuple or array.
+    pub fn size_as<V: GridVector<usize, D>>(&self) -> V {
+        V::from_vector(self.size)
+    }
+
+    /// Returns the origin of the grid as any [`GridVector`], such as a tuple or array.
+    pub fn origin_as<V: GridVector<C, D>>(&self) -> V {
+        V::from_vector(self.origin)
+    }
+
+    /// Returns [`corner`](Self::corner) as any [`GridVector`], such as a tuple or array.
+    pub fn corner_as<V: GridVector<C, D>>(&self) -> V {
+        V::from_vector(self.corner())
+    }
+
+    pub fn get(&self, index: impl GridVector<C, D>) -> Option<&T> {
         Some(&self.data[self.index_of(index)?])
     }
 
-    pub fn get_mut(&mut self, index: Vector2<isize>) -> Option<&mut T> {
-        Some(&mut self.data[self.index_of(index)?])
+    pub fn get_mut(&mut self, index: impl GridVector<C, D>) -> Option<&mut T> {
+        let index = self.index_of(index)?;
+        Some(&mut self.data[index])
     }
 
     /// Returns the index within self.data that a value is present within.
-    pub fn index_of(&self, index: Vector2<isize>) -> Option<usize> {
-        let absolute_index = index - self.origin;
+    pub fn index_of(&self, index: impl GridVector<C, D>) -> Option<usize> {
+        self.index_of_isize(coord::to_isize_vec(index.to_vector()))
+    }
 
-        if absolute_index.x < 0 || absolute_index.y < 0 {
-            None
-        } else {
-            let absolute_index = vector![absolute_index.x as usize, absolute_index.y as usize];
+    /// Same as `index_of`, but takes coordinates in the type grids calculate with, so that
+    /// positions outside the range of `C` are out of bounds rather than failing to convert.
+    pub(crate) fn index_of_isize(&self, index: SVector<isize, D>) -> Option<usize> {
+        let absolute_index =
+            util::relative_position(coord::to_isize_vec(self.origin), self.size, index)?;
 
-            if absolute_index.x >= self.size.x || absolute_index.y >= self.size.y {
-                None
-            } else {
-                // Safety: absolute_index has been bounds checked
-                let data_index = unsafe { self.vector_to_1d_index(absolute_index) };
+        // Safety: absolute_index has been bounds checked
+        let data_index = unsafe { self.vector_to_1d_index(absolute_index) };
 
-                Some(data_index)
-            }
-        }
+        Some(data_index)
+    }
+
+    /// Same as `get`, but takes coordinates in the type grids calculate with. See
+    /// `index_of_isize`.
+    pub(crate) fn get_isize(&self, index: SVector<isize, D>) -> Option<&T> {
+        Some(&self.data[self.index_of_isize(index)?])
     }
 
     /// Returns the index within self.data that a value is present within.
     /// # Safety
     /// `index` is expected to fall within the bounds of the grid
-    pub unsafe fn index_of_unchecked(&self, index: Vector2<isize>) -> usize {
-        let absolute_index = index - self.origin;
-        let absolute_index = vector![absolute_index.x as usize, absolute_index.y as usize];
+    pub unsafe fn index_of_unchecked(&self, index: SVector<C, D>) -> usize {
+        let absolute_index = (coord::to_isize_vec(index) - coord::to_isize_vec(self.origin))
+            .map(|component| component as usize);
 
         self.vector_to_1d_index(absolute_index)
     }
 
-    unsafe fn vector_to_1d_index(&self, index: Vector2<usize>) -> usize {
-        index.x + index.y * self.size.x
+    unsafe fn vector_to_1d_index(&self, index: SVector<usize, D>) -> usize {
+        self.layout.linear_index(index, self.size)
+    }
+}
+
+impl<T, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Creates a grid with the same size, origin, and layout as this one, where each cell is the
+    /// result of `f` on the cell at the same coordinates in this grid.
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> ExpandableGridN<U, D, C> {
+        ExpandableGridN {
+            size: self.size,
+            origin: self.origin,
+            data: self.data.iter().map(f).collect(),
+            layout: self.layout,
+        }
+    }
+
+    /// Converts every cell of this grid with `U::from`, keeping its size, origin, and layout.
+    pub fn convert<U: From<T>>(self) -> ExpandableGridN<U, D, C> {
+        ExpandableGridN {
+            size: self.size,
+            origin: self.origin,
+            data: self.data.into_vec().into_iter().map(U::from).collect(),
+            layout: self.layout,
+        }
+    }
+
+    /// Converts every cell of this grid with `U::try_from`, keeping its size, origin, and layout,
+    /// or returns the error of the first cell which fails to convert.
+    pub fn try_convert<U: TryFrom<T>>(self) -> Result<ExpandableGridN<U, D, C>, U::Error> {
+        Ok(ExpandableGridN {
+            size: self.size,
+            origin: self.origin,
+            data: (self.data.into_vec().into_iter())
+                .map(U::try_from)
+                .collect::<Result<_, _>>()?,
+            layout: self.layout,
+        })
+    }
+
+    /// Creates a grid with the same size, origin, and layout as this one, where each cell is the
+    /// result of `f` on the cells at the same coordinates in this grid and `other`. The grids may
+    /// have different layouts.
+    ///
+    /// # Panics
+    /// Panics if `other` does not have the same size and origin as this grid.
+    pub fn zip_map<U, V>(
+        &self,
+        other: &ExpandableGridN<U, D, C>,
+        mut f: impl FnMut(&T, &U) -> V,
+    ) -> ExpandableGridN<V, D, C> {
+        let other_index = self.zip_index(other);
+
+        ExpandableGridN {
+            size: self.size,
+            origin: self.origin,
+            data: self
+                .data
+                .iter()
+                .enumerate()
+                .map(|(index, cell)| f(cell, &other.data[other_index(index)]))
+                .collect(),
+            layout: self.layout,
+        }
+    }
+
+    /// Returns a function mapping indices within the data of this grid to the indices of the
+    /// cells at the same coordinates within `other`.
+    ///
+    /// # Panics
+    /// Panics if `other` does not have the same size and origin as this grid.
+    pub(crate) fn zip_index<U>(
+        &self,
+        other: &ExpandableGridN<U, D, C>,
+    ) -> impl Fn(usize) -> usize + Sync {
+        assert!(
+            self.size == other.size && self.origin == other.origin,
+            "grids should have the same size and origin",
+        );
+        let (layout, other_layout, size) = (self.layout, other.layout, self.size);
+
+        move |index| {
+            if layout == other_layout {
+                index
+            } else {
+                other_layout.linear_index(layout.position(index, size), size)
+            }
+        }
+    }
+}
+
+impl<T, C, A: GridStorage> ExpandableGridN<T, 2, C, A> {
+    /// Returns an iterator over the rows of the grid as contiguous slices, from the lowest `y` to
+    /// the highest, or `None` if the grid is not stored in `Layout::RowMajor`.
+    pub fn rows(&self) -> Option<ChunksExact<'_, T>> {
+        match self.layout {
+            Layout::RowMajor => Some(self.data.chunks_exact(self.size.x.max(1))),
+            Layout::ColumnMajor => None,
+        }
+    }
+
+    /// Mutable version of `rows`.
+    pub fn rows_mut(&mut self) -> Option<ChunksExactMut<'_, T>> {
+        match self.layout {
+            Layout::RowMajor => Some(self.data.chunks_exact_mut(self.size.x.max(1))),
+            Layout::ColumnMajor => None,
+        }
+    }
+
+    /// Returns an iterator over the columns of the grid as contiguous slices, from the lowest `x`
+    /// to the highest, or `None` if the grid is not stored in `Layout::ColumnMajor`.
+    pub fn columns(&self) -> Option<ChunksExact<'_, T>> {
+        match self.layout {
+            Layout::RowMajor => None,
+            Layout::ColumnMajor => Some(self.data.chunks_exact(self.size.y.max(1))),
+        }
+    }
+
+    /// Mutable version of `columns`.
+    pub fn columns_mut(&mut self) -> Option<ChunksExactMut<'_, T>> {
+        match self.layout {
+            Layout::RowMajor => None,
+            Layout::ColumnMajor => Some(self.data.chunks_exact_mut(self.size.y.max(1))),
+        }
     }
 }
 
-impl<T> std::ops::Index<Vector2<isize>> for ExpandableGrid<T> {
+impl<T, I: GridVector<C, D>, const D: usize, C: Coordinate, A: GridStorage> std::ops::Index<I>
+    for ExpandableGridN<T, D, C, A>
+{
     type Output = T;
 
-    fn index(&self, index: Vector2<isize>) -> &Self::Output {
+    fn index(&self, index: I) -> &Self::Output {
         self.get(index).unwrap()
     }
 }
 
-impl<T> std::ops::IndexMut<Vector2<isize>> for ExpandableGrid<T> {
-    fn index_mut(&mut self, index: Vector2<isize>) -> &mut Self::Output {
+impl<T, I: GridVector<C, D>, const D: usize, C: Coordinate, A: GridStorage> std::ops::IndexMut<I>
+    for ExpandableGridN<T, D, C, A>
+{
+    fn index_mut(&mut self, index: I) -> &mut Self::Output {
         self.get_mut(index).unwrap()
     }
 }
@@ -0,0 +1,291 @@
+//! A C ABI for grids of `int64_t` cells, so that grids can be used from C, C++, or any other
+//! language with a C foreign function interface. The matching header is
+//! `include/expandable_grid.h`.
+//!
+//! Grids are passed as opaque `EgGrid` pointers, created with `eg_grid_new`, `eg_grid_with_size`,
+//! or their `_with_layout` variants, and freed with `eg_grid_free`. Coordinates are signed and
+//! may be negative, exactly as with `ExpandableGrid`. Build a library to link against with
+//! `cargo rustc --release --features ffi --crate-type staticlib` (or `cdylib`).
+//!
+//! Panics cannot unwind into C, so functions which allocate check the size of the grid they
+//! would create first. Constructors return null and the functions which resize a grid return
+//! false, leaving it unchanged, if its cells would not fit within `PTRDIFF_MAX` bytes or its
+//! corners would overflow `ptrdiff_t`. Running out of memory for a grid of a valid size still
+//! aborts the process.
+
+use crate::{util, ExpandableGrid, Layout};
+use nalgebra::{vector, Vector2};
+use std::{ffi::c_void, mem::size_of};
+
+/// The grid behind an `EgGrid *` handle.
+pub type EgGrid = ExpandableGrid<i64>;
+
+/// The value `eg_grid_layout` returns and the constructors take for `Layout::RowMajor`.
+pub const EG_LAYOUT_ROW_MAJOR: u32 = 0;
+/// The value `eg_grid_layout` returns and the constructors take for `Layout::ColumnMajor`.
+pub const EG_LAYOUT_COLUMN_MAJOR: u32 = 1;
+
+fn layout_from_u32(layout: u32) -> Option<Layout> {
+    match layout {
+        EG_LAYOUT_ROW_MAJOR => Some(Layout::RowMajor),
+        EG_LAYOUT_COLUMN_MAJOR => Some(Layout::ColumnMajor),
+        _ => None,
+    }
+}
+
+/// Returns whether a grid of size `size` with its lowest corner at `origin` can be created,
+/// which needs its cells to fit within `isize::MAX` bytes and its corners to fit in `isize`.
+fn fits(size: Vector2<usize>, origin: Vector2<isize>) -> bool {
+    let bytes = util::checked_area(size).and_then(|area| area.checked_mul(size_of::<i64>()));
+
+    bytes.is_some_and(|bytes| bytes <= isize::MAX as usize)
+        && util::checked_add(origin, size).is_some()
+}
+
+/// Creates a new, empty grid. It must be freed with `eg_grid_free`.
+#[no_mangle]
+pub extern "C" fn eg_grid_new() -> *mut EgGrid {
+    Box::into_raw(Box::new(EgGrid::new()))
+}
+
+/// Creates a new grid of size `width` by `height` with its lowest corner at `(x, y)`, filled with
+/// `fill`. Returns null if the grid would be too large. It must be freed with `eg_grid_free`.
+#[no_mangle]
+pub extern "C" fn eg_grid_with_size(
+    width: usize,
+    height: usize,
+    x: isize,
+    y: isize,
+    fill: i64,
+) -> *mut EgGrid {
+    eg_grid_with_size_and_layout(width, height, x, y, fill, EG_LAYOUT_ROW_MAJOR)
+}
+
+/// Creates a new, empty grid which stores its cells in the order given by `layout`, either
+/// `EG_LAYOUT_ROW_MAJOR` or `EG_LAYOUT_COLUMN_MAJOR`. Returns null if `layout` is neither. It
+/// must be freed with `eg_grid_free`.
+#[no_mangle]
+pub extern "C" fn eg_grid_with_layout(layout: u32) -> *mut EgGrid {
+    match layout_from_u32(layout) {
+        Some(layout) => Box::into_raw(Box::new(EgGrid::with_layout(layout))),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Creates a new grid as with `eg_grid_with_size`, which stores its cells in the order given by
+/// `layout`. Returns null if `layout` is neither `EG_LAYOUT_ROW_MAJOR` nor
+/// `EG_LAYOUT_COLUMN_MAJOR`, or if the grid would be too large. It must be freed with
+/// `eg_grid_free`.
+#[no_mangle]
+pub extern "C" fn eg_grid_with_size_and_layout(
+    width: usize,
+    height: usize,
+    x: isize,
+    y: isize,
+    fill: i64,
+    layout: u32,
+) -> *mut EgGrid {
+    let (size, origin) = (vector![width, height], vector![x, y]);
+
+    match layout_from_u32(layout) {
+        Some(layout) if fits(size, origin) => Box::into_raw(Box::new(
+            EgGrid::with_size_and_layout(size, origin, &fill, layout),
+        )),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// Frees a grid. Does nothing if `grid` is null.
+///
+/// # Safety
+/// `grid` must be null or a grid created by this module which has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn eg_grid_free(grid: *mut EgGrid) {
+    if !grid.is_null() {
+        drop(Box::from_raw(grid));
+    }
+}
+
+/// Writes the width and height of a grid to `width` and `height`.
+///
+/// # Safety
+/// `grid` must be a valid grid, and `width` and `height` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn eg_grid_size(grid: *const EgGrid, width: *mut usize, height: *mut usize) {
+    let grid = &*grid;
+    (*width, *height) = (grid.size.x, grid.size.y);
+}
+
+/// Writes the coordinates of the lowest corner of a grid to `x` and `y`.
+///
+/// # Safety
+/// `grid` must be a valid grid, and `x` and `y` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn eg_grid_origin(grid: *const EgGrid, x: *mut isize, y: *mut isize) {
+    let grid = &*grid;
+    (*x, *y) = (grid.origin.x, grid.origin.y);
+}
+
+/// Writes the cell at `(x, y)` to `value` and returns true, or returns false without writing if
+/// the cell is out of bounds.
+///
+/// # Safety
+/// `grid` must be a valid grid, and `value` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn eg_grid_get(
+    grid: *const EgGrid,
+    x: isize,
+    y: isize,
+    value: *mut i64,
+) -> bool {
+    match (*grid).get(vector![x, y]) {
+        Some(&cell) => {
+            *value = cell;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Sets the cell at `(x, y)` to `value` and returns true, or returns false if the cell is out of
+/// bounds.
+///
+/// # Safety
+/// `grid` must be a valid grid.
+#[no_mangle]
+pub unsafe extern "C" fn eg_grid_set(grid: *mut EgGrid, x: isize, y: isize, value: i64) -> bool {
+    match (*grid).get_mut(vector![x, y]) {
+        Some(cell) => {
+            *cell = value;
+            true
+        }
+        None => false,
+    }
+}
+
+/// See `ExpandableGridN::expand_to_fit_point`. Returns false, leaving the grid unchanged, if it
+/// would become too large.
+///
+/// # Safety
+/// `grid` must be a valid grid, and no pointer returned by `eg_grid_data` may be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn eg_grid_expand_to_fit_point(
+    grid: *mut EgGrid,
+    x: isize,
+    y: isize,
+    fill: i64,
+) -> bool {
+    eg_grid_expand_to_fit_box(grid, x, y, 1, 1, fill)
+}
+
+/// See `ExpandableGridN::expand_to_fit_box`. Returns false, leaving the grid unchanged, if it
+/// would become too large.
+///
+/// # Safety
+/// `grid` must be a valid grid, and no pointer returned by `eg_grid_data` may be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn eg_grid_expand_to_fit_box(
+    grid: *mut EgGrid,
+    x: isize,
+    y: isize,
+    width: usize,
+    height: usize,
+    fill: i64,
+) -> bool {
+    let grid = &mut *grid;
+    let (box_origin, box_size) = (vector![x, y], vector![width, height]);
+
+    let resized = if grid.size == Vector2::zeros() {
+        fits(box_size, box_origin)
+    } else {
+        match util::checked_expansion_to_fit_box(grid.size, grid.origin, box_origin, box_size) {
+            Some(Some((size, offset))) => fits(size, grid.origin + offset),
+            Some(None) => true,
+            None => false,
+        }
+    };
+
+    if resized {
+        grid.expand_to_fit_box(box_origin, box_size, &fill);
+    }
+    resized
+}
+
+/// See `ExpandableGridN::change_size`. Returns false, leaving the grid unchanged, if it would
+/// become too large.
+///
+/// # Safety
+/// `grid` must be a valid grid, and no pointer returned by `eg_grid_data` may be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn eg_grid_change_size(
+    grid: *mut EgGrid,
+    width: usize,
+    height: usize,
+    offset_x: isize,
+    offset_y: isize,
+    fill: i64,
+) -> bool {
+    let grid = &mut *grid;
+    let (size, offset) = (vector![width, height], vector![offset_x, offset_y]);
+
+    let resized =
+        util::checked_add_signed(grid.origin, offset).is_some_and(|origin| fits(size, origin));
+    if resized {
+        grid.change_size(size, offset, &fill);
+    }
+    resized
+}
+
+/// Returns either `EG_LAYOUT_ROW_MAJOR` or `EG_LAYOUT_COLUMN_MAJOR`, giving the order of the cells
+/// returned by `eg_grid_data`.
+///
+/// # Safety
+/// `grid` must be a valid grid.
+#[no_mangle]
+pub unsafe extern "C" fn eg_grid_layout(grid: *const EgGrid) -> u32 {
+    match (*grid).layout {
+        Layout::RowMajor => EG_LAYOUT_ROW_MAJOR,
+        Layout::ColumnMajor => EG_LAYOUT_COLUMN_MAJOR,
+    }
+}
+
+/// Returns a pointer to the cells of a grid, in the order given by `eg_grid_layout`, and writes
+/// the number of cells to `length`. The pointer is invalidated when the grid changes size or is
+/// freed.
+///
+/// # Safety
+/// `grid` must be a valid grid, and `length` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn eg_grid_data(grid: *mut EgGrid, length: *mut usize) -> *mut i64 {
+    let grid = &mut *grid;
+    *length = grid.data.len();
+    grid.data.as_mut_ptr()
+}
+
+/// Calls `callback` with the coordinates and value of every cell of a grid, along with
+/// `user_data`, in the order the cells are stored.
+///
+/// # Safety
+/// `grid` must be a valid grid, which `callback` must not modify or free.
+#[no_mangle]
+pub unsafe extern "C" fn eg_grid_for_each(
+    grid: *const EgGrid,
+    callback: extern "C" fn(x: isize, y: isize, value: i64, user_data: *mut c_void),
+    user_data: *mut c_void,
+) {
+    let grid = &*grid;
+    let (width, height) = (grid.size.x, grid.size.y);
+
+    for (index, &value) in grid.data.iter().enumerate() {
+        let (x, y) = match grid.layout {
+            Layout::RowMajor => (index % width, index / width),
+            Layout::ColumnMajor => (index / height, index % height),
+        };
+        callback(
+            grid.origin.x + x as isize,
+            grid.origin.y + y as isize,
+            value,
+            user_data,
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! Interoperability with the `glam` crate.
+//!
+//! `IVec2` and `IVec3` implement `GridVector` for coordinates of any `Coordinate` type, and `UVec2`
+//! and `UVec3` for sizes, so they can be passed directly to any method of a grid, including for
+//! indexing. The
+//! conversion functions in this module convert fields such as `size` and `origin` back to `glam`
+//! vectors.
+
+use crate::coord::{self, Coordinate, GridVector};
+use glam::{IVec2, IVec3, UVec2, UVec3};
+use nalgebra::{vector, SVector, Vector2, Vector3};
+
+/// Converts a `glam` coordinate to a coordinate of an `ExpandableGrid`.
+pub fn from_ivec2(vector: IVec2) -> Vector2<isize> {
+    vector![vector.x as isize, vector.y as isize]
+}
+
+/// Converts a `glam` size to a size of an `ExpandableGrid`.
+pub fn from_uvec2(vector: UVec2) -> Vector2<usize> {
+    vector![vector.x as usize, vector.y as usize]
+}
+
+/// Converts a coordinate of an `ExpandableGrid` to a `glam` coordinate, or returns `None` if it
+/// does not fit in an `i32`.
+pub fn to_ivec2(vector: Vector2<isize>) -> Option<IVec2> {
+    Some(IVec2::new(
+        vector.x.try_into().ok()?,
+        vector.y.try_into().ok()?,
+    ))
+}
+
+/// Converts a size of an `ExpandableGrid` to a `glam` size, or returns `None` if it does not fit
+/// in a `u32`.
+pub fn to_uvec2(vector: Vector2<usize>) -> Option<UVec2> {
+    Some(UVec2::new(
+        vector.x.try_into().ok()?,
+        vector.y.try_into().ok()?,
+    ))
+}
+
+/// Converts a `glam` coordinate to a coordinate of an `ExpandableGrid3`.
+pub fn from_ivec3(vector: IVec3) -> Vector3<isize> {
+    vector![vector.x as isize, vector.y as isize, vector.z as isize]
+}
+
+/// Converts a `glam` size to a size of an `ExpandableGrid3`.
+pub fn from_uvec3(vector: UVec3) -> Vector3<usize> {
+    vector![vector.x as usize, vector.y as usize, vector.z as usize]
+}
+
+/// Converts a coordinate of an `ExpandableGrid3` to a `glam` coordinate, or returns `None` if it
+/// does not fit in an `i32`.
+pub fn to_ivec3(vector: Vector3<isize>) -> Option<IVec3> {
+    Some(IVec3::new(
+        vector.x.try_into().ok()?,
+        vector.y.try_into().ok()?,
+        vector.z.try_into().ok()?,
+    ))
+}
+
+/// Converts a size of an `ExpandableGrid3` to a `glam` size, or returns `None` if it does not
+/// fit in a `u32`.
+pub fn to_uvec3(vector: Vector3<usize>) -> Option<UVec3> {
+    Some(UVec3::new(
+        vector.x.try_into().ok()?,
+        vector.y.try_into().ok()?,
+        vector.z.try_into().ok()?,
+    ))
+}
+
+macro_rules! impl_grid_vector_for_glam_coordinate {
+    ($glam:ident, $dimensions:literal, $($axis:ident),*) => {
+        impl<C: Coordinate> GridVector<C, $dimensions> for $glam {
+            fn to_vector(self) -> SVector<C, $dimensions> {
+                vector![$(C::from_isize(self.$axis as isize)),*]
+            }
+
+            fn from_vector(vector: SVector<C, $dimensions>) -> Self {
+                let [$($axis),*] = coord::to_isize_vec(vector).into();
+                $glam::new($(
+                    $axis.try_into().expect(concat!("vector should fit in a ", stringify!($glam)))
+                ),*)
+            }
+        }
+    };
+}
+
+macro_rules! impl_grid_vector_for_glam_size {
+    ($glam:ident, $to:ident, $dimensions:literal, $($axis:ident),*) => {
+        impl GridVector<usize, $dimensions> for $glam {
+            fn to_vector(self) -> SVector<usize, $dimensions> {
+                vector![$(self.$axis as usize),*]
+            }
+
+            fn from_vector(vector: SVector<usize, $dimensions>) -> Self {
+                $to(vector).expect(concat!(
+                    "vector should fit in a ",
+                    stringify!($glam),
+                ))
+            }
+        }
+    };
+}
+
+impl_grid_vector_for_glam_coordinate!(IVec2, 2, x, y);
+impl_grid_vector_for_glam_size!(UVec2, to_uvec2, 2, x, y);
+impl_grid_vector_for_glam_coordinate!(IVec3, 3, x, y, z);
+impl_grid_vector_for_glam_size!(UVec3, to_uvec3, 3, x, y, z);
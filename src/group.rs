@@ -0,0 +1,187 @@
+//! Linking grids owned by different systems so that they expand together.
+//!
+//! A `GridGroup` keeps track of several grids, each behind an `Arc<RwLock<_>>` so that the
+//! systems using them can own and share them, along with a fill value for each. Expanding the
+//! group expands every member to the same bounds, filling each with its own fill value. This is a
+//! lighter alternative to `LayeredGrid` when the grids are owned by different systems.
+//!
+//! The group only holds weak references, so a member is removed from the group once every `Arc`
+//! to it is dropped. Members should only be resized through the group; a member resized directly
+//! is brought back to the bounds of the group the next time the group changes size.
+
+use crate::{coord::GridVector, util, ExpandableGridN};
+use nalgebra::SVector;
+use std::{
+    fmt,
+    sync::{Arc, PoisonError, RwLock, Weak},
+};
+
+/// A grid which can be shared between the systems using it and the `GridGroup` it belongs to.
+pub type SharedGrid<T, const D: usize = 2> = Arc<RwLock<ExpandableGridN<T, D>>>;
+
+/// A member of a group, which can be resized without knowing its cell type.
+trait Member<const D: usize>: Send + Sync {
+    /// Changes the bounds of the grid, returning `false` if it has been dropped.
+    fn resize(&self, size: SVector<usize, D>, origin: SVector<isize, D>) -> bool;
+
+    /// Returns `false` if the grid has been dropped.
+    fn is_alive(&self) -> bool;
+}
+
+struct Linked<T, const D: usize> {
+    grid: Weak<RwLock<ExpandableGridN<T, D>>>,
+    fill: T,
+}
+
+impl<T: Clone + Send + Sync, const D: usize> Member<D> for Linked<T, D> {
+    fn resize(&self, size: SVector<usize, D>, origin: SVector<isize, D>) -> bool {
+        let Some(grid) = self.grid.upgrade() else {
+            return false;
+        };
+        // Poisoning is ignored, as cells are always left valid
+        let mut grid = grid.write().unwrap_or_else(PoisonError::into_inner);
+
+        if grid.data.is_empty() {
+            let layout = grid.layout;
+            *grid = ExpandableGridN::with_size_and_layout(size, origin, &self.fill, layout);
+        } else if (grid.size, grid.origin) != (size, origin) {
+            let offset = origin - grid.origin;
+            grid.change_size(size, offset, &self.fill);
+        }
+
+        true
+    }
+
+    fn is_alive(&self) -> bool {
+        self.grid.strong_count() > 0
+    }
+}
+
+/// A set of grids which are always expanded together. See the `group` module for details.
+pub struct GridGroup<const D: usize = 2> {
+    size: SVector<usize, D>,
+    origin: SVector<isize, D>,
+    members: Vec<Box<dyn Member<D>>>,
+}
+
+impl<const D: usize> GridGroup<D> {
+    /// Creates a group with no members and no cells.
+    pub fn new() -> Self {
+        Self {
+            size: SVector::zeros(),
+            origin: SVector::zeros(),
+            members: Vec::new(),
+        }
+    }
+
+    /// Returns the size shared by every member.
+    pub fn size(&self) -> SVector<usize, D> {
+        self.size
+    }
+
+    /// Returns the origin shared by every member.
+    pub fn origin(&self) -> SVector<isize, D> {
+        self.origin
+    }
+
+    /// Returns the number of members which have not been dropped.
+    pub fn len(&self) -> usize {
+        self.members
+            .iter()
+            .filter(|member| member.is_alive())
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Creates a new member with the bounds of this group, filled with clones of `fill`, which
+    /// is also used to fill it when the group expands.
+    pub fn add<T: Clone + Send + Sync + 'static>(&mut self, fill: T) -> SharedGrid<T, D> {
+        let grid = ExpandableGridN::with_size(self.size, self.origin, &fill);
+        self.add_grid(Arc::new(RwLock::new(grid)), fill)
+    }
+
+    /// Adds `grid` to this group, filling it with clones of `fill` when the group expands. If
+    /// this group has no cells, it takes the bounds of `grid`. Otherwise `grid` is resized to
+    /// the bounds of this group, keeping the cells within them.
+    pub fn add_grid<T: Clone + Send + Sync + 'static>(
+        &mut self,
+        grid: SharedGrid<T, D>,
+        fill: T,
+    ) -> SharedGrid<T, D> {
+        let member = Linked {
+            grid: Arc::downgrade(&grid),
+            fill,
+        };
+
+        if self.size.product() == 0 {
+            let grid = grid.read().unwrap_or_else(PoisonError::into_inner);
+            (self.size, self.origin) = (grid.size, grid.origin);
+        }
+        // Bring every member to the new bounds, in case this group had no cells before
+        self.members.push(Box::new(member));
+        self.resize_members();
+
+        grid
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_point`. Every member is expanded.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<isize, D>) {
+        self.expand_to_fit_box(point, SVector::<usize, D>::repeat(1));
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_box`. Every member is expanded.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<isize, D>,
+        box_size: impl GridVector<usize, D>,
+    ) {
+        let (box_origin, box_size) = (box_origin.to_vector(), box_size.to_vector());
+
+        if self.size.product() == 0 {
+            (self.size, self.origin) = (box_size, box_origin);
+        } else if let Some((new_size, offset)) =
+            util::expansion_to_fit_box(self.size, self.origin, box_origin, box_size)
+        {
+            (self.size, self.origin) = (new_size, self.origin + offset);
+        } else {
+            return;
+        }
+
+        self.resize_members();
+    }
+
+    /// See `ExpandableGridN::change_size`. Every member is resized.
+    pub fn change_size(
+        &mut self,
+        new_size: impl GridVector<usize, D>,
+        offset: impl GridVector<isize, D>,
+    ) {
+        self.size = new_size.to_vector();
+        self.origin += offset.to_vector();
+        self.resize_members();
+    }
+
+    fn resize_members(&mut self) {
+        let (size, origin) = (self.size, self.origin);
+        self.members.retain(|member| member.resize(size, origin));
+    }
+}
+
+impl<const D: usize> Default for GridGroup<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const D: usize> fmt::Debug for GridGroup<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GridGroup")
+            .field("size", &self.size)
+            .field("origin", &self.origin)
+            .field("members", &self.len())
+            .finish()
+    }
+}
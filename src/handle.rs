@@ -0,0 +1,85 @@
+//! Handles to cells which stay valid when a grid changes size.
+//!
+//! An index into `data` refers to a different cell once the grid changes size, as every cell may
+//! move within its data. A `CellHandle` instead stores the coordinates of a cell, which never
+//! change as the grid expands, along with its index and the bounds of the grid when the handle
+//! was created. While the bounds are unchanged, `resolve` uses the stored index directly, and
+//! otherwise it finds the cell by its coordinates again.
+//!
+//! A handle to a cell which has been removed by shrinking the grid resolves to `None`. If the
+//! grid later grows back over its coordinates, the handle resolves to the new cell there.
+
+use crate::{
+    coord::{Coordinate, GridVector},
+    ExpandableGridN, Layout,
+};
+use nalgebra::SVector;
+
+/// A handle to a cell of an `ExpandableGridN`. See the `handle` module for details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CellHandle<const D: usize = 2, C: Coordinate = isize> {
+    position: SVector<C, D>,
+    index: usize,
+    size: SVector<usize, D>,
+    origin: SVector<C, D>,
+    layout: Layout,
+}
+
+impl<const D: usize, C: Coordinate> CellHandle<D, C> {
+    /// Returns the coordinates of the cell.
+    pub fn position(&self) -> SVector<C, D> {
+        self.position
+    }
+}
+
+impl<T, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Returns a handle to the cell at `index`, or `None` if it is out of bounds.
+    pub fn handle(&self, index: impl GridVector<C, D>) -> Option<CellHandle<D, C>> {
+        let position = index.to_vector();
+
+        Some(CellHandle {
+            position,
+            index: self.index_of(position)?,
+            size: self.size,
+            origin: self.origin,
+            layout: self.layout,
+        })
+    }
+
+    /// Returns the cell of `handle`, or `None` if it is no longer within the bounds of the grid.
+    pub fn resolve(&self, handle: &CellHandle<D, C>) -> Option<&T> {
+        Some(&self.data[self.resolve_index(handle)?])
+    }
+
+    /// Returns the cell of `handle`, or `None` if it is no longer within the bounds of the grid.
+    pub fn resolve_mut(&mut self, handle: &CellHandle<D, C>) -> Option<&mut T> {
+        let index = self.resolve_index(handle)?;
+        Some(&mut self.data[index])
+    }
+
+    /// Returns whether the grid has changed size, origin, or layout since `handle` was created,
+    /// so that resolving it must find its cell again. `refresh` avoids this.
+    pub fn is_stale(&self, handle: &CellHandle<D, C>) -> bool {
+        (handle.size, handle.origin, handle.layout) != (self.size, self.origin, self.layout)
+    }
+
+    /// Updates `handle` to the current bounds of the grid, returning `false` and leaving it
+    /// unchanged if its cell is no longer within them.
+    pub fn refresh(&self, handle: &mut CellHandle<D, C>) -> bool {
+        match self.handle(handle.position) {
+            Some(refreshed) => {
+                *handle = refreshed;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn resolve_index(&self, handle: &CellHandle<D, C>) -> Option<usize> {
+        if self.is_stale(handle) {
+            self.index_of(handle.position)
+        } else {
+            Some(handle.index)
+        }
+    }
+}
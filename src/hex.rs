@@ -0,0 +1,178 @@
+//! Helpers for treating an `ExpandableGrid` as a hexagonal grid.
+//!
+//! Hexes are addressed with axial coordinates stored as a `nalgebra::Vector2<isize>`, where `x`
+//! is the `q` axis and `y` is the `r` axis. Axial coordinates map directly onto the rectangular
+//! storage of the grid, so any `ExpandableGrid` can be indexed by hex without any conversion, and
+//! expands to fit hexes in the same way as it does for points. The implicit third cube axis `s`
+//! is always `-q - r`.
+//!
+//! Offset coordinates, which are more common in map files, can be converted to and from axial
+//! coordinates with `offset_to_axial` and `axial_to_offset`.
+
+use crate::ExpandableGrid;
+use nalgebra::{vector, Vector2};
+
+/// The axial offsets of the six neighbors of a hex, in counterclockwise order starting with the
+/// neighbor in the positive `q` direction.
+pub const DIRECTIONS: [Vector2<isize>; 6] = [
+    Vector2::new(1, 0),
+    Vector2::new(1, -1),
+    Vector2::new(0, -1),
+    Vector2::new(-1, 0),
+    Vector2::new(-1, 1),
+    Vector2::new(0, 1),
+];
+
+/// The ways in which a hex map can be stored in offset coordinates, which determines which rows
+/// or columns are shoved over by half a hex.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OffsetLayout {
+    /// Pointy topped hexes, with odd rows shoved right.
+    OddR,
+    /// Pointy topped hexes, with even rows shoved right.
+    EvenR,
+    /// Flat topped hexes, with odd columns shoved down.
+    OddQ,
+    /// Flat topped hexes, with even columns shoved down.
+    EvenQ,
+}
+
+/// Converts a hex in offset coordinates to axial coordinates.
+pub fn offset_to_axial(offset: Vector2<isize>, layout: OffsetLayout) -> Vector2<isize> {
+    let (column, row) = (offset.x, offset.y);
+
+    match layout {
+        OffsetLayout::OddR => vector![column - (row - (row & 1)) / 2, row],
+        OffsetLayout::EvenR => vector![column - (row + (row & 1)) / 2, row],
+        OffsetLayout::OddQ => vector![column, row - (column - (column & 1)) / 2],
+        OffsetLayout::EvenQ => vector![column, row - (column + (column & 1)) / 2],
+    }
+}
+
+/// Converts a hex in axial coordinates to offset coordinates.
+pub fn axial_to_offset(axial: Vector2<isize>, layout: OffsetLayout) -> Vector2<isize> {
+    let (q, r) = (axial.x, axial.y);
+
+    match layout {
+        OffsetLayout::OddR => vector![q + (r - (r & 1)) / 2, r],
+        OffsetLayout::EvenR => vector![q + (r + (r & 1)) / 2, r],
+        OffsetLayout::OddQ => vector![q, r + (q - (q & 1)) / 2],
+        OffsetLayout::EvenQ => vector![q, r + (q + (q & 1)) / 2],
+    }
+}
+
+/// Returns the number of steps between two hexes.
+pub fn distance(a: Vector2<isize>, b: Vector2<isize>) -> usize {
+    let difference = a - b;
+
+    (difference.x.unsigned_abs()
+        + difference.y.unsigned_abs()
+        + (difference.x + difference.y).unsigned_abs())
+        / 2
+}
+
+/// Returns an iterator over the six neighbors of `hex`, in the same order as `DIRECTIONS`.
+pub fn neighbors(hex: Vector2<isize>) -> impl Iterator<Item = Vector2<isize>> {
+    DIRECTIONS.into_iter().map(move |direction| hex + direction)
+}
+
+/// Returns an iterator over every hex within `radius` steps of `center`, including `center`.
+pub fn range(center: Vector2<isize>, radius: usize) -> impl Iterator<Item = Vector2<isize>> {
+    let radius = radius as isize;
+
+    (-radius..=radius).flat_map(move |q| {
+        let r_start = (-radius).max(-q - radius);
+        let r_end = radius.min(-q + radius);
+
+        (r_start..=r_end).map(move |r| center + vector![q, r])
+    })
+}
+
+/// Returns an iterator over every hex exactly `radius` steps from `center`. A radius of 0 yields
+/// only `center`.
+pub fn ring(center: Vector2<isize>, radius: usize) -> impl Iterator<Item = Vector2<isize>> {
+    let start = center + DIRECTIONS[4] * radius as isize;
+    let steps = if radius == 0 { 1 } else { radius * 6 };
+
+    (0..steps).scan(start, move |hex, step| {
+        let current = *hex;
+        if let Some(side) = step.checked_div(radius) {
+            *hex += DIRECTIONS[side];
+        }
+        Some(current)
+    })
+}
+
+/// Returns an iterator over the hexes along a straight line from `start` to `end`, including both
+/// ends. Consecutive hexes are always neighbors.
+pub fn line(start: Vector2<isize>, end: Vector2<isize>) -> impl Iterator<Item = Vector2<isize>> {
+    let length = distance(start, end);
+
+    // Nudge the line slightly so that it never lies exactly on the edge between two hexes
+    let start_cube = to_cube(start) + vector![1e-6, 1e-6, -2e-6];
+    let end_cube = to_cube(end) + vector![1e-6, 1e-6, -2e-6];
+
+    (0..=length).map(move |step| {
+        let t = if length == 0 {
+            0.0
+        } else {
+            step as f64 / length as f64
+        };
+
+        round_cube(start_cube.lerp(&end_cube, t))
+    })
+}
+
+fn to_cube(hex: Vector2<isize>) -> nalgebra::Vector3<f64> {
+    vector![hex.x as f64, hex.y as f64, (-hex.x - hex.y) as f64]
+}
+
+fn round_cube(cube: nalgebra::Vector3<f64>) -> Vector2<isize> {
+    let rounded = cube.map(f64::round);
+    let difference = (rounded - cube).abs();
+
+    let (q, r) = if difference.x > difference.y && difference.x > difference.z {
+        (-rounded.y - rounded.z, rounded.y)
+    } else if difference.y > difference.z {
+        (rounded.x, -rounded.x - rounded.z)
+    } else {
+        (rounded.x, rounded.y)
+    };
+
+    vector![q as isize, r as isize]
+}
+
+impl<T> ExpandableGrid<T> {
+    /// Returns an iterator over the neighbors of `hex` which are within the bounds of the grid,
+    /// along with their values.
+    pub fn hex_neighbors(
+        &self,
+        hex: Vector2<isize>,
+    ) -> impl Iterator<Item = (Vector2<isize>, &T)> + '_ {
+        neighbors(hex).filter_map(|neighbor| Some((neighbor, self.get(neighbor)?)))
+    }
+
+    /// Returns an iterator over every hex within `radius` steps of `center` which is within the
+    /// bounds of the grid, along with its value.
+    pub fn hex_range(
+        &self,
+        center: Vector2<isize>,
+        radius: usize,
+    ) -> impl Iterator<Item = (Vector2<isize>, &T)> + '_ {
+        range(center, radius).filter_map(|hex| Some((hex, self.get(hex)?)))
+    }
+
+    /// Increases the size of the grid such that every hex within `radius` steps of `center` is
+    /// within the bounds of the grid. The newly created space is filled with clones of `fill`.
+    ///
+    /// See `expand_to_fit_box` for details on how much the grid expands.
+    pub fn expand_to_fit_hex_range(&mut self, center: Vector2<isize>, radius: usize, fill: &T)
+    where
+        T: Clone,
+    {
+        let box_origin = center - Vector2::repeat(radius as isize);
+        let box_size = Vector2::repeat(radius * 2 + 1);
+
+        self.expand_to_fit_box(box_origin, box_size, fill);
+    }
+}
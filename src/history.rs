@@ -0,0 +1,419 @@
+//! Undo and redo for edits to a grid, such as in a level editor.
+//!
+//! A `GridHistory` wraps a grid, recording how to reverse each edit made through it: the old
+//! value of each cell set, the old cells of each rect filled or blitted, and the old bounds of the
+//! grid when it is resized. `undo` reverses the last edit, restoring the grid exactly as it was,
+//! and `redo` makes it again. Several edits can be grouped into one step with `group`.
+//!
+//! Expanding the grid only records its old bounds, since the new cells are all clones of the
+//! fill value, but shrinking it records every cell of the old grid. The history can be limited
+//! to a number of steps and an estimate of the memory it uses, dropping the oldest steps first.
+
+use crate::{
+    coord::{self, Coordinate, GridVector},
+    rect::GridRect,
+    ExpandableGridN,
+};
+use nalgebra::SVector;
+use std::{mem::size_of, ops::Deref};
+
+/// A change to a grid, which when applied returns the change that reverses it.
+#[derive(Clone, Debug)]
+enum Change<T, const D: usize, C: Coordinate> {
+    /// Set a cell.
+    Cell { index: SVector<C, D>, value: T },
+    /// Copy these cells into the grid, which are always within its bounds.
+    Cells(ExpandableGridN<T, D, C>),
+    /// Change the bounds of the grid, where the new bounds contain the current ones, and the new
+    /// cells are clones of `fill`.
+    Expand { rect: GridRect<D, C>, fill: T },
+    /// Change the bounds of the grid, where the current bounds contain the new ones, and the
+    /// removed cells are all clones of `fill`.
+    Crop { rect: GridRect<D, C>, fill: T },
+    /// Replace the whole grid.
+    Grid(ExpandableGridN<T, D, C>),
+}
+
+impl<T: Clone, const D: usize, C: Coordinate> Change<T, D, C> {
+    fn apply(self, grid: &mut ExpandableGridN<T, D, C>) -> Self {
+        match self {
+            Change::Cell { index, value } => Change::Cell {
+                index,
+                value: std::mem::replace(&mut grid[index], value),
+            },
+            Change::Cells(cells) => {
+                let old = grid.copy_box(cells.origin, cells.size, &cells.data[0]);
+                grid.blit(&cells);
+                Change::Cells(old)
+            }
+            Change::Expand { rect, fill } => {
+                let old = grid.bounds();
+                resize_to(grid, rect, &fill);
+                Change::Crop { rect: old, fill }
+            }
+            Change::Crop { rect, fill } => {
+                let old = grid.bounds();
+                resize_to(grid, rect, &fill);
+                Change::Expand { rect: old, fill }
+            }
+            Change::Grid(old) => Change::Grid(std::mem::replace(grid, old)),
+        }
+    }
+
+    /// Returns an estimate of the memory used by this change, in bytes.
+    fn memory_usage(&self) -> usize {
+        size_of::<Self>()
+            + match self {
+                Change::Cells(cells) | Change::Grid(cells) => cells.data.len() * size_of::<T>(),
+                Change::Cell { .. } | Change::Expand { .. } | Change::Crop { .. } => 0,
+            }
+    }
+}
+
+/// A step of the history, made up of changes which are reversed together.
+#[derive(Clone, Debug)]
+struct Step<T, const D: usize, C: Coordinate> {
+    changes: Vec<Change<T, D, C>>,
+    memory_usage: usize,
+}
+
+impl<T: Clone, const D: usize, C: Coordinate> Step<T, D, C> {
+    fn new() -> Self {
+        Self {
+            changes: Vec::new(),
+            memory_usage: 0,
+        }
+    }
+
+    fn push(&mut self, change: Change<T, D, C>) {
+        self.memory_usage += change.memory_usage();
+        self.changes.push(change);
+    }
+
+    /// Applies the changes of this step in reverse order, returning the step which reverses it.
+    fn apply(self, grid: &mut ExpandableGridN<T, D, C>) -> Self {
+        let mut inverse = Step::new();
+        for change in self.changes.into_iter().rev() {
+            inverse.push(change.apply(grid));
+        }
+
+        inverse
+    }
+}
+
+/// A grid which records its edits so that they can be undone and redone. See the `history`
+/// module for details.
+#[derive(Clone, Debug)]
+pub struct GridHistory<T, const D: usize = 2, C: Coordinate = isize> {
+    grid: ExpandableGridN<T, D, C>,
+    undo: Vec<Step<T, D, C>>,
+    redo: Vec<Step<T, D, C>>,
+    /// The step edits are recorded to while within `group`.
+    group: Option<Step<T, D, C>>,
+    max_steps: usize,
+    memory_budget: Option<usize>,
+}
+
+impl<T: Clone, const D: usize, C: Coordinate> GridHistory<T, D, C> {
+    /// The number of steps kept by default.
+    pub const DEFAULT_MAX_STEPS: usize = 100;
+
+    /// Wraps `grid` with an empty history, which keeps up to `DEFAULT_MAX_STEPS` steps.
+    pub fn new(grid: ExpandableGridN<T, D, C>) -> Self {
+        Self {
+            grid,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            group: None,
+            max_steps: Self::DEFAULT_MAX_STEPS,
+            memory_budget: None,
+        }
+    }
+
+    pub fn into_inner(self) -> ExpandableGridN<T, D, C> {
+        self.grid
+    }
+
+    /// Sets the number of steps which can be undone, dropping the oldest steps if there are
+    /// more.
+    pub fn set_max_steps(&mut self, max_steps: usize) {
+        self.max_steps = max_steps;
+        self.enforce_limits();
+    }
+
+    /// Sets the estimated number of bytes the steps which can be undone may use, dropping the
+    /// oldest steps if they use more. The most recent step is always kept, even if it is over
+    /// budget on its own.
+    pub fn set_memory_budget(&mut self, memory_budget: Option<usize>) {
+        self.memory_budget = memory_budget;
+        self.enforce_limits();
+    }
+
+    /// Returns an estimate of the number of bytes used by the history, not including the grid.
+    pub fn memory_usage(&self) -> usize {
+        (self.undo.iter().chain(&self.redo))
+            .map(|step| step.memory_usage)
+            .sum()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Reverses the last step, returning `false` if there is nothing to undo.
+    ///
+    /// # Panics
+    /// Panics if called within `group`.
+    pub fn undo(&mut self) -> bool {
+        assert!(self.group.is_none(), "cannot undo within a group");
+        let Some(step) = self.undo.pop() else {
+            return false;
+        };
+
+        self.redo.push(step.apply(&mut self.grid));
+        true
+    }
+
+    /// Makes the last undone step again, returning `false` if there is nothing to redo.
+    ///
+    /// # Panics
+    /// Panics if called within `group`.
+    pub fn redo(&mut self) -> bool {
+        assert!(self.group.is_none(), "cannot redo within a group");
+        let Some(step) = self.redo.pop() else {
+            return false;
+        };
+
+        self.undo.push(step.apply(&mut self.grid));
+        true
+    }
+
+    /// Removes every step from the history.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Calls `f` with this history, recording every edit it makes as a single step.
+    pub fn group<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let outer = self.group.replace(Step::new());
+        let result = f(self);
+        let step = std::mem::replace(&mut self.group, outer).unwrap();
+
+        // Nested groups become part of the outer group
+        match &mut self.group {
+            Some(group) => step
+                .changes
+                .into_iter()
+                .for_each(|change| group.push(change)),
+            None if !step.changes.is_empty() => self.push_step(step),
+            None => (),
+        }
+        result
+    }
+
+    /// Sets the cell at `index` to `value`, returning the previous value, or `None` if it is out
+    /// of bounds.
+    pub fn set(&mut self, index: impl GridVector<C, D>, value: T) -> Option<T> {
+        let index = index.to_vector();
+        let old = std::mem::replace(self.grid.get_mut(index)?, value);
+
+        self.record(Change::Cell {
+            index,
+            value: old.clone(),
+        });
+        Some(old)
+    }
+
+    /// Calls `f` with a mutable reference to the cell at `index`, returning its result, or `None`
+    /// if it is out of bounds.
+    pub fn modify<R>(
+        &mut self,
+        index: impl GridVector<C, D>,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Option<R> {
+        let index = index.to_vector();
+        let cell = self.grid.get_mut(index)?;
+        let old = cell.clone();
+        let result = f(cell);
+
+        self.record(Change::Cell { index, value: old });
+        Some(result)
+    }
+
+    /// Sets every cell of `rect` within the bounds of the grid to clones of `value`.
+    pub fn fill_rect(&mut self, rect: GridRect<D, C>, value: &T) {
+        let Some(rect) = self.grid.bounds().intersection(&rect) else {
+            return;
+        };
+
+        self.record(Change::Cells(self.grid.copy_box(
+            rect.origin,
+            rect.size,
+            value,
+        )));
+        self.grid.fill_rect(rect, value);
+    }
+
+    /// See `ExpandableGridN::blit`.
+    pub fn blit(&mut self, source: &ExpandableGridN<T, D, C>) {
+        let Some(rect) = self.grid.intersection_bounds(source) else {
+            return;
+        };
+
+        self.record(Change::Cells(self.grid.copy_box(
+            rect.origin,
+            rect.size,
+            &source.data[0],
+        )));
+        self.grid.blit(source);
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_point`.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<C, D>, fill: &T) {
+        self.expand_to_fit_box(point, SVector::<usize, D>::repeat(1), fill);
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_box`.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<C, D>,
+        box_size: impl GridVector<usize, D>,
+        fill: &T,
+    ) {
+        let old = self.grid.clone_if_empty();
+        let rect = self.grid.bounds();
+
+        self.grid.expand_to_fit_box(box_origin, box_size, fill);
+        self.record_resize(old, rect, fill);
+    }
+
+    /// See `ExpandableGridN::change_size`.
+    pub fn change_size(
+        &mut self,
+        new_size: impl GridVector<usize, D>,
+        offset: impl GridVector<C, D>,
+        fill: &T,
+    ) {
+        let (new_size, offset) = (new_size.to_vector(), offset.to_vector());
+        let rect = self.grid.bounds();
+        let new_rect = GridRect::<D>::new(
+            coord::to_isize_vec(self.grid.origin) + coord::to_isize_vec(offset),
+            new_size,
+        );
+
+        // Shrinking on any axis removes cells, so the whole grid must be kept
+        let old = if contains(new_rect, rect.to_isize()) {
+            self.grid.clone_if_empty()
+        } else {
+            Some(self.grid.clone())
+        };
+
+        self.grid.change_size(new_size, offset, fill);
+        self.record_resize(old, rect, fill);
+    }
+
+    /// Records the change which reverses a change of the bounds of the grid from `rect`, where
+    /// `old` is the whole grid before the change if it can't be reversed by cropping.
+    fn record_resize(
+        &mut self,
+        old: Option<ExpandableGridN<T, D, C>>,
+        rect: GridRect<D, C>,
+        fill: &T,
+    ) {
+        if self.grid.bounds() == rect {
+            return;
+        }
+
+        self.record(match old {
+            Some(old) => Change::Grid(old),
+            None => Change::Crop {
+                rect,
+                fill: fill.clone(),
+            },
+        });
+    }
+
+    fn record(&mut self, change: Change<T, D, C>) {
+        self.redo.clear();
+
+        match &mut self.group {
+            Some(group) => group.push(change),
+            None => {
+                let mut step = Step::new();
+                step.push(change);
+                self.push_step(step);
+            }
+        }
+    }
+
+    fn push_step(&mut self, step: Step<T, D, C>) {
+        self.undo.push(step);
+        self.enforce_limits();
+    }
+
+    fn enforce_limits(&mut self) {
+        let excess_steps = self.undo.len().saturating_sub(self.max_steps);
+        self.undo.drain(..excess_steps);
+
+        if let Some(memory_budget) = self.memory_budget {
+            let mut memory_usage: usize = self.undo.iter().map(|step| step.memory_usage).sum();
+            let mut excess_steps = 0;
+
+            while memory_usage > memory_budget && excess_steps + 1 < self.undo.len() {
+                memory_usage -= self.undo[excess_steps].memory_usage;
+                excess_steps += 1;
+            }
+            self.undo.drain(..excess_steps);
+        }
+    }
+}
+
+impl<T, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Returns a clone of this grid if it is empty, since changing the size of an empty grid
+    /// can't be reversed by cropping it.
+    fn clone_if_empty(&self) -> Option<Self>
+    where
+        T: Clone,
+    {
+        self.data.is_empty().then(|| self.clone())
+    }
+}
+
+/// Changes the bounds of `grid` to `rect`, filling new cells with clones of `fill`.
+fn resize_to<T: Clone, const D: usize, C: Coordinate>(
+    grid: &mut ExpandableGridN<T, D, C>,
+    rect: GridRect<D, C>,
+    fill: &T,
+) {
+    let offset = coord::to_isize_vec(rect.origin) - coord::to_isize_vec(grid.origin);
+    grid.change_size_isize(rect.size, offset, fill);
+}
+
+fn contains<const D: usize>(outer: GridRect<D>, inner: GridRect<D>) -> bool {
+    (0..D).all(|axis| {
+        outer.origin[axis] <= inner.origin[axis] && inner.end()[axis] <= outer.end()[axis]
+    })
+}
+
+impl<T, const D: usize, C: Coordinate> Deref for GridHistory<T, D, C> {
+    type Target = ExpandableGridN<T, D, C>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.grid
+    }
+}
+
+impl<T, I: GridVector<C, D>, const D: usize, C: Coordinate> std::ops::Index<I>
+    for GridHistory<T, D, C>
+{
+    type Output = T;
+
+    fn index(&self, index: I) -> &Self::Output {
+        &self.grid[index]
+    }
+}
@@ -0,0 +1,149 @@
+//! Conversions between grids and images from the `image` crate, mostly useful for inspecting
+//! grids while debugging.
+//!
+//! `ExpandableGrid<u8>` converts to and from `GrayImage`, and `ExpandableGrid<[u8; 4]>` converts
+//! to and from `RgbaImage`. The pixel at the top left corner of an image is the cell at the
+//! grid's origin, and `y` increases downwards, as with ASCII maps in the `ascii` module. Images
+//! have no origin of their own, so it is kept alongside the image in a `GridImage`.
+//!
+//! Heightmaps stored as grayscale PNG images can be loaded with
+//! `ExpandableGrid::from_heightmap_png`.
+
+use crate::ExpandableGrid;
+use image::{
+    DynamicImage, GrayImage, ImageBuffer, ImageFormat, ImageResult, Luma, Pixel, Rgba, RgbaImage,
+};
+use nalgebra::{vector, Vector2};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Seek},
+    path::Path,
+};
+
+/// An image created from a grid, along with the origin of the grid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GridImage<P: Pixel> {
+    pub image: ImageBuffer<P, Vec<P::Subpixel>>,
+    /// The coordinates of the cell at the top left corner of the image.
+    pub origin: Vector2<isize>,
+}
+
+impl ExpandableGrid<u8> {
+    /// Creates a grayscale image with a pixel for each cell of this grid.
+    ///
+    /// # Panics
+    /// Panics if the width or height of the grid does not fit in a `u32`.
+    pub fn to_gray_image(&self) -> GridImage<Luma<u8>> {
+        to_image(self, |&cell| Luma([cell]))
+    }
+
+    /// Creates a grid from a grayscale image, with the top left pixel at `origin`.
+    pub fn from_gray_image(image: &GrayImage, origin: Vector2<isize>) -> Self {
+        from_image(image, origin, |&Luma([cell])| cell)
+    }
+}
+
+impl ExpandableGrid<[u8; 4]> {
+    /// Creates an RGBA image with a pixel for each cell of this grid.
+    ///
+    /// # Panics
+    /// Panics if the width or height of the grid does not fit in a `u32`.
+    pub fn to_rgba_image(&self) -> GridImage<Rgba<u8>> {
+        to_image(self, |&cell| Rgba(cell))
+    }
+
+    /// Creates a grid from an RGBA image, with the top left pixel at `origin`.
+    pub fn from_rgba_image(image: &RgbaImage, origin: Vector2<isize>) -> Self {
+        from_image(image, origin, |&Rgba(cell)| cell)
+    }
+}
+
+impl ExpandableGrid<f32> {
+    /// Loads a heightmap from a PNG image, with the top left pixel at `origin`. Each pixel is
+    /// converted to a height between `0.0` for black and `scale` for white.
+    ///
+    /// Both 8 and 16 bit grayscale images are read at their full precision. Color images are
+    /// converted to grayscale first.
+    pub fn from_heightmap_png(
+        reader: impl BufRead + Seek,
+        origin: Vector2<isize>,
+        scale: f32,
+    ) -> ImageResult<Self> {
+        let image = image::load(reader, ImageFormat::Png)?;
+
+        let grid = match image {
+            DynamicImage::ImageLuma8(image) => from_image(&image, origin, |&Luma([height])| {
+                height as f32 / u8::MAX as f32 * scale
+            }),
+            image => from_image(&image.into_luma16(), origin, |&Luma([height])| {
+                height as f32 / u16::MAX as f32 * scale
+            }),
+        };
+
+        Ok(grid)
+    }
+
+    /// Same as `from_heightmap_png`, but reads the image from the file at `path`.
+    pub fn from_heightmap_png_file(
+        path: impl AsRef<Path>,
+        origin: Vector2<isize>,
+        scale: f32,
+    ) -> ImageResult<Self> {
+        let file = File::open(path)?;
+        Self::from_heightmap_png(BufReader::new(file), origin, scale)
+    }
+}
+
+impl From<&ExpandableGrid<u8>> for GridImage<Luma<u8>> {
+    fn from(grid: &ExpandableGrid<u8>) -> Self {
+        grid.to_gray_image()
+    }
+}
+
+impl From<GridImage<Luma<u8>>> for ExpandableGrid<u8> {
+    fn from(image: GridImage<Luma<u8>>) -> Self {
+        ExpandableGrid::from_gray_image(&image.image, image.origin)
+    }
+}
+
+impl From<&ExpandableGrid<[u8; 4]>> for GridImage<Rgba<u8>> {
+    fn from(grid: &ExpandableGrid<[u8; 4]>) -> Self {
+        grid.to_rgba_image()
+    }
+}
+
+impl From<GridImage<Rgba<u8>>> for ExpandableGrid<[u8; 4]> {
+    fn from(image: GridImage<Rgba<u8>>) -> Self {
+        ExpandableGrid::from_rgba_image(&image.image, image.origin)
+    }
+}
+
+fn to_image<T, P: Pixel>(grid: &ExpandableGrid<T>, pixel: impl Fn(&T) -> P) -> GridImage<P> {
+    let width = u32::try_from(grid.size.x).expect("grid width should fit in a u32");
+    let height = u32::try_from(grid.size.y).expect("grid height should fit in a u32");
+
+    let image = ImageBuffer::from_fn(width, height, |x, y| {
+        pixel(&grid[grid.origin + vector![x as isize, y as isize]])
+    });
+
+    GridImage {
+        image,
+        origin: grid.origin,
+    }
+}
+
+fn from_image<T, P: Pixel>(
+    image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    origin: Vector2<isize>,
+    cell: impl Fn(&P) -> T,
+) -> ExpandableGrid<T> {
+    let (width, height) = image.dimensions();
+
+    // Images are stored row by row from the top, which matches a row major grid
+    ExpandableGrid {
+        size: vector![width as usize, height as usize],
+        origin,
+        data: image.pixels().map(cell).collect(),
+        layout: Default::default(),
+    }
+}
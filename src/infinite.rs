@@ -0,0 +1,107 @@
+//! A grid of subchunks with no bounds, which generates each chunk the first time it is accessed.
+//!
+//! An `InfiniteGrid` is created with a generator, which is called with the chunk coordinates of
+//! each chunk the first time one of its cells is accessed. Accessing any cell always succeeds,
+//! expanding the underlying grid of chunks as needed, so `expand_to_fit_point` and friends never
+//! need to be called. This suits procedurally generated worlds, where the generator is a pure
+//! function of the chunk coordinates.
+//!
+//! Chunks are kept by their chunk coordinates, so chunks far apart from each other don't need
+//! anything to be stored for the chunks between them.
+//!
+//! Accesses take `&mut self`, since they may generate chunks. `peek` reads a cell only if its
+//! chunk has already been generated, taking `&self`.
+
+use crate::{coord::GridVector, subchunk::Subchunk, util};
+use nalgebra::SVector;
+use std::collections::HashMap;
+
+/// A grid of subchunks which generates chunks as they are accessed. See the `infinite` module for
+/// details.
+#[derive(Clone, Debug)]
+pub struct InfiniteGrid<C, G, const D: usize = 2> {
+    /// Every chunk which has been generated, by its chunk coordinates.
+    chunks: HashMap<SVector<isize, D>, C>,
+    generator: G,
+}
+
+impl<C, G, const D: usize> InfiniteGrid<C, G, D>
+where
+    C: Subchunk<D> + Clone,
+    C::Output: Sized,
+    G: FnMut(SVector<isize, D>) -> C,
+{
+    /// Creates a grid where each chunk is the result of `generator` on its chunk coordinates.
+    pub fn new(generator: G) -> Self {
+        Self {
+            chunks: HashMap::new(),
+            generator,
+        }
+    }
+
+    /// Returns every chunk which has been generated, by its chunk coordinates.
+    pub fn chunks(&self) -> &HashMap<SVector<isize, D>, C> {
+        &self.chunks
+    }
+
+    /// Returns the number of chunks which have been generated.
+    pub fn generated_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Returns `true` if the chunk with the chunk coordinates `coordinates` has been generated.
+    pub fn is_generated(&self, coordinates: impl GridVector<isize, D>) -> bool {
+        self.chunks.contains_key(&coordinates.to_vector())
+    }
+
+    /// Returns the chunk with the chunk coordinates `coordinates`, generating it if needed.
+    pub fn chunk(&mut self, coordinates: impl GridVector<isize, D>) -> &C {
+        self.chunk_mut(coordinates)
+    }
+
+    /// Returns the chunk with the chunk coordinates `coordinates`, generating it if needed.
+    pub fn chunk_mut(&mut self, coordinates: impl GridVector<isize, D>) -> &mut C {
+        let coordinates = coordinates.to_vector();
+        self.chunks
+            .entry(coordinates)
+            .or_insert_with(|| (self.generator)(coordinates))
+    }
+
+    /// Returns the cell at `index`, generating its chunk if needed.
+    pub fn get(&mut self, index: impl GridVector<isize, D>) -> &C::Output {
+        self.get_mut(index)
+    }
+
+    /// Returns the cell at `index`, generating its chunk if needed.
+    pub fn get_mut(&mut self, index: impl GridVector<isize, D>) -> &mut C::Output {
+        let (chunk, subchunk) = util::split_chunk_index(index.to_vector(), C::SUBCHUNK_SIZE);
+        &mut self.chunk_mut(chunk)[subchunk]
+    }
+
+    /// Returns the cell at `index`, or `None` if its chunk has not been generated.
+    pub fn peek(&self, index: impl GridVector<isize, D>) -> Option<&C::Output> {
+        let (chunk, subchunk) = util::split_chunk_index(index.to_vector(), C::SUBCHUNK_SIZE);
+        Some(&self.chunks.get(&chunk)?[subchunk])
+    }
+
+    /// Generates every chunk containing a cell of the box with its lowest corner at `box_origin`
+    /// and size `box_size` which has not been generated yet, such as the area around a player.
+    pub fn generate_box(
+        &mut self,
+        box_origin: impl GridVector<isize, D>,
+        box_size: impl GridVector<usize, D>,
+    ) {
+        let Some((first_chunk, last_chunk)) = util::chunk_range(
+            box_origin.to_vector(),
+            box_size.to_vector(),
+            C::SUBCHUNK_SIZE,
+        ) else {
+            return;
+        };
+
+        let chunk_box_size = (last_chunk - first_chunk).map(|length| length as usize + 1);
+        for offset in util::iter_box(SVector::zeros(), chunk_box_size) {
+            self.chunk_mut(first_chunk + util::usize_vec_to_isize(offset));
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Influence maps, which spread the influence of sources such as units or threats over the cells
+//! around them.
+//!
+//! An `InfluenceMap` wraps a grid of `f32` where every cell holds the sum of the influence of
+//! each source on it. A source reaches every cell within its radius, by straight line distance,
+//! with its strength reduced by its `Decay` with distance. Adding, moving, or removing a source
+//! only updates the cells within its radius, so maps with many sources can be kept up to date
+//! every frame. Keeping several maps, such as one for allies and one for enemies, and combining
+//! them with `ExpandableGridN::zip_map`, gives layered influence.
+//!
+//! Since each update adds or subtracts influence, rounding errors can build up over many updates.
+//! `InfluenceMap::recompute` calculates every cell from scratch.
+
+use crate::{coord::GridVector, ExpandableGridN};
+use nalgebra::SVector;
+use std::{collections::HashMap, ops::Deref};
+
+/// How the influence of a source falls off with distance.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Decay {
+    /// The full strength of the source reaches every cell within its radius.
+    Constant,
+    /// The influence falls linearly from full strength at the source to zero at its radius.
+    #[default]
+    Linear,
+    /// The influence is multiplied by this factor for every cell of distance from the source.
+    Exponential(f32),
+    /// The influence is multiplied by the result of this function of the distance from the
+    /// source.
+    Custom(fn(f32) -> f32),
+}
+
+impl Decay {
+    /// Returns the fraction of the strength of a source with radius `radius` which reaches a
+    /// cell `distance` cells away from it.
+    pub fn factor(self, distance: f32, radius: usize) -> f32 {
+        match self {
+            Decay::Constant => 1.0,
+            Decay::Linear if radius == 0 => 1.0,
+            Decay::Linear => 1.0 - distance / radius as f32,
+            Decay::Exponential(factor) => factor.powf(distance),
+            Decay::Custom(f) => f(distance),
+        }
+    }
+}
+
+/// A source of influence on the cells within `radius` cells of `position`.
+#[derive(Clone, Copy, Debug)]
+pub struct InfluenceSource<const D: usize = 2> {
+    pub position: SVector<isize, D>,
+    pub strength: f32,
+    pub radius: usize,
+    pub decay: Decay,
+}
+
+impl<const D: usize> InfluenceSource<D> {
+    pub fn new(
+        position: impl GridVector<isize, D>,
+        strength: f32,
+        radius: usize,
+        decay: Decay,
+    ) -> Self {
+        Self {
+            position: position.to_vector(),
+            strength,
+            radius,
+            decay,
+        }
+    }
+}
+
+/// Identifies a source added to an `InfluenceMap`, so that it can be moved or removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SourceId(u64);
+
+/// A grid of the total influence of a set of sources on each cell. See the `influence` module
+/// for details.
+#[derive(Clone, Debug, Default)]
+pub struct InfluenceMap<const D: usize = 2> {
+    grid: ExpandableGridN<f32, D>,
+    sources: HashMap<SourceId, InfluenceSource<D>>,
+    next_id: u64,
+}
+
+impl<const D: usize> InfluenceMap<D> {
+    /// Creates an influence map of size `size` at `origin` with no sources.
+    pub fn with_size(size: impl GridVector<usize, D>, origin: impl GridVector<isize, D>) -> Self {
+        Self {
+            grid: ExpandableGridN::with_size(size, origin, &0.0),
+            sources: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn grid(&self) -> &ExpandableGridN<f32, D> {
+        &self.grid
+    }
+
+    pub fn into_inner(self) -> ExpandableGridN<f32, D> {
+        self.grid
+    }
+
+    pub fn source(&self, id: SourceId) -> Option<&InfluenceSource<D>> {
+        self.sources.get(&id)
+    }
+
+    /// Iterates over every source, in no particular order.
+    pub fn sources(&self) -> impl Iterator<Item = (SourceId, &InfluenceSource<D>)> {
+        self.sources.iter().map(|(&id, source)| (id, source))
+    }
+
+    /// Adds a source, spreading its influence over the cells around it.
+    pub fn add_source(&mut self, source: InfluenceSource<D>) -> SourceId {
+        let id = SourceId(self.next_id);
+        self.next_id += 1;
+
+        spread(&mut self.grid, &source, 1.0);
+        self.sources.insert(id, source);
+        id
+    }
+
+    /// Removes a source and its influence, returning it, or `None` if it isn't present.
+    pub fn remove_source(&mut self, id: SourceId) -> Option<InfluenceSource<D>> {
+        let source = self.sources.remove(&id)?;
+        spread(&mut self.grid, &source, -1.0);
+        Some(source)
+    }
+
+    /// Replaces a source, such as to change its strength, returning the previous source, or
+    /// `None` if it isn't present.
+    pub fn replace_source(
+        &mut self,
+        id: SourceId,
+        source: InfluenceSource<D>,
+    ) -> Option<InfluenceSource<D>> {
+        let previous = self.sources.get_mut(&id)?;
+        let previous = std::mem::replace(previous, source);
+
+        spread(&mut self.grid, &previous, -1.0);
+        spread(&mut self.grid, &source, 1.0);
+        Some(previous)
+    }
+
+    /// Moves a source to `position`, returning whether it is present.
+    pub fn move_source(&mut self, id: SourceId, position: impl GridVector<isize, D>) -> bool {
+        let Some(&source) = self.sources.get(&id) else {
+            return false;
+        };
+        let position = position.to_vector();
+
+        if position != source.position {
+            self.replace_source(id, InfluenceSource { position, ..source });
+        }
+        true
+    }
+
+    /// Calculates the influence on every cell from scratch, removing any rounding errors built
+    /// up by earlier updates.
+    pub fn recompute(&mut self) {
+        self.grid.data.fill(0.0);
+
+        for source in self.sources.values() {
+            spread(&mut self.grid, source, 1.0);
+        }
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_point`. Spreads the influence of every source over
+    /// the new cells.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<isize, D>) {
+        self.expand_to_fit_box(point, SVector::<usize, D>::repeat(1));
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_box`. Spreads the influence of every source over the
+    /// new cells.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<isize, D>,
+        box_size: impl GridVector<usize, D>,
+    ) {
+        let old_bounds = self.grid.bounds();
+        self.grid.expand_to_fit_box(box_origin, box_size, &0.0);
+
+        if self.grid.bounds() != old_bounds {
+            self.recompute();
+        }
+    }
+
+    /// See `ExpandableGridN::change_size`. Spreads the influence of every source over the new
+    /// cells.
+    pub fn change_size(
+        &mut self,
+        new_size: impl GridVector<usize, D>,
+        offset: impl GridVector<isize, D>,
+    ) {
+        self.grid.change_size(new_size, offset, &0.0);
+        self.recompute();
+    }
+}
+
+/// Adds the influence of `source` multiplied by `sign` to each cell of `grid` it reaches.
+fn spread<const D: usize>(
+    grid: &mut ExpandableGridN<f32, D>,
+    source: &InfluenceSource<D>,
+    sign: f32,
+) {
+    for (position, cell) in grid.iter_within_radius_mut(source.position, source.radius) {
+        let offset = (position - source.position).map(|length| length as f32);
+        let factor = source.decay.factor(offset.norm(), source.radius);
+
+        *cell += sign * source.strength * factor;
+    }
+}
+
+impl<const D: usize> Deref for InfluenceMap<D> {
+    type Target = ExpandableGridN<f32, D>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.grid
+    }
+}
+
+impl<I: GridVector<isize, D>, const D: usize> std::ops::Index<I> for InfluenceMap<D> {
+    type Output = f32;
+
+    fn index(&self, index: I) -> &Self::Output {
+        &self.grid[index]
+    }
+}
@@ -0,0 +1,178 @@
+//! A grid which stores each distinct value once, for large cell types with many duplicates.
+//!
+//! An `InternedGrid` stores an `Arc` in each cell, and keeps every distinct value it has seen in
+//! an interner, so that cells with equal values share a single allocation. Values are written
+//! and read as `T`; the `Arc`s are only visible through `cells`. Values which no cell uses any
+//! more stay in the interner until `purge` is called.
+
+use crate::{coord::GridVector, rect::GridRect, ExpandableGridN};
+use std::{collections::HashSet, hash::Hash, sync::Arc};
+
+/// A grid which shares equal values between its cells. See the `interned` module for details.
+#[derive(Clone, Debug, Default)]
+pub struct InternedGrid<T, const D: usize = 2> {
+    grid: ExpandableGridN<Arc<T>, D>,
+    interner: HashSet<Arc<T>>,
+}
+
+impl<T: Eq + Hash, const D: usize> InternedGrid<T, D> {
+    /// Creates a new, empty grid.
+    pub fn new() -> Self {
+        Self {
+            grid: ExpandableGridN::new(),
+            interner: HashSet::new(),
+        }
+    }
+
+    /// Creates a grid with the same cells as `grid`, interning each of them.
+    pub fn from_grid(grid: ExpandableGridN<T, D>) -> Self {
+        let mut interner = HashSet::new();
+        let data = grid
+            .data
+            .into_vec()
+            .into_iter()
+            .map(|value| intern(&mut interner, value))
+            .collect();
+
+        Self {
+            grid: ExpandableGridN {
+                size: grid.size,
+                origin: grid.origin,
+                data,
+                layout: grid.layout,
+            },
+            interner,
+        }
+    }
+
+    /// Returns the grid of shared values. Cells with equal values point to the same allocation.
+    pub fn cells(&self) -> &ExpandableGridN<Arc<T>, D> {
+        &self.grid
+    }
+
+    /// Returns the number of distinct values in the interner, including any which no cell uses
+    /// since the last call to `purge`.
+    pub fn interned_values(&self) -> usize {
+        self.interner.len()
+    }
+
+    /// Returns the shared copy of `value`, adding it to the interner if it is not already there.
+    pub fn intern(&mut self, value: T) -> Arc<T> {
+        intern(&mut self.interner, value)
+    }
+
+    /// Removes every value which no cell uses from the interner.
+    pub fn purge(&mut self) {
+        self.interner.retain(|value| Arc::strong_count(value) > 1);
+    }
+
+    pub fn get(&self, index: impl GridVector<isize, D>) -> Option<&T> {
+        self.grid.get(index).map(|value| &**value)
+    }
+
+    /// Sets the cell at `index` to the shared copy of `value`, returning `false` if it is out of
+    /// bounds.
+    pub fn set(&mut self, index: impl GridVector<isize, D>, value: T) -> bool {
+        let Some(index) = self.grid.index_of(index) else {
+            return false;
+        };
+
+        self.grid.data[index] = self.intern(value);
+        true
+    }
+
+    /// Calls `f` with a copy of the cell at `index`, then sets the cell to the shared copy of the
+    /// result, returning `false` if it is out of bounds.
+    pub fn modify(&mut self, index: impl GridVector<isize, D>, f: impl FnOnce(&mut T)) -> bool
+    where
+        T: Clone,
+    {
+        let Some(mut value) = self.get(index).cloned() else {
+            return false;
+        };
+
+        f(&mut value);
+        self.set(index, value)
+    }
+
+    /// Sets every cell of `rect` within the bounds of the grid to the shared copy of `value`.
+    pub fn fill_rect(&mut self, rect: GridRect<D>, value: &T)
+    where
+        T: Clone,
+    {
+        let value = self.intern_ref(value);
+        self.grid.fill_box(rect.origin, rect.size, &value);
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_point`. New cells share a single copy of `fill`.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<isize, D>, fill: &T)
+    where
+        T: Clone,
+    {
+        let fill = self.intern_ref(fill);
+        self.grid.expand_to_fit_point(point, &fill);
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_box`. New cells share a single copy of `fill`.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<isize, D>,
+        box_size: impl GridVector<usize, D>,
+        fill: &T,
+    ) where
+        T: Clone,
+    {
+        let fill = self.intern_ref(fill);
+        self.grid.expand_to_fit_box(box_origin, box_size, &fill);
+    }
+
+    /// See `ExpandableGridN::change_size`. New cells share a single copy of `fill`.
+    pub fn change_size(
+        &mut self,
+        new_size: impl GridVector<usize, D>,
+        offset: impl GridVector<isize, D>,
+        fill: &T,
+    ) where
+        T: Clone,
+    {
+        let fill = self.intern_ref(fill);
+        self.grid.change_size(new_size, offset, &fill);
+    }
+
+    /// Copies every cell into a new `ExpandableGridN`.
+    pub fn to_grid(&self) -> ExpandableGridN<T, D>
+    where
+        T: Clone,
+    {
+        self.grid.map(|value| T::clone(value))
+    }
+
+    /// Returns the shared copy of `value`, only cloning it if it is not already in the interner.
+    fn intern_ref(&mut self, value: &T) -> Arc<T>
+    where
+        T: Clone,
+    {
+        match self.interner.get(value) {
+            Some(shared) => shared.clone(),
+            None => self.intern(value.clone()),
+        }
+    }
+}
+
+impl<T, I: GridVector<isize, D>, const D: usize> std::ops::Index<I> for InternedGrid<T, D> {
+    type Output = T;
+
+    fn index(&self, index: I) -> &Self::Output {
+        &self.grid[index]
+    }
+}
+
+fn intern<T: Eq + Hash>(interner: &mut HashSet<Arc<T>>, value: T) -> Arc<T> {
+    if let Some(shared) = interner.get(&value) {
+        return shared.clone();
+    }
+
+    let shared = Arc::new(value);
+    interner.insert(shared.clone());
+    shared
+}
@@ -0,0 +1,227 @@
+//! Several grids of different cell types which always share the same bounds.
+//!
+//! A `LayeredGrid` owns a tuple of `ExpandableGridN`s, called layers, such as terrain, objects,
+//! and lighting. Every layer is expanded together, so the layers can never drift out of sync,
+//! and since they also share a layout, a cell has the same index within the data of every layer.
+//! Each method which creates cells takes a tuple with a fill value for each layer.
+//!
+//! `Layers` is implemented for tuples of up to 6 grids.
+
+use crate::{coord::GridVector, util, ExpandableGridN, Layout};
+use nalgebra::SVector;
+
+/// A tuple of grids which can be expanded together. See the `layered` module for details.
+pub trait Layers<const D: usize>: Sized {
+    /// A tuple with a fill value for each layer.
+    type Fill;
+    /// A tuple with a reference to a cell of each layer.
+    type Refs<'a>
+    where
+        Self: 'a;
+    /// A tuple with a mutable reference to a cell of each layer.
+    type Muts<'a>
+    where
+        Self: 'a;
+    /// A tuple with the data of each layer.
+    type DataMut<'a>
+    where
+        Self: 'a;
+
+    /// Creates each layer with size `size` and its lowest corner at `origin`, filled with clones
+    /// of its fill value.
+    fn with_size(size: SVector<usize, D>, origin: SVector<isize, D>, fill: &Self::Fill) -> Self;
+
+    /// Returns the size, origin, and layout of the first layer.
+    fn bounds(&self) -> (SVector<usize, D>, SVector<isize, D>, Layout);
+
+    /// Returns whether every layer has the same size, origin, and layout.
+    fn bounds_match(&self) -> bool;
+
+    /// Calls `ExpandableGridN::change_size` on each layer.
+    fn change_size(
+        &mut self,
+        new_size: SVector<usize, D>,
+        offset: SVector<isize, D>,
+        fill: &Self::Fill,
+    );
+
+    /// Returns the cell at `index` within the data of each layer.
+    fn get(&self, index: usize) -> Self::Refs<'_>;
+
+    /// Returns the cell at `index` within the data of each layer.
+    fn get_mut(&mut self, index: usize) -> Self::Muts<'_>;
+
+    fn data_mut(&mut self) -> Self::DataMut<'_>;
+}
+
+macro_rules! impl_layers {
+    ($first:ident $first_index:tt $(, $name:ident $index:tt)*) => {
+        impl<$first: Clone, $($name: Clone,)* const D: usize> Layers<D>
+            for (ExpandableGridN<$first, D>, $(ExpandableGridN<$name, D>,)*)
+        {
+            type Fill = ($first, $($name,)*);
+            type Refs<'a> = (&'a $first, $(&'a $name,)*) where Self: 'a;
+            type Muts<'a> = (&'a mut $first, $(&'a mut $name,)*) where Self: 'a;
+            type DataMut<'a> = (&'a mut [$first], $(&'a mut [$name],)*) where Self: 'a;
+
+            fn with_size(
+                size: SVector<usize, D>,
+                origin: SVector<isize, D>,
+                fill: &Self::Fill,
+            ) -> Self {
+                (
+                    ExpandableGridN::with_size(size, origin, &fill.$first_index),
+                    $(ExpandableGridN::with_size(size, origin, &fill.$index),)*
+                )
+            }
+
+            fn bounds(&self) -> (SVector<usize, D>, SVector<isize, D>, Layout) {
+                let first = &self.$first_index;
+                (first.size, first.origin, first.layout)
+            }
+
+            fn bounds_match(&self) -> bool {
+                let _bounds = self.bounds();
+                true $(&& (self.$index.size, self.$index.origin, self.$index.layout) == _bounds)*
+            }
+
+            fn change_size(
+                &mut self,
+                new_size: SVector<usize, D>,
+                offset: SVector<isize, D>,
+                fill: &Self::Fill,
+            ) {
+                self.$first_index.change_size(new_size, offset, &fill.$first_index);
+                $(self.$index.change_size(new_size, offset, &fill.$index);)*
+            }
+
+            fn get(&self, index: usize) -> Self::Refs<'_> {
+                (&self.$first_index.data[index], $(&self.$index.data[index],)*)
+            }
+
+            fn get_mut(&mut self, index: usize) -> Self::Muts<'_> {
+                (&mut self.$first_index.data[index], $(&mut self.$index.data[index],)*)
+            }
+
+            fn data_mut(&mut self) -> Self::DataMut<'_> {
+                (&mut self.$first_index.data, $(&mut self.$index.data,)*)
+            }
+        }
+    };
+}
+
+impl_layers!(A 0);
+impl_layers!(A 0, B 1);
+impl_layers!(A 0, B 1, C 2);
+impl_layers!(A 0, B 1, C 2, E 3);
+impl_layers!(A 0, B 1, C 2, E 3, F 4);
+impl_layers!(A 0, B 1, C 2, E 3, F 4, G 5);
+
+/// Several grids which always share the same bounds. See the `layered` module for details.
+#[derive(Clone, Debug)]
+pub struct LayeredGrid<L, const D: usize = 2> {
+    layers: L,
+}
+
+impl<L: Layers<D>, const D: usize> LayeredGrid<L, D> {
+    /// Creates layers of size `size` with their lowest corner at `origin`, each filled with
+    /// clones of its value in `fill`.
+    pub fn with_size(
+        size: impl GridVector<usize, D>,
+        origin: impl GridVector<isize, D>,
+        fill: &L::Fill,
+    ) -> Self {
+        Self {
+            layers: L::with_size(size.to_vector(), origin.to_vector(), fill),
+        }
+    }
+
+    /// Wraps `layers`, or returns them back if they do not all have the same size, origin, and
+    /// layout.
+    pub fn from_layers(layers: L) -> Result<Self, L> {
+        if layers.bounds_match() {
+            Ok(Self { layers })
+        } else {
+            Err(layers)
+        }
+    }
+
+    /// Returns the layers. They can only be modified through this grid, so that they stay in
+    /// sync.
+    pub fn layers(&self) -> &L {
+        &self.layers
+    }
+
+    pub fn into_layers(self) -> L {
+        self.layers
+    }
+
+    pub fn size(&self) -> SVector<usize, D> {
+        self.layers.bounds().0
+    }
+
+    pub fn origin(&self) -> SVector<isize, D> {
+        self.layers.bounds().1
+    }
+
+    /// Returns the cell at `index` of each layer, or `None` if it is out of bounds.
+    pub fn get(&self, index: impl GridVector<isize, D>) -> Option<L::Refs<'_>> {
+        let index = self.index_of(index)?;
+        Some(self.layers.get(index))
+    }
+
+    /// Returns the cell at `index` of each layer, or `None` if it is out of bounds.
+    pub fn get_mut(&mut self, index: impl GridVector<isize, D>) -> Option<L::Muts<'_>> {
+        let index = self.index_of(index)?;
+        Some(self.layers.get_mut(index))
+    }
+
+    /// Returns the data of each layer, which all share the same layout.
+    pub fn data_mut(&mut self) -> L::DataMut<'_> {
+        self.layers.data_mut()
+    }
+
+    /// Returns the index of the cell at `index` within the data of every layer, or `None` if it
+    /// is out of bounds.
+    pub fn index_of(&self, index: impl GridVector<isize, D>) -> Option<usize> {
+        let (size, origin, layout) = self.layers.bounds();
+        let position = util::relative_position(origin, size, index.to_vector())?;
+
+        Some(layout.linear_index(position, size))
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_point`.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<isize, D>, fill: &L::Fill) {
+        self.expand_to_fit_box(point, SVector::<usize, D>::repeat(1), fill);
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_box`.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<isize, D>,
+        box_size: impl GridVector<usize, D>,
+        fill: &L::Fill,
+    ) {
+        let (box_origin, box_size) = (box_origin.to_vector(), box_size.to_vector());
+        let (size, origin, _) = self.layers.bounds();
+
+        if size == SVector::<usize, D>::zeros() {
+            self.layers = L::with_size(box_size, box_origin, fill);
+        } else if let Some((new_size, offset)) =
+            util::expansion_to_fit_box(size, origin, box_origin, box_size)
+        {
+            self.layers.change_size(new_size, offset, fill);
+        }
+    }
+
+    /// See `ExpandableGridN::change_size`.
+    pub fn change_size(
+        &mut self,
+        new_size: impl GridVector<usize, D>,
+        offset: impl GridVector<isize, D>,
+        fill: &L::Fill,
+    ) {
+        self.layers
+            .change_size(new_size.to_vector(), offset.to_vector(), fill);
+    }
+}
@@ -1,12 +1,172 @@
-//! Vectors are stored and modified via `nalgebra::Vector2`
+//! Vectors are stored and modified via `nalgebra::SVector`, usually `nalgebra::Vector2`
 //!
-//! See `expandable_grid::ExpanableGrid` for more information.
+//! See `expandable_grid::ExpandableGridN` for more information.
 
 pub mod expandable_grid;
-pub use expandable_grid::ExpandableGrid;
+pub use expandable_grid::{ExpandableGrid, ExpandableGrid3, ExpandableGridN, Layout};
+
+pub mod coord;
+pub use coord::GridVector;
 
 pub mod subchunk;
 
+pub mod hex;
+
+pub mod ascii;
+
+pub mod binary;
+
+pub mod checksum;
+
+pub mod rle;
+
+pub mod persistence;
+
+pub mod migration;
+
+pub mod concurrent;
+
+pub mod atomic;
+
+pub mod cow;
+
+pub mod cell;
+
+pub mod double_buffer;
+
+pub mod active;
+
+pub mod schedule;
+
+pub mod order;
+
+pub mod rect;
+pub use rect::GridRect;
+
+pub mod dirty;
+
+pub mod observe;
+
+pub mod history;
+
+pub mod transaction;
+
+pub mod edit_log;
+
+pub mod diff;
+
+pub mod sync;
+
+pub mod temporal;
+
+pub mod content;
+
+pub mod memory;
+
+pub mod eviction;
+
+pub mod infinite;
+
+pub mod alloc;
+
+pub mod small;
+
+pub mod margins;
+
+pub mod interned;
+
+pub mod layered;
+
+pub mod zip;
+
+pub mod overlay;
+
+pub mod group;
+
+pub mod arena;
+
+pub mod palette;
+
+pub mod handle;
+
+pub mod search;
+
+pub mod shapes;
+
+pub mod segment;
+pub use segment::GridSegment;
+
+pub mod collision;
+
+pub mod regions;
+
+pub mod influence;
+
+pub mod cellular;
+
+#[cfg(feature = "compression")]
+pub mod compression;
+
+#[cfg(feature = "bytemuck")]
+pub mod bytemuck;
+
+#[cfg(feature = "mmap")]
+pub mod mmap;
+
+#[cfg(feature = "image")]
+pub mod image;
+
+#[cfg(feature = "ndarray")]
+pub mod ndarray;
+
+#[cfg(feature = "mint")]
+pub mod mint;
+
+#[cfg(feature = "glam")]
+pub mod glam;
+
+#[cfg(feature = "bevy")]
+pub mod bevy;
+
+#[cfg(feature = "egui")]
+pub mod egui;
+
+#[cfg(feature = "wgpu")]
+pub mod wgpu;
+
+#[cfg(feature = "tiled")]
+pub mod tiled;
+
+#[cfg(feature = "python")]
+pub mod python;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "proptest")]
+pub mod proptest;
+
+#[cfg(feature = "quickcheck")]
+pub mod quickcheck;
+
+#[cfg(feature = "scripting")]
+pub mod scripting;
+
+#[cfg(feature = "rayon")]
+pub mod parallel;
+
+#[cfg(feature = "rand")]
+pub mod sampling;
+
+#[cfg(feature = "rand")]
+pub mod synthesis;
+
 pub(crate) mod util;
 
+#[cfg(feature = "serde")]
+mod serde_impls;
+
+#[cfg(feature = "serde")]
+pub mod readable;
+
 mod tests;
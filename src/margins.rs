@@ -0,0 +1,119 @@
+//! Expanding grids by a chosen amount on each side, for worlds which only grow in some
+//! directions.
+//!
+//! `ExpandableGridN::expand_to_fit_box` doubles the grid on every side it expands past, which
+//! wastes space when a grid only ever grows towards one side. `with_margins` and
+//! `expand_margins` instead add `Margins` of extra cells beyond a box, given separately for the
+//! low and high side of each axis, in a single allocation.
+
+use crate::{
+    coord::{self, Coordinate, GridVector},
+    util, ExpandableGridN,
+};
+use nalgebra::SVector;
+
+/// An amount of extra cells beyond the low and high side of a box on each axis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Margins<const D: usize = 2> {
+    /// The cells beyond the lowest corner of the box on each axis.
+    pub low: SVector<usize, D>,
+    /// The cells beyond the highest corner of the box on each axis.
+    pub high: SVector<usize, D>,
+}
+
+impl<const D: usize> Margins<D> {
+    pub fn new(low: impl GridVector<usize, D>, high: impl GridVector<usize, D>) -> Self {
+        Self {
+            low: low.to_vector(),
+            high: high.to_vector(),
+        }
+    }
+
+    /// Returns margins of `margin` cells on every side.
+    pub fn uniform(margin: usize) -> Self {
+        Self::new(
+            SVector::<usize, D>::repeat(margin),
+            SVector::<usize, D>::repeat(margin),
+        )
+    }
+}
+
+impl<const D: usize> Default for Margins<D> {
+    fn default() -> Self {
+        Self::uniform(0)
+    }
+}
+
+impl From<[usize; 4]> for Margins<2> {
+    /// Converts margins in the order low `x`, high `x`, low `y`, high `y`.
+    fn from([low_x, high_x, low_y, high_y]: [usize; 4]) -> Self {
+        Self::new([low_x, low_y], [high_x, high_y])
+    }
+}
+
+impl<T: Clone, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Creates a new grid covering the box with its lowest corner at `origin` and size `size`,
+    /// extended by `margins` on each side, and filled with clones of `fill`.
+    pub fn with_margins(
+        size: impl GridVector<usize, D>,
+        origin: impl GridVector<C, D>,
+        margins: impl Into<Margins<D>>,
+        fill: &T,
+    ) -> Self {
+        let margins = margins.into();
+        let origin = coord::to_isize_vec(origin.to_vector());
+
+        Self::with_size(
+            size.to_vector() + margins.low + margins.high,
+            coord::from_isize_vec::<C, D>(origin - util::usize_vec_to_isize(margins.low)),
+            fill,
+        )
+    }
+
+    /// Increases the size of the grid such that every cell of the box with its lowest corner at
+    /// `box_origin` and size `box_size` is within its bounds. On each side the box extends past,
+    /// the grid expands to the edge of the box plus the margin of that side, rather than doubling
+    /// as `expand_to_fit_box` does. Sides which the box does not extend past are left unchanged.
+    /// The newly created space is filled with clones of `fill`.
+    pub fn expand_margins(
+        &mut self,
+        box_origin: impl GridVector<C, D>,
+        box_size: impl GridVector<usize, D>,
+        margins: impl Into<Margins<D>>,
+        fill: &T,
+    ) {
+        let (box_origin, box_size) = (box_origin.to_vector(), box_size.to_vector());
+        let margins = margins.into();
+
+        if self.size == SVector::<usize, D>::zeros() {
+            let layout = self.layout;
+            *self = Self::with_margins(box_size, box_origin, margins, fill);
+            self.layout = layout;
+            return;
+        }
+
+        let (origin, end) = (
+            coord::to_isize_vec(self.origin),
+            coord::to_isize_vec(self.corner()),
+        );
+        let box_origin = coord::to_isize_vec(box_origin);
+        let box_end = box_origin + util::usize_vec_to_isize(box_size);
+
+        let mut new_origin = origin;
+        let mut new_end = end;
+        for axis in 0..D {
+            if box_origin[axis] < origin[axis] {
+                new_origin[axis] = box_origin[axis] - margins.low[axis] as isize;
+            }
+            if box_end[axis] > end[axis] {
+                new_end[axis] = box_end[axis] + margins.high[axis] as isize;
+            }
+        }
+
+        if (new_origin, new_end) != (origin, end) {
+            let new_size = (new_end - new_origin).map(|length| length as usize);
+            let offset = coord::from_isize_vec::<C, D>(new_origin - origin);
+            self.change_size(new_size, offset, fill);
+        }
+    }
+}
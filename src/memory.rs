@@ -0,0 +1,67 @@
+//! Reporting how much memory grids use, for capacity planning.
+//!
+//! `memory_usage` is implemented for `ExpandableGridN` and the chunked grids, returning a
+//! `MemoryUsage`. It counts the buffers holding cells and the bookkeeping of chunked grids, but
+//! not the size of the grid value itself, or anything owned by the cells. `memory_usage_with`
+//! takes a callback estimating the heap memory owned by each cell, for cell types such as
+//! `Vec` or `String`.
+
+use crate::{coord::Coordinate, ExpandableGridN};
+use std::mem::size_of;
+
+/// The memory used by a grid, in bytes unless stated otherwise. See the `memory` module for
+/// details.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MemoryUsage {
+    /// The number of cells within the bounds of the grid.
+    pub cells: usize,
+    /// The bytes the cells within the bounds of the grid take up.
+    pub used_bytes: usize,
+    /// The bytes of the buffers allocated to hold cells. This is more than `used_bytes` when
+    /// chunks extend past the bounds of the grid, and less when chunks are shared.
+    pub buffer_bytes: usize,
+    /// The number of chunk buffers allocated, or 0 for grids which are not chunked.
+    pub chunks: usize,
+    /// The bytes used to keep track of chunks, such as the grid of pointers to them, their
+    /// reference counts, and their locks.
+    pub chunk_overhead_bytes: usize,
+    /// The bytes on the heap owned by the cells, as estimated by the callback passed to
+    /// `memory_usage_with`.
+    pub heap_bytes: usize,
+}
+
+impl MemoryUsage {
+    /// Returns the total bytes allocated by the grid, including the heap memory owned by cells.
+    pub fn total_bytes(&self) -> usize {
+        self.buffer_bytes + self.chunk_overhead_bytes + self.heap_bytes
+    }
+
+    /// Returns the bytes of the buffers allocated to hold cells which are not within the bounds
+    /// of the grid.
+    pub fn unused_bytes(&self) -> usize {
+        self.buffer_bytes.saturating_sub(self.used_bytes)
+    }
+}
+
+impl<T, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Returns the memory used by this grid, not counting memory owned by cells. See the `memory`
+    /// module for details.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.memory_usage_with(|_| 0)
+    }
+
+    /// Returns the memory used by this grid, where `heap_bytes` returns the bytes on the heap
+    /// owned by each cell.
+    pub fn memory_usage_with(&self, heap_bytes: impl FnMut(&T) -> usize) -> MemoryUsage {
+        let bytes = self.data.len() * size_of::<T>();
+
+        MemoryUsage {
+            cells: self.data.len(),
+            used_bytes: bytes,
+            buffer_bytes: bytes,
+            chunks: 0,
+            chunk_overhead_bytes: 0,
+            heap_bytes: self.data.iter().map(heap_bytes).sum(),
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! Upgrading grids written with older versions of an application's cell type.
+//!
+//! Every grid in the binary format records a data version chosen by the application, written
+//! with `ExpandableGridN::write_binary_with_version`. When the cell type changes, the application
+//! increments its data version and provides a `Migrator` which can upgrade the raw bytes of the
+//! cells from each previous version to the next. `ExpandableGridN::read_binary_migrated` then
+//! applies every migration needed to bring a grid up to the current version before decoding its
+//! cells.
+//!
+//! Most applications will want to use `Migrations`, which lets upgrade functions be registered
+//! for each version.
+//!
+//! Grids serialized with serde, in any of the forms provided by this crate, also record a data
+//! version. Since their cells are not stored as bytes, they are upgraded by a `CellMigrator`
+//! instead, which deserializes each cell written with an older data version as the type it had
+//! then and converts it to the current type. The same goes for the tiles of a `DirectoryStore`,
+//! which can be read with `DirectoryStore::read_tile_migrated`.
+
+use crate::{
+    binary::{BinaryCell, BinaryError, Header},
+    checksum::ChecksumReader,
+    coord::{self, Coordinate},
+    persistence::{DirectoryStore, RegionReader},
+    util, ExpandableGridN, Layout,
+};
+use nalgebra::SVector;
+use std::{
+    collections::BTreeMap,
+    io::{Read, Seek},
+};
+
+/// A grid whose cells have been read as bytes but not yet decoded, passed to each migration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawGrid<const D: usize> {
+    pub size: SVector<usize, D>,
+    pub origin: SVector<isize, D>,
+    pub layout: Layout,
+    /// The number of bytes each cell takes up.
+    pub cell_size: usize,
+    /// The bytes of every cell, in the order given by `layout`.
+    pub bytes: Vec<u8>,
+}
+
+impl<const D: usize> RawGrid<D> {
+    /// Returns an iterator over the bytes of each cell.
+    pub fn cells(&self) -> impl Iterator<Item = &[u8]> {
+        self.bytes.chunks_exact(self.cell_size.max(1))
+    }
+
+    /// Replaces every cell with a new cell of `new_cell_size` bytes, which is written by `f`
+    /// given the bytes of the old cell. The new cell starts out zeroed.
+    pub fn map_cells(&mut self, new_cell_size: usize, mut f: impl FnMut(&[u8], &mut [u8])) {
+        let area = util::checked_area(self.size).unwrap_or(0);
+
+        let mut bytes = vec![0; area * new_cell_size];
+        for index in 0..area {
+            let old = &self.bytes[index * self.cell_size..(index + 1) * self.cell_size];
+            let new = &mut bytes[index * new_cell_size..(index + 1) * new_cell_size];
+            f(old, new);
+        }
+
+        self.bytes = bytes;
+        self.cell_size = new_cell_size;
+    }
+
+    /// Decodes the cells of this grid, once it has been migrated to the current version.
+    pub fn decode<T: BinaryCell, C: Coordinate>(
+        self,
+    ) -> Result<ExpandableGridN<T, D, C>, BinaryError> {
+        if self.cell_size != T::SIZE {
+            return Err(BinaryError::CellSizeMismatch {
+                expected: T::SIZE,
+                found: self.cell_size,
+            });
+        }
+
+        let area = util::checked_area(self.size).ok_or(BinaryError::SizeOverflow)?;
+        let origin =
+            coord::checked_origin(self.origin, self.size).ok_or(BinaryError::SizeOverflow)?;
+        if self.bytes.len() != area * T::SIZE {
+            // Report the first cell which is missing or partially present
+            let index = self.bytes.len().min(area * T::SIZE) / T::SIZE.max(1);
+            return Err(BinaryError::InvalidCell { index });
+        }
+
+        let data = (0..area)
+            .map(|index| {
+                T::read_bytes(&self.bytes[index * T::SIZE..(index + 1) * T::SIZE])
+                    .ok_or(BinaryError::InvalidCell { index })
+            })
+            .collect::<Result<Box<[T]>, _>>()?;
+
+        Ok(ExpandableGridN {
+            size: self.size,
+            origin,
+            data,
+            layout: self.layout,
+        })
+    }
+}
+
+/// Upgrades grids from older data versions to the current one.
+pub trait Migrator<const D: usize> {
+    /// The data version grids are currently written with.
+    fn current_version(&self) -> u32;
+
+    /// Upgrades `grid` from data version `version` to `version + 1`.
+    fn migrate(&self, version: u32, grid: &mut RawGrid<D>) -> Result<(), BinaryError>;
+}
+
+/// The signature of a migration registered with `Migrations`.
+pub type Migration<const D: usize> = dyn Fn(&mut RawGrid<D>) -> Result<(), BinaryError>;
+
+/// A `Migrator` built from a function registered for each data version.
+pub struct Migrations<const D: usize> {
+    current_version: u32,
+    migrations: BTreeMap<u32, Box<Migration<D>>>,
+}
+
+impl<const D: usize> Migrations<D> {
+    /// Creates a migrator for grids whose current data version is `current_version`, with no
+    /// migrations registered.
+    pub fn new(current_version: u32) -> Self {
+        Self {
+            current_version,
+            migrations: BTreeMap::new(),
+        }
+    }
+
+    /// Registers `migration` to upgrade grids from data version `version` to `version + 1`,
+    /// replacing any migration previously registered for `version`.
+    pub fn register(
+        &mut self,
+        version: u32,
+        migration: impl Fn(&mut RawGrid<D>) -> Result<(), BinaryError> + 'static,
+    ) {
+        self.migrations.insert(version, Box::new(migration));
+    }
+}
+
+impl<const D: usize> Migrator<D> for Migrations<D> {
+    fn current_version(&self) -> u32 {
+        self.current_version
+    }
+
+    fn migrate(&self, version: u32, grid: &mut RawGrid<D>) -> Result<(), BinaryError> {
+        let migration = self
+            .migrations
+            .get(&version)
+            .ok_or(BinaryError::MissingMigration { version })?;
+
+        migration(grid)
+    }
+}
+
+impl<const D: usize> std::fmt::Debug for Migrations<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Migrations")
+            .field("current_version", &self.current_version)
+            .field("versions", &self.migrations.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<T: BinaryCell, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Reads a grid in the binary format like `read_binary`, upgrading it with `migrator` if it
+    /// was written with an older data version.
+    pub fn read_binary_migrated(
+        reader: &mut impl Read,
+        migrator: &impl Migrator<D>,
+    ) -> Result<Self, BinaryError> {
+        let reader = &mut ChecksumReader::new(reader);
+
+        let header = Header::<D>::read(reader)?;
+
+        let current = migrator.current_version();
+        if header.data_version > current {
+            return Err(BinaryError::UnsupportedDataVersion {
+                current,
+                found: header.data_version,
+            });
+        }
+
+        if header.data_version == current {
+            header.check_cell_size(T::SIZE)?;
+            let data = header.read_cells(reader)?;
+            header.verify_checksum(reader)?;
+
+            return Ok(Self {
+                size: header.size,
+                origin: header.checked_origin()?,
+                data: data.into_boxed_slice(),
+                layout: header.layout,
+            });
+        }
+
+        let bytes = header.read_cell_bytes(reader)?;
+        header.verify_checksum(reader)?;
+
+        let mut grid = RawGrid {
+            size: header.size,
+            origin: header.origin,
+            layout: header.layout,
+            cell_size: header.cell_size,
+            bytes,
+        };
+        for version in header.data_version..current {
+            migrator.migrate(version, &mut grid)?;
+        }
+
+        grid.decode()
+    }
+}
+
+impl<R: Read + Seek, const D: usize> RegionReader<R, D> {
+    /// Reads the tile with coordinates `tile` like `read_tile`, upgrading it with `migrator` if
+    /// it was written with an older data version.
+    pub fn read_tile_migrated<T: BinaryCell>(
+        &mut self,
+        tile: SVector<isize, D>,
+        migrator: &impl Migrator<D>,
+    ) -> Result<Option<ExpandableGridN<T, D>>, BinaryError> {
+        let Some(bytes) = self.read_stored_tile_bytes(tile)? else {
+            return Ok(None);
+        };
+
+        let grid = ExpandableGridN::read_binary_migrated(&mut bytes.as_slice(), migrator)
+            .map_err(|error| error.in_tile(tile))?;
+
+        Ok(Some(grid))
+    }
+}
+
+impl DirectoryStore {
+    /// Reads the tile with coordinates `tile` like `read_tile`, upgrading it with `migrator` if
+    /// it was written with an older data version.
+    pub fn read_tile_migrated<T: BinaryCell, const D: usize>(
+        &self,
+        tile: SVector<isize, D>,
+        migrator: &impl Migrator<D>,
+    ) -> Result<Option<ExpandableGridN<T, D>>, BinaryError> {
+        let Some(mut reader) = self.open_tile(tile)? else {
+            return Ok(None);
+        };
+
+        ExpandableGridN::read_binary_migrated(&mut reader, migrator)
+            .map(Some)
+            .map_err(|error| error.in_tile(tile))
+    }
+}
+
+/// Upgrades the cells of grids deserialized with serde from older data versions to the current
+/// one, for use with `ExpandableGridN::deserialize_migrated` and its counterparts for the other
+/// serialized forms.
+#[cfg(feature = "serde")]
+pub trait CellMigrator<T> {
+    /// The data version grids are currently serialized with. Cells of this version are
+    /// deserialized directly.
+    fn current_version(&self) -> u32;
+
+    /// Deserializes a cell which was serialized with the older data version `version`, and
+    /// upgrades it to the current version.
+    fn deserialize_cell<'de, De: serde::Deserializer<'de>>(
+        &self,
+        version: u32,
+        deserializer: De,
+    ) -> Result<T, De::Error>;
+}
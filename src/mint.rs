@@ -0,0 +1,28 @@
+//! Interoperability with other math crates through the `mint` crate.
+//!
+//! `mint` vectors and points implement `GridVector`, so they can be used directly as coordinates
+//! and sizes, including for indexing grids. This also enables nalgebra's own `mint` conversions,
+//! so fields such as `size` and `origin` can be converted to `mint` types with `.into()`.
+
+use crate::coord::GridVector;
+use nalgebra::{vector, SVector};
+
+macro_rules! impl_grid_vector_for_mint {
+    ($mint:ident, $dimensions:literal, $($axis:ident),*) => {
+        impl<N: nalgebra::Scalar + Copy> GridVector<N, $dimensions> for mint::$mint<N> {
+            fn to_vector(self) -> SVector<N, $dimensions> {
+                vector![$(self.$axis),*]
+            }
+
+            fn from_vector(vector: SVector<N, $dimensions>) -> Self {
+                let [$($axis),*] = vector.into();
+                mint::$mint { $($axis),* }
+            }
+        }
+    };
+}
+
+impl_grid_vector_for_mint!(Vector2, 2, x, y);
+impl_grid_vector_for_mint!(Point2, 2, x, y);
+impl_grid_vector_for_mint!(Vector3, 3, x, y, z);
+impl_grid_vector_for_mint!(Point3, 3, x, y, z);
@@ -0,0 +1,369 @@
+//! Grids whose data lives in a memory mapped file, enabled with the `mmap` feature.
+//!
+//! An `MmapGrid` is an `ExpandableGridN` whose storage is `MmapStorage`, so it has the whole API
+//! of other grids, but its cells are stored in a file which the operating system pages in and out
+//! of memory as needed. This allows grids larger than the available memory, and grids which
+//! persist between runs of a program without explicitly saving them.
+//!
+//! Grids are created with `MmapGrid::create` and reopened with `MmapGrid::open`. Whenever the
+//! grid changes size, its cells are copied to a new file next to the current one, a contiguous
+//! run at a time, which then replaces it, so this needs enough disk space for both the old and
+//! new grids. Since `GridStorage` can't fail, changing the size of the grid panics if the new file
+//! can't be created.
+//!
+//! The size, origin, and layout of the grid are only written to the file by `MmapGrid::create`
+//! and `MmapGrid::flush`. Until a grid has been flushed after changing size, its file can't be
+//! opened.
+//!
+//! # File layout
+//!
+//! All integers are little endian. The header is padded with zeroes to a multiple of 64 bytes so
+//! that the cells are aligned in memory.
+//!
+//! | Field      | Encoding    | Notes                                     |
+//! |------------|-------------|-------------------------------------------|
+//! | magic      | 4 bytes     | Always `b"EXMM"`                          |
+//! | version    | `u16`       | Currently `1`                             |
+//! | dimensions | `u8`        | `D`                                       |
+//! | layout     | `u8`        | `0` for row major, `1` for column major   |
+//! | cell size  | `u32`       | `size_of::<T>()`                          |
+//! | size       | `D` × `u64` | One per axis, starting with `x`           |
+//! | origin     | `D` × `i64` | One per axis, starting with `x`           |
+//! | padding    |             | Up to the next multiple of 64 bytes       |
+//! | data       | area × cell size | The cells in the order given by layout, in native byte order |
+//!
+//! Since cells are stored in native byte order, files are only portable between platforms with
+//! the same endianness.
+
+use crate::{
+    alloc::GridStorage,
+    binary::{self, BinaryError},
+    coord::{self, Coordinate, GridVector},
+    util, ExpandableGridN, Layout,
+};
+use bytemuck::Pod;
+use memmap2::{MmapMut, MmapOptions};
+use nalgebra::SVector;
+use std::{
+    ffi::OsString,
+    fmt,
+    fs::{File, OpenOptions},
+    io,
+    marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+};
+
+/// The bytes every memory mapped grid file starts with.
+pub const MAGIC: [u8; 4] = *b"EXMM";
+
+/// The version of the file layout written by this version of the crate.
+pub const VERSION: u16 = 1;
+
+/// A grid that can be expanded in any direction, with its data stored in a memory mapped file.
+/// See the `mmap` module for more information.
+pub type MmapGrid<T, const D: usize = 2, C = isize> = ExpandableGridN<T, D, C, MmapStorage>;
+
+/// The storage of an `ExpandableGridN` which keeps its cells in a memory mapped file. It is
+/// only created by `MmapGrid::create` and `MmapGrid::open`, since two grids must never use the
+/// same file.
+#[derive(Debug)]
+pub struct MmapStorage {
+    path: PathBuf,
+    header_length: usize,
+}
+
+impl MmapStorage {
+    /// The path of the file the grid is stored in.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// The cells of an `MmapGrid`, which dereferences to `[T]`.
+pub struct MmapBuffer<T> {
+    storage: MmapStorage,
+    file: File,
+    mmap: MmapMut,
+    length: usize,
+    marker: PhantomData<T>,
+}
+
+impl<T> MmapBuffer<T> {
+    /// Creates a file at `path` with a zeroed header followed by `length` cells, where each cell
+    /// is the result of `f` on its index, and maps it. Any existing file at `path` is
+    /// overwritten. If `f` panics, the cells written so far are leaked.
+    fn create(
+        storage: MmapStorage,
+        path: &Path,
+        length: usize,
+        mut f: impl FnMut(usize) -> T,
+    ) -> io::Result<Self> {
+        assert!(
+            mem::align_of::<T>() <= 64,
+            "cells should not be aligned to more than 64 bytes",
+        );
+
+        let file_length = length
+            .checked_mul(mem::size_of::<T>())
+            .and_then(|length| length.checked_add(storage.header_length))
+            .ok_or_else(|| io::Error::other(BinaryError::SizeOverflow))?;
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(file_length as u64)?;
+
+        // Safety: the file was just created by this buffer, and is not modified by anything else
+        // while it is mapped
+        let mut mmap = unsafe { MmapOptions::new().map_mut(&file)? };
+
+        // Safety: the mapping is page aligned and has space for `length` cells after the header,
+        // which is a multiple of 64 bytes
+        let cells = unsafe { mmap.as_mut_ptr().add(storage.header_length).cast::<T>() };
+        for index in 0..length {
+            unsafe { cells.add(index).write(f(index)) };
+        }
+
+        Ok(Self {
+            storage,
+            file,
+            mmap,
+            length,
+            marker: PhantomData,
+        })
+    }
+}
+
+impl<T> Deref for MmapBuffer<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // Safety: the buffer holds `length` initialized cells after the header
+        unsafe {
+            std::slice::from_raw_parts(
+                self.mmap.as_ptr().add(self.storage.header_length).cast(),
+                self.length,
+            )
+        }
+    }
+}
+
+impl<T> DerefMut for MmapBuffer<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        // Safety: the buffer holds `length` initialized cells after the header, and is borrowed
+        // mutably
+        unsafe {
+            std::slice::from_raw_parts_mut(
+                self.mmap
+                    .as_mut_ptr()
+                    .add(self.storage.header_length)
+                    .cast(),
+                self.length,
+            )
+        }
+    }
+}
+
+impl<T> Drop for MmapBuffer<T> {
+    fn drop(&mut self) {
+        // Safety: the cells are never used again. The file is kept, since it holds the grid
+        unsafe { std::ptr::drop_in_place(&mut **self as *mut [T]) };
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MmapBuffer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl GridStorage for MmapStorage {
+    type Buffer<T> = MmapBuffer<T>;
+
+    fn buffer_from_fn<T>(self, length: usize, f: impl FnMut(usize) -> T) -> MmapBuffer<T> {
+        let path = self.path.clone();
+        MmapBuffer::create(self, &path, length, f)
+            .expect("creating a memory mapped grid file should not fail")
+    }
+
+    fn storage_of<T>(buffer: &MmapBuffer<T>) -> &Self {
+        &buffer.storage
+    }
+
+    fn replace_buffer<T: Clone>(
+        buffer: &mut MmapBuffer<T>,
+        length: usize,
+        fill: &T,
+        copy: impl FnOnce(&[T], &mut [T]),
+    ) {
+        let mut temporary_path = OsString::from(&buffer.storage.path);
+        temporary_path.push(".resizing");
+
+        let storage = MmapStorage {
+            path: buffer.storage.path.clone(),
+            header_length: buffer.storage.header_length,
+        };
+        let mut new_buffer =
+            MmapBuffer::create(storage, temporary_path.as_ref(), length, |_| fill.clone())
+                .expect("creating a memory mapped grid file should not fail");
+        copy(buffer, &mut new_buffer);
+
+        // Unmap and close the old file before replacing it, which some platforms require
+        drop(mem::replace(buffer, new_buffer));
+        std::fs::rename(&temporary_path, &buffer.storage.path)
+            .expect("replacing a memory mapped grid file should not fail");
+    }
+}
+
+impl<T: Pod, const D: usize, C: Coordinate> ExpandableGridN<T, D, C, MmapStorage> {
+    /// Creates a new grid filled with copies of `fill`, stored in a file at `path`. Any existing
+    /// file at `path` is overwritten.
+    pub fn create(
+        path: impl AsRef<Path>,
+        size: impl GridVector<usize, D>,
+        origin: impl GridVector<C, D>,
+        fill: &T,
+    ) -> io::Result<Self> {
+        let size = size.to_vector();
+        let area =
+            util::checked_area(size).ok_or_else(|| io::Error::other(BinaryError::SizeOverflow))?;
+
+        let path = path.as_ref().to_path_buf();
+        let storage = MmapStorage {
+            path: path.clone(),
+            header_length: header_length::<D>(),
+        };
+
+        let mut grid = Self {
+            size,
+            origin: origin.to_vector(),
+            data: MmapBuffer::create(storage, &path, area, |_| *fill)?,
+            layout: Layout::RowMajor,
+        };
+        grid.write_header();
+
+        Ok(grid)
+    }
+
+    /// Opens a grid previously created with `create`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, BinaryError> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().read(true).write(true).open(&path)?;
+
+        // Safety: the file is expected to not be modified by anything else while it is mapped
+        let mmap = unsafe { MmapOptions::new().map_mut(&file)? };
+
+        let mut header = &mmap[..];
+        let magic = take::<4>(&mut header)?;
+        if magic != MAGIC {
+            return Err(BinaryError::InvalidMagic(magic));
+        }
+        let version = u16::from_le_bytes(take(&mut header)?);
+        if version != VERSION {
+            return Err(BinaryError::UnsupportedVersion(version));
+        }
+        let [dimensions, layout] = take(&mut header)?;
+        if dimensions as usize != D {
+            return Err(BinaryError::DimensionMismatch {
+                expected: D,
+                found: dimensions as usize,
+            });
+        }
+        let layout = binary::layout_from_byte(layout).ok_or(BinaryError::InvalidLayout(layout))?;
+        let cell_size = u32::from_le_bytes(take(&mut header)?) as usize;
+        if cell_size != mem::size_of::<T>() {
+            return Err(BinaryError::CellSizeMismatch {
+                expected: mem::size_of::<T>(),
+                found: cell_size,
+            });
+        }
+
+        let mut size = SVector::<usize, D>::zeros();
+        for length in size.iter_mut() {
+            *length = u64::from_le_bytes(take(&mut header)?)
+                .try_into()
+                .map_err(|_| BinaryError::SizeOverflow)?;
+        }
+        let mut origin = SVector::<isize, D>::zeros();
+        for position in origin.iter_mut() {
+            *position = i64::from_le_bytes(take(&mut header)?)
+                .try_into()
+                .map_err(|_| BinaryError::SizeOverflow)?;
+        }
+        let origin = coord::checked_origin(origin, size).ok_or(BinaryError::SizeOverflow)?;
+
+        let area = util::checked_area(size).ok_or(BinaryError::SizeOverflow)?;
+        let expected_length = area
+            .checked_mul(cell_size)
+            .and_then(|length| length.checked_add(header_length::<D>()))
+            .ok_or(BinaryError::SizeOverflow)?;
+        if mmap.len() < expected_length {
+            return Err(BinaryError::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+
+        Ok(Self {
+            size,
+            origin,
+            data: MmapBuffer {
+                storage: MmapStorage {
+                    path,
+                    header_length: header_length::<D>(),
+                },
+                file,
+                mmap,
+                length: area,
+                marker: PhantomData,
+            },
+            layout,
+        })
+    }
+}
+
+impl<T, const D: usize, C: Coordinate> ExpandableGridN<T, D, C, MmapStorage> {
+    /// The path of the file the grid is stored in.
+    pub fn path(&self) -> &Path {
+        self.data.storage.path()
+    }
+
+    /// Writes the size, origin, and layout of the grid and any changes to its cells to its file.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.write_header();
+        self.data.mmap.flush()?;
+        self.data.file.sync_data()
+    }
+
+    fn write_header(&mut self) {
+        let mut header = Vec::with_capacity(header_length::<D>());
+        header.extend_from_slice(&MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&[D as u8, binary::layout_to_byte(self.layout)]);
+        header.extend_from_slice(&(mem::size_of::<T>() as u32).to_le_bytes());
+        for &length in self.size.iter() {
+            header.extend_from_slice(&(length as u64).to_le_bytes());
+        }
+        for &position in coord::to_isize_vec(self.origin).iter() {
+            header.extend_from_slice(&(position as i64).to_le_bytes());
+        }
+        header.resize(header_length::<D>(), 0);
+
+        self.data.mmap[..header.len()].copy_from_slice(&header);
+    }
+}
+
+/// The length of the header of a file for a grid with `D` dimensions.
+fn header_length<const D: usize>() -> usize {
+    (12 + D * 16).next_multiple_of(64)
+}
+
+fn take<const N: usize>(bytes: &mut &[u8]) -> io::Result<[u8; N]> {
+    let Some((taken, rest)) = bytes.split_first_chunk() else {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    };
+    *bytes = rest;
+    Ok(*taken)
+}
@@ -0,0 +1,56 @@
+//! Conversions between grids and arrays from the `ndarray` crate.
+//!
+//! Arrays are indexed with `[y, x]` relative to the origin of the grid, following the usual
+//! convention of the row coming first. Views borrow the data of the grid directly, so they can be
+//! used with the rest of the `ndarray` ecosystem without copying, whichever layout the grid has.
+
+use crate::{coord::Coordinate, ExpandableGrid, Layout};
+use nalgebra::{vector, Vector2};
+use ndarray::{Array2, ArrayView2, ArrayViewMut2, ShapeBuilder};
+
+impl<T, C: Coordinate> ExpandableGrid<T, C> {
+    /// Returns a view of the cells of this grid as an array indexed with `[y, x]`.
+    pub fn as_array_view(&self) -> ArrayView2<'_, T> {
+        let shape = (self.size.y, self.size.x);
+
+        match self.layout {
+            Layout::RowMajor => ArrayView2::from_shape(shape, &self.data),
+            Layout::ColumnMajor => ArrayView2::from_shape(shape.f(), &self.data),
+        }
+        .expect("the data of a grid should match its size")
+    }
+
+    /// Mutable version of `as_array_view`.
+    pub fn as_array_view_mut(&mut self) -> ArrayViewMut2<'_, T> {
+        let shape = (self.size.y, self.size.x);
+
+        match self.layout {
+            Layout::RowMajor => ArrayViewMut2::from_shape(shape, &mut self.data),
+            Layout::ColumnMajor => ArrayViewMut2::from_shape(shape.f(), &mut self.data),
+        }
+        .expect("the data of a grid should match its size")
+    }
+
+    /// Creates a grid from an array indexed with `[y, x]`, with the element at `[0, 0]` at
+    /// `origin`. Arrays in column major (Fortran) order become grids with `Layout::ColumnMajor`,
+    /// and all others become grids with `Layout::RowMajor`.
+    pub fn from_array(array: Array2<T>, origin: Vector2<C>) -> Self {
+        let (height, width) = array.dim();
+
+        let (layout, data) = if array.is_standard_layout() || !array.t().is_standard_layout() {
+            (Layout::RowMajor, array.into_iter().collect())
+        } else {
+            (
+                Layout::ColumnMajor,
+                array.reversed_axes().into_iter().collect(),
+            )
+        };
+
+        Self {
+            size: vector![width, height],
+            origin,
+            data,
+            layout,
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Notifying other systems when a grid changes, such as a UI or a spatial index.
+//!
+//! An `ObservedGrid` wraps a grid along with a list of observers, which are called with a
+//! `GridEvent` after every change made through it. Observers are any `GridObserver`, which
+//! includes closures taking the grid and the event. Cells can only be changed through methods
+//! which can report the change afterwards, so there is no `get_mut` or `IndexMut`; use `set` or
+//! `modify` instead.
+
+use crate::{coord::GridVector, rect::GridRect, util, ExpandableGridN};
+use nalgebra::SVector;
+use std::ops::Deref;
+
+/// A change made to an `ObservedGrid`, which is given to its observers.
+#[derive(Debug)]
+pub enum GridEvent<'a, T, const D: usize = 2> {
+    /// The cell at `index` was set from `old` to `new`.
+    Set {
+        index: SVector<isize, D>,
+        old: &'a T,
+        new: &'a T,
+    },
+    /// Every cell of `rect` was set to `value`.
+    Fill { rect: GridRect<D>, value: &'a T },
+    /// The cells of `rect` were copied from another grid.
+    Blit { rect: GridRect<D> },
+    /// The bounds of the grid changed from `old` to `new`. Cells within both keep their values.
+    Resize { old: GridRect<D>, new: GridRect<D> },
+}
+
+/// Something which is notified of the changes made to an `ObservedGrid`.
+pub trait GridObserver<T, const D: usize = 2> {
+    /// Called after `event` happens to `grid`.
+    fn notify(&mut self, grid: &ExpandableGridN<T, D>, event: &GridEvent<T, D>);
+}
+
+impl<T, const D: usize, F> GridObserver<T, D> for F
+where
+    F: FnMut(&ExpandableGridN<T, D>, &GridEvent<T, D>),
+{
+    fn notify(&mut self, grid: &ExpandableGridN<T, D>, event: &GridEvent<T, D>) {
+        self(grid, event)
+    }
+}
+
+/// Identifies an observer added to an `ObservedGrid`, so that it can be removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ObserverId(u64);
+
+/// A grid which notifies observers of every change made to it. See the `observe` module for
+/// details.
+pub struct ObservedGrid<'a, T, const D: usize = 2> {
+    grid: ExpandableGridN<T, D>,
+    observers: Observers<'a, T, D>,
+    next_id: u64,
+}
+
+impl<'a, T, const D: usize> ObservedGrid<'a, T, D> {
+    pub fn new(grid: ExpandableGridN<T, D>) -> Self {
+        Self {
+            grid,
+            observers: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> ExpandableGridN<T, D> {
+        self.grid
+    }
+
+    /// Adds an observer, which is notified of every later change, after the observers added
+    /// before it.
+    pub fn observe(&mut self, observer: impl GridObserver<T, D> + 'a) -> ObserverId {
+        let id = ObserverId(self.next_id);
+        self.next_id += 1;
+        self.observers.push((id, Box::new(observer)));
+
+        id
+    }
+
+    /// Removes an observer, returning whether it was present.
+    pub fn unobserve(&mut self, id: ObserverId) -> bool {
+        let length = self.observers.len();
+        self.observers.retain(|(observer, _)| *observer != id);
+
+        self.observers.len() != length
+    }
+
+    /// Sets the cell at `index` to `value`, returning the previous value, or `None` if it is out
+    /// of bounds.
+    pub fn set(&mut self, index: impl GridVector<isize, D>, value: T) -> Option<T> {
+        let index = index.to_vector();
+        let old = std::mem::replace(self.grid.get_mut(index)?, value);
+
+        notify(
+            &mut self.observers,
+            &self.grid,
+            GridEvent::Set {
+                index,
+                old: &old,
+                new: &self.grid[index],
+            },
+        );
+        Some(old)
+    }
+
+    /// Calls `f` with a mutable reference to the cell at `index`, returning its result, or `None`
+    /// if it is out of bounds. Observers are notified of the cell's old and new value.
+    pub fn modify<R>(
+        &mut self,
+        index: impl GridVector<isize, D>,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Option<R>
+    where
+        T: Clone,
+    {
+        let index = index.to_vector();
+        let cell = self.grid.get_mut(index)?;
+        let old = cell.clone();
+        let result = f(cell);
+
+        notify(
+            &mut self.observers,
+            &self.grid,
+            GridEvent::Set {
+                index,
+                old: &old,
+                new: &self.grid[index],
+            },
+        );
+        Some(result)
+    }
+
+    /// Sets every cell of `rect` within the bounds of the grid to clones of `value`.
+    pub fn fill_rect(&mut self, rect: GridRect<D>, value: &T)
+    where
+        T: Clone,
+    {
+        let Some((origin, size)) =
+            util::intersect_boxes(self.grid.origin, self.grid.size, rect.origin, rect.size)
+        else {
+            return;
+        };
+
+        self.grid.fill_box(origin, size, value);
+        self.emit(GridEvent::Fill {
+            rect: GridRect { origin, size },
+            value,
+        });
+    }
+
+    /// See `ExpandableGridN::blit`.
+    pub fn blit(&mut self, source: &ExpandableGridN<T, D>)
+    where
+        T: Clone,
+    {
+        self.grid.blit(source);
+
+        if let Some((origin, size)) =
+            util::intersect_boxes(self.grid.origin, self.grid.size, source.origin, source.size)
+        {
+            self.emit(GridEvent::Blit {
+                rect: GridRect { origin, size },
+            });
+        }
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_point`.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<isize, D>, fill: &T)
+    where
+        T: Clone,
+    {
+        self.track_bounds(|grid| grid.expand_to_fit_point(point, fill));
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_box`.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<isize, D>,
+        box_size: impl GridVector<usize, D>,
+        fill: &T,
+    ) where
+        T: Clone,
+    {
+        self.track_bounds(|grid| grid.expand_to_fit_box(box_origin, box_size, fill));
+    }
+
+    /// See `ExpandableGridN::change_size`.
+    pub fn change_size(
+        &mut self,
+        new_size: impl GridVector<usize, D>,
+        offset: impl GridVector<isize, D>,
+        fill: &T,
+    ) where
+        T: Clone,
+    {
+        self.track_bounds(|grid| grid.change_size(new_size, offset, fill));
+    }
+
+    /// Calls `f` with the grid, notifying observers if its bounds changed.
+    fn track_bounds(&mut self, f: impl FnOnce(&mut ExpandableGridN<T, D>)) {
+        let old = self.grid.bounds();
+        f(&mut self.grid);
+        let new = self.grid.bounds();
+
+        if old != new {
+            self.emit(GridEvent::Resize { old, new });
+        }
+    }
+
+    fn emit(&mut self, event: GridEvent<T, D>) {
+        notify(&mut self.observers, &self.grid, event);
+    }
+}
+
+type Observers<'a, T, const D: usize> = Vec<(ObserverId, Box<dyn GridObserver<T, D> + 'a>)>;
+
+/// Notifies every observer of `event`. This is separate from `ObservedGrid` so that `event` can
+/// borrow from the grid.
+fn notify<T, const D: usize>(
+    observers: &mut Observers<T, D>,
+    grid: &ExpandableGridN<T, D>,
+    event: GridEvent<T, D>,
+) {
+    for (_, observer) in observers {
+        observer.notify(grid, &event);
+    }
+}
+
+impl<T, const D: usize> Deref for ObservedGrid<'_, T, D> {
+    type Target = ExpandableGridN<T, D>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.grid
+    }
+}
+
+impl<T, I: GridVector<isize, D>, const D: usize> std::ops::Index<I> for ObservedGrid<'_, T, D> {
+    type Output = T;
+
+    fn index(&self, index: I) -> &Self::Output {
+        &self.grid[index]
+    }
+}
+
+impl<T: std::fmt::Debug, const D: usize> std::fmt::Debug for ObservedGrid<'_, T, D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObservedGrid")
+            .field("grid", &self.grid)
+            .field("observers", &self.observers.len())
+            .finish()
+    }
+}
@@ -0,0 +1,142 @@
+//! Iterating over the cells of a grid in a chosen order.
+//!
+//! The order cells are stored in depends on the grid's `Layout`, but code which must visit cells
+//! in a particular order, such as lockstep multiplayer simulations which must be deterministic,
+//! or gravity which must update the lowest cells first, can choose an `IterationOrder`. The
+//! order only depends on the size and origin of the grid, never on its layout.
+
+use crate::{
+    coord::{self, Coordinate},
+    util, ExpandableGridN, Layout,
+};
+use nalgebra::SVector;
+
+/// An order to visit the cells of a grid in. Axes are numbered from 0 (`x`) to `D - 1`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum IterationOrder {
+    /// Ascending coordinates, where `x` varies fastest and the last axis varies slowest. In 2d,
+    /// this visits each row from the lowest `y` to the highest, and each row from the lowest `x`.
+    #[default]
+    RowMajor,
+    /// Ascending coordinates, where the last axis varies fastest and `x` varies slowest. In 2d,
+    /// this visits each column from the lowest `x` to the highest, and each column from the
+    /// lowest `y`.
+    ColumnMajor,
+    /// The reverse of `RowMajor`, visiting each row from the highest `y` to the lowest, and each
+    /// row from the highest `x`.
+    ReverseRowMajor,
+    /// The reverse of `ColumnMajor`, visiting each column from the highest `x` to the lowest, and
+    /// each column from the highest `y`.
+    ReverseColumnMajor,
+    /// The Z-order curve over coordinates relative to the grid's origin, which visits cells that
+    /// are near each other in space near each other in time. Cells with lower interleaved bits
+    /// come first, where `x` holds the lowest bit of each group.
+    Morton,
+}
+
+impl IterationOrder {
+    /// Iterates over every position within a box of size `size` (relative to its lowest corner)
+    /// in this order.
+    pub fn positions<const D: usize>(
+        self,
+        size: SVector<usize, D>,
+    ) -> impl Iterator<Item = SVector<usize, D>> {
+        let area = size.product();
+
+        let positions: Box<dyn Iterator<Item = SVector<usize, D>>> = match self {
+            IterationOrder::RowMajor => {
+                Box::new((0..area).map(move |index| Layout::RowMajor.position(index, size)))
+            }
+            IterationOrder::ColumnMajor => {
+                Box::new((0..area).map(move |index| Layout::ColumnMajor.position(index, size)))
+            }
+            IterationOrder::ReverseRowMajor => Box::new(
+                (0..area)
+                    .rev()
+                    .map(move |index| Layout::RowMajor.position(index, size)),
+            ),
+            IterationOrder::ReverseColumnMajor => Box::new(
+                (0..area)
+                    .rev()
+                    .map(move |index| Layout::ColumnMajor.position(index, size)),
+            ),
+            IterationOrder::Morton => {
+                let mut positions: Vec<_> = util::iter_box(SVector::zeros(), size).collect();
+                positions.sort_by_key(|&position| morton_code(position));
+                Box::new(positions.into_iter())
+            }
+        };
+
+        positions
+    }
+}
+
+/// Returns the position of `position` along the Z-order curve, by interleaving the bits of its
+/// components.
+///
+/// # Panics
+/// Panics if a component has more than `128 / D` significant bits.
+pub fn morton_code<const D: usize>(position: SVector<usize, D>) -> u128 {
+    let bits = 128 / D.max(1);
+    assert!(
+        position
+            .iter()
+            .all(|&component| (usize::BITS - component.leading_zeros()) as usize <= bits),
+        "{position:?} should fit in a morton code",
+    );
+
+    let mut code = 0;
+    for bit in 0..bits.min(usize::BITS as usize) {
+        for (axis, &component) in position.iter().enumerate() {
+            code |= (((component >> bit) & 1) as u128) << (bit * D + axis);
+        }
+    }
+
+    code
+}
+
+impl<T, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Iterates over the coordinates of every cell of this grid in `order`.
+    pub fn positions_in(&self, order: IterationOrder) -> impl Iterator<Item = SVector<C, D>> + '_ {
+        order
+            .positions(self.size)
+            .map(|offset| self.offset_position(offset))
+    }
+
+    /// Iterates over the coordinates and values of every cell of this grid in `order`.
+    pub fn iter_in(&self, order: IterationOrder) -> impl Iterator<Item = (SVector<C, D>, &T)> + '_ {
+        order.positions(self.size).map(|offset| {
+            let index = self.layout.linear_index(offset, self.size);
+            (self.offset_position(offset), &self.data[index])
+        })
+    }
+
+    /// Calls `f` with the coordinates and a mutable reference to every cell of this grid in
+    /// `order`.
+    pub fn for_each_mut_in(
+        &mut self,
+        order: IterationOrder,
+        mut f: impl FnMut(SVector<C, D>, &mut T),
+    ) {
+        for offset in order.positions(self.size) {
+            let index = self.layout.linear_index(offset, self.size);
+            f(self.offset_position(offset), &mut self.data[index]);
+        }
+    }
+
+    /// Calls `f` with this grid and the coordinates of every cell of this grid in `order`, so
+    /// that `f` can read and write any cell as it goes, such as moving a falling cell down.
+    pub fn apply_in(&mut self, order: IterationOrder, mut f: impl FnMut(&mut Self, SVector<C, D>)) {
+        let size = self.size;
+
+        for offset in order.positions(size) {
+            let position = self.offset_position(offset);
+            f(self, position);
+        }
+    }
+
+    /// Returns the coordinates of the cell at `offset` from the origin of this grid.
+    fn offset_position(&self, offset: SVector<usize, D>) -> SVector<C, D> {
+        coord::from_isize_vec(coord::to_isize_vec(self.origin) + util::usize_vec_to_isize(offset))
+    }
+}
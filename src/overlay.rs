@@ -0,0 +1,123 @@
+//! Speculative edits to a grid, without copying it.
+//!
+//! An `OverlayGrid` holds a dense base grid and a sparse set of cells which override it. Reads
+//! return the overriding cell if there is one, and the base cell otherwise, while writes only
+//! change the overlay. The overlay can then be discarded, such as when the player cancels a
+//! building preview, or flattened into the base to keep its changes. Only the cells within the
+//! bounds of the base can be overridden.
+
+use crate::{coord::GridVector, ExpandableGridN};
+use nalgebra::SVector;
+use std::collections::HashMap;
+
+/// A grid with a sparse layer of cells overriding it. See the `overlay` module for details.
+#[derive(Clone, Debug, Default)]
+pub struct OverlayGrid<T, const D: usize = 2> {
+    base: ExpandableGridN<T, D>,
+    overlay: HashMap<SVector<isize, D>, T>,
+}
+
+impl<T, const D: usize> OverlayGrid<T, D> {
+    /// Wraps `base`, with no cells overridden.
+    pub fn new(base: ExpandableGridN<T, D>) -> Self {
+        Self {
+            base,
+            overlay: HashMap::new(),
+        }
+    }
+
+    /// Returns the base grid, ignoring the overlay.
+    pub fn base(&self) -> &ExpandableGridN<T, D> {
+        &self.base
+    }
+
+    /// Returns the base grid, discarding the overlay.
+    pub fn into_base(self) -> ExpandableGridN<T, D> {
+        self.base
+    }
+
+    /// Returns the number of overridden cells.
+    pub fn override_count(&self) -> usize {
+        self.overlay.len()
+    }
+
+    /// Iterates over the coordinates and values of every overridden cell, in no particular
+    /// order.
+    pub fn overrides(&self) -> impl Iterator<Item = (SVector<isize, D>, &T)> {
+        self.overlay.iter().map(|(&index, value)| (index, value))
+    }
+
+    pub fn is_overridden(&self, index: impl GridVector<isize, D>) -> bool {
+        self.overlay.contains_key(&index.to_vector())
+    }
+
+    /// Returns the overriding cell at `index` if there is one, or else the base cell, or `None`
+    /// if it is out of bounds.
+    pub fn get(&self, index: impl GridVector<isize, D>) -> Option<&T> {
+        let index = index.to_vector();
+        self.overlay.get(&index).or_else(|| self.base.get(index))
+    }
+
+    /// Returns a mutable reference to the overriding cell at `index`, overriding it with a clone
+    /// of the base cell first if it is not already, or `None` if it is out of bounds.
+    pub fn get_mut(&mut self, index: impl GridVector<isize, D>) -> Option<&mut T>
+    where
+        T: Clone,
+    {
+        let index = index.to_vector();
+        let base = self.base.get(index)?;
+
+        Some(self.overlay.entry(index).or_insert_with(|| base.clone()))
+    }
+
+    /// Overrides the cell at `index` with `value`, returning `false` if it is out of bounds.
+    pub fn set(&mut self, index: impl GridVector<isize, D>, value: T) -> bool {
+        let index = index.to_vector();
+        if self.base.index_of(index).is_none() {
+            return false;
+        }
+
+        self.overlay.insert(index, value);
+        true
+    }
+
+    /// Removes the overriding cell at `index`, returning it, so that the base cell is read
+    /// again.
+    pub fn remove_override(&mut self, index: impl GridVector<isize, D>) -> Option<T> {
+        self.overlay.remove(&index.to_vector())
+    }
+
+    /// Removes every overriding cell.
+    pub fn discard(&mut self) {
+        self.overlay.clear();
+    }
+
+    /// Writes every overriding cell into the base grid, leaving the overlay empty.
+    pub fn flatten(&mut self) {
+        for (index, value) in self.overlay.drain() {
+            self.base[index] = value;
+        }
+    }
+
+    /// Returns a copy of the base grid with every overriding cell written into it, leaving this
+    /// grid unchanged.
+    pub fn to_flattened(&self) -> ExpandableGridN<T, D>
+    where
+        T: Clone,
+    {
+        let mut grid = self.base.clone();
+        for (&index, value) in &self.overlay {
+            grid[index] = value.clone();
+        }
+
+        grid
+    }
+}
+
+impl<T, I: GridVector<isize, D>, const D: usize> std::ops::Index<I> for OverlayGrid<T, D> {
+    type Output = T;
+
+    fn index(&self, index: I) -> &Self::Output {
+        self.get(index).unwrap()
+    }
+}
@@ -0,0 +1,206 @@
+//! Grids of fieldless enums, stored as the smallest integer which fits every variant.
+//!
+//! An enum implementing `PaletteEnum` lists its variants, and chooses an integer type to store
+//! their indices in, such as `u8` for up to 256 variants. An `EnumGrid` stores only these
+//! indices, while its methods take and return the enum itself. Comparing indices is cheap, so
+//! scans such as `count` are fast.
+//!
+//! `PaletteEnum` is usually implemented with the `palette_enum!` macro:
+//!
+//! ```
+//! #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+//! enum Tile {
+//!     Air,
+//!     Stone,
+//!     Water,
+//! }
+//!
+//! expandable_grid::palette_enum!(Tile: u8 { Air, Stone, Water });
+//! ```
+
+use crate::{coord::GridVector, rect::GridRect, ExpandableGridN};
+
+/// An integer type which can store the index of an enum variant.
+pub trait PaletteIndex: Copy + Eq + 'static {
+    /// The largest index which can be stored.
+    const MAX: usize;
+
+    /// Converts `index`, which is at most `MAX`.
+    fn from_usize(index: usize) -> Self;
+
+    fn to_usize(self) -> usize;
+}
+
+macro_rules! impl_palette_index {
+    ($($index:ty),*) => {
+        $(
+            impl PaletteIndex for $index {
+                const MAX: usize = <$index>::MAX as usize;
+
+                fn from_usize(index: usize) -> Self {
+                    index as $index
+                }
+
+                fn to_usize(self) -> usize {
+                    self as usize
+                }
+            }
+        )*
+    };
+}
+
+impl_palette_index!(u8, u16, u32);
+
+/// A fieldless enum which can be stored in an `EnumGrid`. See the `palette` module for details.
+pub trait PaletteEnum: Copy + 'static {
+    /// The integer type indices are stored as. Its `MAX` must be at least `VARIANTS.len() - 1`.
+    type Index: PaletteIndex;
+
+    /// Every variant of the enum, in order of their indices.
+    const VARIANTS: &'static [Self];
+
+    /// Returns the position of this variant within `VARIANTS`.
+    fn to_index(self) -> Self::Index;
+
+    /// Returns the variant at `index` within `VARIANTS`.
+    ///
+    /// # Panics
+    /// Panics if `index` is not less than `VARIANTS.len()`.
+    fn from_index(index: Self::Index) -> Self {
+        Self::VARIANTS[index.to_usize()]
+    }
+}
+
+/// Implements `PaletteEnum` for a fieldless enum, given the integer type to store it as and
+/// every one of its variants. Fails to compile if there are too many variants for the integer
+/// type.
+#[macro_export]
+macro_rules! palette_enum {
+    ($enum:ty: $index:ty { $($variant:ident),+ $(,)? }) => {
+        impl $crate::palette::PaletteEnum for $enum {
+            type Index = $index;
+
+            const VARIANTS: &'static [Self] = &[$(<$enum>::$variant),+];
+
+            fn to_index(self) -> Self::Index {
+                const _: () = assert!(
+                    [$(stringify!($variant)),+].len() - 1
+                        <= <$index as $crate::palette::PaletteIndex>::MAX,
+                    "there should be few enough variants to fit in the index type",
+                );
+
+                let mut index = 0;
+                $(
+                    if matches!(self, <$enum>::$variant) {
+                        return index;
+                    }
+                    index += 1;
+                )+
+                unreachable!("every variant should be listed")
+            }
+        }
+    };
+}
+
+/// A grid of a fieldless enum, storing the index of each variant. See the `palette` module for
+/// details.
+#[derive(Clone, Debug, Default)]
+pub struct EnumGrid<E: PaletteEnum, const D: usize = 2> {
+    grid: ExpandableGridN<E::Index, D>,
+}
+
+impl<E: PaletteEnum, const D: usize> EnumGrid<E, D> {
+    /// Creates a new, empty grid.
+    pub fn new() -> Self {
+        Self {
+            grid: ExpandableGridN::new(),
+        }
+    }
+
+    /// Creates a grid of size `size` with its lowest corner at `origin`, filled with `fill`.
+    pub fn with_size(
+        size: impl GridVector<usize, D>,
+        origin: impl GridVector<isize, D>,
+        fill: E,
+    ) -> Self {
+        Self {
+            grid: ExpandableGridN::with_size(size, origin, &fill.to_index()),
+        }
+    }
+
+    /// Creates a grid with the same cells as `grid`.
+    pub fn from_grid(grid: &ExpandableGridN<E, D>) -> Self {
+        Self {
+            grid: grid.map(|&value| value.to_index()),
+        }
+    }
+
+    /// Returns the grid of indices of each cell.
+    pub fn indices(&self) -> &ExpandableGridN<E::Index, D> {
+        &self.grid
+    }
+
+    pub fn into_indices(self) -> ExpandableGridN<E::Index, D> {
+        self.grid
+    }
+
+    /// Copies every cell into a new `ExpandableGridN`.
+    pub fn to_grid(&self) -> ExpandableGridN<E, D> {
+        self.grid.map(|&index| E::from_index(index))
+    }
+
+    pub fn get(&self, index: impl GridVector<isize, D>) -> Option<E> {
+        self.grid.get(index).map(|&index| E::from_index(index))
+    }
+
+    /// Sets the cell at `index` to `value`, returning `false` if it is out of bounds.
+    pub fn set(&mut self, index: impl GridVector<isize, D>, value: E) -> bool {
+        self.grid
+            .get_mut(index)
+            .map(|cell| *cell = value.to_index())
+            .is_some()
+    }
+
+    /// Sets every cell of `rect` within the bounds of the grid to `value`.
+    pub fn fill_rect(&mut self, rect: GridRect<D>, value: E) {
+        self.grid
+            .fill_box(rect.origin, rect.size, &value.to_index());
+    }
+
+    /// Returns the number of cells equal to `value`.
+    pub fn count(&self, value: E) -> usize {
+        let index = value.to_index();
+        self.grid.data.iter().filter(|&&cell| cell == index).count()
+    }
+
+    /// Returns whether any cell is equal to `value`.
+    pub fn contains(&self, value: E) -> bool {
+        self.grid.data.contains(&value.to_index())
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_point`.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<isize, D>, fill: E) {
+        self.grid.expand_to_fit_point(point, &fill.to_index());
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_box`.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<isize, D>,
+        box_size: impl GridVector<usize, D>,
+        fill: E,
+    ) {
+        self.grid
+            .expand_to_fit_box(box_origin, box_size, &fill.to_index());
+    }
+
+    /// See `ExpandableGridN::change_size`.
+    pub fn change_size(
+        &mut self,
+        new_size: impl GridVector<usize, D>,
+        offset: impl GridVector<isize, D>,
+        fill: E,
+    ) {
+        self.grid.change_size(new_size, offset, &fill.to_index());
+    }
+}
@@ -0,0 +1,207 @@
+//! Running updates over a grid in parallel with `rayon`.
+//!
+//! A `TileExecutor` splits a grid into tiles and runs a closure on every tile in parallel, once
+//! per pass. Each pass reads from the state of the grid at the start of the pass and writes to a
+//! new state, so tiles never see each other's writes within a pass, and the cells bordering each
+//! tile (its halo) always hold the results of the previous pass. Reads outside the bounds of the
+//! grid return a border value, so updates near the edges need no special handling.
+//!
+//! `par_map` and `par_zip_map` are parallel versions of `ExpandableGridN::map` and
+//! `ExpandableGridN::zip_map`, which split the work by rows (or columns, for
+//! `Layout::ColumnMajor`).
+
+use crate::{
+    coord::{Coordinate, GridVector},
+    util, ExpandableGridN, Layout,
+};
+use nalgebra::SVector;
+use rayon::prelude::*;
+
+/// Runs updates on the tiles of a grid in parallel. See the `parallel` module for details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileExecutor<const D: usize = 2> {
+    tile_size: SVector<usize, D>,
+    halo: usize,
+}
+
+/// The state of a grid at the start of a pass, as seen by the update of one tile.
+#[derive(Clone, Copy, Debug)]
+pub struct TileView<'a, T, const D: usize = 2> {
+    grid: &'a ExpandableGridN<T, D>,
+    border: &'a T,
+    origin: SVector<isize, D>,
+    size: SVector<usize, D>,
+    halo: usize,
+}
+
+impl<const D: usize> TileExecutor<D> {
+    /// Creates an executor which splits grids into tiles of size `tile_size`, where updates may
+    /// read cells up to `halo` cells outside of their tile on each axis.
+    ///
+    /// # Panics
+    /// Panics if `tile_size` is 0 on any axis.
+    pub fn new(tile_size: impl GridVector<usize, D>, halo: usize) -> Self {
+        let tile_size = tile_size.to_vector();
+        assert!(
+            tile_size.iter().all(|&length| length > 0),
+            "tile size should not be 0 on any axis",
+        );
+
+        Self { tile_size, halo }
+    }
+
+    /// Runs one pass over `grid`. `update` is called in parallel for every tile with a view of the
+    /// grid at the start of the pass, and a grid covering the tile to write the new state of its
+    /// cells to, which starts as a copy of their current state. Cells outside the bounds of the
+    /// grid are read as `border`.
+    pub fn step<T, F>(&self, grid: &mut ExpandableGridN<T, D>, border: &T, update: F)
+    where
+        T: Clone + Send + Sync,
+        F: Fn(&TileView<T, D>, &mut ExpandableGridN<T, D>) + Sync,
+    {
+        let tile_counts = grid.size.zip_map(&self.tile_size, |length, tile_length| {
+            length.div_ceil(tile_length)
+        });
+        let tiles: Vec<_> = util::iter_box(SVector::zeros(), tile_counts).collect();
+
+        let previous = &*grid;
+        let outputs: Vec<_> = tiles
+            .into_par_iter()
+            .map(|tile| {
+                let origin =
+                    previous.origin + util::usize_vec_to_isize(tile.component_mul(&self.tile_size));
+                let size = (previous.corner() - origin)
+                    .zip_map(&self.tile_size, |remaining, tile_length| {
+                        (remaining as usize).min(tile_length)
+                    });
+
+                let view = TileView {
+                    grid: previous,
+                    border,
+                    origin,
+                    size,
+                    halo: self.halo,
+                };
+                let mut output = previous.copy_box(origin, size, border);
+                update(&view, &mut output);
+
+                output
+            })
+            .collect();
+
+        for output in &outputs {
+            grid.blit(output);
+        }
+    }
+
+    /// Runs `passes` passes over `grid`, as with `step`.
+    pub fn run<T, F>(&self, grid: &mut ExpandableGridN<T, D>, passes: usize, border: &T, update: F)
+    where
+        T: Clone + Send + Sync,
+        F: Fn(&TileView<T, D>, &mut ExpandableGridN<T, D>) + Sync,
+    {
+        for _ in 0..passes {
+            self.step(grid, border, &update);
+        }
+    }
+}
+
+impl<T: Sync, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Parallel version of `map`.
+    pub fn par_map<U: Send>(&self, f: impl Fn(&T) -> U + Sync) -> ExpandableGridN<U, D, C> {
+        ExpandableGridN {
+            size: self.size,
+            origin: self.origin,
+            data: self
+                .data
+                .par_chunks(self.line_length())
+                .flat_map_iter(|line| line.iter().map(&f))
+                .collect::<Vec<_>>()
+                .into_boxed_slice(),
+            layout: self.layout,
+        }
+    }
+
+    /// Parallel version of `zip_map`.
+    ///
+    /// # Panics
+    /// Panics if `other` does not have the same size and origin as this grid.
+    pub fn par_zip_map<U: Sync, V: Send>(
+        &self,
+        other: &ExpandableGridN<U, D, C>,
+        f: impl Fn(&T, &U) -> V + Sync,
+    ) -> ExpandableGridN<V, D, C> {
+        let other_index = self.zip_index(other);
+        let line_length = self.line_length();
+
+        ExpandableGridN {
+            size: self.size,
+            origin: self.origin,
+            data: self
+                .data
+                .par_chunks(line_length)
+                .enumerate()
+                .flat_map_iter(|(line, cells)| {
+                    let other_index = &other_index;
+                    let f = &f;
+
+                    cells.iter().enumerate().map(move |(offset, cell)| {
+                        f(cell, &other.data[other_index(line * line_length + offset)])
+                    })
+                })
+                .collect::<Vec<_>>()
+                .into_boxed_slice(),
+            layout: self.layout,
+        }
+    }
+
+    /// Returns the number of cells in each contiguous row (or column) of this grid.
+    fn line_length(&self) -> usize {
+        let axis = match self.layout {
+            Layout::RowMajor => 0,
+            Layout::ColumnMajor => D - 1,
+        };
+
+        self.size.get(axis).copied().unwrap_or(0).max(1)
+    }
+}
+
+impl<T, const D: usize> TileView<'_, T, D> {
+    /// Returns the coordinates of the lowest corner of the tile.
+    pub fn origin(&self) -> SVector<isize, D> {
+        self.origin
+    }
+
+    /// Returns the size of the tile, which is smaller than the executor's tile size at the
+    /// highest edges of the grid.
+    pub fn size(&self) -> SVector<usize, D> {
+        self.size
+    }
+
+    /// Iterates over the coordinates of every cell within the tile.
+    pub fn positions(&self) -> impl Iterator<Item = SVector<isize, D>> + '_ {
+        util::iter_box(SVector::zeros(), self.size)
+            .map(|offset| self.origin + util::usize_vec_to_isize(offset))
+    }
+
+    /// Returns the cell at `index` at the start of the pass, or the border if it is outside the
+    /// bounds of the grid.
+    ///
+    /// # Panics
+    /// Panics if `index` is further than the halo from the tile on any axis.
+    pub fn get(&self, index: impl GridVector<isize, D>) -> &T {
+        let index = index.to_vector();
+        let halo = self.halo as isize;
+
+        assert!(
+            (0..D).all(|axis| {
+                index[axis] >= self.origin[axis] - halo
+                    && index[axis] < self.origin[axis] + self.size[axis] as isize + halo
+            }),
+            "{index:?} should be within the halo of the tile at {:?}",
+            self.origin,
+        );
+
+        self.grid.get(index).unwrap_or(self.border)
+    }
+}
@@ -0,0 +1,733 @@
+//! Saving grids to region files, which split a grid into fixed size tiles that can be loaded
+//! individually.
+//!
+//! Region files are written with `ExpandableGridN::write_region`, and read with a
+//! `RegionReader`, which can load only the tiles that intersect a box. Each tile is stored
+//! using the run length encoded binary format from the `binary` module, so grids of subchunks
+//! can be stored this way as long as the chunk type implements `BinaryCell`.
+//!
+//! For saving a grid repeatedly as it changes, a `TrackedGrid` records which tiles have been
+//! modified, and `TrackedGrid::save_dirty` writes only those tiles to a `TileStore`, such as a
+//! `DirectoryStore` which keeps each tile in its own file.
+//!
+//! # Layout
+//!
+//! All integers are little endian.
+//!
+//! | Field       | Encoding        | Notes                                                  |
+//! |-------------|-----------------|--------------------------------------------------------|
+//! | magic       | 4 bytes         | Always `b"EXRG"`                                       |
+//! | version     | `u16`           | Currently `2`                                          |
+//! | dimensions  | `u8`            | `D`                                                    |
+//! | layout      | `u8`            | `0` for row major, `1` for column major                |
+//! | size        | `D` × `u64`     | The size of the whole grid                             |
+//! | origin      | `D` × `i64`     | The origin of the whole grid                           |
+//! | tile size   | `D` × `u64`     | The size of each tile, never 0                         |
+//! | tile count  | `u64`           |                                                        |
+//! | index       | tile count × entry | Each entry is the tile coordinates (`D` × `i64`), then the offset of the tile from the start of the file (`u64`), then its length in bytes (`u64`), then the CRC-32 of its bytes (`u32`) |
+//! | checksum    | `u32`           | The CRC-32 of the header and index                     |
+//! | tiles       | binary grids    | Each tile as written by `write_binary_rle`             |
+//!
+//! The tile with coordinates `t` covers the cells from `t * tile_size` to
+//! `(t + 1) * tile_size - 1`, clipped to the bounds of the whole grid. Tiles entirely outside the
+//! grid are never stored.
+//!
+//! Checksums are verified whenever the index or a tile is read, and errors encountered while
+//! reading a tile are wrapped in `BinaryError::InTile` to identify which tile is corrupted.
+
+use crate::{
+    binary::{self, BinaryCell, BinaryError},
+    checksum::{self, ChecksumReader},
+    coord::{self, Coordinate, GridVector},
+    util, ExpandableGridN, Layout,
+};
+use nalgebra::SVector;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+/// The bytes every region file starts with.
+pub const MAGIC: [u8; 4] = *b"EXRG";
+
+/// The version of the region format written by this version of the crate.
+pub const VERSION: u16 = 2;
+
+impl<T: BinaryCell + PartialEq + Clone, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Writes this grid to `writer` as a region file with tiles of size `tile_size`. See the
+    /// `persistence` module for details on the format.
+    ///
+    /// # Panics
+    /// Panics if `tile_size` is 0 on any axis.
+    pub fn write_region<W: Write + Seek>(
+        &self,
+        tile_size: SVector<usize, D>,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        self.write_region_with_version(tile_size, writer, 0)
+    }
+
+    /// Same as `write_region`, but writes every tile with `data_version` as its data version (see
+    /// `write_binary_with_version`).
+    ///
+    /// # Panics
+    /// Panics if `tile_size` is 0 on any axis.
+    pub fn write_region_with_version<W: Write + Seek>(
+        &self,
+        tile_size: SVector<usize, D>,
+        writer: &mut W,
+        data_version: u32,
+    ) -> io::Result<()> {
+        assert!(
+            tile_size.iter().all(|&length| length > 0),
+            "tile size should not be 0 on any axis",
+        );
+
+        let origin = coord::to_isize_vec(self.origin);
+        let tiles = tiles_intersecting(origin, self.size, tile_size).collect::<Vec<_>>();
+
+        let start = writer.stream_position()?;
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&[D as u8, binary::layout_to_byte(self.layout)]);
+        binary::write_size(&mut header, self.size)?;
+        binary::write_position(&mut header, origin)?;
+        binary::write_size(&mut header, tile_size)?;
+        header.extend_from_slice(&(tiles.len() as u64).to_le_bytes());
+
+        // Leave space for the header and index, which are written once the tiles have been
+        let entry_length = D * 8 + 20;
+        let header_length = header.len() + entry_length * tiles.len() + 4;
+        writer.seek(SeekFrom::Start(start + header_length as u64))?;
+
+        let mut tile_bytes = Vec::new();
+        for &tile in &tiles {
+            let tile_grid = copy_tile(self, tile, tile_size)
+                .expect("tiles_intersecting should only yield tiles which intersect the grid");
+
+            tile_bytes.clear();
+            tile_grid.write_binary_rle_with_version(&mut tile_bytes, data_version)?;
+
+            let offset = writer.stream_position()? - start;
+            writer.write_all(&tile_bytes)?;
+
+            binary::write_position(&mut header, tile)?;
+            header.extend_from_slice(&offset.to_le_bytes());
+            header.extend_from_slice(&(tile_bytes.len() as u64).to_le_bytes());
+            header.extend_from_slice(&checksum::crc32(&tile_bytes).to_le_bytes());
+        }
+        let end = writer.stream_position()?;
+
+        let header_checksum = checksum::crc32(&header);
+        header.extend_from_slice(&header_checksum.to_le_bytes());
+        debug_assert_eq!(header.len(), header_length);
+
+        writer.seek(SeekFrom::Start(start))?;
+        writer.write_all(&header)?;
+        writer.seek(SeekFrom::Start(end))?;
+
+        Ok(())
+    }
+}
+
+/// Reads tiles from a region file written by `ExpandableGridN::write_region`.
+#[derive(Clone, Debug)]
+pub struct RegionReader<R, const D: usize> {
+    reader: R,
+    start: u64,
+    size: SVector<usize, D>,
+    origin: SVector<isize, D>,
+    layout: Layout,
+    tile_size: SVector<usize, D>,
+    tiles: HashMap<SVector<isize, D>, TileEntry>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TileEntry {
+    offset: u64,
+    length: u64,
+    checksum: u32,
+}
+
+impl<R: Read + Seek, const D: usize> RegionReader<R, D> {
+    /// Reads the header and index of a region file, starting at the current position of
+    /// `reader`. No tiles are read until requested.
+    pub fn open(mut reader: R) -> Result<Self, BinaryError> {
+        let start = reader.stream_position()?;
+        let mut reader = ChecksumReader::new(reader);
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(BinaryError::InvalidMagic(magic));
+        }
+
+        let version = u16::from_le_bytes(binary::read_array(&mut reader)?);
+        if version != VERSION {
+            return Err(BinaryError::UnsupportedVersion(version));
+        }
+
+        let [dimensions, layout] = binary::read_array(&mut reader)?;
+        if dimensions as usize != D {
+            return Err(BinaryError::DimensionMismatch {
+                expected: D,
+                found: dimensions as usize,
+            });
+        }
+        let layout = binary::layout_from_byte(layout).ok_or(BinaryError::InvalidLayout(layout))?;
+
+        let size = binary::read_size(&mut reader)?;
+        let origin = binary::read_position(&mut reader)?;
+        let tile_size: SVector<usize, D> = binary::read_size(&mut reader)?;
+        if tile_size.iter().any(|&length| length == 0) {
+            return Err(BinaryError::InvalidTileSize);
+        }
+
+        let tile_count = u64::from_le_bytes(binary::read_array(&mut reader)?);
+
+        let mut tiles = HashMap::new();
+        for _ in 0..tile_count {
+            let tile = binary::read_position(&mut reader)?;
+            let offset = u64::from_le_bytes(binary::read_array(&mut reader)?);
+            let length = u64::from_le_bytes(binary::read_array(&mut reader)?);
+            let checksum = u32::from_le_bytes(binary::read_array(&mut reader)?);
+
+            tiles.insert(
+                tile,
+                TileEntry {
+                    offset,
+                    length,
+                    checksum,
+                },
+            );
+        }
+
+        let expected = reader.checksum();
+        let found = reader.read_stored_checksum()?;
+        if found != expected {
+            return Err(BinaryError::ChecksumMismatch { expected, found });
+        }
+
+        Ok(Self {
+            reader: reader.inner,
+            start,
+            size,
+            origin,
+            layout,
+            tile_size,
+            tiles,
+        })
+    }
+
+    /// The size of the whole grid stored in the region file.
+    pub fn size(&self) -> SVector<usize, D> {
+        self.size
+    }
+
+    /// The origin of the whole grid stored in the region file.
+    pub fn origin(&self) -> SVector<isize, D> {
+        self.origin
+    }
+
+    /// The size of each tile within the region file.
+    pub fn tile_size(&self) -> SVector<usize, D> {
+        self.tile_size
+    }
+
+    /// Returns an iterator over the coordinates of every tile stored in the region file.
+    pub fn tiles(&self) -> impl Iterator<Item = SVector<isize, D>> + '_ {
+        self.tiles.keys().copied()
+    }
+
+    /// Returns the coordinates of the tile containing the cell at `point`.
+    pub fn tile_of(&self, point: impl GridVector<isize, D>) -> SVector<isize, D> {
+        point.to_vector().zip_map(
+            &util::usize_vec_to_isize(self.tile_size),
+            |position, length| position.div_euclid(length),
+        )
+    }
+
+    /// Reads the tile with coordinates `tile`, or returns `None` if it is not stored in the
+    /// region file.
+    pub fn read_tile<T: BinaryCell>(
+        &mut self,
+        tile: SVector<isize, D>,
+    ) -> Result<Option<ExpandableGridN<T, D>>, BinaryError> {
+        let Some(bytes) = self.read_stored_tile_bytes(tile)? else {
+            return Ok(None);
+        };
+
+        let grid = ExpandableGridN::read_binary(&mut bytes.as_slice())
+            .map_err(|error| error.in_tile(tile))?;
+
+        Ok(Some(grid))
+    }
+
+    /// Checks the checksum of every tile in the region file without decoding them, returning the
+    /// first error encountered.
+    pub fn verify(&mut self) -> Result<(), BinaryError> {
+        let tiles = self.tiles.keys().copied().collect::<Vec<_>>();
+
+        for tile in tiles {
+            self.read_tile_bytes(tile)
+                .map_err(|error| error.in_tile(tile))?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the bytes of the tile with coordinates `tile` and checks its checksum, or returns
+    /// `None` if it is not stored in the region file.
+    pub(crate) fn read_stored_tile_bytes(
+        &mut self,
+        tile: SVector<isize, D>,
+    ) -> Result<Option<Vec<u8>>, BinaryError> {
+        if !self.tiles.contains_key(&tile) {
+            return Ok(None);
+        }
+
+        self.read_tile_bytes(tile)
+            .map(Some)
+            .map_err(|error| error.in_tile(tile))
+    }
+
+    fn read_tile_bytes(&mut self, tile: SVector<isize, D>) -> Result<Vec<u8>, BinaryError> {
+        let TileEntry {
+            offset,
+            length,
+            checksum,
+        } = self.tiles[&tile];
+
+        self.reader.seek(SeekFrom::Start(self.start + offset))?;
+
+        // Avoid trusting the index with a large allocation before any data has been read
+        let mut bytes = Vec::new();
+        (&mut self.reader).take(length).read_to_end(&mut bytes)?;
+        if (bytes.len() as u64) < length {
+            return Err(BinaryError::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+
+        let found = checksum::crc32(&bytes);
+        if found != checksum {
+            return Err(BinaryError::ChecksumMismatch {
+                expected: checksum,
+                found,
+            });
+        }
+
+        Ok(bytes)
+    }
+
+    /// Reads the box with its lowest corner at `box_origin` and size `box_size`, reading only the
+    /// tiles which intersect it. Cells of the box which are not stored in the region file are
+    /// clones of `fill`.
+    pub fn read_box<T: BinaryCell + Clone>(
+        &mut self,
+        box_origin: SVector<isize, D>,
+        box_size: SVector<usize, D>,
+        fill: &T,
+    ) -> Result<ExpandableGridN<T, D>, BinaryError> {
+        let mut grid =
+            ExpandableGridN::with_size_and_layout(box_size, box_origin, fill, self.layout);
+
+        for tile in tiles_intersecting(box_origin, box_size, self.tile_size) {
+            if let Some(tile_grid) = self.read_tile(tile)? {
+                grid.blit(&tile_grid);
+            }
+        }
+
+        Ok(grid)
+    }
+
+    /// Reads the whole grid stored in the region file. Cells which are not stored in the region
+    /// file are clones of `fill`.
+    pub fn read_all<T: BinaryCell + Clone>(
+        &mut self,
+        fill: &T,
+    ) -> Result<ExpandableGridN<T, D>, BinaryError> {
+        self.read_box(self.origin, self.size, fill)
+    }
+
+    /// Returns the reader this was created with.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// Somewhere that individual tiles of a grid can be saved to, used by `TrackedGrid::save_dirty`.
+pub trait TileStore<T, const D: usize> {
+    /// Saves `grid` as the contents of the tile with coordinates `tile`, replacing any previously
+    /// saved contents. `grid` covers the part of the tile within the bounds of the whole grid.
+    fn store_tile(
+        &mut self,
+        tile: SVector<isize, D>,
+        grid: &ExpandableGridN<T, D>,
+    ) -> io::Result<()>;
+
+    /// Removes the tile with coordinates `tile`, which is no longer within the bounds of the
+    /// whole grid.
+    fn remove_tile(&mut self, tile: SVector<isize, D>) -> io::Result<()>;
+}
+
+/// A `TileStore` which saves each tile to its own file within a directory, in the run length
+/// encoded binary format. Tile files are named after their coordinates, such as `-1_3.tile`.
+///
+/// Tiles are saved with `data_version` as their data version (see the `migration` module), so
+/// tiles saved with an older version can be upgraded by `read_tile_migrated`.
+#[derive(Clone, Debug)]
+pub struct DirectoryStore {
+    pub directory: PathBuf,
+    pub data_version: u32,
+}
+
+impl DirectoryStore {
+    /// Creates a store which saves tiles within `directory`, creating it if it does not exist.
+    pub fn new(directory: impl Into<PathBuf>) -> io::Result<Self> {
+        Self::with_data_version(directory, 0)
+    }
+
+    /// Same as `new`, but saves tiles with `data_version` as their data version.
+    pub fn with_data_version(directory: impl Into<PathBuf>, data_version: u32) -> io::Result<Self> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory)?;
+        Ok(Self {
+            directory,
+            data_version,
+        })
+    }
+
+    /// The path of the file the tile with coordinates `tile` is stored in.
+    pub fn tile_path<const D: usize>(&self, tile: SVector<isize, D>) -> PathBuf {
+        let name = tile
+            .iter()
+            .map(|position| position.to_string())
+            .collect::<Vec<_>>()
+            .join("_");
+
+        self.directory.join(name + ".tile")
+    }
+
+    /// Opens the file of the tile with coordinates `tile`, or returns `None` if it has not been
+    /// saved.
+    pub(crate) fn open_tile<const D: usize>(
+        &self,
+        tile: SVector<isize, D>,
+    ) -> io::Result<Option<BufReader<File>>> {
+        match File::open(self.tile_path(tile)) {
+            Ok(file) => Ok(Some(BufReader::new(file))),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Reads the tile with coordinates `tile`, or returns `None` if it has not been saved.
+    ///
+    /// The data version of the tile is ignored, see `read_tile_migrated` to upgrade tiles written
+    /// with older versions of the cell type.
+    pub fn read_tile<T: BinaryCell, const D: usize>(
+        &self,
+        tile: SVector<isize, D>,
+    ) -> Result<Option<ExpandableGridN<T, D>>, BinaryError> {
+        let Some(mut reader) = self.open_tile(tile)? else {
+            return Ok(None);
+        };
+
+        ExpandableGridN::read_binary(&mut reader)
+            .map(Some)
+            .map_err(|error| error.in_tile(tile))
+    }
+
+    /// Reads the box with its lowest corner at `box_origin` and size `box_size`, reading only the
+    /// tiles of size `tile_size` which intersect it. Cells of the box which have not been saved
+    /// are clones of `fill`.
+    pub fn read_box<T: BinaryCell + Clone, const D: usize>(
+        &self,
+        box_origin: SVector<isize, D>,
+        box_size: SVector<usize, D>,
+        tile_size: SVector<usize, D>,
+        fill: &T,
+    ) -> Result<ExpandableGridN<T, D>, BinaryError> {
+        let mut grid = ExpandableGridN::with_size(box_size, box_origin, fill);
+
+        for tile in tiles_intersecting(box_origin, box_size, tile_size) {
+            if let Some(tile_grid) = self.read_tile(tile)? {
+                grid.blit(&tile_grid);
+            }
+        }
+
+        Ok(grid)
+    }
+}
+
+impl<T: BinaryCell + PartialEq, const D: usize> TileStore<T, D> for DirectoryStore {
+    fn store_tile(
+        &mut self,
+        tile: SVector<isize, D>,
+        grid: &ExpandableGridN<T, D>,
+    ) -> io::Result<()> {
+        // Write to a separate file first so that a failed save never leaves a partial tile
+        let path = self.tile_path(tile);
+        let temporary_path = path.with_extension("tile.saving");
+
+        let mut writer = BufWriter::new(File::create(&temporary_path)?);
+        grid.write_binary_rle_with_version(&mut writer, self.data_version)?;
+        writer
+            .into_inner()
+            .map_err(|error| error.into_error())?
+            .sync_data()?;
+
+        std::fs::rename(temporary_path, path)
+    }
+
+    fn remove_tile(&mut self, tile: SVector<isize, D>) -> io::Result<()> {
+        match std::fs::remove_file(self.tile_path(tile)) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// A grid which records which of its tiles have been modified since they were last saved, so that
+/// only those tiles need to be saved again with `save_dirty`.
+///
+/// Modifications are only recorded when made through the methods of this type. Changes made to
+/// the grid by other means can be recorded with `mark_dirty` and `mark_box_dirty`.
+#[derive(Clone, Debug)]
+pub struct TrackedGrid<T, const D: usize> {
+    grid: ExpandableGridN<T, D>,
+    tile_size: SVector<usize, D>,
+    dirty: HashSet<SVector<isize, D>>,
+    removed: HashSet<SVector<isize, D>>,
+}
+
+impl<T: Clone, const D: usize> TrackedGrid<T, D> {
+    /// Starts tracking changes to `grid` in tiles of size `tile_size`. Every tile starts out
+    /// dirty, since none have been saved yet.
+    ///
+    /// # Panics
+    /// Panics if `tile_size` is 0 on any axis.
+    pub fn new(grid: ExpandableGridN<T, D>, tile_size: SVector<usize, D>) -> Self {
+        assert!(
+            tile_size.iter().all(|&length| length > 0),
+            "tile size should not be 0 on any axis",
+        );
+
+        let dirty = tiles_intersecting(grid.origin, grid.size, tile_size).collect();
+
+        Self {
+            grid,
+            tile_size,
+            dirty,
+            removed: HashSet::new(),
+        }
+    }
+
+    /// Starts tracking changes to `grid`, which has already been saved, so no tiles start out
+    /// dirty.
+    pub fn new_clean(grid: ExpandableGridN<T, D>, tile_size: SVector<usize, D>) -> Self {
+        let mut tracked = Self::new(grid, tile_size);
+        tracked.dirty.clear();
+        tracked
+    }
+
+    pub fn grid(&self) -> &ExpandableGridN<T, D> {
+        &self.grid
+    }
+
+    pub fn tile_size(&self) -> SVector<usize, D> {
+        self.tile_size
+    }
+
+    /// Stops tracking changes, returning the grid.
+    pub fn into_inner(self) -> ExpandableGridN<T, D> {
+        self.grid
+    }
+
+    pub fn get(&self, index: impl GridVector<isize, D>) -> Option<&T> {
+        self.grid.get(index)
+    }
+
+    /// Returns a mutable reference to a cell, marking its tile as dirty.
+    pub fn get_mut(&mut self, index: impl GridVector<isize, D>) -> Option<&mut T> {
+        if self.grid.index_of(index).is_some() {
+            self.mark_dirty(index);
+        }
+        self.grid.get_mut(index)
+    }
+
+    /// Returns the coordinates of the tile containing the cell at `point`.
+    pub fn tile_of(&self, point: impl GridVector<isize, D>) -> SVector<isize, D> {
+        point.to_vector().zip_map(
+            &util::usize_vec_to_isize(self.tile_size),
+            |position, length| position.div_euclid(length),
+        )
+    }
+
+    /// Marks the tile containing `point` as dirty.
+    pub fn mark_dirty(&mut self, point: impl GridVector<isize, D>) {
+        let tile = self.tile_of(point);
+        self.dirty.insert(tile);
+    }
+
+    /// Marks every tile intersecting the box with its lowest corner at `box_origin` and size
+    /// `box_size` as dirty.
+    pub fn mark_box_dirty(&mut self, box_origin: SVector<isize, D>, box_size: SVector<usize, D>) {
+        self.dirty
+            .extend(tiles_intersecting(box_origin, box_size, self.tile_size));
+    }
+
+    /// Returns an iterator over the coordinates of every tile which has been modified since it
+    /// was last saved.
+    pub fn dirty_tiles(&self) -> impl Iterator<Item = SVector<isize, D>> + '_ {
+        self.dirty.iter().copied()
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_point`.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<isize, D>, fill: &T) {
+        self.expand_to_fit_box(point, SVector::<usize, D>::repeat(1), fill);
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_box`. Tiles which gain new cells are marked as dirty.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<isize, D>,
+        box_size: impl GridVector<usize, D>,
+        fill: &T,
+    ) {
+        let (old_origin, old_size) = (self.grid.origin, self.grid.size);
+        self.grid.expand_to_fit_box(box_origin, box_size, fill);
+        self.mark_bounds_changed(old_origin, old_size);
+    }
+
+    /// See `ExpandableGridN::change_size`. Tiles which gain new cells are marked as dirty, and
+    /// tiles which are no longer within the bounds of the grid are removed from the store on the
+    /// next save.
+    pub fn change_size(
+        &mut self,
+        new_size: impl GridVector<usize, D>,
+        offset: impl GridVector<isize, D>,
+        fill: &T,
+    ) {
+        let (old_origin, old_size) = (self.grid.origin, self.grid.size);
+        self.grid.change_size(new_size, offset, fill);
+        self.mark_bounds_changed(old_origin, old_size);
+    }
+
+    fn mark_bounds_changed(&mut self, old_origin: SVector<isize, D>, old_size: SVector<usize, D>) {
+        let (origin, size) = (self.grid.origin, self.grid.size);
+        if (origin, size) == (old_origin, old_size) {
+            return;
+        }
+
+        let old_tiles: HashSet<_> =
+            tiles_intersecting(old_origin, old_size, self.tile_size).collect();
+        let new_tiles: HashSet<_> = tiles_intersecting(origin, size, self.tile_size).collect();
+
+        // A tile which is not entirely within both the old and new bounds has a different part
+        // of it within the grid, so it needs to be saved again
+        let tile_size = util::usize_vec_to_isize(self.tile_size);
+        for &tile in &new_tiles {
+            let tile_origin = tile.component_mul(&tile_size);
+            let unchanged =
+                [(old_origin, old_size), (origin, size)]
+                    .into_iter()
+                    .all(|(origin, size)| {
+                        util::intersect_boxes(tile_origin, self.tile_size, origin, size)
+                            .is_some_and(|(_, overlap)| overlap == self.tile_size)
+                    });
+
+            if !unchanged {
+                self.dirty.insert(tile);
+            }
+        }
+
+        for tile in old_tiles.difference(&new_tiles) {
+            self.dirty.remove(tile);
+            self.removed.insert(*tile);
+        }
+        for tile in &new_tiles {
+            self.removed.remove(tile);
+        }
+    }
+
+    /// Saves every tile which has been modified since it was last saved to `store`, and marks
+    /// them as clean. Tiles which are no longer within the bounds of the grid are removed from
+    /// `store`. Returns the number of tiles saved.
+    ///
+    /// If saving a tile fails, the tiles which have not been saved yet remain dirty.
+    pub fn save_dirty(&mut self, store: &mut impl TileStore<T, D>) -> io::Result<usize> {
+        for tile in self.removed.clone() {
+            store.remove_tile(tile)?;
+            self.removed.remove(&tile);
+        }
+
+        let mut saved = 0;
+        for tile in self.dirty.clone() {
+            if let Some(tile_grid) = copy_tile(&self.grid, tile, self.tile_size) {
+                store.store_tile(tile, &tile_grid)?;
+                saved += 1;
+            }
+            self.dirty.remove(&tile);
+        }
+
+        Ok(saved)
+    }
+}
+
+impl<T: Clone, I: GridVector<isize, D>, const D: usize> std::ops::Index<I> for TrackedGrid<T, D> {
+    type Output = T;
+
+    fn index(&self, index: I) -> &Self::Output {
+        self.get(index).unwrap()
+    }
+}
+
+impl<T: Clone, I: GridVector<isize, D>, const D: usize> std::ops::IndexMut<I>
+    for TrackedGrid<T, D>
+{
+    fn index_mut(&mut self, index: I) -> &mut Self::Output {
+        self.get_mut(index).unwrap()
+    }
+}
+
+/// Copies the part of the tile with coordinates `tile` which is within the bounds of `grid`, or
+/// returns `None` if the tile does not intersect the grid.
+fn copy_tile<T: Clone, const D: usize, C: Coordinate>(
+    grid: &ExpandableGridN<T, D, C>,
+    tile: SVector<isize, D>,
+    tile_size: SVector<usize, D>,
+) -> Option<ExpandableGridN<T, D, C>> {
+    let (origin, size) = util::intersect_boxes(
+        tile.component_mul(&util::usize_vec_to_isize(tile_size)),
+        tile_size,
+        coord::to_isize_vec(grid.origin),
+        grid.size,
+    )?;
+    let origin = coord::from_isize_vec::<C, D>(origin);
+
+    Some(grid.copy_box(origin, size, &grid[origin]))
+}
+
+/// Returns an iterator over the coordinates of every tile of size `tile_size` which intersects the
+/// box with its lowest corner at `box_origin` and size `box_size`.
+fn tiles_intersecting<const D: usize>(
+    box_origin: SVector<isize, D>,
+    box_size: SVector<usize, D>,
+    tile_size: SVector<usize, D>,
+) -> impl Iterator<Item = SVector<isize, D>> {
+    let tile_size = util::usize_vec_to_isize(tile_size);
+    let box_corner = box_origin + util::usize_vec_to_isize(box_size);
+
+    let first = box_origin.zip_map(&tile_size, |position, length| position.div_euclid(length));
+    let last = box_corner.zip_map(&tile_size, |position, length| {
+        (position - 1).div_euclid(length)
+    });
+    let count = (last - first).map(|count| (count + 1).max(0) as usize);
+
+    util::iter_box(SVector::zeros(), count)
+        .map(move |offset| first + util::usize_vec_to_isize(offset))
+}
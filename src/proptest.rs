@@ -0,0 +1,83 @@
+//! Strategies for generating grids with `proptest`.
+//!
+//! `ExpandableGridN<T, D>` implements `Arbitrary` whenever `T` does, and `grids` creates a
+//! strategy from a strategy for the cells. Generated grids always have as many cells as their
+//! size requires, and may use either `Layout`, so that code under test handles both.
+
+use crate::{ExpandableGridN, Layout};
+use nalgebra::SVector;
+use proptest::{
+    arbitrary::{any_with, Arbitrary},
+    prelude::*,
+    strategy::BoxedStrategy,
+};
+use std::{fmt::Debug, ops::RangeInclusive};
+
+/// The parameters for generating grids with `Arbitrary`.
+#[derive(Clone, Debug)]
+pub struct GridParameters<P> {
+    /// The range of the length of the grid on each axis.
+    pub size: RangeInclusive<usize>,
+    /// The range of the origin of the grid on each axis.
+    pub origin: RangeInclusive<isize>,
+    /// The parameters for generating each cell.
+    pub cell: P,
+}
+
+impl<P: Default> Default for GridParameters<P> {
+    fn default() -> Self {
+        Self {
+            size: 0..=16,
+            origin: -16..=16,
+            cell: P::default(),
+        }
+    }
+}
+
+/// Returns a strategy for grids with cells generated by `cell`, a length on each axis within
+/// `size`, and an origin within `origin` on each axis.
+pub fn grids<S, const D: usize>(
+    cell: S,
+    size: RangeInclusive<usize>,
+    origin: RangeInclusive<isize>,
+) -> impl Strategy<Value = ExpandableGridN<S::Value, D>>
+where
+    S: Strategy + Clone,
+    S::Value: Debug,
+{
+    proptest::array::uniform::<_, D>(size)
+        .prop_flat_map(move |size| {
+            let size = SVector::from(size);
+
+            (
+                Just(size),
+                proptest::array::uniform::<_, D>(origin.clone()),
+                proptest::collection::vec(cell.clone(), size.product()),
+                prop_oneof![Just(Layout::RowMajor), Just(Layout::ColumnMajor)],
+            )
+        })
+        .prop_map(|(size, origin, data, layout)| ExpandableGridN {
+            size,
+            origin: SVector::from(origin),
+            data: data.into_boxed_slice(),
+            layout,
+        })
+}
+
+impl<T, const D: usize> Arbitrary for ExpandableGridN<T, D>
+where
+    T: Arbitrary + 'static,
+    T::Strategy: Clone + 'static,
+{
+    type Parameters = GridParameters<T::Parameters>;
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(parameters: Self::Parameters) -> Self::Strategy {
+        grids(
+            any_with::<T>(parameters.cell),
+            parameters.size,
+            parameters.origin,
+        )
+        .boxed()
+    }
+}
@@ -0,0 +1,152 @@
+//! Python bindings through `pyo3`, with conversions to and from `numpy` arrays.
+//!
+//! `GridF64` and `GridI64` wrap an `ExpandableGrid<f64>` and an `ExpandableGrid<i64>`. Coordinates
+//! and sizes are given as `(x, y)` tuples, and cells can be read and written with
+//! `grid[x, y]`. Arrays are indexed with `[y, x]` relative to the origin of the grid, as in the
+//! `ndarray` module, and are copied to and from the grid.
+//!
+//! The classes are added to a Python module by `expandable_grid`. To build an extension module,
+//! wrap it in a `cdylib` crate with pyo3's `extension-module` feature:
+//!
+//! ```ignore
+//! #[pymodule]
+//! fn my_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+//!     expandable_grid::python::expandable_grid(m)
+//! }
+//! ```
+
+use crate::{ExpandableGrid, Layout};
+use nalgebra::vector;
+use numpy::{
+    ndarray::{Array2, ShapeBuilder},
+    PyArray2, PyReadonlyArray2,
+};
+use pyo3::{exceptions::PyIndexError, prelude::*};
+
+macro_rules! py_grid {
+    ($name:ident, $py_name:literal, $cell:ty) => {
+        #[doc = concat!("An `ExpandableGrid<", stringify!($cell), ">` which can be used from Python.")]
+        #[pyclass(name = $py_name)]
+        #[derive(Clone, Debug, Default)]
+        pub struct $name {
+            pub grid: ExpandableGrid<$cell>,
+        }
+
+        #[pymethods]
+        impl $name {
+            #[new]
+            #[pyo3(signature = (size = (0, 0), origin = (0, 0), fill = Default::default()))]
+            fn new(size: (usize, usize), origin: (isize, isize), fill: $cell) -> Self {
+                Self {
+                    grid: ExpandableGrid::with_size(size, origin, &fill),
+                }
+            }
+
+            #[getter]
+            fn size(&self) -> (usize, usize) {
+                (self.grid.size.x, self.grid.size.y)
+            }
+
+            #[getter]
+            fn origin(&self) -> (isize, isize) {
+                (self.grid.origin.x, self.grid.origin.y)
+            }
+
+            fn get(&self, index: (isize, isize)) -> Option<$cell> {
+                self.grid.get(index).copied()
+            }
+
+            fn __getitem__(&self, index: (isize, isize)) -> PyResult<$cell> {
+                self.get(index)
+                    .ok_or_else(|| PyIndexError::new_err("grid index out of bounds"))
+            }
+
+            fn __setitem__(&mut self, index: (isize, isize), value: $cell) -> PyResult<()> {
+                let cell = self
+                    .grid
+                    .get_mut(index)
+                    .ok_or_else(|| PyIndexError::new_err("grid index out of bounds"))?;
+                *cell = value;
+                Ok(())
+            }
+
+            fn __repr__(&self) -> String {
+                format!(
+                    "{}(size={:?}, origin={:?})",
+                    $py_name,
+                    self.size(),
+                    self.origin(),
+                )
+            }
+
+            fn expand_to_fit_point(&mut self, point: (isize, isize), fill: $cell) {
+                self.grid.expand_to_fit_point(point, &fill);
+            }
+
+            fn expand_to_fit_box(
+                &mut self,
+                box_origin: (isize, isize),
+                box_size: (usize, usize),
+                fill: $cell,
+            ) {
+                self.grid.expand_to_fit_box(box_origin, box_size, &fill);
+            }
+
+            fn change_size(
+                &mut self,
+                new_size: (usize, usize),
+                offset: (isize, isize),
+                fill: $cell,
+            ) {
+                self.grid.change_size(new_size, offset, &fill);
+            }
+
+            /// Returns a copy of the cells as an array indexed with `[y, x]`.
+            fn to_numpy<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<$cell>> {
+                let shape = (self.grid.size.y, self.grid.size.x);
+                let data = self.grid.data.to_vec();
+
+                match self.grid.layout {
+                    Layout::RowMajor => Array2::from_shape_vec(shape, data),
+                    Layout::ColumnMajor => Array2::from_shape_vec(shape.f(), data),
+                }
+                .map(|array| PyArray2::from_owned_array_bound(py, array))
+                .expect("the data of a grid should match its size")
+            }
+
+            /// Creates a grid from an array indexed with `[y, x]`, with the element at `[0, 0]`
+            /// at `origin`.
+            #[staticmethod]
+            #[pyo3(signature = (array, origin = (0, 0)))]
+            fn from_numpy(array: PyReadonlyArray2<'_, $cell>, origin: (isize, isize)) -> Self {
+                let array = array.as_array();
+                let (height, width) = array.dim();
+
+                Self {
+                    grid: ExpandableGrid {
+                        size: vector![width, height],
+                        origin: vector![origin.0, origin.1],
+                        data: array.iter().copied().collect(),
+                        layout: Layout::RowMajor,
+                    },
+                }
+            }
+        }
+
+        impl From<ExpandableGrid<$cell>> for $name {
+            fn from(grid: ExpandableGrid<$cell>) -> Self {
+                Self { grid }
+            }
+        }
+    };
+}
+
+py_grid!(PyGridF64, "GridF64", f64);
+py_grid!(PyGridI64, "GridI64", i64);
+
+/// Adds the grid classes to the Python module `module`.
+pub fn expandable_grid(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyGridF64>()?;
+    module.add_class::<PyGridI64>()?;
+    Ok(())
+}
@@ -0,0 +1,104 @@
+//! Generating and shrinking grids with `quickcheck`.
+//!
+//! Generated grids have at most about `Gen::size` cells, origins within `Gen::size` of zero, and
+//! either `Layout`. When a property fails, grids are shrunk first by cropping them on each axis,
+//! keeping the coordinates of the remaining cells, then by moving their origin towards zero, and
+//! finally by shrinking each of their cells.
+
+use crate::{ExpandableGridN, Layout};
+use nalgebra::SVector;
+use quickcheck::{Arbitrary, Gen};
+
+impl<T: Arbitrary, const D: usize> Arbitrary for ExpandableGridN<T, D> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        // Keep the area of the grid near `g.size()`, whatever its number of dimensions
+        let max_length = (g.size() as f64).powf(1.0 / D as f64).ceil() as usize;
+        let max_origin = g.size() as isize;
+
+        let size = SVector::from_fn(|_, _| usize::arbitrary(g) % (max_length + 1));
+        let origin = SVector::from_fn(|_, _| isize::arbitrary(g) % (max_origin + 1));
+        let layout = *g.choose(&[Layout::RowMajor, Layout::ColumnMajor]).unwrap();
+
+        Self {
+            size,
+            origin,
+            data: (0..size.product()).map(|_| T::arbitrary(g)).collect(),
+            layout,
+        }
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let grid = self.clone();
+        let crops = (0..D).flat_map(move |axis| {
+            let length = grid.size[axis];
+            let grid = grid.clone();
+
+            // Keep the lowest cells, then the highest cells, on `axis`
+            let lengths = [0, length / 2, length.saturating_sub(1)];
+            let mut boxes: Vec<_> = lengths
+                .into_iter()
+                .filter(|&new_length| new_length < length)
+                .flat_map(|new_length| [(0, new_length), (length - new_length, new_length)])
+                .collect();
+            boxes.dedup();
+
+            boxes
+                .into_iter()
+                .map(move |(start, new_length)| crop(&grid, axis, start, new_length))
+        });
+
+        let grid = self.clone();
+        let origins = (0..D).flat_map(move |axis| {
+            let origin = grid.origin[axis];
+            let grid = grid.clone();
+
+            let mut origins = vec![0, origin / 2, origin - origin.signum()];
+            origins.retain(|&new_origin| new_origin != origin);
+            origins.dedup();
+
+            origins.into_iter().map(move |new_origin| {
+                let mut grid = grid.clone();
+                grid.origin[axis] = new_origin;
+                grid
+            })
+        });
+
+        let grid = self.clone();
+        let cells = (0..self.data.len()).flat_map(move |index| {
+            let grid = grid.clone();
+
+            grid.data[index].shrink().map(move |cell| {
+                let mut grid = grid.clone();
+                grid.data[index] = cell;
+                grid
+            })
+        });
+
+        Box::new(crops.chain(origins).chain(cells))
+    }
+}
+
+/// Returns the part of `grid` which is `length` cells long on `axis`, starting `start` cells from
+/// its origin, keeping the coordinates of each cell.
+fn crop<T: Clone, const D: usize>(
+    grid: &ExpandableGridN<T, D>,
+    axis: usize,
+    start: usize,
+    length: usize,
+) -> ExpandableGridN<T, D> {
+    let mut origin = grid.origin;
+    origin[axis] += start as isize;
+    let mut size = grid.size;
+    size[axis] = length;
+
+    match grid.data.first() {
+        Some(fill) => grid.copy_box(origin, size, fill),
+        // The grid has no cells, and will have none after cropping
+        None => ExpandableGridN {
+            size,
+            origin,
+            data: Box::new([]),
+            layout: grid.layout,
+        },
+    }
+}
@@ -0,0 +1,353 @@
+//! A human readable serialized form for grids, which stores the cells as a list of rows rather
+//! than one flat list, so that serialized grids can be read and diffed line by line.
+//!
+//! Rows run along the `x` axis, and are listed with `y` varying fastest, followed by each later
+//! axis in turn, regardless of the layout of the grid. Use this module with
+//! `#[serde(with = "expandable_grid::readable")]`, or with the `ron` and `json` features, convert
+//! grids directly with `ExpandableGridN::to_ron_string`, `ExpandableGridN::to_json_string` and
+//! their counterparts.
+//!
+//! Like the default serialized form, this starts with the version of the form and the data
+//! version of the cells, and grids written with an older data version can be upgraded with
+//! `deserialize_migrated`, `ExpandableGridN::from_ron_str_migrated` or
+//! `ExpandableGridN::from_json_str_migrated`.
+
+use crate::{
+    coord::Coordinate,
+    migration::CellMigrator,
+    serde_impls::{self, CellSeed, Fields, Format, NoMigration, SeqSeed},
+    ExpandableGridN, Layout,
+};
+use nalgebra::SVector;
+use serde::{de::Error, ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
+
+/// The version of the readable form written by this version of the crate.
+const VERSION: u32 = 1;
+
+const FORMAT: Format = Format {
+    name: "ExpandableGrid",
+    fields: &[
+        "version",
+        "data_version",
+        "size",
+        "origin",
+        "layout",
+        "rows",
+    ],
+    version: VERSION,
+    data_version_since: 1,
+};
+
+#[derive(Serialize)]
+#[serde(
+    rename = "ExpandableGrid",
+    bound = "T: Serialize, C: Coordinate + Serialize"
+)]
+struct RowsRef<'a, T, const D: usize, C> {
+    version: u32,
+    data_version: u32,
+    size: &'a SVector<usize, D>,
+    origin: &'a SVector<C, D>,
+    layout: Layout,
+    rows: Rows<'a, T, D, C>,
+}
+
+/// Serializes every row of a grid as a sequence.
+struct Rows<'a, T, const D: usize, C>(&'a ExpandableGridN<T, D, C>);
+
+/// Serializes the row of a grid with this index as a sequence.
+struct Row<'a, T, const D: usize, C>(&'a ExpandableGridN<T, D, C>, usize);
+
+impl<T: Serialize, const D: usize, C> Serialize for Rows<'_, T, D, C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let grid = self.0;
+        serializer.collect_seq((0..row_count(grid.size)).map(|row| Row(grid, row)))
+    }
+}
+
+impl<T: Serialize, const D: usize, C> Serialize for Row<'_, T, D, C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Row(grid, row) = *self;
+
+        let mut seq = serializer.serialize_seq(Some(grid.size[0]))?;
+        for index in row_indices(grid.layout, grid.size, row) {
+            seq.serialize_element(&grid.data[index])?;
+        }
+        seq.end()
+    }
+}
+
+/// Returns the number of rows in a grid of size `size`.
+fn row_count<const D: usize>(size: SVector<usize, D>) -> usize {
+    size.iter().skip(1).product()
+}
+
+/// Returns the indices within the data of a grid of the cells in the row with index `row`.
+fn row_indices<const D: usize>(
+    layout: Layout,
+    size: SVector<usize, D>,
+    row: usize,
+) -> impl Iterator<Item = usize> {
+    let mut position = SVector::<usize, D>::zeros();
+    let mut remaining = row;
+    for axis in 1..D {
+        position[axis] = remaining % size[axis];
+        remaining /= size[axis];
+    }
+
+    (0..size[0]).map(move |x| {
+        let mut position = position;
+        position[0] = x;
+        layout.linear_index(position, size)
+    })
+}
+
+pub fn serialize<T, S, const D: usize, C>(
+    grid: &ExpandableGridN<T, D, C>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    S: Serializer,
+    C: Coordinate + Serialize,
+{
+    serialize_with_version(grid, 0, serializer)
+}
+
+/// Same as `serialize`, but with `data_version` as the data version of the grid.
+pub fn serialize_with_version<T, S, const D: usize, C>(
+    grid: &ExpandableGridN<T, D, C>,
+    data_version: u32,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    S: Serializer,
+    C: Coordinate + Serialize,
+{
+    RowsRef {
+        version: VERSION,
+        data_version,
+        size: &grid.size,
+        origin: &grid.origin,
+        layout: grid.layout,
+        rows: Rows(grid),
+    }
+    .serialize(serializer)
+}
+
+/// Deserializes a grid, ignoring its data version.
+pub fn deserialize<'de, T, De, const D: usize, C>(
+    deserializer: De,
+) -> Result<ExpandableGridN<T, D, C>, De::Error>
+where
+    T: Deserialize<'de>,
+    De: Deserializer<'de>,
+    C: Coordinate + Deserialize<'de>,
+{
+    let fields = serde_impls::deserialize_fields(deserializer, &FORMAT, |_| {
+        Ok(SeqSeed(SeqSeed(CellSeed::<T, NoMigration>::current())))
+    })?;
+    from_fields(fields)
+}
+
+/// Same as `deserialize`, but upgrades the cells with `migrator` if the grid was written with an
+/// older data version.
+pub fn deserialize_migrated<'de, T, De, const D: usize, C>(
+    deserializer: De,
+    migrator: &impl CellMigrator<T>,
+) -> Result<ExpandableGridN<T, D, C>, De::Error>
+where
+    T: Deserialize<'de>,
+    De: Deserializer<'de>,
+    C: Coordinate + Deserialize<'de>,
+{
+    let fields = serde_impls::deserialize_fields(deserializer, &FORMAT, |data_version| {
+        CellSeed::new(migrator, data_version).map(|seed| SeqSeed(SeqSeed(seed)))
+    })?;
+    from_fields(fields)
+}
+
+fn from_fields<T, E: Error, const D: usize, C>(
+    grid: Fields<Vec<Vec<T>>, D, C>,
+) -> Result<ExpandableGridN<T, D, C>, E> {
+    let area = serde_impls::checked_area(grid.size)?;
+
+    let rows = row_count(grid.size);
+    if grid.cells.len() != rows {
+        return Err(E::custom(format_args!(
+            "grid of size {:?} should have {rows} rows, but has {}",
+            grid.size.as_slice(),
+            grid.cells.len(),
+        )));
+    }
+    if let Some(row) = grid.cells.iter().find(|row| row.len() != grid.size[0]) {
+        return Err(E::custom(format_args!(
+            "rows of a grid of size {:?} should have {} cells, but one has {}",
+            grid.size.as_slice(),
+            grid.size[0],
+            row.len(),
+        )));
+    }
+
+    let data = match grid.layout {
+        Layout::RowMajor => grid.cells.into_iter().flatten().collect(),
+        Layout::ColumnMajor => {
+            let mut data = std::iter::repeat_with(|| None)
+                .take(area)
+                .collect::<Vec<_>>();
+            for (row, cells) in grid.cells.into_iter().enumerate() {
+                for (index, cell) in row_indices(grid.layout, grid.size, row).zip(cells) {
+                    data[index] = Some(cell);
+                }
+            }
+            data.into_iter()
+                .map(|cell| cell.expect("every cell should be part of exactly one row"))
+                .collect()
+        }
+    };
+
+    Ok(ExpandableGridN {
+        size: grid.size,
+        origin: grid.origin,
+        data,
+        layout: grid.layout,
+    })
+}
+
+/// Wraps a grid so that it is serialized in the readable form, with this data version.
+#[cfg(feature = "ron")]
+struct ReadableRef<'a, T, const D: usize, C>(&'a ExpandableGridN<T, D, C>, u32);
+
+#[cfg(feature = "ron")]
+impl<T: Serialize, const D: usize, C: Coordinate + Serialize> Serialize
+    for ReadableRef<'_, T, D, C>
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_with_version(self.0, self.1, serializer)
+    }
+}
+
+/// Wraps a grid so that it is deserialized from the readable form.
+#[cfg(any(feature = "ron", feature = "json"))]
+struct ReadableOwned<T, const D: usize, C>(ExpandableGridN<T, D, C>);
+
+#[cfg(any(feature = "ron", feature = "json"))]
+impl<'de, T: Deserialize<'de>, const D: usize, C: Coordinate + Deserialize<'de>> Deserialize<'de>
+    for ReadableOwned<T, D, C>
+{
+    fn deserialize<De: Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+        deserialize(deserializer).map(ReadableOwned)
+    }
+}
+
+#[cfg(feature = "ron")]
+impl<T: Serialize, const D: usize, C: Coordinate + Serialize> ExpandableGridN<T, D, C> {
+    /// Serializes this grid as pretty printed RON in the form described in the `readable`
+    /// module, with each row on its own line.
+    pub fn to_ron_string(&self) -> Result<String, ron::Error> {
+        self.to_ron_string_with_version(0)
+    }
+
+    /// Same as `to_ron_string`, but with `data_version` as the data version of the grid.
+    pub fn to_ron_string_with_version(&self, data_version: u32) -> Result<String, ron::Error> {
+        // Rows are nested two levels deep, so are written on a single line
+        let config = ron::ser::PrettyConfig::new().depth_limit(2);
+        ron::ser::to_string_pretty(&ReadableRef(self, data_version), config)
+    }
+}
+
+#[cfg(feature = "ron")]
+impl<T, const D: usize, C> ExpandableGridN<T, D, C>
+where
+    T: for<'de> Deserialize<'de>,
+    C: Coordinate + for<'de> Deserialize<'de>,
+{
+    /// Deserializes a grid from RON in the form written by `to_ron_string`, ignoring its data
+    /// version.
+    pub fn from_ron_str(ron: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str::<ReadableOwned<T, D, C>>(ron).map(|ReadableOwned(grid)| grid)
+    }
+
+    /// Same as `from_ron_str`, but upgrades the cells with `migrator` if the grid was written
+    /// with an older data version.
+    pub fn from_ron_str_migrated(
+        ron: &str,
+        migrator: &impl CellMigrator<T>,
+    ) -> Result<Self, ron::error::SpannedError> {
+        let mut deserializer = ron::Deserializer::from_str(ron)?;
+        let grid = deserialize_migrated(&mut deserializer, migrator)
+            .map_err(|error| deserializer.span_error(error))?;
+        deserializer
+            .end()
+            .map_err(|error| deserializer.span_error(error))?;
+
+        Ok(grid)
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T: Serialize, const D: usize, C: Coordinate + Serialize> ExpandableGridN<T, D, C> {
+    /// Serializes this grid as pretty printed JSON in the form described in the `readable`
+    /// module, with each row on its own line.
+    pub fn to_json_string(&self) -> Result<String, serde_json::Error> {
+        self.to_json_string_with_version(0)
+    }
+
+    /// Same as `to_json_string`, but with `data_version` as the data version of the grid.
+    pub fn to_json_string_with_version(
+        &self,
+        data_version: u32,
+    ) -> Result<String, serde_json::Error> {
+        // serde_json can only pretty print every level of nesting, so the rows are written
+        // compactly and the rest is formatted by hand
+        let mut json = String::from("{\n");
+        json += &format!("  \"version\": {VERSION},\n");
+        json += &format!("  \"data_version\": {data_version},\n");
+        json += &format!("  \"size\": {},\n", serde_json::to_string(&self.size)?);
+        json += &format!("  \"origin\": {},\n", serde_json::to_string(&self.origin)?);
+        json += &format!("  \"layout\": {},\n", serde_json::to_string(&self.layout)?);
+
+        json += "  \"rows\": [";
+        let rows = row_count(self.size);
+        for row in 0..rows {
+            if row > 0 {
+                json.push(',');
+            }
+            json += "\n    ";
+            json += &serde_json::to_string(&Row(self, row))?;
+        }
+        if rows > 0 {
+            json += "\n  ";
+        }
+        json += "]\n}";
+
+        Ok(json)
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T, const D: usize, C> ExpandableGridN<T, D, C>
+where
+    T: for<'de> Deserialize<'de>,
+    C: Coordinate + for<'de> Deserialize<'de>,
+{
+    /// Deserializes a grid from JSON in the form written by `to_json_string`, ignoring its data
+    /// version.
+    pub fn from_json_str(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str::<ReadableOwned<T, D, C>>(json).map(|ReadableOwned(grid)| grid)
+    }
+
+    /// Same as `from_json_str`, but upgrades the cells with `migrator` if the grid was written
+    /// with an older data version.
+    pub fn from_json_str_migrated(
+        json: &str,
+        migrator: &impl CellMigrator<T>,
+    ) -> Result<Self, serde_json::Error> {
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let grid = deserialize_migrated(&mut deserializer, migrator)?;
+        deserializer.end()?;
+
+        Ok(grid)
+    }
+}
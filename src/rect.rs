@@ -0,0 +1,232 @@
+//! The `GridRect` type, a box of cells within a grid, and finding where two grids overlap.
+//!
+//! Methods of `ExpandableGridN` which take a `GridRect` are equivalent to the methods taking a
+//! box origin and size, such as `expand_to_fit_rect` and `expand_to_fit_box`.
+
+use crate::{
+    coord::{self, Coordinate, GridVector},
+    util, ExpandableGridN,
+};
+use nalgebra::SVector;
+
+/// A `D` dimensional box of cells, with its lowest corner at `origin` and size `size`. Despite
+/// the name, this is a box in any number of dimensions, though it defaults to a 2d rectangle.
+///
+/// Like grids, the coordinates of a rect can be any `Coordinate`. Methods which return
+/// coordinates past the cells of the rect, such as `end` and `inflate`, panic if those
+/// coordinates don't fit in `C`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GridRect<const D: usize = 2, C: Coordinate = isize> {
+    pub origin: SVector<C, D>,
+    pub size: SVector<usize, D>,
+}
+
+impl<const D: usize, C: Coordinate> GridRect<D, C> {
+    pub fn new(origin: impl GridVector<C, D>, size: impl GridVector<usize, D>) -> Self {
+        Self {
+            origin: origin.to_vector(),
+            size: size.to_vector(),
+        }
+    }
+
+    /// Returns the coordinates just past the highest corner of this rect on every axis.
+    pub fn end(&self) -> SVector<C, D> {
+        coord::from_isize_vec(self.isize_end())
+    }
+
+    /// Returns `true` if this rect contains no cells.
+    pub fn is_empty(&self) -> bool {
+        self.size.iter().any(|&length| length == 0)
+    }
+
+    /// Returns `true` if the cell at `point` is within this rect.
+    pub fn contains(&self, point: impl GridVector<C, D>) -> bool {
+        util::relative_position(
+            self.isize_origin(),
+            self.size,
+            coord::to_isize_vec(point.to_vector()),
+        )
+        .is_some()
+    }
+
+    /// Returns `true` if every cell of `other` is within this rect. An empty rect is contained
+    /// by every rect.
+    pub fn contains_rect(&self, other: &Self) -> bool {
+        other.is_empty()
+            || (0..D).all(|axis| {
+                self.origin[axis] <= other.origin[axis]
+                    && other.isize_end()[axis] <= self.isize_end()[axis]
+            })
+    }
+
+    /// Returns the rect of cells within both this rect and `other`, or `None` if they do not
+    /// overlap.
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let (origin, size) = util::intersect_boxes(
+            self.isize_origin(),
+            self.size,
+            other.isize_origin(),
+            other.size,
+        )?;
+        Some(Self {
+            origin: coord::from_isize_vec(origin),
+            size,
+        })
+    }
+
+    /// Returns the smallest rect containing every cell of both this rect and `other`. Empty
+    /// rects are ignored.
+    pub fn union(&self, other: &Self) -> Self {
+        if other.is_empty() {
+            return *self;
+        } else if self.is_empty() {
+            return *other;
+        }
+
+        let origin = self.isize_origin().inf(&other.isize_origin());
+        let end = self.isize_end().sup(&other.isize_end());
+        Self {
+            origin: coord::from_isize_vec(origin),
+            size: (end - origin).map(|length| length as usize),
+        }
+    }
+
+    /// Returns this rect grown by `amount` cells on every side.
+    pub fn inflate(&self, amount: usize) -> Self {
+        Self {
+            origin: coord::from_isize_vec(self.isize_origin().add_scalar(-(amount as isize))),
+            size: self.size.add_scalar(2 * amount),
+        }
+    }
+
+    /// Iterates over the coordinates of every cell within this rect, varying `x` fastest.
+    pub fn iter(&self) -> impl Iterator<Item = SVector<C, D>> {
+        let origin = self.isize_origin();
+        util::iter_box(SVector::zeros(), self.size)
+            .map(move |offset| coord::from_isize_vec(origin + util::usize_vec_to_isize(offset)))
+    }
+
+    /// Converts this rect to the type grids calculate with.
+    pub(crate) fn to_isize(self) -> GridRect<D> {
+        GridRect::new(self.isize_origin(), self.size)
+    }
+
+    /// Converts a rect from the type grids calculate with.
+    pub(crate) fn from_isize(rect: GridRect<D>) -> Self {
+        Self::new(coord::from_isize_vec(rect.origin), rect.size)
+    }
+
+    fn isize_origin(&self) -> SVector<isize, D> {
+        coord::to_isize_vec(self.origin)
+    }
+
+    fn isize_end(&self) -> SVector<isize, D> {
+        self.isize_origin() + util::usize_vec_to_isize(self.size)
+    }
+}
+
+/// A read only view of the cells of a grid within a rect, created by `ExpandableGridN::view`.
+#[derive(Debug)]
+pub struct GridView<'a, T, const D: usize = 2, C: Coordinate = isize> {
+    grid: &'a ExpandableGridN<T, D, C>,
+    rect: GridRect<D, C>,
+}
+
+impl<'a, T, const D: usize, C: Coordinate> GridView<'a, T, D, C> {
+    /// Returns the rect of cells within this view, which is always within the bounds of the grid.
+    pub fn rect(&self) -> GridRect<D, C> {
+        self.rect
+    }
+
+    /// Returns a reference to the cell at `index`, or `None` if it is outside of this view.
+    pub fn get(&self, index: impl GridVector<C, D>) -> Option<&'a T> {
+        let index = index.to_vector();
+        (self.rect.contains(index)).then(|| &self.grid[index])
+    }
+
+    /// Iterates over the coordinates and values of every cell within this view, varying `x`
+    /// fastest.
+    pub fn iter(&self) -> impl Iterator<Item = (SVector<C, D>, &'a T)> {
+        let grid = self.grid;
+        self.rect
+            .iter()
+            .map(move |position| (position, &grid[position]))
+    }
+}
+
+impl<T, const D: usize, C: Coordinate> Clone for GridView<'_, T, D, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, const D: usize, C: Coordinate> Copy for GridView<'_, T, D, C> {}
+
+impl<T, I: GridVector<C, D>, const D: usize, C: Coordinate> std::ops::Index<I>
+    for GridView<'_, T, D, C>
+{
+    type Output = T;
+
+    fn index(&self, index: I) -> &Self::Output {
+        self.get(index).expect("index should be within the view")
+    }
+}
+
+impl<T, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Returns the rect of cells within the bounds of this grid.
+    pub fn bounds(&self) -> GridRect<D, C> {
+        GridRect::new(self.origin, self.size)
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_box`
+    pub fn expand_to_fit_rect(&mut self, rect: GridRect<D, C>, fill: &T)
+    where
+        T: Clone,
+    {
+        self.expand_to_fit_box(rect.origin, rect.size, fill);
+    }
+
+    /// See `ExpandableGridN::fill_box`
+    pub fn fill_rect(&mut self, rect: GridRect<D, C>, value: &T)
+    where
+        T: Clone,
+    {
+        self.fill_box(rect.origin, rect.size, value);
+    }
+
+    /// Returns a view of the cells of this grid within `rect`. Cells of `rect` outside the bounds
+    /// of this grid are not part of the view.
+    pub fn view(&self, rect: GridRect<D, C>) -> GridView<'_, T, D, C> {
+        GridView {
+            grid: self,
+            rect: (self.bounds().intersection(&rect))
+                .unwrap_or(GridRect::new(rect.origin, SVector::zeros())),
+        }
+    }
+
+    /// Returns the rect of cells within the bounds of both this grid and `other`, or `None` if
+    /// they do not overlap.
+    pub fn intersection_bounds<U>(
+        &self,
+        other: &ExpandableGridN<U, D, C>,
+    ) -> Option<GridRect<D, C>> {
+        self.bounds().intersection(&other.bounds())
+    }
+
+    /// Calls `f` with the coordinates of every cell within the bounds of both this grid and
+    /// `other`, along with that cell of each grid. The grids may have different layouts.
+    pub fn intersect_with<U>(
+        &self,
+        other: &ExpandableGridN<U, D, C>,
+        mut f: impl FnMut(SVector<C, D>, &T, &U),
+    ) {
+        let Some(bounds) = self.intersection_bounds(other) else {
+            return;
+        };
+
+        for position in bounds.iter() {
+            // Both grids contain `position`, so neither index is out of bounds
+            f(position, &self[position], &other[position]);
+        }
+    }
+}
@@ -0,0 +1,423 @@
+//! Tracking which cells of a grid are connected to each other.
+//!
+//! A `RegionGrid` wraps a grid along with the connected regions of its passable cells, where two
+//! passable cells are connected if they are orthogonal neighbors, or are connected through other
+//! passable cells. Regions are found within chunks of the grid first, then merged across the
+//! borders between chunks.
+//!
+//! The regions of each chunk are linked to the regions of neighboring chunks they touch, and
+//! each connected region keeps a list of the chunk regions within it. When a cell changes between
+//! passable and impassable, only its chunk is searched again and only the borders of that chunk
+//! are linked again. The connected regions which contained the chunk are then split or joined by
+//! following the links between chunk regions, without looking at any cells. Each change costs
+//! time proportional to the area of a chunk, plus the number of chunk regions within the
+//! connected regions it splits or joins, rather than to the area of the whole grid.
+//! `RegionGrid::same_region` is then a pair of lookups.
+
+use crate::{coord::GridVector, util, ExpandableGridN};
+use nalgebra::SVector;
+
+/// The label of cells which are not passable, and the region of chunk regions which have not
+/// been assigned to a connected region yet.
+const IMPASSABLE: u32 = u32::MAX;
+
+/// A region within a chunk, as the index of the chunk within the data of `RegionGrid::chunks`
+/// and the label of the region within it.
+type Node = (usize, u32);
+
+/// A grid which keeps track of the connected regions of its passable cells. See the `regions`
+/// module for details.
+#[derive(Clone, Debug)]
+pub struct RegionGrid<T, const D: usize = 2> {
+    grid: ExpandableGridN<T, D>,
+    is_passable: fn(&T) -> bool,
+    chunk_size: SVector<usize, D>,
+    /// The region of each cell within its chunk, stored in the same order as the grid's data.
+    labels: Box<[u32]>,
+    chunks: ExpandableGridN<Vec<ChunkRegion>, D>,
+    /// The chunk regions within each connected region.
+    regions: Vec<Vec<Node>>,
+}
+
+#[derive(Clone, Debug)]
+struct ChunkRegion {
+    /// The connected region this is part of.
+    region: u32,
+    /// The regions of neighboring chunks which this touches.
+    links: Vec<Node>,
+}
+
+impl<T, const D: usize> RegionGrid<T, D> {
+    /// Creates a region grid which finds the regions of the cells of `grid` for which
+    /// `is_passable` returns `true`, in chunks of size `chunk_size`.
+    ///
+    /// # Panics
+    /// Panics if `chunk_size` is 0 on any axis.
+    pub fn new(
+        grid: ExpandableGridN<T, D>,
+        chunk_size: impl GridVector<usize, D>,
+        is_passable: fn(&T) -> bool,
+    ) -> Self {
+        let chunk_size = chunk_size.to_vector();
+        assert!(
+            chunk_size.iter().all(|&length| length > 0),
+            "chunk size should not be 0 on any axis",
+        );
+
+        let mut regions = Self {
+            grid,
+            is_passable,
+            chunk_size,
+            labels: Box::new([]),
+            chunks: ExpandableGridN::new(),
+            regions: Vec::new(),
+        };
+        regions.rebuild();
+        regions
+    }
+
+    pub fn grid(&self) -> &ExpandableGridN<T, D> {
+        &self.grid
+    }
+
+    pub fn into_inner(self) -> ExpandableGridN<T, D> {
+        self.grid
+    }
+
+    pub fn chunk_size(&self) -> SVector<usize, D> {
+        self.chunk_size
+    }
+
+    /// Returns the number of connected regions.
+    pub fn region_count(&self) -> usize {
+        self.regions.len()
+    }
+
+    /// Returns the region of the cell at `index`, numbered from 0 up to `region_count`, or `None`
+    /// if it is out of bounds or not passable. Regions may be renumbered whenever a cell changes.
+    pub fn region(&self, index: impl GridVector<isize, D>) -> Option<u32> {
+        let position =
+            util::relative_position(self.grid.origin, self.grid.size, index.to_vector())?;
+        self.node(position)
+            .map(|(chunk, label)| self.chunks.data[chunk][label as usize].region)
+    }
+
+    /// Returns `true` if the cells at `a` and `b` are both passable and connected to each other.
+    pub fn same_region(&self, a: impl GridVector<isize, D>, b: impl GridVector<isize, D>) -> bool {
+        match (self.region(a), self.region(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Sets the cell at `index` to `value`, returning the previous value, or `None` if it is out
+    /// of bounds. Regions are only updated if the cell changes between passable and impassable.
+    pub fn set(&mut self, index: impl GridVector<isize, D>, value: T) -> Option<T> {
+        let index = index.to_vector();
+        let position = util::relative_position(self.grid.origin, self.grid.size, index)?;
+
+        let was_passable = (self.is_passable)(&self.grid[index]);
+        let is_passable = (self.is_passable)(&value);
+        let previous = std::mem::replace(&mut self.grid[index], value);
+
+        if was_passable != is_passable {
+            self.update_chunk(self.chunk_of(position));
+        }
+        Some(previous)
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_point`. Finds every region again if the grid expands.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<isize, D>, fill: &T)
+    where
+        T: Clone,
+    {
+        self.expand_to_fit_box(point, SVector::<usize, D>::repeat(1), fill);
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_box`. Finds every region again if the grid expands.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<isize, D>,
+        box_size: impl GridVector<usize, D>,
+        fill: &T,
+    ) where
+        T: Clone,
+    {
+        let old_bounds = self.grid.bounds();
+        self.grid.expand_to_fit_box(box_origin, box_size, fill);
+
+        if self.grid.bounds() != old_bounds {
+            self.rebuild();
+        }
+    }
+
+    /// See `ExpandableGridN::change_size`. Finds every region again.
+    pub fn change_size(
+        &mut self,
+        new_size: impl GridVector<usize, D>,
+        offset: impl GridVector<isize, D>,
+        fill: &T,
+    ) where
+        T: Clone,
+    {
+        self.grid.change_size(new_size, offset, fill);
+        self.rebuild();
+    }
+
+    /// Finds the regions of every chunk, then links and merges them.
+    fn rebuild(&mut self) {
+        let chunk_counts = self
+            .grid
+            .size
+            .zip_map(&self.chunk_size, |length, chunk_length| {
+                length.div_ceil(chunk_length)
+            });
+
+        self.labels = vec![IMPASSABLE; self.grid.data.len()].into_boxed_slice();
+        self.chunks = ExpandableGridN::with_size(chunk_counts, SVector::zeros(), &Vec::new());
+        self.regions.clear();
+
+        for chunk in util::iter_box(SVector::zeros(), chunk_counts) {
+            self.label_chunk(chunk);
+        }
+        for chunk in util::iter_box(SVector::zeros(), chunk_counts) {
+            for axis in 0..D {
+                self.link_face(chunk, axis, true);
+            }
+        }
+
+        let nodes = (0..self.chunks.data.len())
+            .flat_map(|chunk| {
+                (0..self.chunks.data[chunk].len() as u32).map(move |label| (chunk, label))
+            })
+            .collect::<Vec<_>>();
+        self.assign_regions(nodes);
+    }
+
+    /// Finds the regions of `chunk` again after one of its cells changed, then splits or joins
+    /// the connected regions it was part of.
+    fn update_chunk(&mut self, chunk: SVector<usize, D>) {
+        let index = self.chunk_index(chunk);
+        let old = std::mem::take(&mut self.chunks.data[index]);
+
+        // Unlink the old regions of the chunk from its neighbors
+        let mut affected = Vec::new();
+        for chunk_region in &old {
+            affected.push(chunk_region.region);
+            for &(neighbor, label) in &chunk_region.links {
+                self.chunks.data[neighbor][label as usize]
+                    .links
+                    .retain(|&(linked, _)| linked != index);
+            }
+        }
+        affected.sort_unstable();
+        affected.dedup();
+
+        self.label_chunk(chunk);
+        for axis in 0..D {
+            self.link_face(chunk, axis, false);
+            self.link_face(chunk, axis, true);
+        }
+
+        // Every other chunk region of the affected connected regions must be assigned again
+        let mut nodes = (0..self.chunks.data[index].len() as u32)
+            .map(|label| (index, label))
+            .collect::<Vec<_>>();
+        for &region in &affected {
+            for node in std::mem::take(&mut self.regions[region as usize]) {
+                if node.0 != index {
+                    self.chunks.data[node.0][node.1 as usize].region = IMPASSABLE;
+                    nodes.push(node);
+                }
+            }
+        }
+
+        let joined = self.assign_regions(nodes);
+        affected.extend(joined);
+        self.remove_regions(affected);
+    }
+
+    /// Finds the regions of the cells within `chunk`, ignoring the cells around it. The regions
+    /// are not linked to any others yet.
+    fn label_chunk(&mut self, chunk: SVector<usize, D>) {
+        let (start, end) = self.chunk_bounds(chunk);
+        let (size, layout) = (self.grid.size, self.grid.layout);
+
+        for position in util::iter_box(start, end) {
+            self.labels[layout.linear_index(position, size)] = IMPASSABLE;
+        }
+
+        let mut count = 0;
+        let mut stack = Vec::new();
+        for position in util::iter_box(start, end) {
+            let index = layout.linear_index(position, size);
+            if self.labels[index] != IMPASSABLE || !(self.is_passable)(&self.grid.data[index]) {
+                continue;
+            }
+
+            // Flood fill a new region from this cell
+            self.labels[index] = count;
+            stack.push(position);
+            while let Some(position) = stack.pop() {
+                for neighbor in orthogonal_neighbors(position, start, end) {
+                    let index = layout.linear_index(neighbor, size);
+                    if self.labels[index] == IMPASSABLE
+                        && (self.is_passable)(&self.grid.data[index])
+                    {
+                        self.labels[index] = count;
+                        stack.push(neighbor);
+                    }
+                }
+            }
+            count += 1;
+        }
+
+        let index = self.chunk_index(chunk);
+        self.chunks.data[index] = (0..count)
+            .map(|_| ChunkRegion {
+                region: IMPASSABLE,
+                links: Vec::new(),
+            })
+            .collect();
+    }
+
+    /// Links each region of `chunk` along its face on the high or low side of `axis` to the
+    /// regions of the neighboring chunk it touches.
+    fn link_face(&mut self, chunk: SVector<usize, D>, axis: usize, high: bool) {
+        let (start, end) = self.chunk_bounds(chunk);
+        if (high && end[axis] == self.grid.size[axis]) || (!high && start[axis] == 0) {
+            return;
+        }
+
+        let (mut face_start, mut face_end) = (start, end);
+        if high {
+            face_start[axis] = end[axis] - 1;
+        } else {
+            face_end[axis] = start[axis] + 1;
+        }
+
+        for position in util::iter_box(face_start, face_end) {
+            let mut beyond = position;
+            if high {
+                beyond[axis] += 1;
+            } else {
+                beyond[axis] -= 1;
+            }
+
+            if let (Some(a), Some(b)) = (self.node(position), self.node(beyond)) {
+                let links = &mut self.chunks.data[a.0][a.1 as usize].links;
+                if !links.contains(&b) {
+                    links.push(b);
+                    self.chunks.data[b.0][b.1 as usize].links.push(a);
+                }
+            }
+        }
+    }
+
+    /// Assigns a connected region to each of `nodes` which doesn't have one yet, by following
+    /// the links between chunk regions. Connected regions reached along the way are joined into
+    /// the new region, and their numbers are returned so that they can be removed.
+    fn assign_regions(&mut self, nodes: Vec<Node>) -> Vec<u32> {
+        let mut joined = Vec::new();
+        let mut stack = Vec::new();
+
+        for node in nodes {
+            if self.chunks.data[node.0][node.1 as usize].region != IMPASSABLE {
+                continue;
+            }
+
+            let region = self.regions.len() as u32;
+            let mut members = Vec::new();
+            self.chunks.data[node.0][node.1 as usize].region = region;
+            stack.push(node);
+
+            while let Some(node) = stack.pop() {
+                members.push(node);
+
+                for index in 0..self.chunks.data[node.0][node.1 as usize].links.len() {
+                    let linked = self.chunks.data[node.0][node.1 as usize].links[index];
+                    let linked_region = &mut self.chunks.data[linked.0][linked.1 as usize].region;
+
+                    if *linked_region == IMPASSABLE {
+                        *linked_region = region;
+                        stack.push(linked);
+                    } else if *linked_region != region {
+                        // A whole connected region which was not affected, so none of its
+                        // chunk regions need to be followed
+                        let other = *linked_region;
+                        for member in std::mem::take(&mut self.regions[other as usize]) {
+                            self.chunks.data[member.0][member.1 as usize].region = region;
+                            members.push(member);
+                        }
+                        joined.push(other);
+                    }
+                }
+            }
+
+            self.regions.push(members);
+        }
+
+        joined
+    }
+
+    /// Removes the connected regions numbered `removed`, which must be empty, keeping the rest
+    /// numbered from 0 by moving the last region into each gap.
+    fn remove_regions(&mut self, mut removed: Vec<u32>) {
+        removed.sort_unstable_by(|a, b| b.cmp(a));
+        removed.dedup();
+
+        for region in removed {
+            self.regions.swap_remove(region as usize);
+            if let Some(moved) = self.regions.get(region as usize) {
+                for &(chunk, label) in moved {
+                    self.chunks.data[chunk][label as usize].region = region;
+                }
+            }
+        }
+    }
+
+    /// Returns the chunk region of the cell at `position`, relative to the origin of the grid,
+    /// or `None` if it isn't passable.
+    fn node(&self, position: SVector<usize, D>) -> Option<Node> {
+        let label = self.labels[self.grid.layout.linear_index(position, self.grid.size)];
+
+        (label != IMPASSABLE).then(|| (self.chunk_index(self.chunk_of(position)), label))
+    }
+
+    /// Returns the index of `chunk` within the data of `chunks`.
+    fn chunk_index(&self, chunk: SVector<usize, D>) -> usize {
+        self.chunks.layout.linear_index(chunk, self.chunks.size)
+    }
+
+    fn chunk_of(&self, position: SVector<usize, D>) -> SVector<usize, D> {
+        position.component_div(&self.chunk_size)
+    }
+
+    /// Returns the positions of the lowest cell of `chunk` and just past its highest cell,
+    /// relative to the origin of the grid.
+    fn chunk_bounds(&self, chunk: SVector<usize, D>) -> (SVector<usize, D>, SVector<usize, D>) {
+        let start = chunk.component_mul(&self.chunk_size);
+        (start, (start + self.chunk_size).inf(&self.grid.size))
+    }
+}
+
+/// Returns the neighbors of `position` along each axis within the box from `start` to `end`.
+fn orthogonal_neighbors<const D: usize>(
+    position: SVector<usize, D>,
+    start: SVector<usize, D>,
+    end: SVector<usize, D>,
+) -> impl Iterator<Item = SVector<usize, D>> {
+    (0..D).flat_map(move |axis| {
+        let below = (position[axis] > start[axis]).then(|| {
+            let mut neighbor = position;
+            neighbor[axis] -= 1;
+            neighbor
+        });
+        let above = (position[axis] + 1 < end[axis]).then(|| {
+            let mut neighbor = position;
+            neighbor[axis] += 1;
+            neighbor
+        });
+        below.into_iter().chain(above)
+    })
+}
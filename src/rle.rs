@@ -0,0 +1,252 @@
+//! Run length encoding of grid contents, where each run of equal consecutive cells is stored as
+//! a `(length, value)` pair.
+//!
+//! Cells are encoded in the order they are stored within `data`, so the runs depend on the
+//! `layout` of the grid. The binary format can store grids this way with
+//! `ExpandableGridN::write_binary_rle`, and with the `serde` feature, grids can be serialized
+//! this way by using the `rle::serde` module with `#[serde(with = "...")]`.
+
+use crate::{
+    coord::{self, Coordinate, GridVector},
+    util, ExpandableGridN, Layout,
+};
+use nalgebra::SVector;
+
+/// Returns an iterator over the maximal runs of equal consecutive values within `cells`, as the
+/// length of the run and a reference to its value. Lengths are never 0.
+pub fn runs<T: PartialEq>(cells: &[T]) -> impl Iterator<Item = (usize, &T)> {
+    let mut remaining = cells;
+
+    std::iter::from_fn(move || {
+        let first = remaining.first()?;
+        let length = remaining
+            .iter()
+            .position(|cell| cell != first)
+            .unwrap_or(remaining.len());
+
+        remaining = &remaining[length..];
+        Some((length, first))
+    })
+}
+
+/// Encodes `cells` as a list of runs of equal consecutive values.
+pub fn encode<T: PartialEq + Clone>(cells: &[T]) -> Vec<(usize, T)> {
+    runs(cells)
+        .map(|(length, value)| (length, value.clone()))
+        .collect()
+}
+
+/// Decodes a list of runs created by `encode` back into the list of cells.
+pub fn decode<T: Clone>(runs: impl IntoIterator<Item = (usize, T)>) -> Vec<T> {
+    let mut cells = Vec::new();
+    for (length, value) in runs {
+        cells.extend(std::iter::repeat_n(value, length));
+    }
+    cells
+}
+
+impl<T: PartialEq, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Iterates over the maximal runs of equal cells along the `x` axis within each row, as the
+    /// coordinates of the first cell of the run, the length of the run, and its value. Unlike
+    /// `encode_runs`, runs never continue from one row to the next, and the runs are the same
+    /// whatever the layout of the grid. Rows are visited from the lowest `y` to the highest, as
+    /// with `IterationOrder::RowMajor`.
+    pub fn iter_runs(&self) -> impl Iterator<Item = (SVector<C, D>, usize, &T)> {
+        let width = self.size[0];
+        let mut rows_end = self.size;
+        rows_end[0] = width.min(1);
+
+        util::iter_box(SVector::zeros(), rows_end).flat_map(move |row| {
+            let cell = move |x| {
+                let mut position = row;
+                position[0] = x;
+                &self.data[self.layout.linear_index(position, self.size)]
+            };
+
+            let mut x = 0;
+            std::iter::from_fn(move || {
+                if x >= width {
+                    return None;
+                }
+
+                let (start, value) = (x, cell(x));
+                x += 1;
+                while x < width && cell(x) == value {
+                    x += 1;
+                }
+
+                let mut position = row;
+                position[0] = start;
+                Some((
+                    coord::from_isize_vec(
+                        coord::to_isize_vec(self.origin) + util::usize_vec_to_isize(position),
+                    ),
+                    x - start,
+                    value,
+                ))
+            })
+        })
+    }
+}
+
+impl<T: PartialEq + Clone, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Returns the contents of this grid as a list of runs of equal consecutive values, in the
+    /// order they are stored within `data`.
+    pub fn encode_runs(&self) -> Vec<(usize, T)> {
+        encode(&self.data)
+    }
+}
+
+impl<T: Clone, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Creates a grid from a list of runs created by `encode_runs`. Returns `None` if the runs
+    /// do not cover exactly the area of the grid, or if their cells cannot be allocated.
+    ///
+    /// Memory is only allocated for each run as it is read, so a size much larger than the runs
+    /// fails without allocating it.
+    pub fn from_runs(
+        size: SVector<usize, D>,
+        origin: impl GridVector<C, D>,
+        layout: Layout,
+        runs: impl IntoIterator<Item = (usize, T)>,
+    ) -> Option<Self> {
+        let area = util::checked_area(size)?;
+
+        let mut data = Vec::new();
+        for (length, value) in runs {
+            if length > area - data.len() {
+                return None;
+            }
+            data.try_reserve(length).ok()?;
+            data.extend(std::iter::repeat_n(value, length));
+        }
+
+        (data.len() == area).then(|| Self {
+            size,
+            origin: origin.to_vector(),
+            data: data.into_boxed_slice(),
+            layout,
+        })
+    }
+}
+
+/// Serializes grids with their contents run length encoded, for use with
+/// `#[serde(with = "expandable_grid::rle::serde")]`.
+///
+/// Like the default serialized form, this starts with the version of the form and the data
+/// version of the cells, and grids written with an older data version can be upgraded with
+/// `deserialize_migrated`.
+#[cfg(feature = "serde")]
+pub mod serde {
+    use crate::{
+        migration::CellMigrator,
+        serde_impls::{self, CellSeed, Fields, Format, NoMigration, RunSeed, SeqSeed},
+        ExpandableGridN, Layout,
+    };
+    use ::serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+    use nalgebra::SVector;
+
+    /// The version of this serialized form written by this version of the crate.
+    const VERSION: u32 = 1;
+
+    const FORMAT: Format = Format {
+        name: "ExpandableGrid",
+        fields: &[
+            "version",
+            "data_version",
+            "size",
+            "origin",
+            "layout",
+            "runs",
+        ],
+        version: VERSION,
+        data_version_since: 1,
+    };
+
+    #[derive(Serialize)]
+    #[serde(rename = "ExpandableGrid")]
+    struct RunsRef<'a, T, const D: usize> {
+        version: u32,
+        data_version: u32,
+        size: &'a SVector<usize, D>,
+        origin: &'a SVector<isize, D>,
+        layout: Layout,
+        runs: Vec<(usize, &'a T)>,
+    }
+
+    pub fn serialize<T, S, const D: usize>(
+        grid: &ExpandableGridN<T, D>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize + PartialEq,
+        S: Serializer,
+    {
+        serialize_with_version(grid, 0, serializer)
+    }
+
+    /// Same as `serialize`, but with `data_version` as the data version of the grid.
+    pub fn serialize_with_version<T, S, const D: usize>(
+        grid: &ExpandableGridN<T, D>,
+        data_version: u32,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize + PartialEq,
+        S: Serializer,
+    {
+        RunsRef {
+            version: VERSION,
+            data_version,
+            size: &grid.size,
+            origin: &grid.origin,
+            layout: grid.layout,
+            runs: super::runs(&grid.data).collect(),
+        }
+        .serialize(serializer)
+    }
+
+    /// Deserializes a grid, ignoring its data version.
+    pub fn deserialize<'de, T, De, const D: usize>(
+        deserializer: De,
+    ) -> Result<ExpandableGridN<T, D>, De::Error>
+    where
+        T: Deserialize<'de> + Clone,
+        De: Deserializer<'de>,
+    {
+        let fields = serde_impls::deserialize_fields(deserializer, &FORMAT, |_| {
+            Ok(SeqSeed(RunSeed(CellSeed::<T, NoMigration>::current())))
+        })?;
+        from_fields(fields)
+    }
+
+    /// Same as `deserialize`, but upgrades the cells with `migrator` if the grid was written with
+    /// an older data version.
+    pub fn deserialize_migrated<'de, T, De, const D: usize>(
+        deserializer: De,
+        migrator: &impl CellMigrator<T>,
+    ) -> Result<ExpandableGridN<T, D>, De::Error>
+    where
+        T: Deserialize<'de> + Clone,
+        De: Deserializer<'de>,
+    {
+        let fields = serde_impls::deserialize_fields(deserializer, &FORMAT, |data_version| {
+            CellSeed::new(migrator, data_version).map(|seed| SeqSeed(RunSeed(seed)))
+        })?;
+        from_fields(fields)
+    }
+
+    fn from_fields<T: Clone, E: Error, const D: usize>(
+        fields: Fields<Vec<(usize, T)>, D>,
+    ) -> Result<ExpandableGridN<T, D>, E> {
+        let size = fields.size;
+        ExpandableGridN::from_runs(size, fields.origin, fields.layout, fields.cells).ok_or_else(
+            || {
+                E::custom(format_args!(
+                    "runs do not cover exactly the area of a grid of size {:?}, or do not fit in \
+                     memory",
+                    size.as_slice(),
+                ))
+            },
+        )
+    }
+}
@@ -0,0 +1,199 @@
+//! Choosing random cells of a grid, such as places to spawn entities.
+//!
+//! The `choose` methods each make a single pass over the data of the grid, without allocating.
+//! Which cell is chosen for a given random number generator depends on the grid's `Layout`.
+//!
+//! `ExpandableGridN::scatter_poisson` places points which are spread out evenly but randomly
+//! (blue noise), such as trees or rocks, using Bridson's algorithm. It keeps track of the points
+//! placed so far in a coarser grid of buckets, each small enough to hold at most one point.
+
+use crate::{
+    coord::{self, Coordinate},
+    rect::GridRect,
+    util, ExpandableGridN,
+};
+use nalgebra::SVector;
+use rand::Rng;
+
+/// The number of candidates tried around each point before it stops spreading.
+const POISSON_CANDIDATES: usize = 30;
+
+impl<T, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Returns the coordinates and value of a uniformly random cell, or `None` if the grid is
+    /// empty.
+    pub fn choose(&self, rng: &mut impl Rng) -> Option<(SVector<C, D>, &T)> {
+        if self.data.is_empty() {
+            return None;
+        }
+
+        let index = rng.gen_range(0..self.data.len());
+        let position = coord::from_isize_vec(self.sampled_position(index));
+        Some((position, &self.data[index]))
+    }
+
+    /// Returns the coordinates and value of a uniformly random cell for which `predicate`
+    /// returns `true`, or `None` if there is none.
+    pub fn choose_where(
+        &self,
+        rng: &mut impl Rng,
+        mut predicate: impl FnMut(&T) -> bool,
+    ) -> Option<(SVector<C, D>, &T)> {
+        // Reservoir sampling, where the nth match replaces the chosen cell with probability 1/n
+        let mut matches = 0;
+        let mut chosen = None;
+        for (index, cell) in self.data.iter().enumerate() {
+            if predicate(cell) {
+                matches += 1;
+                if rng.gen_range(0..matches) == 0 {
+                    chosen = Some(index);
+                }
+            }
+        }
+
+        chosen.map(|index| {
+            let position = coord::from_isize_vec(self.sampled_position(index));
+            (position, &self.data[index])
+        })
+    }
+
+    /// Returns the coordinates and value of a random cell, where the probability of each cell
+    /// being chosen is proportional to `weight` of that cell, or `None` if no cell has a
+    /// positive weight. Negative, infinite and NaN weights are treated as 0.
+    pub fn choose_weighted(
+        &self,
+        rng: &mut impl Rng,
+        mut weight: impl FnMut(&T) -> f64,
+    ) -> Option<(SVector<C, D>, &T)> {
+        // Like `choose_where`, the nth cell replaces the chosen cell with probability equal to
+        // its share of the total weight so far. Weights are scaled down by powers of two
+        // whenever the total would overflow, which keeps their relative shares
+        let mut total = 0.0;
+        let mut scale = 1.0;
+        let mut chosen = None;
+        for (index, cell) in self.data.iter().enumerate() {
+            let weight = weight(cell);
+            if !weight.is_finite() {
+                continue;
+            }
+            let mut weight = weight * scale;
+            if weight > 0.0 {
+                if !(total + weight).is_finite() {
+                    total *= 0.5;
+                    weight *= 0.5;
+                    scale *= 0.5;
+                }
+                total += weight;
+                if rng.gen_range(0.0..total) < weight {
+                    chosen = Some(index);
+                }
+            }
+        }
+
+        chosen.map(|index| {
+            let position = coord::from_isize_vec(self.sampled_position(index));
+            (position, &self.data[index])
+        })
+    }
+
+    /// Returns the coordinates of randomly placed cells which are at least `min_distance` cells
+    /// apart, by straight line distance, and for which `acceptable` returns `true`. Points are
+    /// added until no more acceptable cells can be placed near the existing points, so each
+    /// connected area of acceptable cells is filled evenly.
+    ///
+    /// Points spread outwards from the first acceptable cell of each area in storage order, so
+    /// which points are chosen for a given random number generator depends on the grid's
+    /// `Layout`.
+    pub fn scatter_poisson(
+        &self,
+        rng: &mut impl Rng,
+        min_distance: f64,
+        mut acceptable: impl FnMut(SVector<C, D>, &T) -> bool,
+    ) -> Vec<SVector<C, D>> {
+        // Distinct cells are always at least 1 apart
+        let min_distance = min_distance.max(1.0);
+
+        // Any two cells of a bucket are less than `min_distance` apart
+        let bucket_length = ((min_distance / (D as f64).sqrt()).floor() as usize).max(1);
+        let reach = (min_distance / bucket_length as f64).ceil() as usize;
+        let bucket_counts = self.size.map(|length| length.div_ceil(bucket_length));
+        let mut buckets =
+            ExpandableGridN::<Option<usize>, D>::with_size(bucket_counts, SVector::zeros(), &None);
+
+        let origin = coord::to_isize_vec(self.origin);
+        let bucket_of = |position: SVector<isize, D>| {
+            (position - origin).map(|length| length / bucket_length as isize)
+        };
+        let mut points: Vec<SVector<isize, D>> = Vec::new();
+        let mut can_place =
+            |position: SVector<isize, D>,
+             points: &[SVector<isize, D>],
+             buckets: &ExpandableGridN<Option<usize>, D>| {
+                // Candidates may be outside the range of `C`, so are checked before converting
+                let Some(cell) = self.get_isize(position) else {
+                    return false;
+                };
+                let nearby = GridRect::new(bucket_of(position), SVector::repeat(1)).inflate(reach);
+
+                acceptable(coord::from_isize_vec(position), cell)
+                    && (buckets.view(nearby).iter()).all(|(_, point)| {
+                        point.is_none_or(|point| {
+                            let offset = (points[point] - position).map(|length| length as f64);
+                            offset.norm_squared() >= min_distance * min_distance
+                        })
+                    })
+            };
+
+        let mut active = Vec::new();
+        for index in 0..self.data.len() {
+            let seed = self.sampled_position(index);
+            if !can_place(seed, &points, &buckets) {
+                continue;
+            }
+            buckets[bucket_of(seed)] = Some(points.len());
+            active.push(points.len());
+            points.push(seed);
+
+            // Spread points from the seed until no more fit around them
+            while !active.is_empty() {
+                let slot = rng.gen_range(0..active.len());
+                let center = points[active[slot]];
+
+                let candidate = (0..POISSON_CANDIDATES).find_map(|_| {
+                    let offset = random_annulus_offset::<D>(rng, min_distance);
+                    let candidate = center + offset.map(|length| length.round() as isize);
+                    can_place(candidate, &points, &buckets).then_some(candidate)
+                });
+
+                match candidate {
+                    Some(candidate) => {
+                        buckets[bucket_of(candidate)] = Some(points.len());
+                        active.push(points.len());
+                        points.push(candidate);
+                    }
+                    None => {
+                        active.swap_remove(slot);
+                    }
+                }
+            }
+        }
+
+        points.into_iter().map(coord::from_isize_vec).collect()
+    }
+
+    fn sampled_position(&self, index: usize) -> SVector<isize, D> {
+        coord::to_isize_vec(self.origin)
+            + util::usize_vec_to_isize(self.layout.position(index, self.size))
+    }
+}
+
+/// Returns a uniformly random offset between `min_distance` and twice `min_distance` long.
+fn random_annulus_offset<const D: usize>(rng: &mut impl Rng, min_distance: f64) -> SVector<f64, D> {
+    let outer = 2.0 * min_distance;
+    loop {
+        let offset = SVector::<f64, D>::from_fn(|_, _| rng.gen_range(-outer..outer));
+        let length = offset.norm();
+        if (min_distance..=outer).contains(&length) {
+            return offset;
+        }
+    }
+}
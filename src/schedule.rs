@@ -0,0 +1,122 @@
+//! Scheduling cells to be updated after a delay, such as crops growing or fire spreading.
+//!
+//! A `TickScheduler` is a priority queue of cell coordinates keyed by the tick they are due on.
+//! Entries are stored by their coordinates rather than their index within a grid's data, so they
+//! remain correct however the grid expands or changes size. Cells which are removed from a grid by
+//! shrinking it can be discarded with `retain_within`.
+
+use crate::{coord::GridVector, util, ExpandableGridN};
+use nalgebra::SVector;
+use std::collections::{BTreeMap, HashMap};
+
+/// A queue of cells to update on later ticks. See the `schedule` module for details.
+#[derive(Clone, Debug, Default)]
+pub struct TickScheduler<const D: usize = 2> {
+    tick: u64,
+    /// The order in which entries were scheduled, so that cells due on the same tick are
+    /// returned in the order they were scheduled.
+    next_sequence: u64,
+    queue: BTreeMap<(u64, u64), SVector<isize, D>>,
+    /// The key of each cell within `queue`.
+    scheduled: HashMap<SVector<isize, D>, (u64, u64)>,
+}
+
+impl<const D: usize> TickScheduler<D> {
+    pub fn new() -> Self {
+        Self {
+            tick: 0,
+            next_sequence: 0,
+            queue: BTreeMap::new(),
+            scheduled: HashMap::new(),
+        }
+    }
+
+    /// Returns the current tick, which starts at 0 and is advanced by `advance`.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Returns the number of scheduled cells.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Schedules the cell at `index` to be returned by `advance` in `delay` ticks, where a
+    /// `delay` of 0 or 1 means the next tick. If the cell is already scheduled, it is kept at
+    /// whichever tick is earlier. Returns the tick the cell is due on.
+    pub fn schedule(&mut self, index: impl GridVector<isize, D>, delay: u64) -> u64 {
+        let index = index.to_vector();
+        let due = self.tick + delay.max(1);
+
+        match self.scheduled.get(&index) {
+            Some(&(scheduled_due, _)) if scheduled_due <= due => scheduled_due,
+            _ => {
+                self.insert(index, due);
+                due
+            }
+        }
+    }
+
+    /// Schedules the cell at `index` to be returned by `advance` in `delay` ticks, replacing any
+    /// existing entry for it, even if it was earlier.
+    pub fn reschedule(&mut self, index: impl GridVector<isize, D>, delay: u64) {
+        self.insert(index.to_vector(), self.tick + delay.max(1));
+    }
+
+    /// Returns the tick the cell at `index` is due on, if it is scheduled.
+    pub fn due(&self, index: impl GridVector<isize, D>) -> Option<u64> {
+        Some(self.scheduled.get(&index.to_vector())?.0)
+    }
+
+    /// Removes the entry for the cell at `index`, returning the tick it was due on.
+    pub fn cancel(&mut self, index: impl GridVector<isize, D>) -> Option<u64> {
+        let key = self.scheduled.remove(&index.to_vector())?;
+        self.queue.remove(&key);
+
+        Some(key.0)
+    }
+
+    /// Advances to the next tick, removing and returning every cell due on or before it, in the
+    /// order they are due, and then the order they were scheduled.
+    pub fn advance(&mut self) -> Vec<SVector<isize, D>> {
+        self.tick += 1;
+
+        let mut due = Vec::new();
+        while let Some(entry) = self.queue.first_entry() {
+            if entry.key().0 > self.tick {
+                break;
+            }
+
+            let index = entry.remove();
+            self.scheduled.remove(&index);
+            due.push(index);
+        }
+
+        due
+    }
+
+    /// Removes every entry for a cell outside the bounds of `grid`.
+    pub fn retain_within<T>(&mut self, grid: &ExpandableGridN<T, D>) {
+        self.retain(|index| util::relative_position(grid.origin, grid.size, index).is_some());
+    }
+
+    /// Removes every entry for which `f` returns false.
+    pub fn retain(&mut self, mut f: impl FnMut(SVector<isize, D>) -> bool) {
+        self.queue.retain(|_, &mut index| f(index));
+        self.scheduled.retain(|_, key| self.queue.contains_key(key));
+    }
+
+    fn insert(&mut self, index: SVector<isize, D>, due: u64) {
+        let key = (due, self.next_sequence);
+        self.next_sequence += 1;
+
+        if let Some(old_key) = self.scheduled.insert(index, key) {
+            self.queue.remove(&old_key);
+        }
+        self.queue.insert(key, index);
+    }
+}
@@ -0,0 +1,222 @@
+//! Running `rhai` scripts against grids, so that maps can be transformed by designers and mods.
+//!
+//! `register_grid` adds a `Grid` type to an engine, and `run_script` runs a script with the
+//! grid it is given available as the variable `grid`, writing any changes back to that grid.
+//! Within scripts, grids have the following API, where coordinates are integers:
+//!
+//! - `grid.width`, `grid.height`, `grid.min_x` and `grid.min_y` give the bounds of the grid
+//! - `grid.get(x, y)` returns the cell at `(x, y)`, or `()` if it is out of bounds
+//! - `grid.set(x, y, value)` sets the cell at `(x, y)`, returning whether it was in bounds
+//! - `grid.fill_box(x, y, width, height, value)` sets every cell of a box within the bounds
+//! - `grid.expand_to_fit_point(x, y, fill)` and `grid.expand_to_fit_box(x, y, width, height,
+//!   fill)` expand the grid as in Rust
+//! - `grid.positions()` returns the coordinates of every cell as `[x, y]` arrays
+//! - `grid.map(|value| ...)` replaces every cell with the result of a function
+//!
+//! Cells are passed to scripts as their Rust type, so grids of `i64` (`rhai::INT`), `f64`,
+//! `bool`, and other types rhai understands are the most convenient to script. Setting a cell to
+//! a value of another type is a runtime error.
+
+use crate::ExpandableGrid;
+use nalgebra::{vector, Vector2};
+use rhai::{
+    Array, Dynamic, Engine, EvalAltResult, FnPtr, NativeCallContext, Position, Scope, AST, INT,
+};
+use std::{cell::RefCell, rc::Rc};
+
+/// A grid shared between Rust and a script, which scripts see as the type `Grid`.
+#[derive(Clone, Debug, Default)]
+pub struct ScriptGrid<T>(pub Rc<RefCell<ExpandableGrid<T>>>);
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Registers the `Grid` type for grids of `T` with `engine`.
+pub fn register_grid<T: Clone + 'static>(engine: &mut Engine) {
+    engine
+        .register_type_with_name::<ScriptGrid<T>>("Grid")
+        .register_get("width", |grid: &mut ScriptGrid<T>| {
+            grid.0.borrow().size.x as INT
+        })
+        .register_get("height", |grid: &mut ScriptGrid<T>| {
+            grid.0.borrow().size.y as INT
+        })
+        .register_get("min_x", |grid: &mut ScriptGrid<T>| {
+            grid.0.borrow().origin.x as INT
+        })
+        .register_get("min_y", |grid: &mut ScriptGrid<T>| {
+            grid.0.borrow().origin.y as INT
+        })
+        .register_fn("get", |grid: &mut ScriptGrid<T>, x: INT, y: INT| match grid
+            .0
+            .borrow()
+            .get(vector![x as isize, y as isize])
+        {
+            Some(cell) => Dynamic::from(cell.clone()),
+            None => Dynamic::UNIT,
+        })
+        .register_fn(
+            "set",
+            |grid: &mut ScriptGrid<T>, x: INT, y: INT, value: Dynamic| -> ScriptResult<bool> {
+                let value = cast::<T>(value)?;
+                match grid.0.borrow_mut().get_mut(vector![x as isize, y as isize]) {
+                    Some(cell) => {
+                        *cell = value;
+                        Ok(true)
+                    }
+                    None => Ok(false),
+                }
+            },
+        )
+        .register_fn(
+            "fill_box",
+            |grid: &mut ScriptGrid<T>,
+             x: INT,
+             y: INT,
+             width: INT,
+             height: INT,
+             value: Dynamic|
+             -> ScriptResult<()> {
+                let value = cast::<T>(value)?;
+                let (box_origin, box_size) = script_box(x, y, width, height)?;
+                grid.0.borrow_mut().fill_box(box_origin, box_size, &value);
+                Ok(())
+            },
+        )
+        .register_fn(
+            "expand_to_fit_point",
+            |grid: &mut ScriptGrid<T>, x: INT, y: INT, fill: Dynamic| -> ScriptResult<()> {
+                let fill = cast::<T>(fill)?;
+                grid.0
+                    .borrow_mut()
+                    .expand_to_fit_point(vector![x as isize, y as isize], &fill);
+                Ok(())
+            },
+        )
+        .register_fn(
+            "expand_to_fit_box",
+            |grid: &mut ScriptGrid<T>,
+             x: INT,
+             y: INT,
+             width: INT,
+             height: INT,
+             fill: Dynamic|
+             -> ScriptResult<()> {
+                let fill = cast::<T>(fill)?;
+                let (box_origin, box_size) = script_box(x, y, width, height)?;
+                grid.0
+                    .borrow_mut()
+                    .expand_to_fit_box(box_origin, box_size, &fill);
+                Ok(())
+            },
+        )
+        .register_fn("positions", |grid: &mut ScriptGrid<T>| {
+            let grid = grid.0.borrow();
+            let mut positions = Array::with_capacity(grid.data.len());
+            for y in 0..grid.size.y as isize {
+                for x in 0..grid.size.x as isize {
+                    let position: Array = vec![
+                        Dynamic::from((grid.origin.x + x) as INT),
+                        Dynamic::from((grid.origin.y + y) as INT),
+                    ];
+                    positions.push(position.into());
+                }
+            }
+            positions
+        })
+        .register_fn(
+            "map",
+            |context: NativeCallContext,
+             grid: &mut ScriptGrid<T>,
+             function: FnPtr|
+             -> ScriptResult<()> {
+                // Don't hold the borrow while calling the function, as it may access the grid
+                let length = grid.0.borrow().data.len();
+                for index in 0..length {
+                    let cell = grid.0.borrow().data[index].clone();
+                    let value = function.call_within_context::<Dynamic>(&context, (cell,))?;
+                    grid.0.borrow_mut().data[index] = cast::<T>(value)?;
+                }
+                Ok(())
+            },
+        );
+}
+
+/// Runs `script` with `grid` available as the variable `grid`, returning the result of the
+/// script. The types of `T` must have been registered with `register_grid`.
+pub fn run_script<T: Clone + 'static>(
+    engine: &Engine,
+    script: &str,
+    grid: &mut ExpandableGrid<T>,
+) -> ScriptResult<Dynamic> {
+    with_script_grid(grid, |scope| engine.eval_with_scope(scope, script))
+}
+
+/// Runs an already compiled script with `grid` available as the variable `grid`, returning the
+/// result of the script. The types of `T` must have been registered with `register_grid`.
+pub fn run_ast<T: Clone + 'static>(
+    engine: &Engine,
+    ast: &AST,
+    grid: &mut ExpandableGrid<T>,
+) -> ScriptResult<Dynamic> {
+    with_script_grid(grid, |scope| engine.eval_ast_with_scope(scope, ast))
+}
+
+fn with_script_grid<T: Clone + 'static>(
+    grid: &mut ExpandableGrid<T>,
+    run: impl FnOnce(&mut Scope) -> ScriptResult<Dynamic>,
+) -> ScriptResult<Dynamic> {
+    let shared = ScriptGrid(Rc::new(RefCell::new(std::mem::take(grid))));
+
+    let mut scope = Scope::new();
+    scope.push("grid", shared.clone());
+    let result = run(&mut scope);
+    drop(scope);
+
+    // The script may have kept the grid in its result, in which case it must be cloned back
+    *grid = match Rc::try_unwrap(shared.0) {
+        Ok(cell) => cell.into_inner(),
+        Err(shared) => shared.borrow().clone(),
+    };
+
+    result
+}
+
+/// Converts a box given by a script to an origin and size, treating negative lengths as empty.
+/// Boxes which extend past the range of `isize` are a runtime error rather than an overflow.
+fn script_box(
+    x: INT,
+    y: INT,
+    width: INT,
+    height: INT,
+) -> ScriptResult<(Vector2<isize>, Vector2<usize>)> {
+    let axis = |start: INT, length: INT| -> Option<(isize, usize)> {
+        let length = length.max(0);
+        isize::try_from(start.checked_add(length)?).ok()?;
+        Some((start.try_into().ok()?, length.try_into().ok()?))
+    };
+
+    match (axis(x, width), axis(y, height)) {
+        (Some((x, width)), Some((y, height))) => Ok((vector![x, y], vector![width, height])),
+        _ => Err(EvalAltResult::ErrorArithmetic(
+            format!("the box at ({x}, {y}) with size ({width}, {height}) is out of range"),
+            Position::NONE,
+        )
+        .into()),
+    }
+}
+
+fn cast<T: Clone + 'static>(value: Dynamic) -> ScriptResult<T> {
+    let type_name = value.type_name();
+
+    value.try_cast::<T>().ok_or_else(|| {
+        EvalAltResult::ErrorRuntime(
+            format!(
+                "expected a cell of type {}, found {type_name}",
+                std::any::type_name::<T>(),
+            )
+            .into(),
+            Position::NONE,
+        )
+        .into()
+    })
+}
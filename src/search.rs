@@ -0,0 +1,274 @@
+//! Finding cells of a grid by their values.
+//!
+//! Unless stated otherwise, these methods visit cells in the order they are stored in, so which
+//! of several equally good cells is returned depends on the grid's `Layout`.
+
+use crate::{
+    coord::{self, Coordinate, GridVector},
+    rect::GridRect,
+    util, ExpandableGridN,
+};
+use nalgebra::SVector;
+use std::cmp::Ordering;
+
+/// A way to measure the distance between two cells.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Metric {
+    /// The largest distance along any axis, so every cell of a box around a cell is within its
+    /// radius.
+    #[default]
+    Chebyshev,
+    /// The sum of the distances along each axis, the number of orthogonal steps between cells.
+    Manhattan,
+    /// The straight line distance between cells.
+    Euclidean,
+}
+
+impl Metric {
+    /// Returns a value which orders offsets by their distance under this metric. For
+    /// `Euclidean`, this is the squared distance.
+    pub fn key<const D: usize>(self, offset: SVector<isize, D>) -> usize {
+        let lengths = offset.map(|length| length.unsigned_abs());
+
+        // Keys saturate rather than overflow for offsets spanning most of the coordinate space
+        match self {
+            Metric::Chebyshev => lengths.max(),
+            Metric::Manhattan => lengths.fold(0, usize::saturating_add),
+            Metric::Euclidean => lengths
+                .map(|length| length.saturating_mul(length))
+                .fold(0, usize::saturating_add),
+        }
+    }
+
+    /// Returns the smallest key of any offset with a Chebyshev distance of `radius`.
+    fn ring_key(self, radius: usize) -> usize {
+        match self {
+            Metric::Chebyshev | Metric::Manhattan => radius,
+            Metric::Euclidean => radius.saturating_mul(radius),
+        }
+    }
+}
+
+impl<T, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Returns the coordinates and value of the first cell for which `predicate` returns `true`,
+    /// or `None` if there is none.
+    pub fn find(&self, mut predicate: impl FnMut(&T) -> bool) -> Option<(SVector<C, D>, &T)> {
+        self.find_all(|cell| predicate(cell)).next()
+    }
+
+    /// Iterates over the coordinates and values of every cell for which `predicate` returns
+    /// `true`.
+    pub fn find_all(
+        &self,
+        mut predicate: impl FnMut(&T) -> bool,
+    ) -> impl Iterator<Item = (SVector<C, D>, &T)> {
+        self.data
+            .iter()
+            .enumerate()
+            .filter(move |(_, cell)| predicate(cell))
+            .map(|(index, cell)| (coord::from_isize_vec(self.position_of(index)), cell))
+    }
+
+    /// Returns the number of cells for which `predicate` returns `true`.
+    pub fn count_where(&self, mut predicate: impl FnMut(&T) -> bool) -> usize {
+        self.data.iter().filter(|cell| predicate(cell)).count()
+    }
+
+    /// Returns the number of cells of the box with its lowest corner at `box_origin` and size
+    /// `box_size` for which `predicate` returns `true`. Cells of the box outside the bounds of
+    /// the grid are not counted.
+    pub fn count_where_box(
+        &self,
+        box_origin: impl GridVector<C, D>,
+        box_size: impl GridVector<usize, D>,
+        mut predicate: impl FnMut(&T) -> bool,
+    ) -> usize {
+        let origin = coord::to_isize_vec(self.origin);
+        let Some((box_origin, box_size)) = util::intersect_boxes(
+            origin,
+            self.size,
+            coord::to_isize_vec(box_origin.to_vector()),
+            box_size.to_vector(),
+        ) else {
+            return 0;
+        };
+        let start = (box_origin - origin).map(|component| component as usize);
+
+        util::box_runs(self.layout, self.size, start, box_size)
+            .map(|(index, length)| {
+                (self.data[index..index + length].iter())
+                    .filter(|cell| predicate(cell))
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Returns the smallest rect containing every cell for which `predicate` returns `true`, or
+    /// `None` if there is none.
+    pub fn bounding_box_of(&self, mut predicate: impl FnMut(&T) -> bool) -> Option<GridRect<D>> {
+        let mut cells = (self.data.iter().enumerate())
+            .filter(|(_, cell)| predicate(cell))
+            .map(|(index, _)| self.position_of(index));
+        let first = cells.next()?;
+        let (low, high) = cells.fold((first, first), |(low, high), position| {
+            (low.inf(&position), high.sup(&position))
+        });
+
+        Some(GridRect::new(
+            low,
+            (high - low).map(|length| length as usize + 1),
+        ))
+    }
+
+    /// Returns the coordinates and value of the cell with the smallest key, or `None` if the grid
+    /// is empty. If several cells share the smallest key, the first is returned.
+    pub fn min_by_key<K: Ord>(&self, mut key: impl FnMut(&T) -> K) -> Option<(SVector<C, D>, &T)> {
+        self.min_by(|a, b| key(a).cmp(&key(b)))
+    }
+
+    /// Returns the coordinates and value of the cell with the largest key, or `None` if the grid
+    /// is empty. If several cells share the largest key, the last is returned.
+    pub fn max_by_key<K: Ord>(&self, mut key: impl FnMut(&T) -> K) -> Option<(SVector<C, D>, &T)> {
+        self.max_by(|a, b| key(a).cmp(&key(b)))
+    }
+
+    /// Returns the coordinates and value of the smallest cell according to `compare`, or `None`
+    /// if the grid is empty. If several cells are equally small, the first is returned.
+    pub fn min_by(
+        &self,
+        mut compare: impl FnMut(&T, &T) -> Ordering,
+    ) -> Option<(SVector<C, D>, &T)> {
+        let (index, cell) =
+            (self.data.iter().enumerate()).min_by(|(_, a), (_, b)| compare(a, b))?;
+        Some((coord::from_isize_vec(self.position_of(index)), cell))
+    }
+
+    /// Returns the coordinates and value of the largest cell according to `compare`, or `None`
+    /// if the grid is empty. If several cells are equally large, the last is returned.
+    pub fn max_by(
+        &self,
+        mut compare: impl FnMut(&T, &T) -> Ordering,
+    ) -> Option<(SVector<C, D>, &T)> {
+        let (index, cell) =
+            (self.data.iter().enumerate()).max_by(|(_, a), (_, b)| compare(a, b))?;
+        Some((coord::from_isize_vec(self.position_of(index)), cell))
+    }
+
+    /// Returns the coordinates and value of the cell nearest to `from` for which `predicate`
+    /// returns `true`, searching no further than `max_radius` cells away, or `None` if there is
+    /// none. Distance is measured as the Chebyshev distance, the largest distance along any
+    /// axis, and the search stops at the first ring of cells containing a match. Cells more than
+    /// `isize::MAX` away from `from` on any axis are never found.
+    pub fn nearest_where(
+        &self,
+        from: impl GridVector<C, D>,
+        mut predicate: impl FnMut(&T) -> bool,
+        max_radius: usize,
+    ) -> Option<(SVector<C, D>, &T)> {
+        let from = coord::to_isize_vec(from.to_vector());
+
+        // Rings nearer than the grid are empty, so the search starts at its bounds
+        let (nearest, furthest) = self.ring_bounds(from)?;
+        (nearest..=max_radius.min(furthest))
+            .flat_map(|radius| self.iter_ring(from, radius))
+            .find(|(_, cell)| predicate(cell))
+            .map(|(position, cell)| (coord::from_isize_vec(position), cell))
+    }
+
+    /// Returns the coordinates and values of up to `k` cells nearest to `from` under `metric`
+    /// for which `predicate` returns `true`, sorted from nearest to furthest. Cells at the same
+    /// distance are in no particular order, as are cells whose distances are too large to
+    /// represent. Cells more than `isize::MAX` away from `from` on any axis are never found.
+    pub fn k_nearest_where(
+        &self,
+        from: impl GridVector<C, D>,
+        k: usize,
+        metric: Metric,
+        mut predicate: impl FnMut(&T) -> bool,
+    ) -> Vec<(SVector<C, D>, &T)> {
+        let from = coord::to_isize_vec(from.to_vector());
+        let Some((nearest, furthest)) = self.ring_bounds(from).filter(|_| k > 0) else {
+            return Vec::new();
+        };
+
+        // Matches with their keys, kept sorted by key
+        let mut found: Vec<(usize, SVector<isize, D>, &T)> = Vec::new();
+        for radius in nearest..=furthest {
+            // No later ring can contain a nearer cell than the furthest of `k` matches
+            if let Some(&(key, ..)) = found.get(k - 1) {
+                if metric.ring_key(radius) > key {
+                    break;
+                }
+            }
+
+            for (position, cell) in self.iter_ring(from, radius) {
+                if predicate(cell) {
+                    let key = metric.key(position - from);
+                    let index = found.partition_point(|&(other, ..)| other <= key);
+                    found.insert(index, (key, position, cell));
+                }
+            }
+            found.truncate(k);
+        }
+
+        found
+            .into_iter()
+            .map(|(_, position, cell)| (coord::from_isize_vec(position), cell))
+            .collect()
+    }
+
+    /// Returns the Chebyshev distances from `from` to the nearest and furthest cells of the grid,
+    /// which are the first and last rings around `from` that can contain cells, or `None` if the
+    /// grid is empty. Both are saturated to `isize::MAX`, the furthest ring `iter_ring` can visit.
+    fn ring_bounds(&self, from: SVector<isize, D>) -> Option<(usize, usize)> {
+        if self.data.is_empty() {
+            return None;
+        }
+
+        let origin = coord::to_isize_vec(self.origin);
+        let end = coord::to_isize_vec(self.corner()).add_scalar(-1);
+        (0..D)
+            .map(|axis| {
+                let (low, high) = (
+                    from[axis].abs_diff(origin[axis]),
+                    from[axis].abs_diff(end[axis]),
+                );
+                let outside = from[axis] < origin[axis] || from[axis] > end[axis];
+                (if outside { low.min(high) } else { 0 }, low.max(high))
+            })
+            .reduce(|(nearest, furthest), (low, high)| (nearest.max(low), furthest.max(high)))
+            .map(|(nearest, furthest)| {
+                let limit = isize::MAX as usize;
+                (nearest.min(limit), furthest.min(limit))
+            })
+    }
+
+    /// Iterates over the coordinates and values of the cells of the grid at a Chebyshev distance
+    /// of exactly `radius` from `from`. Only the part of the ring within the bounds of the grid is
+    /// visited.
+    fn iter_ring(
+        &self,
+        from: SVector<isize, D>,
+        radius: usize,
+    ) -> impl Iterator<Item = (SVector<isize, D>, &T)> {
+        // Offsets to the grid's bounds may not fit in an `isize` when `from` is far away. The
+        // ring never reaches past `isize::MAX`, so saturating only grows the box where the ring
+        // can't reach it
+        let low = coord::to_isize_vec(self.origin).zip_map(&from, |origin, from| {
+            origin.saturating_sub(from).max(-isize::MAX)
+        });
+        let high = (coord::to_isize_vec(self.corner()).add_scalar(-1))
+            .zip_map(&from, |end, from| end.saturating_sub(from));
+        let box_size = high.zip_map(&low, |high, low| high.abs_diff(low) + 1);
+        util::iter_ring_within(radius, low, box_size).filter_map(move |offset| {
+            let position = from + offset;
+            Some((position, self.get_isize(position)?))
+        })
+    }
+
+    /// Returns the coordinates of the cell stored at `index` within `data`.
+    fn position_of(&self, index: usize) -> SVector<isize, D> {
+        coord::to_isize_vec(self.origin)
+            + util::usize_vec_to_isize(self.layout.position(index, self.size))
+    }
+}
@@ -0,0 +1,118 @@
+//! The `GridSegment` type, a line segment through the cells of a grid.
+//!
+//! Segments have floating point endpoints, where the cell at `[x, y]` covers the square from
+//! `[x, y]` to `[x + 1, y + 1]`. `GridSegment::cells` is a supercover traversal: it visits every
+//! cell the segment touches, including both cells beside a corner the segment passes exactly
+//! through, so a segment can never slip diagonally between two blocking cells. This differs from
+//! a Bresenham line, which only visits one cell per step along its longest axis.
+
+use crate::{coord::GridVector, ExpandableGridN};
+use nalgebra::SVector;
+
+/// A line segment from `start` to `end`. See the `segment` module for details.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridSegment<const D: usize = 2> {
+    pub start: SVector<f32, D>,
+    pub end: SVector<f32, D>,
+}
+
+impl<const D: usize> GridSegment<D> {
+    pub fn new(start: impl GridVector<f32, D>, end: impl GridVector<f32, D>) -> Self {
+        Self {
+            start: start.to_vector(),
+            end: end.to_vector(),
+        }
+    }
+
+    /// Creates a segment from the center of the cell at `start` to the center of the cell at
+    /// `end`.
+    pub fn between_cells(start: impl GridVector<isize, D>, end: impl GridVector<isize, D>) -> Self {
+        let center = |cell: SVector<isize, D>| cell.map(|component| component as f32 + 0.5);
+        Self::new(center(start.to_vector()), center(end.to_vector()))
+    }
+
+    /// Iterates over the coordinates of every cell this segment touches, in order from `start`
+    /// to `end`. Where the segment passes exactly through a corner between cells, the cells
+    /// beside the corner are visited before the cell diagonally across it.
+    pub fn cells(&self) -> impl Iterator<Item = SVector<isize, D>> {
+        let cell_of = |point: SVector<f32, D>| point.map(|component| component.floor() as isize);
+        let (mut cell, end_cell) = (cell_of(self.start), cell_of(self.end));
+        let direction = self.end - self.start;
+
+        let step = direction.map(|component| {
+            if component > 0.0 {
+                1
+            } else if component < 0.0 {
+                -1
+            } else {
+                0
+            }
+        });
+        // The fraction of the segment between crossing cell boundaries on each axis
+        let t_delta = direction.map(|component| 1.0 / component.abs());
+        // The fraction of the segment before it first crosses a cell boundary on each axis
+        let mut t_max = SVector::<f32, D>::from_fn(|axis, _| {
+            let boundary = match step[axis] {
+                1 => cell[axis] as f32 + 1.0,
+                -1 => cell[axis] as f32,
+                _ => return f32::INFINITY,
+            };
+            (boundary - self.start[axis]).abs() * t_delta[axis]
+        });
+
+        let mut pending = Vec::new();
+        let mut done = false;
+        std::iter::from_fn(move || {
+            if let Some(next) = pending.pop() {
+                return Some(next);
+            }
+            if done {
+                return None;
+            }
+
+            let current = cell;
+            let t = t_max.min();
+            if current == end_cell || t > 1.0 {
+                done = true;
+                return Some(current);
+            }
+
+            // Every axis crossed at once, as there may be several if a corner is crossed
+            let crossed: Vec<usize> = (0..D).filter(|&axis| t_max[axis] == t).collect();
+            for &axis in &crossed {
+                cell[axis] += step[axis];
+                t_max[axis] += t_delta[axis];
+            }
+
+            // Queue the cells beside the corner before `cell`, with fewer axes crossed first.
+            // `pending` is popped from the end, so they are pushed in reverse.
+            let mut corners: Vec<usize> = (1..(1usize << crossed.len()) - 1).collect();
+            corners.sort_by_key(|mask| mask.count_ones());
+            pending.extend(corners.into_iter().rev().map(|mask| {
+                let mut corner = current;
+                for (bit, &axis) in crossed.iter().enumerate() {
+                    if mask & (1 << bit) != 0 {
+                        corner[axis] += step[axis];
+                    }
+                }
+                corner
+            }));
+
+            Some(current)
+        })
+    }
+
+    /// Returns the coordinates and value of the first cell of `grid` touched by this segment for
+    /// which `predicate` returns `true`, or `None` if there is none. Cells outside the bounds of
+    /// `grid` never block the segment.
+    pub fn first_blocked<'a, T>(
+        &self,
+        grid: &'a ExpandableGridN<T, D>,
+        mut predicate: impl FnMut(&T) -> bool,
+    ) -> Option<(SVector<isize, D>, &'a T)> {
+        self.cells().find_map(|position| {
+            let cell = grid.get(position)?;
+            predicate(cell).then_some((position, cell))
+        })
+    }
+}
@@ -0,0 +1,471 @@
+//! The serde implementations for `ExpandableGridN`, and the deserialization shared by every serde
+//! form of a grid.
+//!
+//! Every form starts with the version of the form itself, followed by the data version of the
+//! cells (see the `migration` module), so both are known before any cells are read, and cells
+//! written with an older data version can be deserialized as their old type and upgraded. Fields
+//! are always written in the same order, which formats that are not self describing rely on.
+
+use crate::{coord::Coordinate, migration::CellMigrator, util, ExpandableGridN, Layout};
+use nalgebra::SVector;
+use serde::{
+    de::{DeserializeSeed, Error, IgnoredAny, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::marker::PhantomData;
+
+/// The version of the serialized form written by this version of the crate. Version 1 had no
+/// data version, so its cells are treated as data version 0.
+const VERSION: u32 = 2;
+
+const FORMAT: Format = Format {
+    name: "ExpandableGrid",
+    fields: &[
+        "version",
+        "data_version",
+        "size",
+        "origin",
+        "layout",
+        "data",
+    ],
+    version: VERSION,
+    data_version_since: 2,
+};
+
+/// The serialized form of an `ExpandableGridN`.
+#[derive(Serialize)]
+#[serde(
+    rename = "ExpandableGrid",
+    bound = "T: Serialize, C: Coordinate + Serialize"
+)]
+struct GridRef<'a, T, const D: usize, C> {
+    version: u32,
+    data_version: u32,
+    size: &'a SVector<usize, D>,
+    origin: &'a SVector<C, D>,
+    layout: Layout,
+    data: &'a [T],
+}
+
+impl<T: Serialize, const D: usize, C: Coordinate + Serialize> Serialize
+    for ExpandableGridN<T, D, C>
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize_with_version(0, serializer)
+    }
+}
+
+impl<T: Serialize, const D: usize, C: Coordinate + Serialize> ExpandableGridN<T, D, C> {
+    /// Serializes this grid like its `Serialize` implementation, but with `data_version` as its
+    /// data version, so that it can be upgraded by `deserialize_migrated` once the cell type
+    /// changes.
+    pub fn serialize_with_version<S: Serializer>(
+        &self,
+        data_version: u32,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        GridRef {
+            version: VERSION,
+            data_version,
+            size: &self.size,
+            origin: &self.origin,
+            layout: self.layout,
+            data: &self.data,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>, const D: usize, C: Coordinate + Deserialize<'de>> Deserialize<'de>
+    for ExpandableGridN<T, D, C>
+{
+    /// Deserializes a grid, ignoring its data version. See `deserialize_migrated` to upgrade
+    /// grids written with older versions of the cell type.
+    fn deserialize<De: Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+        let fields = deserialize_fields(deserializer, &FORMAT, |_| {
+            Ok(SeqSeed(CellSeed::<T, NoMigration>::current()))
+        })?;
+        fields.into_grid()
+    }
+}
+
+impl<'de, T: Deserialize<'de>, const D: usize, C: Coordinate + Deserialize<'de>>
+    ExpandableGridN<T, D, C>
+{
+    /// Deserializes a grid like its `Deserialize` implementation, upgrading its cells with
+    /// `migrator` if it was written with an older data version.
+    pub fn deserialize_migrated<De: Deserializer<'de>>(
+        deserializer: De,
+        migrator: &impl CellMigrator<T>,
+    ) -> Result<Self, De::Error> {
+        let fields = deserialize_fields(deserializer, &FORMAT, |data_version| {
+            CellSeed::new(migrator, data_version).map(SeqSeed)
+        })?;
+        fields.into_grid()
+    }
+}
+
+/// Describes one of the serialized forms of a grid.
+pub(crate) struct Format {
+    /// The name of the struct the form is serialized as.
+    pub name: &'static str,
+    /// The names of the fields, in the order they are written. The last field holds the cells.
+    pub fields: &'static [&'static str; 6],
+    /// The latest version of the form.
+    pub version: u32,
+    /// The first version of the form with a data version.
+    pub data_version_since: u32,
+}
+
+/// The fields of a grid in any serialized form, which have not yet been checked for consistency.
+pub(crate) struct Fields<P, const D: usize, C = isize> {
+    pub size: SVector<usize, D>,
+    pub origin: SVector<C, D>,
+    pub layout: Layout,
+    /// The cells of the grid, in the form given by the format.
+    pub cells: P,
+}
+
+impl<T, const D: usize, C> Fields<Vec<T>, D, C> {
+    /// Creates a grid from these fields, where the cells are stored in the order given by the
+    /// layout.
+    fn into_grid<E: Error>(self) -> Result<ExpandableGridN<T, D, C>, E> {
+        let area = checked_area(self.size)?;
+
+        if self.cells.len() != area {
+            return Err(E::custom(format_args!(
+                "grid of size {:?} should have {area} cells, but has {}",
+                self.size.as_slice(),
+                self.cells.len(),
+            )));
+        }
+
+        Ok(ExpandableGridN {
+            size: self.size,
+            origin: self.origin,
+            data: self.cells.into_boxed_slice(),
+            layout: self.layout,
+        })
+    }
+}
+
+/// Returns the area of a grid of size `size`, or an error if it overflows.
+pub(crate) fn checked_area<E: Error, const D: usize>(size: SVector<usize, D>) -> Result<usize, E> {
+    util::checked_area(size).ok_or_else(|| E::custom("grid size overflows usize"))
+}
+
+/// Deserializes a grid in the form `format`, where the field holding the cells is deserialized
+/// with the seed returned by `cells` for the data version of the grid.
+pub(crate) fn deserialize_fields<'de, De, S, C, const D: usize>(
+    deserializer: De,
+    format: &'static Format,
+    cells: impl FnOnce(u32) -> Result<S, String>,
+) -> Result<Fields<S::Value, D, C>, De::Error>
+where
+    De: Deserializer<'de>,
+    S: DeserializeSeed<'de>,
+    C: Coordinate + Deserialize<'de>,
+{
+    deserializer.deserialize_struct(
+        format.name,
+        format.fields,
+        FieldsVisitor {
+            format,
+            cells,
+            _marker: PhantomData,
+        },
+    )
+}
+
+struct FieldsVisitor<F, C, const D: usize> {
+    format: &'static Format,
+    cells: F,
+    _marker: PhantomData<C>,
+}
+
+impl Format {
+    fn check_version<E: Error>(&self, version: u32) -> Result<(), E> {
+        if version == 0 || version > self.version {
+            return Err(E::custom(format_args!(
+                "unsupported grid version {version}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl<'de, F, S, C, const D: usize> Visitor<'de> for FieldsVisitor<F, C, D>
+where
+    F: FnOnce(u32) -> Result<S, String>,
+    S: DeserializeSeed<'de>,
+    C: Coordinate + Deserialize<'de>,
+{
+    type Value = Fields<S::Value, D, C>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "struct {}", self.format.name)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let next = |index: usize| A::Error::invalid_length(index, &"the fields of a grid");
+
+        let version: u32 = seq.next_element()?.ok_or_else(|| next(0))?;
+        self.format.check_version(version)?;
+        let data_version = if version >= self.format.data_version_since {
+            seq.next_element()?.ok_or_else(|| next(1))?
+        } else {
+            0
+        };
+
+        let size = seq.next_element()?.ok_or_else(|| next(2))?;
+        let origin = seq.next_element()?.ok_or_else(|| next(3))?;
+        let layout = seq.next_element()?.ok_or_else(|| next(4))?;
+
+        let seed = (self.cells)(data_version).map_err(A::Error::custom)?;
+        let cells = seq.next_element_seed(seed)?.ok_or_else(|| next(5))?;
+
+        Ok(Fields {
+            size,
+            origin,
+            layout,
+            cells,
+        })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let fields = self.format.fields;
+
+        let mut version = None;
+        let mut data_version = None;
+        let mut size = None;
+        let mut origin = None;
+        let mut layout = None;
+        let mut cells = None;
+        let mut make_seed = Some(self.cells);
+
+        while let Some(field) = map.next_key_seed(FieldSeed(fields))? {
+            match field {
+                Some(0) => {
+                    let value = map.next_value()?;
+                    self.format.check_version::<A::Error>(value)?;
+                    version = Some(value);
+                }
+                Some(1) => data_version = Some(map.next_value()?),
+                Some(2) => size = Some(map.next_value()?),
+                Some(3) => origin = Some(map.next_value()?),
+                Some(4) => layout = Some(map.next_value()?),
+                Some(_) => {
+                    let Some(make_seed) = make_seed.take() else {
+                        return Err(A::Error::duplicate_field(fields[5]));
+                    };
+
+                    // The cells can only be read once the data version is known
+                    let version = version.ok_or_else(|| {
+                        A::Error::custom(format_args!(
+                            "`version` should come before `{}`",
+                            fields[5]
+                        ))
+                    })?;
+                    let data_version = match data_version {
+                        Some(data_version) => data_version,
+                        None if version < self.format.data_version_since => 0,
+                        None => {
+                            return Err(A::Error::custom(format_args!(
+                                "`data_version` should come before `{}`",
+                                fields[5],
+                            )))
+                        }
+                    };
+
+                    let seed = make_seed(data_version).map_err(A::Error::custom)?;
+                    cells = Some(map.next_value_seed(seed)?);
+                }
+                None => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        version.ok_or_else(|| A::Error::missing_field(fields[0]))?;
+        Ok(Fields {
+            size: size.ok_or_else(|| A::Error::missing_field(fields[2]))?,
+            origin: origin.ok_or_else(|| A::Error::missing_field(fields[3]))?,
+            layout: layout.ok_or_else(|| A::Error::missing_field(fields[4]))?,
+            cells: cells.ok_or_else(|| A::Error::missing_field(fields[5]))?,
+        })
+    }
+}
+
+/// Deserializes the name of a field as its index within the fields of a format, or `None` if it
+/// is not one of them.
+struct FieldSeed(&'static [&'static str; 6]);
+
+impl<'de> DeserializeSeed<'de> for FieldSeed {
+    type Value = Option<usize>;
+
+    fn deserialize<De: Deserializer<'de>>(
+        self,
+        deserializer: De,
+    ) -> Result<Self::Value, De::Error> {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FieldSeed {
+    type Value = Option<usize>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a field name")
+    }
+
+    fn visit_u64<E: Error>(self, index: u64) -> Result<Self::Value, E> {
+        Ok(usize::try_from(index)
+            .ok()
+            .filter(|&index| index < self.0.len()))
+    }
+
+    fn visit_str<E: Error>(self, name: &str) -> Result<Self::Value, E> {
+        Ok(self.0.iter().position(|&field| field == name))
+    }
+
+    fn visit_bytes<E: Error>(self, name: &[u8]) -> Result<Self::Value, E> {
+        Ok(self.0.iter().position(|&field| field.as_bytes() == name))
+    }
+}
+
+/// Deserializes a single cell, upgrading it with a migrator if it was written with an older data
+/// version.
+pub(crate) struct CellSeed<'a, T, M> {
+    /// The migrator and the data version of the cell, if it is not the current version.
+    migration: Option<(&'a M, u32)>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, T, M: CellMigrator<T>> CellSeed<'a, T, M> {
+    /// Creates a seed for cells of data version `data_version`, or an error if it is newer than
+    /// the current version of `migrator`.
+    pub fn new(migrator: &'a M, data_version: u32) -> Result<Self, String> {
+        let current = migrator.current_version();
+        if data_version > current {
+            return Err(format!(
+                "data version {data_version} is newer than the current version {current}"
+            ));
+        }
+
+        Ok(Self {
+            migration: (data_version < current).then_some((migrator, data_version)),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Creates a seed which deserializes cells as they are.
+    pub fn current() -> Self {
+        Self {
+            migration: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, M> Clone for CellSeed<'_, T, M> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, M> Copy for CellSeed<'_, T, M> {}
+
+impl<'de, T: Deserialize<'de>, M: CellMigrator<T>> DeserializeSeed<'de> for CellSeed<'_, T, M> {
+    type Value = T;
+
+    fn deserialize<De: Deserializer<'de>>(self, deserializer: De) -> Result<T, De::Error> {
+        match self.migration {
+            Some((migrator, version)) => migrator.deserialize_cell(version, deserializer),
+            None => T::deserialize(deserializer),
+        }
+    }
+}
+
+/// The migrator used when deserializing cells as they are, which is never asked to migrate.
+pub(crate) struct NoMigration;
+
+impl<T> CellMigrator<T> for NoMigration {
+    fn current_version(&self) -> u32 {
+        0
+    }
+
+    fn deserialize_cell<'de, De: Deserializer<'de>>(
+        &self,
+        version: u32,
+        _deserializer: De,
+    ) -> Result<T, De::Error> {
+        Err(De::Error::custom(format_args!(
+            "no migration from data version {version}"
+        )))
+    }
+}
+
+/// Deserializes a sequence of values with copies of a seed.
+#[derive(Clone, Copy)]
+pub(crate) struct SeqSeed<S>(pub S);
+
+impl<'de, S: DeserializeSeed<'de> + Copy> DeserializeSeed<'de> for SeqSeed<S> {
+    type Value = Vec<S::Value>;
+
+    fn deserialize<De: Deserializer<'de>>(
+        self,
+        deserializer: De,
+    ) -> Result<Self::Value, De::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de> + Copy> Visitor<'de> for SeqSeed<S> {
+    type Value = Vec<S::Value>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        // Avoid trusting the length given by the data with a large allocation
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(value) = seq.next_element_seed(self.0)? {
+            values.push(value);
+        }
+        Ok(values)
+    }
+}
+
+/// Deserializes a run of cells as its length followed by the cell.
+#[derive(Clone, Copy)]
+pub(crate) struct RunSeed<S>(pub S);
+
+impl<'de, S: DeserializeSeed<'de>> DeserializeSeed<'de> for RunSeed<S> {
+    type Value = (usize, S::Value);
+
+    fn deserialize<De: Deserializer<'de>>(
+        self,
+        deserializer: De,
+    ) -> Result<Self::Value, De::Error> {
+        deserializer.deserialize_tuple(2, self)
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de>> Visitor<'de> for RunSeed<S> {
+    type Value = (usize, S::Value);
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a run of a length and a cell")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let length = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(0, &"a run of a length and a cell"))?;
+        let cell = seq
+            .next_element_seed(self.0)?
+            .ok_or_else(|| A::Error::invalid_length(1, &"a run of a length and a cell"))?;
+        Ok((length, cell))
+    }
+}
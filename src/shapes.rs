@@ -0,0 +1,99 @@
+//! Iterating over the cells of a grid within shapes, such as the area of effect of an explosion.
+//!
+//! Only cells within the bounds of the grid are visited. The shapes may extend beyond the grid,
+//! and may be centered on cells outside of it.
+
+use crate::{
+    coord::{self, Coordinate, GridVector},
+    util, ExpandableGridN,
+};
+use nalgebra::SVector;
+
+impl<T, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Iterates over the coordinates and values of every cell whose straight line distance from
+    /// `center` is at most `radius`, in no particular order.
+    pub fn iter_within_radius(
+        &self,
+        center: impl GridVector<C, D>,
+        radius: usize,
+    ) -> impl Iterator<Item = (SVector<C, D>, &T)> {
+        self.positions_within_radius(center.to_vector(), radius)
+            .map(|(position, index)| (position, &self.data[index]))
+    }
+
+    /// Iterates over the coordinates and mutable references to every cell whose straight line
+    /// distance from `center` is at most `radius`, in the order they are stored in.
+    pub fn iter_within_radius_mut(
+        &mut self,
+        center: impl GridVector<C, D>,
+        radius: usize,
+    ) -> impl Iterator<Item = (SVector<C, D>, &mut T)> {
+        let mut cells: Vec<_> = self
+            .positions_within_radius(center.to_vector(), radius)
+            .collect();
+        cells.sort_unstable_by_key(|&(_, index)| index);
+
+        // Each index is visited once, in increasing order, so the cells can be taken from a
+        // single iterator
+        let mut data = self.data.iter_mut();
+        let mut next_index = 0;
+        cells.into_iter().map(move |(position, index)| {
+            let cell = data.nth(index - next_index).unwrap();
+            next_index = index + 1;
+            (position, cell)
+        })
+    }
+
+    /// Iterates over the cells from `start` onwards along repeated steps of `step`, such as a
+    /// knight's moves or a line of sight along an axis, with their coordinates. This stops at
+    /// the first position outside the bounds of the grid, or after `max_steps` steps, whichever
+    /// comes first. `start` itself is not visited.
+    pub fn iter_ray(
+        &self,
+        start: impl GridVector<C, D>,
+        step: impl GridVector<C, D>,
+        max_steps: usize,
+    ) -> impl Iterator<Item = (SVector<C, D>, &T)> {
+        let mut position = coord::to_isize_vec(start.to_vector());
+        let step = coord::to_isize_vec(step.to_vector());
+
+        (0..max_steps).map_while(move |_| {
+            position += step;
+            let cell = self.get_isize(position)?;
+            Some((coord::from_isize_vec(position), cell))
+        })
+    }
+
+    /// Iterates over the coordinates and data indices of every cell within `radius` of `center`.
+    fn positions_within_radius(
+        &self,
+        center: SVector<C, D>,
+        radius: usize,
+    ) -> impl Iterator<Item = (SVector<C, D>, usize)> + '_ {
+        let (grid_origin, center) = (
+            coord::to_isize_vec(self.origin),
+            coord::to_isize_vec(center),
+        );
+        let signed_radius = radius as isize;
+        let bounds = util::intersect_boxes(
+            grid_origin,
+            self.size,
+            center.add_scalar(-signed_radius),
+            SVector::<usize, D>::repeat(2 * radius + 1),
+        );
+        let (origin, size) = bounds.unwrap_or((grid_origin, SVector::zeros()));
+
+        util::iter_box(SVector::zeros(), size).filter_map(move |offset| {
+            let position = origin + util::usize_vec_to_isize(offset);
+            let distance = (position - center).map(|length| length.unsigned_abs());
+
+            (distance.dot(&distance) <= radius * radius).then(|| {
+                let relative = (position - grid_origin).map(|length| length as usize);
+                (
+                    coord::from_isize_vec(position),
+                    self.layout.linear_index(relative, self.size),
+                )
+            })
+        })
+    }
+}
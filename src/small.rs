@@ -0,0 +1,323 @@
+//! A grid which stores a few cells inline, without allocating, for many tiny grids.
+//!
+//! A `SmallGrid<T, N>` stores up to `N` cells inline within itself, and only allocates once it
+//! grows to more than `N` cells, when it spills into an `ExpandableGridN`. This suits grids such
+//! as the footprints of entities or the stamps of brushes, which are small but numerous, so
+//! that a heap allocation per grid would dominate. Once spilled, a grid stays on the heap even
+//! if it shrinks again.
+//!
+//! Inline cells are always stored in `Layout::RowMajor`.
+
+use crate::{coord::GridVector, util, ExpandableGridN, Layout};
+use nalgebra::SVector;
+use std::{fmt, mem::MaybeUninit};
+
+/// Up to `N` cells stored inline.
+struct InlineCells<T, const N: usize> {
+    length: usize,
+    cells: [MaybeUninit<T>; N],
+}
+
+impl<T, const N: usize> InlineCells<T, N> {
+    /// Creates `length` cells, where each cell is the result of `f` on its index.
+    ///
+    /// # Panics
+    /// Panics if `length` is more than `N`.
+    fn from_fn(length: usize, mut f: impl FnMut(usize) -> T) -> Self {
+        assert!(length <= N, "inline cells should fit within N");
+
+        let mut inline = Self {
+            length: 0,
+            cells: [const { MaybeUninit::uninit() }; N],
+        };
+        // If `f` panics, only the cells written so far are dropped
+        while inline.length < length {
+            inline.cells[inline.length].write(f(inline.length));
+            inline.length += 1;
+        }
+
+        inline
+    }
+
+    fn as_slice(&self) -> &[T] {
+        // Safety: the first `length` cells are initialized
+        unsafe { std::slice::from_raw_parts(self.cells.as_ptr().cast(), self.length) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        // Safety: the first `length` cells are initialized
+        unsafe { std::slice::from_raw_parts_mut(self.cells.as_mut_ptr().cast(), self.length) }
+    }
+}
+
+impl<T, const N: usize> Drop for InlineCells<T, N> {
+    fn drop(&mut self) {
+        // Safety: the first `length` cells are initialized, and never used again
+        unsafe { std::ptr::drop_in_place(self.as_mut_slice() as *mut [T]) };
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for InlineCells<T, N> {
+    fn clone(&self) -> Self {
+        let cells = self.as_slice();
+        Self::from_fn(cells.len(), |index| cells[index].clone())
+    }
+}
+
+#[derive(Clone)]
+enum Storage<T, const N: usize, const D: usize> {
+    Inline {
+        size: SVector<usize, D>,
+        origin: SVector<isize, D>,
+        cells: InlineCells<T, N>,
+    },
+    Heap(ExpandableGridN<T, D>),
+}
+
+/// A grid which stores up to `N` cells without allocating. See the `small` module for details.
+#[derive(Clone)]
+pub struct SmallGrid<T, const N: usize, const D: usize = 2> {
+    storage: Storage<T, N, D>,
+}
+
+impl<T, const N: usize, const D: usize> Default for SmallGrid<T, N, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize, const D: usize> SmallGrid<T, N, D> {
+    /// Creates a grid with no cells, which does not allocate.
+    pub fn new() -> Self {
+        Self {
+            storage: Storage::Inline {
+                size: SVector::zeros(),
+                origin: SVector::zeros(),
+                cells: InlineCells::from_fn(0, |_| unreachable!()),
+            },
+        }
+    }
+
+    /// Creates a grid of size `size` with its lowest corner at `origin`, filled with clones of
+    /// `fill`. This only allocates if the grid has more than `N` cells.
+    pub fn with_size(
+        size: impl GridVector<usize, D>,
+        origin: impl GridVector<isize, D>,
+        fill: &T,
+    ) -> Self
+    where
+        T: Clone,
+    {
+        let (size, origin) = (size.to_vector(), origin.to_vector());
+        let area = size.product();
+
+        let storage = if area <= N {
+            Storage::Inline {
+                size,
+                origin,
+                cells: InlineCells::from_fn(area, |_| fill.clone()),
+            }
+        } else {
+            Storage::Heap(ExpandableGridN::with_size(size, origin, fill))
+        };
+        Self { storage }
+    }
+
+    pub fn size(&self) -> SVector<usize, D> {
+        match &self.storage {
+            Storage::Inline { size, .. } => *size,
+            Storage::Heap(grid) => grid.size,
+        }
+    }
+
+    pub fn origin(&self) -> SVector<isize, D> {
+        match &self.storage {
+            Storage::Inline { origin, .. } => *origin,
+            Storage::Heap(grid) => grid.origin,
+        }
+    }
+
+    pub fn layout(&self) -> Layout {
+        match &self.storage {
+            Storage::Inline { .. } => Layout::RowMajor,
+            Storage::Heap(grid) => grid.layout,
+        }
+    }
+
+    /// Returns `true` if the cells are stored inline, rather than on the heap.
+    pub fn is_inline(&self) -> bool {
+        matches!(self.storage, Storage::Inline { .. })
+    }
+
+    /// Returns every cell, in the order given by `layout`.
+    pub fn data(&self) -> &[T] {
+        match &self.storage {
+            Storage::Inline { cells, .. } => cells.as_slice(),
+            Storage::Heap(grid) => &grid.data,
+        }
+    }
+
+    /// Returns every cell, in the order given by `layout`.
+    pub fn data_mut(&mut self) -> &mut [T] {
+        match &mut self.storage {
+            Storage::Inline { cells, .. } => cells.as_mut_slice(),
+            Storage::Heap(grid) => &mut grid.data,
+        }
+    }
+
+    pub fn get(&self, index: impl GridVector<isize, D>) -> Option<&T> {
+        let index = self.index_of(index)?;
+        Some(&self.data()[index])
+    }
+
+    pub fn get_mut(&mut self, index: impl GridVector<isize, D>) -> Option<&mut T> {
+        let index = self.index_of(index)?;
+        Some(&mut self.data_mut()[index])
+    }
+
+    /// Returns the index within `data` of the cell at `index`, or `None` if it is out of bounds.
+    pub fn index_of(&self, index: impl GridVector<isize, D>) -> Option<usize> {
+        let size = self.size();
+        let position = util::relative_position(self.origin(), size, index.to_vector())?;
+
+        Some(self.layout().linear_index(position, size))
+    }
+
+    /// Moves the cells into an `ExpandableGridN`, allocating if they are stored inline.
+    pub fn into_grid(self) -> ExpandableGridN<T, D> {
+        match self.storage {
+            Storage::Inline {
+                size,
+                origin,
+                mut cells,
+            } => {
+                let length = cells.length;
+                // The cells are moved out, so they must not be dropped
+                cells.length = 0;
+
+                ExpandableGridN {
+                    size,
+                    origin,
+                    // Safety: the first `length` cells were initialized, and are only read once
+                    data: (0..length)
+                        .map(|index| unsafe { cells.cells[index].assume_init_read() })
+                        .collect(),
+                    layout: Layout::RowMajor,
+                }
+            }
+            Storage::Heap(grid) => grid,
+        }
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_point`.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<isize, D>, fill: &T)
+    where
+        T: Clone,
+    {
+        self.expand_to_fit_box(point, SVector::<usize, D>::repeat(1), fill);
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_box`. The grid spills onto the heap if it grows to
+    /// more than `N` cells.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<isize, D>,
+        box_size: impl GridVector<usize, D>,
+        fill: &T,
+    ) where
+        T: Clone,
+    {
+        let (box_origin, box_size) = (box_origin.to_vector(), box_size.to_vector());
+
+        if let Storage::Heap(grid) = &mut self.storage {
+            grid.expand_to_fit_box(box_origin, box_size, fill);
+        } else if self.size() == SVector::<usize, D>::zeros() {
+            *self = Self::with_size(box_size, box_origin, fill);
+        } else if let Some((new_size, offset)) =
+            util::expansion_to_fit_box(self.size(), self.origin(), box_origin, box_size)
+        {
+            self.change_size(new_size, offset, fill);
+        }
+    }
+
+    /// See `ExpandableGridN::change_size`. The grid spills onto the heap if it grows to more than
+    /// `N` cells.
+    pub fn change_size(
+        &mut self,
+        new_size: impl GridVector<usize, D>,
+        offset: impl GridVector<isize, D>,
+        fill: &T,
+    ) where
+        T: Clone,
+    {
+        let (new_size, offset) = (new_size.to_vector(), offset.to_vector());
+
+        match &mut self.storage {
+            Storage::Heap(grid) => grid.change_size(new_size, offset, fill),
+            Storage::Inline { size, origin, .. } if new_size.product() > N => {
+                let (old_size, old_origin) = (*size, *origin);
+                let mut grid = ExpandableGridN::with_size(new_size, old_origin + offset, fill);
+                for (new_index, old_index) in
+                    util::kept_cells(Layout::RowMajor, old_size, new_size, offset)
+                {
+                    grid.data[new_index] = self.data()[old_index].clone();
+                }
+                self.storage = Storage::Heap(grid);
+            }
+            Storage::Inline {
+                size,
+                origin,
+                cells,
+            } => {
+                let mut new_cells = InlineCells::from_fn(new_size.product(), |_| fill.clone());
+                for (new_index, old_index) in
+                    util::kept_cells(Layout::RowMajor, *size, new_size, offset)
+                {
+                    new_cells.as_mut_slice()[new_index] = cells.as_slice()[old_index].clone();
+                }
+
+                *cells = new_cells;
+                *size = new_size;
+                *origin += offset;
+            }
+        }
+    }
+}
+
+impl<T: fmt::Debug, const N: usize, const D: usize> fmt::Debug for SmallGrid<T, N, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SmallGrid")
+            .field("size", &self.size())
+            .field("origin", &self.origin())
+            .field("inline", &self.is_inline())
+            .field("data", &self.data())
+            .finish()
+    }
+}
+
+impl<T, const N: usize, const D: usize> From<ExpandableGridN<T, D>> for SmallGrid<T, N, D> {
+    /// Wraps `grid` without moving its cells inline, even if there are `N` or fewer.
+    fn from(grid: ExpandableGridN<T, D>) -> Self {
+        Self {
+            storage: Storage::Heap(grid),
+        }
+    }
+}
+
+impl<T, I: GridVector<isize, D>, const N: usize, const D: usize> std::ops::Index<I>
+    for SmallGrid<T, N, D>
+{
+    type Output = T;
+
+    fn index(&self, index: I) -> &Self::Output {
+        self.get(index).unwrap()
+    }
+}
+
+impl<T, I: GridVector<isize, D>, const N: usize, const D: usize> std::ops::IndexMut<I>
+    for SmallGrid<T, N, D>
+{
+    fn index_mut(&mut self, index: I) -> &mut Self::Output {
+        self.get_mut(index).unwrap()
+    }
+}
@@ -1,49 +1,511 @@
-use crate::{util, ExpandableGrid};
-use nalgebra::{vector, Vector2};
+use crate::{
+    coord::{self, Coordinate, GridVector},
+    rect::GridRect,
+    util, ExpandableGridN,
+};
+use nalgebra::{vector, SVector, Vector2};
 
-pub trait Subchunk
+/// Derives `Subchunk` and its indexing for a struct wrapping a 2d array or a flat buffer of
+/// cells. See the `expandable_grid_derive` crate for details.
+#[cfg(feature = "derive")]
+pub use expandable_grid_derive::Subchunk;
+
+/// Paths used by the code generated by `#[derive(Subchunk)]`, so that it doesn't require a
+/// direct dependency on `nalgebra`.
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub mod __derive {
+    pub use nalgebra::Vector2;
+}
+
+/// A chunk of cells stored within each cell of an `ExpandableGridN`, allowing the grid to be
+/// indexed by the coordinates of individual subchunk cells. `D` is the number of dimensions of
+/// the grid, and defaults to 2.
+pub trait Subchunk<const D: usize = 2>
 where
-    Self: std::ops::Index<Vector2<usize>> + std::ops::IndexMut<Vector2<usize>>,
+    Self: std::ops::Index<SVector<usize, D>> + std::ops::IndexMut<SVector<usize, D>>,
     Self::Output: Sized,
 {
-    const SUBCHUNK_SIZE: Vector2<usize>;
+    const SUBCHUNK_SIZE: SVector<usize, D>;
 }
 
-impl<T: Subchunk> ExpandableGrid<T>
+impl<T: Subchunk<D>, const D: usize, C: Coordinate> ExpandableGridN<T, D, C>
 where
     T::Output: Sized,
 {
-    pub fn get_from_subchunk(&self, index: Vector2<isize>) -> Option<&T::Output> {
+    pub fn get_from_subchunk(&self, index: SVector<C, D>) -> Option<&T::Output> {
         let (chunk, subchunk) = Self::subchunk_index_of(index);
 
         Some(&self.get(chunk)?[subchunk])
     }
 
-    pub fn get_mut_from_subchunk(&mut self, index: Vector2<isize>) -> Option<&mut T::Output> {
+    pub fn get_mut_from_subchunk(&mut self, index: SVector<C, D>) -> Option<&mut T::Output> {
         let (chunk, subchunk) = Self::subchunk_index_of(index);
 
         Some(&mut self.get_mut(chunk)?[subchunk])
     }
 
-    pub fn subchunk_index_of(index: Vector2<isize>) -> (Vector2<isize>, Vector2<usize>) {
-        (
-            vector![
-                index.x.div_euclid(T::SUBCHUNK_SIZE.x as isize),
-                index.y.div_euclid(T::SUBCHUNK_SIZE.y as isize),
-            ],
-            vector![
-                index.x.rem_euclid(T::SUBCHUNK_SIZE.x as isize) as usize,
-                index.y.rem_euclid(T::SUBCHUNK_SIZE.y as isize) as usize,
-            ],
-        )
+    /// Sets the subchunk cell at `index` to `value`. If its chunk is out of bounds, the grid is
+    /// first expanded to fit it, with every new chunk a clone of the result of `chunk_factory`,
+    /// such as `T::default`.
+    pub fn set_from_subchunk(
+        &mut self,
+        index: SVector<C, D>,
+        value: T::Output,
+        chunk_factory: impl FnOnce() -> T,
+    ) where
+        T: Clone,
+    {
+        let (chunk, subchunk) = Self::subchunk_index_of(index);
+
+        self.expand_to_fit_cell(index, chunk_factory);
+        self[chunk][subchunk] = value;
+    }
+
+    /// Expands the grid such that the chunk containing the subchunk cell at `point` is within
+    /// bounds. See `ExpandableGridN::expand_to_fit_cell_box`.
+    pub fn expand_to_fit_cell(
+        &mut self,
+        point: impl GridVector<C, D>,
+        chunk_factory: impl FnOnce() -> T,
+    ) where
+        T: Clone,
+    {
+        self.expand_to_fit_cell_box(point, SVector::<usize, D>::repeat(1), chunk_factory);
+    }
+
+    /// Expands the grid such that every chunk containing a subchunk cell of the box with its
+    /// lowest corner at `box_origin` and size `box_size` is within bounds, as in
+    /// `ExpandableGridN::expand_to_fit_box`. Every new chunk is a clone of the result of
+    /// `chunk_factory`, such as `T::default`, which is only called if the grid needs to expand.
+    pub fn expand_to_fit_cell_box(
+        &mut self,
+        box_origin: impl GridVector<C, D>,
+        box_size: impl GridVector<usize, D>,
+        chunk_factory: impl FnOnce() -> T,
+    ) where
+        T: Clone,
+    {
+        let Some((first, last)) = util::chunk_range(
+            coord::to_isize_vec(box_origin.to_vector()),
+            box_size.to_vector(),
+            T::SUBCHUNK_SIZE,
+        ) else {
+            return;
+        };
+        let chunks = GridRect::new(
+            coord::from_isize_vec::<C, D>(first),
+            (last - first).map(|length| length as usize + 1),
+        );
+
+        if !self.bounds().contains_rect(&chunks) {
+            self.expand_to_fit_rect(chunks, &chunk_factory());
+        }
+    }
+
+    /// Returns a view of this grid indexed by the coordinates of individual subchunk cells.
+    pub fn cells(&self) -> SubchunkCells<'_, T, D, C> {
+        SubchunkCells { grid: self }
+    }
+
+    /// Returns a mutable view of this grid indexed by the coordinates of individual subchunk
+    /// cells.
+    pub fn cells_mut(&mut self) -> SubchunkCellsMut<'_, T, D, C> {
+        SubchunkCellsMut { grid: self }
+    }
+
+    /// Iterates over the coordinates and values of every subchunk cell, a chunk at a time in the
+    /// order the chunks are stored, and varying `x` fastest within each chunk.
+    pub fn iter_subchunk_cells(&self) -> impl Iterator<Item = (SVector<C, D>, &T::Output)> {
+        (self.data.iter().enumerate()).flat_map(move |(index, chunk)| {
+            let chunk_origin = self.subchunk_cell_origin(index);
+
+            util::iter_box(SVector::zeros(), T::SUBCHUNK_SIZE).map(move |subchunk| {
+                (
+                    coord::from_isize_vec(chunk_origin + util::usize_vec_to_isize(subchunk)),
+                    &chunk[subchunk],
+                )
+            })
+        })
+    }
+
+    /// Calls `f` with the coordinates and a mutable reference to every subchunk cell, in the
+    /// same order as `iter_subchunk_cells`.
+    pub fn for_each_subchunk_cell_mut(&mut self, mut f: impl FnMut(SVector<C, D>, &mut T::Output)) {
+        for index in 0..self.data.len() {
+            let chunk_origin = self.subchunk_cell_origin(index);
+            let chunk = &mut self.data[index];
+
+            for subchunk in util::iter_box(SVector::zeros(), T::SUBCHUNK_SIZE) {
+                f(
+                    coord::from_isize_vec(chunk_origin + util::usize_vec_to_isize(subchunk)),
+                    &mut chunk[subchunk],
+                );
+            }
+        }
     }
 
-    pub fn subchunk_index_size(&self) -> Vector2<usize> {
+    pub fn subchunk_index_of(index: SVector<C, D>) -> (SVector<C, D>, SVector<usize, D>) {
+        let (chunk, subchunk) =
+            util::split_chunk_index(coord::to_isize_vec(index), T::SUBCHUNK_SIZE);
+
+        (coord::from_isize_vec(chunk), subchunk)
+    }
+
+    pub fn subchunk_index_size(&self) -> SVector<usize, D> {
         self.size.component_mul(&T::SUBCHUNK_SIZE)
     }
 
-    pub fn subchunk_index_origin(&self) -> Vector2<isize> {
-        self.origin
-            .component_mul(&util::usize_vec_to_isize(T::SUBCHUNK_SIZE))
+    pub fn subchunk_index_origin(&self) -> SVector<C, D> {
+        coord::from_isize_vec(
+            coord::to_isize_vec(self.origin)
+                .component_mul(&util::usize_vec_to_isize(T::SUBCHUNK_SIZE)),
+        )
+    }
+
+    /// Returns the coordinates of the first subchunk cell of the chunk stored at `index` within
+    /// `data`.
+    fn subchunk_cell_origin(&self, index: usize) -> SVector<isize, D> {
+        (coord::to_isize_vec(self.origin)
+            + util::usize_vec_to_isize(self.layout.position(index, self.size)))
+        .component_mul(&util::usize_vec_to_isize(T::SUBCHUNK_SIZE))
+    }
+}
+
+/// A view of a grid of subchunks indexed by the coordinates of individual subchunk cells,
+/// created by `ExpandableGridN::cells`.
+#[derive(Debug)]
+pub struct SubchunkCells<'a, T, const D: usize = 2, C = isize> {
+    grid: &'a ExpandableGridN<T, D, C>,
+}
+
+impl<'a, T: Subchunk<D>, const D: usize, C: Coordinate> SubchunkCells<'a, T, D, C>
+where
+    T::Output: Sized,
+{
+    /// Returns a reference to the subchunk cell at `index`, or `None` if its chunk is out of
+    /// bounds.
+    pub fn get(&self, index: impl GridVector<C, D>) -> Option<&'a T::Output> {
+        self.grid.get_from_subchunk(index.to_vector())
+    }
+}
+
+impl<T, const D: usize, C> Clone for SubchunkCells<'_, T, D, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, const D: usize, C> Copy for SubchunkCells<'_, T, D, C> {}
+
+impl<T: Subchunk<D>, I: GridVector<C, D>, const D: usize, C: Coordinate> std::ops::Index<I>
+    for SubchunkCells<'_, T, D, C>
+where
+    T::Output: Sized,
+{
+    type Output = T::Output;
+
+    fn index(&self, index: I) -> &Self::Output {
+        self.get(index).expect("chunk should be within the grid")
+    }
+}
+
+/// A mutable view of a grid of subchunks indexed by the coordinates of individual subchunk
+/// cells, created by `ExpandableGridN::cells_mut`.
+#[derive(Debug)]
+pub struct SubchunkCellsMut<'a, T, const D: usize = 2, C = isize> {
+    grid: &'a mut ExpandableGridN<T, D, C>,
+}
+
+impl<T: Subchunk<D>, const D: usize, C: Coordinate> SubchunkCellsMut<'_, T, D, C>
+where
+    T::Output: Sized,
+{
+    /// Returns a reference to the subchunk cell at `index`, or `None` if its chunk is out of
+    /// bounds.
+    pub fn get(&self, index: impl GridVector<C, D>) -> Option<&T::Output> {
+        self.grid.get_from_subchunk(index.to_vector())
+    }
+
+    /// Returns a mutable reference to the subchunk cell at `index`, or `None` if its chunk is
+    /// out of bounds.
+    pub fn get_mut(&mut self, index: impl GridVector<C, D>) -> Option<&mut T::Output> {
+        self.grid.get_mut_from_subchunk(index.to_vector())
+    }
+}
+
+impl<T: Subchunk<D>, I: GridVector<C, D>, const D: usize, C: Coordinate> std::ops::Index<I>
+    for SubchunkCellsMut<'_, T, D, C>
+where
+    T::Output: Sized,
+{
+    type Output = T::Output;
+
+    fn index(&self, index: I) -> &Self::Output {
+        self.get(index).expect("chunk should be within the grid")
+    }
+}
+
+impl<T: Subchunk<D>, I: GridVector<C, D>, const D: usize, C: Coordinate> std::ops::IndexMut<I>
+    for SubchunkCellsMut<'_, T, D, C>
+where
+    T::Output: Sized,
+{
+    fn index_mut(&mut self, index: I) -> &mut Self::Output {
+        self.get_mut(index)
+            .expect("chunk should be within the grid")
+    }
+}
+
+/// A subchunk along with metadata about it, such as its generation stage or the entities within
+/// it. Since the metadata is stored with its chunk, it moves with it when the grid is expanded
+/// or resized, and is removed along with it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ChunkEntry<T, M> {
+    pub chunk: T,
+    pub metadata: M,
+}
+
+impl<T, M> ChunkEntry<T, M> {
+    pub fn new(chunk: T, metadata: M) -> Self {
+        Self { chunk, metadata }
+    }
+}
+
+impl<T: Subchunk<D>, M, const D: usize, C: Coordinate> ExpandableGridN<ChunkEntry<T, M>, D, C>
+where
+    T::Output: Sized,
+{
+    /// Returns the metadata of the chunk containing the subchunk cell at `index`, or `None` if
+    /// it is out of bounds.
+    pub fn metadata_of_cell(&self, index: impl GridVector<C, D>) -> Option<&M> {
+        let (chunk, _) = Self::subchunk_index_of(index.to_vector());
+        Some(&self.get(chunk)?.metadata)
+    }
+
+    /// Returns a mutable reference to the metadata of the chunk containing the subchunk cell at
+    /// `index`, or `None` if it is out of bounds.
+    pub fn metadata_of_cell_mut(&mut self, index: impl GridVector<C, D>) -> Option<&mut M> {
+        let (chunk, _) = Self::subchunk_index_of(index.to_vector());
+        Some(&mut self.get_mut(chunk)?.metadata)
+    }
+}
+
+impl<T: Subchunk<D>, M, const D: usize> std::ops::Index<SVector<usize, D>> for ChunkEntry<T, M>
+where
+    T::Output: Sized,
+{
+    type Output = T::Output;
+
+    fn index(&self, index: SVector<usize, D>) -> &Self::Output {
+        &self.chunk[index]
+    }
+}
+
+impl<T: Subchunk<D>, M, const D: usize> std::ops::IndexMut<SVector<usize, D>> for ChunkEntry<T, M>
+where
+    T::Output: Sized,
+{
+    fn index_mut(&mut self, index: SVector<usize, D>) -> &mut Self::Output {
+        &mut self.chunk[index]
+    }
+}
+
+impl<T: Subchunk<D>, M, const D: usize> Subchunk<D> for ChunkEntry<T, M>
+where
+    T::Output: Sized,
+{
+    const SUBCHUNK_SIZE: SVector<usize, D> = T::SUBCHUNK_SIZE;
+}
+
+/// A subchunk which is stored as a single value while every one of its cells is the same, such
+/// as a chunk of air or ocean, and as a boxed `C` otherwise. Mutably indexing a uniform chunk
+/// decompresses it, creating a `C` with every cell set to its value, while `compress` turns a
+/// chunk back into a single value if its cells are all equal.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CompressedSubchunk<C: Subchunk<D>, const D: usize = 2>
+where
+    C::Output: Sized,
+{
+    Uniform(C::Output),
+    Full(Box<C>),
+}
+
+impl<C: Subchunk<D>, const D: usize> CompressedSubchunk<C, D>
+where
+    C::Output: Sized,
+{
+    pub fn is_uniform(&self) -> bool {
+        matches!(self, CompressedSubchunk::Uniform(_))
+    }
+
+    /// Stores this chunk as a single value if every one of its cells is the same, returning
+    /// whether it is now uniform.
+    pub fn compress(&mut self) -> bool
+    where
+        C::Output: Clone + PartialEq,
+    {
+        if let CompressedSubchunk::Full(chunk) = self {
+            let mut cells =
+                util::iter_box(SVector::zeros(), C::SUBCHUNK_SIZE).map(|index| &chunk[index]);
+
+            match cells.next() {
+                Some(first) if cells.all(|cell| cell == first) => {
+                    *self = CompressedSubchunk::Uniform(first.clone());
+                }
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// Stores this chunk as a full `C` if it is uniform, returning a mutable reference to it.
+    pub fn decompress(&mut self) -> &mut C
+    where
+        C: Default,
+        C::Output: Clone,
+    {
+        if let CompressedSubchunk::Uniform(value) = self {
+            let mut chunk = Box::<C>::default();
+            for index in util::iter_box(SVector::zeros(), C::SUBCHUNK_SIZE) {
+                chunk[index] = value.clone();
+            }
+            *self = CompressedSubchunk::Full(chunk);
+        }
+
+        match self {
+            CompressedSubchunk::Full(chunk) => chunk,
+            CompressedSubchunk::Uniform(_) => unreachable!(),
+        }
+    }
+}
+
+impl<S: Subchunk<D>, const D: usize, C: Coordinate> ExpandableGridN<CompressedSubchunk<S, D>, D, C>
+where
+    S::Output: Sized,
+{
+    /// Compresses every chunk whose cells are all the same, returning how many chunks are
+    /// uniform afterwards.
+    pub fn compress_uniform_chunks(&mut self) -> usize
+    where
+        S::Output: Clone + PartialEq,
+    {
+        self.data
+            .iter_mut()
+            .map(|chunk| chunk.compress())
+            .filter(|&uniform| uniform)
+            .count()
+    }
+}
+
+impl<C: Subchunk<D>, const D: usize> Default for CompressedSubchunk<C, D>
+where
+    C::Output: Sized + Default,
+{
+    fn default() -> Self {
+        CompressedSubchunk::Uniform(C::Output::default())
+    }
+}
+
+impl<C: Subchunk<D>, const D: usize> std::ops::Index<SVector<usize, D>> for CompressedSubchunk<C, D>
+where
+    C::Output: Sized,
+{
+    type Output = C::Output;
+
+    fn index(&self, index: SVector<usize, D>) -> &Self::Output {
+        match self {
+            CompressedSubchunk::Uniform(value) => value,
+            CompressedSubchunk::Full(chunk) => &chunk[index],
+        }
+    }
+}
+
+impl<C: Subchunk<D> + Default, const D: usize> std::ops::IndexMut<SVector<usize, D>>
+    for CompressedSubchunk<C, D>
+where
+    C::Output: Sized + Clone,
+{
+    fn index_mut(&mut self, index: SVector<usize, D>) -> &mut Self::Output {
+        &mut self.decompress()[index]
+    }
+}
+
+impl<C: Subchunk<D> + Default, const D: usize> Subchunk<D> for CompressedSubchunk<C, D>
+where
+    C::Output: Sized + Clone,
+{
+    const SUBCHUNK_SIZE: SVector<usize, D> = C::SUBCHUNK_SIZE;
+}
+
+/// A 2d subchunk of `W` by `H` cells, stored as an array of rows, so the cell at `[x, y]` is
+/// `cells[y][x]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ArraySubchunk<T, const W: usize, const H: usize> {
+    pub cells: [[T; W]; H],
+}
+
+impl<T, const W: usize, const H: usize> ArraySubchunk<T, W, H> {
+    pub fn new(cells: [[T; W]; H]) -> Self {
+        Self { cells }
+    }
+
+    /// Creates a subchunk where each cell is the result of `f` called with its position.
+    pub fn from_fn(mut f: impl FnMut(Vector2<usize>) -> T) -> Self {
+        Self::new(std::array::from_fn(|y| {
+            std::array::from_fn(|x| f(vector![x, y]))
+        }))
     }
+
+    /// Creates a subchunk filled with clones of `value`.
+    pub fn filled(value: &T) -> Self
+    where
+        T: Clone,
+    {
+        Self::from_fn(|_| value.clone())
+    }
+
+    pub fn into_inner(self) -> [[T; W]; H] {
+        self.cells
+    }
+
+    /// Iterates over the positions and values of every cell, varying `x` fastest.
+    pub fn iter(&self) -> impl Iterator<Item = (Vector2<usize>, &T)> {
+        (self.cells.iter().enumerate()).flat_map(|(y, row)| {
+            (row.iter().enumerate()).map(move |(x, cell)| (vector![x, y], cell))
+        })
+    }
+
+    /// Iterates over the positions and mutable references to every cell, varying `x` fastest.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Vector2<usize>, &mut T)> {
+        (self.cells.iter_mut().enumerate()).flat_map(|(y, row)| {
+            (row.iter_mut().enumerate()).map(move |(x, cell)| (vector![x, y], cell))
+        })
+    }
+}
+
+impl<T: Default, const W: usize, const H: usize> Default for ArraySubchunk<T, W, H> {
+    fn default() -> Self {
+        Self::from_fn(|_| T::default())
+    }
+}
+
+impl<T, const W: usize, const H: usize> std::ops::Index<Vector2<usize>> for ArraySubchunk<T, W, H> {
+    type Output = T;
+
+    fn index(&self, index: Vector2<usize>) -> &Self::Output {
+        &self.cells[index.y][index.x]
+    }
+}
+
+impl<T, const W: usize, const H: usize> std::ops::IndexMut<Vector2<usize>>
+    for ArraySubchunk<T, W, H>
+{
+    fn index_mut(&mut self, index: Vector2<usize>) -> &mut Self::Output {
+        &mut self.cells[index.y][index.x]
+    }
+}
+
+impl<T, const W: usize, const H: usize> Subchunk for ArraySubchunk<T, W, H> {
+    const SUBCHUNK_SIZE: Vector2<usize> = Vector2::new(W, H);
 }
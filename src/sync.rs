@@ -0,0 +1,497 @@
+//! A compact wire format for keeping copies of a grid in sync over a network.
+//!
+//! A `SyncSender` encodes each change to a grid as a delta, either from a `GridDiff` or from the
+//! rects recorded by a `DirtyGrid`, and a `SyncReceiver` applies the deltas to its copy of the
+//! grid in order. Each delta has a sequence number, so deltas which were lost or arrive out of
+//! order are detected rather than corrupting the copy, and the bounds of the grid it applies
+//! to, so a copy which has fallen out of sync is detected as well. Both copies start as empty
+//! grids, and the first delta sent includes the whole grid.
+//!
+//! Deltas can be compressed with LZ4 when the `compression` feature is enabled, which is worth
+//! it for large deltas with varied cells.
+//!
+//! Deltas usually arrive from the network, so a `SyncReceiver` validates every delta before
+//! applying it, and returns an error rather than panicking on any malformed delta. Since runs of
+//! equal cells let a small delta describe a large grid, the receiver also limits the number of
+//! cells a delta may create, which can be changed with `SyncReceiver::with_max_cells`.
+//!
+//! # Layout
+//!
+//! Varints are unsigned LEB128 integers, and signed varints are zigzag encoded first, so that
+//! small negative numbers stay small.
+//!
+//! | Field    | Encoding       | Notes                                             |
+//! |----------|----------------|---------------------------------------------------|
+//! | sequence | varint         | `0` for the first delta, increasing by `1`        |
+//! | flags    | `u8`           | Bit `0` is set if the body is compressed          |
+//! | body     | see below      | An LZ4 frame containing the body if compressed    |
+//!
+//! The body is the old origin (`D` signed varints) and old size (`D` varints), the new origin
+//! relative to the old origin and the new size, then the number of patches (a varint) followed
+//! by each patch. A patch is its origin relative to the origin of the previous patch (or the new
+//! origin for the first), its size, and then its cells in row major order as runs of equal
+//! cells, each stored as its length (a varint, never `0`) followed by the cell as written by
+//! `BinaryCell`.
+
+use crate::{
+    binary::{self, BinaryCell, BinaryError},
+    diff::GridDiff,
+    rect::GridRect,
+    util, ExpandableGridN, Layout,
+};
+use nalgebra::SVector;
+use std::io::{self, Read, Write};
+
+/// Set in the flags of a delta when its body is compressed.
+const COMPRESSED: u8 = 1;
+
+/// The default limit on the number of cells of the grid and patches of a delta.
+pub const DEFAULT_MAX_CELLS: usize = 1 << 24;
+
+/// An error encountered while applying a delta.
+#[derive(Debug)]
+pub enum SyncError {
+    /// The delta could not be decoded.
+    Decode(BinaryError),
+    /// The delta does not have the next sequence number, so a delta was lost or reordered.
+    OutOfOrder { expected: u64, found: u64 },
+    /// The grid does not have the bounds the delta was computed from, so it is out of sync.
+    BoundsMismatch,
+    /// The delta is compressed, but the `compression` feature is not enabled.
+    CompressionUnsupported,
+    /// The grid or patches of the delta have more cells than the receiver allows, or their
+    /// coordinates overflow.
+    TooLarge,
+    /// A patch of the delta extends past the new bounds of the grid.
+    PatchOutOfBounds,
+    /// A patch of the delta has no cells. Senders never write empty patches, and since they cost
+    /// nothing against the limit on cells, a delta could otherwise hold any number of them.
+    EmptyPatch,
+    /// The patches of the delta do not cover every cell added to the grid.
+    MissingCells,
+}
+
+impl std::fmt::Display for SyncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyncError::Decode(error) => write!(f, "failed to decode delta: {error}"),
+            SyncError::OutOfOrder { expected, found } => {
+                write!(f, "expected delta {expected}, found delta {found}")
+            }
+            SyncError::BoundsMismatch => write!(f, "grid bounds do not match the delta"),
+            SyncError::CompressionUnsupported => {
+                write!(f, "delta is compressed, but compression is not enabled")
+            }
+            SyncError::TooLarge => write!(f, "delta has too many cells"),
+            SyncError::PatchOutOfBounds => write!(f, "patch extends past the bounds of the grid"),
+            SyncError::EmptyPatch => write!(f, "patch has no cells"),
+            SyncError::MissingCells => {
+                write!(f, "patches do not cover every cell added to the grid")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SyncError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SyncError::Decode(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<BinaryError> for SyncError {
+    fn from(error: BinaryError) -> Self {
+        SyncError::Decode(error)
+    }
+}
+
+impl From<io::Error> for SyncError {
+    fn from(error: io::Error) -> Self {
+        SyncError::Decode(BinaryError::Io(error))
+    }
+}
+
+/// Encodes the changes to a grid as deltas. See the `sync` module for details.
+#[derive(Clone, Debug)]
+pub struct SyncSender<const D: usize = 2> {
+    sequence: u64,
+    /// The bounds of the grid as of the last delta.
+    bounds: GridRect<D>,
+    #[cfg(feature = "compression")]
+    compress: bool,
+}
+
+impl<const D: usize> Default for SyncSender<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const D: usize> SyncSender<D> {
+    pub fn new() -> Self {
+        Self {
+            sequence: 0,
+            bounds: GridRect::new(SVector::zeros(), SVector::zeros()),
+            #[cfg(feature = "compression")]
+            compress: false,
+        }
+    }
+
+    /// Sets whether deltas are compressed with LZ4.
+    #[cfg(feature = "compression")]
+    pub fn set_compression(&mut self, compress: bool) {
+        self.compress = compress;
+    }
+
+    /// Returns the sequence number of the next delta.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Encodes `diff` as the next delta. The diff must be from the grid as of the last delta.
+    pub fn encode_diff<T: BinaryCell + PartialEq>(&mut self, diff: &GridDiff<T, D>) -> Vec<u8> {
+        let mut body = Vec::new();
+        write_body(&mut body, diff).expect("writing to a vec should never fail");
+
+        let mut bytes = Vec::new();
+        binary::write_varint(&mut bytes, self.sequence)
+            .expect("writing to a vec should never fail");
+        self.encode_body(&mut bytes, body);
+
+        self.sequence += 1;
+        self.bounds = diff.new_bounds;
+        bytes
+    }
+
+    /// Encodes the next delta from the cells of `grid` within `rects`, where no other cells have
+    /// changed since the last delta. See `GridDiff::from_rects`.
+    pub fn encode_rects<T: BinaryCell + PartialEq + Clone>(
+        &mut self,
+        grid: &ExpandableGridN<T, D>,
+        rects: impl IntoIterator<Item = GridRect<D>>,
+    ) -> Vec<u8> {
+        self.encode_diff(&GridDiff::from_rects(self.bounds, grid, rects))
+    }
+
+    #[cfg(feature = "compression")]
+    fn encode_body(&self, bytes: &mut Vec<u8>, body: Vec<u8>) {
+        if self.compress {
+            bytes.push(COMPRESSED);
+            bytes.extend(
+                crate::compression::compress(&body)
+                    .expect("compressing into a vec should never fail"),
+            );
+        } else {
+            bytes.push(0);
+            bytes.extend(body);
+        }
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn encode_body(&self, bytes: &mut Vec<u8>, body: Vec<u8>) {
+        bytes.push(0);
+        bytes.extend(body);
+    }
+}
+
+/// Applies deltas from a `SyncSender` to a copy of its grid. See the `sync` module for details.
+#[derive(Clone, Debug)]
+pub struct SyncReceiver {
+    next_sequence: u64,
+    max_cells: usize,
+}
+
+impl Default for SyncReceiver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyncReceiver {
+    /// Creates a receiver which accepts deltas with up to `DEFAULT_MAX_CELLS` cells.
+    pub fn new() -> Self {
+        Self::with_max_cells(DEFAULT_MAX_CELLS)
+    }
+
+    /// Creates a receiver which rejects deltas where the new grid, or the patches together,
+    /// have more than `max_cells` cells.
+    pub fn with_max_cells(max_cells: usize) -> Self {
+        Self {
+            next_sequence: 0,
+            max_cells,
+        }
+    }
+
+    /// Returns the sequence number of the next delta to be applied.
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+
+    /// Decodes `delta` and applies it to `grid`. The whole delta is validated first, so on
+    /// error, `grid` is left unchanged.
+    pub fn apply<T: BinaryCell + PartialEq + Clone, const D: usize>(
+        &mut self,
+        grid: &mut ExpandableGridN<T, D>,
+        delta: &[u8],
+    ) -> Result<(), SyncError> {
+        let (sequence, diff) = decode_with_max_cells(delta, self.max_cells)?;
+
+        if sequence != self.next_sequence {
+            return Err(SyncError::OutOfOrder {
+                expected: self.next_sequence,
+                found: sequence,
+            });
+        }
+        if grid.bounds() != diff.old_bounds {
+            return Err(SyncError::BoundsMismatch);
+        }
+
+        grid.apply_diff(&diff);
+        self.next_sequence += 1;
+        Ok(())
+    }
+}
+
+/// Decodes and validates `delta`, returning its sequence number and the diff it contains. Deltas
+/// with more than `DEFAULT_MAX_CELLS` cells are rejected.
+pub fn decode<T: BinaryCell, const D: usize>(
+    delta: &[u8],
+) -> Result<(u64, GridDiff<T, D>), SyncError> {
+    decode_with_max_cells(delta, DEFAULT_MAX_CELLS)
+}
+
+fn decode_with_max_cells<T: BinaryCell, const D: usize>(
+    mut delta: &[u8],
+    max_cells: usize,
+) -> Result<(u64, GridDiff<T, D>), SyncError> {
+    let reader = &mut delta;
+
+    let sequence = binary::read_varint(reader)?.ok_or(BinaryError::SizeOverflow)?;
+    let [flags] = binary::read_array(reader)?;
+
+    let diff = if flags & COMPRESSED != 0 {
+        #[cfg(feature = "compression")]
+        {
+            read_body(&mut lz4_flex::frame::FrameDecoder::new(reader), max_cells)?
+        }
+        #[cfg(not(feature = "compression"))]
+        return Err(SyncError::CompressionUnsupported);
+    } else {
+        read_body(reader, max_cells)?
+    };
+
+    Ok((sequence, diff))
+}
+
+fn write_body<T: BinaryCell + PartialEq, const D: usize>(
+    writer: &mut impl Write,
+    diff: &GridDiff<T, D>,
+) -> io::Result<()> {
+    write_position(writer, diff.old_bounds.origin)?;
+    write_size(writer, diff.old_bounds.size)?;
+    write_position(writer, diff.new_bounds.origin - diff.old_bounds.origin)?;
+    write_size(writer, diff.new_bounds.size)?;
+
+    binary::write_varint(writer, diff.patches.len() as u64)?;
+    let mut base = diff.new_bounds.origin;
+    let mut bytes = vec![0; T::SIZE];
+    for patch in &diff.patches {
+        write_position(writer, patch.origin - base)?;
+        write_size(writer, patch.size)?;
+        base = patch.origin;
+
+        // Cells are written in row major order regardless of the layout of the patch
+        let mut cells = util::iter_box(SVector::zeros(), patch.size)
+            .map(|offset| &patch[patch.origin + util::usize_vec_to_isize(offset)])
+            .peekable();
+        while let Some(cell) = cells.next() {
+            let mut length = 1;
+            while cells.next_if(|&next| next == cell).is_some() {
+                length += 1;
+            }
+
+            binary::write_varint(writer, length)?;
+            cell.write_bytes(&mut bytes);
+            writer.write_all(&bytes)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the body of a delta, checking that every rect fits within the coordinate space, that
+/// there are no more than `max_cells` cells, and that the patches are not empty, are within the
+/// new bounds, and cover every cell added to the grid, so that the diff can be applied without
+/// panicking.
+fn read_body<T: BinaryCell, const D: usize>(
+    reader: &mut impl Read,
+    max_cells: usize,
+) -> Result<GridDiff<T, D>, SyncError> {
+    let old_bounds = read_rect(reader, SVector::zeros())?;
+    let new_bounds = read_rect(reader, old_bounds.origin)?;
+    util::checked_area(new_bounds.size)
+        .filter(|&area| area <= max_cells)
+        .ok_or(SyncError::TooLarge)?;
+
+    let patch_count = read_length(reader)?;
+    let mut patches = Vec::new();
+    let mut budget = max_cells;
+    let mut base = new_bounds.origin;
+    let mut bytes = vec![0; T::SIZE];
+    for _ in 0..patch_count {
+        let rect = read_rect(reader, base)?;
+        base = rect.origin;
+        if rect.is_empty() {
+            return Err(SyncError::EmptyPatch);
+        }
+        if !new_bounds.contains_rect(&rect) {
+            return Err(SyncError::PatchOutOfBounds);
+        }
+
+        let area = util::checked_area(rect.size)
+            .filter(|&area| area <= budget)
+            .ok_or(SyncError::TooLarge)?;
+        budget -= area;
+
+        let mut data = Vec::new();
+        while data.len() < area {
+            let index = data.len();
+            let length = binary::read_varint(reader)?
+                .and_then(|length| usize::try_from(length).ok())
+                .filter(|&length| length > 0 && length <= area - index)
+                .ok_or(BinaryError::InvalidRun { index })?;
+
+            // Only allocate once the cell of the run has been read
+            reader.read_exact(&mut bytes)?;
+            data.try_reserve(length).map_err(|_| SyncError::TooLarge)?;
+            for _ in 0..length {
+                data.push(T::read_bytes(&bytes).ok_or(BinaryError::InvalidCell { index })?);
+            }
+        }
+
+        patches.push(ExpandableGridN {
+            size: rect.size,
+            origin: rect.origin,
+            data: data.into_boxed_slice(),
+            layout: Layout::RowMajor,
+        });
+    }
+
+    check_new_cells_covered(old_bounds, new_bounds, &patches)?;
+
+    Ok(GridDiff {
+        old_bounds,
+        new_bounds,
+        patches,
+    })
+}
+
+/// Returns an error unless every cell within `new_bounds` but outside `old_bounds` is within
+/// some patch, so that applying the diff never has to fill in cells. The patches must already be
+/// within `new_bounds`.
+fn check_new_cells_covered<T, const D: usize>(
+    old_bounds: GridRect<D>,
+    new_bounds: GridRect<D>,
+    patches: &[ExpandableGridN<T, D>],
+) -> Result<(), SyncError> {
+    let new_area = new_bounds.size.product();
+    let kept_area = old_bounds
+        .intersection(&new_bounds)
+        .map_or(0, |kept| kept.size.product());
+    let mut missing = new_area - kept_area;
+    if missing == 0 {
+        return Ok(());
+    }
+
+    let (old_start, old_end) = (old_bounds.origin, old_bounds.end());
+    let mut covered = vec![false; new_area];
+    for patch in patches {
+        for position in GridRect::new(patch.origin, patch.size).iter() {
+            let kept = (0..D)
+                .all(|axis| old_start[axis] <= position[axis] && position[axis] < old_end[axis]);
+            let offset = (position - new_bounds.origin).map(|length| length as usize);
+            let index = Layout::RowMajor.linear_index(offset, new_bounds.size);
+
+            if !kept && !covered[index] {
+                covered[index] = true;
+                missing -= 1;
+            }
+        }
+    }
+
+    if missing == 0 {
+        Ok(())
+    } else {
+        Err(SyncError::MissingCells)
+    }
+}
+
+/// Reads a rect stored as its origin relative to `base` and its size, returning an error if its
+/// coordinates overflow.
+fn read_rect<const D: usize>(
+    reader: &mut impl Read,
+    base: SVector<isize, D>,
+) -> Result<GridRect<D>, SyncError> {
+    let offset = read_position::<D>(reader)?;
+    let size = read_size(reader)?;
+
+    let mut origin = SVector::<isize, D>::zeros();
+    for axis in 0..D {
+        origin[axis] = base[axis]
+            .checked_add(offset[axis])
+            .ok_or(SyncError::TooLarge)?;
+
+        // Check the end of the rect as well, so that it can be used without overflowing
+        isize::try_from(size[axis])
+            .ok()
+            .and_then(|length| origin[axis].checked_add(length))
+            .ok_or(SyncError::TooLarge)?;
+    }
+
+    Ok(GridRect::new(origin, size))
+}
+
+fn write_position<const D: usize>(
+    writer: &mut impl Write,
+    position: SVector<isize, D>,
+) -> io::Result<()> {
+    for &component in position.iter() {
+        let zigzag = ((component as i64) << 1 ^ (component as i64) >> 63) as u64;
+        binary::write_varint(writer, zigzag)?;
+    }
+    Ok(())
+}
+
+fn write_size<const D: usize>(writer: &mut impl Write, size: SVector<usize, D>) -> io::Result<()> {
+    for &length in size.iter() {
+        binary::write_varint(writer, length as u64)?;
+    }
+    Ok(())
+}
+
+fn read_position<const D: usize>(reader: &mut impl Read) -> Result<SVector<isize, D>, BinaryError> {
+    let mut position = SVector::<isize, D>::zeros();
+    for component in position.iter_mut() {
+        let zigzag = binary::read_varint(reader)?.ok_or(BinaryError::SizeOverflow)?;
+        *component = ((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64))
+            .try_into()
+            .map_err(|_| BinaryError::SizeOverflow)?;
+    }
+    Ok(position)
+}
+
+fn read_size<const D: usize>(reader: &mut impl Read) -> Result<SVector<usize, D>, BinaryError> {
+    let mut size = SVector::<usize, D>::zeros();
+    for length in size.iter_mut() {
+        *length = read_length(reader)?;
+    }
+    Ok(size)
+}
+
+fn read_length(reader: &mut impl Read) -> Result<usize, BinaryError> {
+    binary::read_varint(reader)?
+        .and_then(|length| usize::try_from(length).ok())
+        .ok_or(BinaryError::SizeOverflow)
+}
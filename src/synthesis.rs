@@ -0,0 +1,250 @@
+//! Generating grids which look like an example grid, with the simple tiled model of wave function
+//! collapse.
+//!
+//! `TileRules::learn` records which tiles appear next to each other along each axis of an
+//! example grid, and how often each tile appears. `TileRules::generate` then fills a new grid
+//! where every pair of neighboring tiles also appears next to each other in the example. It
+//! repeatedly chooses the cell with the fewest possible tiles left, picks one of them at random,
+//! weighted by how often it appears in the example, and removes the tiles which can no longer fit
+//! from the cells around it. If some cell is left with no possible tiles, the attempt is
+//! abandoned and generation restarts.
+
+use crate::{coord::GridVector, util, ExpandableGridN, Layout};
+use nalgebra::SVector;
+use rand::Rng;
+use std::{collections::HashMap, hash::Hash};
+
+/// The tiles of an example grid, and which of them may be placed next to each other. See the
+/// `synthesis` module for details.
+#[derive(Clone, Debug)]
+pub struct TileRules<T, const D: usize = 2> {
+    tiles: Vec<T>,
+    weights: Vec<u32>,
+    /// Whether each tile may be placed next to each other tile in each direction, indexed by
+    /// `direction * tiles² + tile * tiles + neighbor`.
+    allowed: Vec<bool>,
+}
+
+impl<T: Clone + Eq + Hash, const D: usize> TileRules<T, D> {
+    /// Learns the tiles of `example` and which of them appear next to each other.
+    pub fn learn(example: &ExpandableGridN<T, D>) -> Self {
+        let mut ids = HashMap::new();
+        let mut tiles = Vec::new();
+        let mut weights = Vec::new();
+        let cells: Vec<usize> = (example.data.iter())
+            .map(|cell| {
+                let id = *ids.entry(cell).or_insert_with(|| {
+                    tiles.push(cell.clone());
+                    weights.push(0);
+                    tiles.len() - 1
+                });
+                weights[id] += 1;
+                id
+            })
+            .collect();
+
+        let count = tiles.len();
+        let mut rules = Self {
+            tiles,
+            weights,
+            allowed: vec![false; 2 * D * count * count],
+        };
+
+        for position in util::iter_box(SVector::zeros(), example.size) {
+            let tile = cells[example.layout.linear_index(position, example.size)];
+
+            for axis in (0..D).filter(|&axis| position[axis] + 1 < example.size[axis]) {
+                let mut next = position;
+                next[axis] += 1;
+                let neighbor = cells[example.layout.linear_index(next, example.size)];
+
+                let forward = rules.allowed_index(direction(axis, true), tile, neighbor);
+                let backward = rules.allowed_index(direction(axis, false), neighbor, tile);
+                rules.allowed[forward] = true;
+                rules.allowed[backward] = true;
+            }
+        }
+
+        rules
+    }
+
+    /// Returns the distinct tiles of the example, in the order they first appear.
+    pub fn tiles(&self) -> &[T] {
+        &self.tiles
+    }
+
+    /// Returns `true` if `next` may be placed one cell after `tile` along `axis`, or `false` if
+    /// either doesn't appear in the example.
+    pub fn allows(&self, tile: &T, next: &T, axis: usize) -> bool {
+        let id = |tile| self.tiles.iter().position(|other| other == tile);
+
+        match (id(tile), id(next)) {
+            (Some(tile), Some(next)) if axis < D => {
+                self.allowed[self.allowed_index(direction(axis, true), tile, next)]
+            }
+            _ => false,
+        }
+    }
+
+    /// Generates a grid of size `size` at `origin` where every pair of neighboring tiles appear
+    /// next to each other in the example. Restarts up to `max_attempts` times if it can't
+    /// continue, returning `None` if every attempt fails, or if the example was empty.
+    pub fn generate(
+        &self,
+        rng: &mut impl Rng,
+        size: impl GridVector<usize, D>,
+        origin: impl GridVector<isize, D>,
+        max_attempts: usize,
+    ) -> Option<ExpandableGridN<T, D>> {
+        let (size, origin) = (size.to_vector(), origin.to_vector());
+        if self.tiles.is_empty() && size.product() > 0 {
+            return None;
+        }
+
+        let tiles = (0..max_attempts).find_map(|_| self.collapse(rng, size))?;
+        Some(ExpandableGridN {
+            size,
+            origin,
+            data: tiles.into_iter().map(|id| self.tiles[id].clone()).collect(),
+            layout: Layout::RowMajor,
+        })
+    }
+
+    /// Makes one attempt at choosing a tile for every cell of a grid of size `size`, returning
+    /// the tile of each cell in row major order, or `None` on a contradiction.
+    fn collapse(&self, rng: &mut impl Rng, size: SVector<usize, D>) -> Option<Vec<usize>> {
+        let count = self.tiles.len();
+        let cells = size.product();
+
+        // Whether each tile is still possible for each cell, and how many are
+        let mut wave = vec![true; cells * count];
+        let mut options = vec![count; cells];
+
+        // Remove the tiles which can't be next to any tile at all
+        for cell in 0..cells {
+            self.propagate(size, cell, &mut wave, &mut options)?;
+        }
+
+        loop {
+            // Choose the undecided cell with the fewest options, breaking ties at random
+            let mut fewest = usize::MAX;
+            let mut ties = 0;
+            let mut chosen = None;
+            for (cell, &cell_options) in options.iter().enumerate() {
+                if cell_options <= 1 || cell_options > fewest {
+                    continue;
+                }
+                if cell_options < fewest {
+                    (fewest, ties) = (cell_options, 0);
+                }
+                ties += 1;
+                if rng.gen_range(0..ties) == 0 {
+                    chosen = Some(cell);
+                }
+            }
+
+            let Some(cell) = chosen else {
+                let tiles = wave.chunks_exact(count.max(1));
+                return Some(
+                    tiles
+                        .map(|tiles| tiles.iter().position(|&possible| possible).unwrap())
+                        .collect(),
+                );
+            };
+
+            // Pick one of its tiles, weighted by how often it appears in the example
+            let possible = &mut wave[cell * count..(cell + 1) * count];
+            let total: u32 = (0..count)
+                .filter(|&tile| possible[tile])
+                .map(|tile| self.weights[tile])
+                .sum();
+            let mut target = rng.gen_range(0..total);
+            let tile = (0..count).filter(|&tile| possible[tile]).find(|&tile| {
+                let found = target < self.weights[tile];
+                target = target.saturating_sub(self.weights[tile]);
+                found
+            })?;
+
+            possible.fill(false);
+            possible[tile] = true;
+            options[cell] = 1;
+
+            self.propagate(size, cell, &mut wave, &mut options)?;
+        }
+    }
+
+    /// Removes the tiles which can no longer fit from the cells around `start`, and around every
+    /// cell which changes as a result. Returns `None` if some cell has no tiles left.
+    fn propagate(
+        &self,
+        size: SVector<usize, D>,
+        start: usize,
+        wave: &mut [bool],
+        options: &mut [usize],
+    ) -> Option<()> {
+        let count = self.tiles.len();
+        let mut stack = vec![start];
+        let mut supported = vec![false; count];
+
+        while let Some(cell) = stack.pop() {
+            let position = Layout::RowMajor.position(cell, size);
+
+            for axis in 0..D {
+                for forward in [false, true] {
+                    let neighbor_position = if forward {
+                        Some(position[axis] + 1).filter(|&next| next < size[axis])
+                    } else {
+                        position[axis].checked_sub(1)
+                    };
+                    let Some(neighbor_position) = neighbor_position.map(|next| {
+                        let mut neighbor = position;
+                        neighbor[axis] = next;
+                        neighbor
+                    }) else {
+                        continue;
+                    };
+                    let neighbor = Layout::RowMajor.linear_index(neighbor_position, size);
+
+                    // Find every tile allowed next to any tile still possible for this cell
+                    supported.fill(false);
+                    for tile in (0..count).filter(|&tile| wave[cell * count + tile]) {
+                        let start = self.allowed_index(direction(axis, forward), tile, 0);
+                        for (next, &allowed) in
+                            self.allowed[start..start + count].iter().enumerate()
+                        {
+                            supported[next] |= allowed;
+                        }
+                    }
+
+                    let mut changed = false;
+                    for tile in 0..count {
+                        let possible = &mut wave[neighbor * count + tile];
+                        if *possible && !supported[tile] {
+                            *possible = false;
+                            options[neighbor] -= 1;
+                            changed = true;
+                        }
+                    }
+
+                    if options[neighbor] == 0 {
+                        return None;
+                    } else if changed {
+                        stack.push(neighbor);
+                    }
+                }
+            }
+        }
+
+        Some(())
+    }
+
+    fn allowed_index(&self, direction: usize, tile: usize, neighbor: usize) -> usize {
+        let count = self.tiles.len();
+        (direction * count + tile) * count + neighbor
+    }
+}
+
+/// Returns the index of the direction along `axis`, towards higher coordinates if `forward`.
+fn direction(axis: usize, forward: bool) -> usize {
+    axis * 2 + forward as usize
+}
@@ -0,0 +1,136 @@
+//! Keeping the last few states of every cell of a grid, for interpolation, rollback, or trails.
+//!
+//! A `TemporalGrid` holds `N` grids with the same bounds in a ring: the current grid, and the
+//! grids as they were at each of the last `N - 1` ticks. `tick` starts a new tick, overwriting
+//! the oldest grid with a copy of the current one, so no grids are allocated once the grid has
+//! stopped expanding. Expanding or resizing the temporal grid expands every grid, and the new
+//! cells have the fill value at every tick.
+
+use crate::{coord::GridVector, ExpandableGridN};
+
+/// A grid which keeps its state at each of the last `N - 1` ticks. See the `temporal` module
+/// for details.
+#[derive(Clone, Debug)]
+pub struct TemporalGrid<T, const N: usize, const D: usize = 2> {
+    frames: [ExpandableGridN<T, D>; N],
+    /// The index of the current grid within `frames`.
+    current: usize,
+}
+
+impl<T: Clone, const N: usize, const D: usize> TemporalGrid<T, N, D> {
+    /// Creates a temporal grid where `grid` is the state at the current tick and every previous
+    /// tick.
+    ///
+    /// # Panics
+    /// Panics if `N` is 0.
+    pub fn new(grid: ExpandableGridN<T, D>) -> Self {
+        assert!(N > 0, "a temporal grid should keep at least one grid");
+
+        Self {
+            frames: std::array::from_fn(|_| grid.clone()),
+            current: 0,
+        }
+    }
+
+    /// Returns the grid at the current tick.
+    pub fn current(&self) -> &ExpandableGridN<T, D> {
+        &self.frames[self.current]
+    }
+
+    /// Returns the grid at the current tick. Its size must not be changed, use the methods of
+    /// `TemporalGrid` for that.
+    pub fn current_mut(&mut self) -> &mut ExpandableGridN<T, D> {
+        &mut self.frames[self.current]
+    }
+
+    /// Returns the current grid, discarding the previous ones.
+    pub fn into_current(self) -> ExpandableGridN<T, D> {
+        let current = self.current;
+        self.frames.into_iter().nth(current).unwrap()
+    }
+
+    /// Returns the grid as it was `ticks_ago` ticks ago, or `None` if it is `N` or more.
+    pub fn at_ticks_ago(&self, ticks_ago: usize) -> Option<&ExpandableGridN<T, D>> {
+        (ticks_ago < N).then(|| &self.frames[(self.current + N - ticks_ago) % N])
+    }
+
+    /// Returns the cell at `index` as it was `ticks_ago` ticks ago, or `None` if it is out of
+    /// bounds or `ticks_ago` is `N` or more.
+    pub fn get(&self, index: impl GridVector<isize, D>, ticks_ago: usize) -> Option<&T> {
+        self.at_ticks_ago(ticks_ago)?.get(index)
+    }
+
+    /// Returns an iterator over the values of the cell at `index`, starting at the current tick
+    /// and going back `N - 1` ticks, or `None` if it is out of bounds.
+    pub fn history(
+        &self,
+        index: impl GridVector<isize, D>,
+    ) -> Option<impl Iterator<Item = &T> + '_> {
+        let index = self.current().index_of(index)?;
+        Some((0..N).map(move |ticks_ago| &self.at_ticks_ago(ticks_ago).unwrap().data[index]))
+    }
+
+    /// Starts a new tick, where the current grid starts as a copy of the previous one, and the
+    /// oldest grid is discarded.
+    pub fn tick(&mut self) {
+        let next = (self.current + 1) % N;
+
+        if next != self.current {
+            let (previous, next) = if next > self.current {
+                let (before, after) = self.frames.split_at_mut(next);
+                (&before[self.current], &mut after[0])
+            } else {
+                let (before, after) = self.frames.split_at_mut(self.current);
+                (&after[0], &mut before[next])
+            };
+            next.clone_from(previous);
+        }
+        self.current = next;
+    }
+
+    /// Increases the size of every grid such that `point` is within their bounds. See
+    /// `ExpandableGridN::expand_to_fit_point`.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<isize, D>, fill: &T) {
+        self.current_mut().expand_to_fit_point(point, fill);
+        self.match_previous(fill);
+    }
+
+    /// Increases the size of every grid such that every cell of a box is within their bounds.
+    /// See `ExpandableGridN::expand_to_fit_box`.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<isize, D>,
+        box_size: impl GridVector<usize, D>,
+        fill: &T,
+    ) {
+        self.current_mut()
+            .expand_to_fit_box(box_origin, box_size, fill);
+        self.match_previous(fill);
+    }
+
+    /// Changes the size of every grid. See `ExpandableGridN::change_size`.
+    pub fn change_size(
+        &mut self,
+        new_size: impl GridVector<usize, D>,
+        offset: impl GridVector<isize, D>,
+        fill: &T,
+    ) {
+        self.current_mut().change_size(new_size, offset, fill);
+        self.match_previous(fill);
+    }
+
+    /// Changes the bounds of the previous grids to match the current grid, keeping their cells
+    /// which remain within bounds.
+    fn match_previous(&mut self, fill: &T) {
+        let (size, origin) = (self.current().size, self.current().origin);
+
+        for frame in &mut self.frames {
+            if (frame.size, frame.origin) == (size, origin) {
+                continue;
+            }
+
+            let offset = origin - frame.origin;
+            frame.change_size(size, offset, fill);
+        }
+    }
+}
@@ -1,7 +1,51 @@
 #![cfg(test)]
 
-use crate::expandable_grid::ExpandableGrid;
-use nalgebra::{vector, Vector2};
+use crate::expandable_grid::{ExpandableGrid, ExpandableGrid3, ExpandableGridN, Layout};
+use crate::{
+    active::ActiveGrid,
+    alloc::{Global, GridAlloc},
+    arena::GridArena,
+    atomic::{AtomicCell, AtomicGrid},
+    binary,
+    cell::CellGrid,
+    cellular::{Automaton, BirthSurvival, Boundary, Neighborhood},
+    collision::{Aabb, SweepResult},
+    concurrent::ConcurrentGrid,
+    content::ContentGrid,
+    coord::GridVector,
+    cow::CowGrid,
+    dirty::DirtyGrid,
+    double_buffer::DoubleBufferedGrid,
+    edit_log::{Edit, EditLog},
+    eviction::EvictingGrid,
+    group::GridGroup,
+    hex,
+    history::GridHistory,
+    infinite::InfiniteGrid,
+    influence::{Decay, InfluenceMap, InfluenceSource},
+    interned::InternedGrid,
+    layered::LayeredGrid,
+    margins::Margins,
+    migration::Migrations,
+    observe::{GridEvent, ObservedGrid},
+    order::IterationOrder,
+    overlay::OverlayGrid,
+    palette::{EnumGrid, PaletteEnum},
+    persistence::{DirectoryStore, RegionReader, TileStore, TrackedGrid},
+    rect::GridRect,
+    regions::RegionGrid,
+    schedule::TickScheduler,
+    search::Metric,
+    segment::GridSegment,
+    small::SmallGrid,
+    subchunk::{ArraySubchunk, ChunkEntry, CompressedSubchunk, Subchunk},
+    sync::{SyncError, SyncReceiver, SyncSender},
+    temporal::TemporalGrid,
+    transaction::Transaction,
+    util,
+    zip::zip_view_mut,
+};
+use nalgebra::{vector, Point2, SVector, Vector2};
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
 
@@ -119,3 +163,3894 @@ fn corners_of_box(origin: Vector2<isize>, size: Vector2<usize>) -> [Vector2<isiz
         corner,
     ]
 }
+
+#[test]
+fn column_major_grid_matches_row_major_grid() {
+    let mut rng = ChaCha8Rng::seed_from_u64(10);
+
+    let mut row_major = ExpandableGrid::new();
+    let mut column_major = ExpandableGrid::with_layout(Layout::ColumnMajor);
+
+    for i in 0..100 {
+        let point = vector![rng.gen_range(-50..=50), rng.gen_range(-50..=50)];
+
+        row_major.expand_to_fit_point(point, &0);
+        column_major.expand_to_fit_point(point, &0);
+
+        row_major[point] = i;
+        column_major[point] = i;
+    }
+
+    assert_eq!(row_major.size, column_major.size);
+    assert_eq!(row_major.origin, column_major.origin);
+
+    let columns = column_major.columns().unwrap();
+    assert!(row_major.columns().is_none());
+    assert_eq!(columns.len(), column_major.size.x);
+
+    for (x, column) in columns.enumerate() {
+        for (y, value) in column.iter().enumerate() {
+            let point = column_major.origin + vector![x as isize, y as isize];
+            assert_eq!(*value, row_major[point], "point {point:?} should match");
+        }
+    }
+}
+
+#[test]
+fn grid3_expands_to_fit_points() {
+    let mut rng = ChaCha8Rng::seed_from_u64(10);
+    let mut grid = ExpandableGrid3::new();
+
+    let mut points = Vec::new();
+    for i in 0..100 {
+        let point = vector![
+            rng.gen_range(-100..=100),
+            rng.gen_range(-100..=100),
+            rng.gen_range(-100..=100),
+        ];
+
+        grid.expand_to_fit_point(point, &-1);
+        grid[point] = i;
+        points.push((point, i));
+    }
+
+    assert!(
+        grid.size.max() <= 201 * 4,
+        "size {:?} is too big",
+        grid.size
+    );
+    for (point, i) in points.into_iter().rev() {
+        // Later writes to the same point overwrite earlier ones
+        if grid[point] != -1 {
+            assert_eq!(grid[point], i, "point {point:?} should keep its value");
+            grid[point] = -1;
+        }
+    }
+}
+
+#[test]
+fn grid4_keeps_values_when_changing_size() {
+    let mut grid = ExpandableGridN::with_size(SVector::repeat(3), SVector::zeros(), &0);
+    for (i, value) in grid.data.iter_mut().enumerate() {
+        *value = i;
+    }
+    let original = grid.clone();
+
+    grid.change_size(SVector::repeat(5), vector![-1, 0, -2, 1], &usize::MAX);
+
+    assert_eq!(grid.origin, original.origin + vector![-1, 0, -2, 1]);
+    for (index, value) in original.data.iter().enumerate() {
+        let mut point = SVector::<isize, 4>::zeros();
+        let mut remaining = index;
+        for axis in 0..4 {
+            point[axis] = (remaining % 3) as isize;
+            remaining /= 3;
+        }
+
+        if let Some(new_value) = grid.get(point) {
+            assert_eq!(new_value, value, "point {point:?} should keep its value");
+        } else {
+            assert!(point[3] == 0, "only points with w = 0 should be cut off");
+        }
+    }
+}
+
+#[test]
+fn hex_helpers_agree_with_distance() {
+    let center = vector![3, -5];
+
+    for radius in 0..6 {
+        let ring: Vec<_> = hex::ring(center, radius).collect();
+        assert_eq!(ring.len(), if radius == 0 { 1 } else { radius * 6 });
+        assert!(ring.iter().all(|&hex| hex::distance(center, hex) == radius));
+
+        let range_size = hex::range(center, radius).count();
+        assert_eq!(range_size, 3 * radius * (radius + 1) + 1);
+    }
+
+    let mut rng = ChaCha8Rng::seed_from_u64(10);
+    for _ in 0..100 {
+        let start = vector![rng.gen_range(-50..=50), rng.gen_range(-50..=50)];
+        let end = vector![rng.gen_range(-50..=50), rng.gen_range(-50..=50)];
+
+        let line: Vec<_> = hex::line(start, end).collect();
+        assert_eq!(line.first(), Some(&start));
+        assert_eq!(line.last(), Some(&end));
+        assert!(line
+            .windows(2)
+            .all(|pair| hex::distance(pair[0], pair[1]) == 1));
+
+        for layout in [
+            hex::OffsetLayout::OddR,
+            hex::OffsetLayout::EvenR,
+            hex::OffsetLayout::OddQ,
+            hex::OffsetLayout::EvenQ,
+        ] {
+            let offset = hex::axial_to_offset(start, layout);
+            assert_eq!(hex::offset_to_axial(offset, layout), start);
+        }
+    }
+}
+
+#[test]
+fn grids_accept_other_vector_types() {
+    #[derive(Clone, Copy)]
+    struct Coord {
+        x: i32,
+        y: i32,
+    }
+
+    impl GridVector<isize, 2> for Coord {
+        fn to_vector(self) -> Vector2<isize> {
+            vector![self.x as isize, self.y as isize]
+        }
+
+        fn from_vector(vector: Vector2<isize>) -> Self {
+            Coord {
+                x: vector.x as i32,
+                y: vector.y as i32,
+            }
+        }
+    }
+
+    let mut grid = ExpandableGrid::new();
+    grid.expand_to_fit_point(Coord { x: -3, y: 5 }, &0);
+    grid.expand_to_fit_box(Point2::new(4, -1), vector![2, 2], &0);
+    grid[Coord { x: -3, y: 5 }] = 1;
+    grid[Point2::new(2, 2)] = 2;
+
+    assert_eq!(grid[vector![-3, 5]], 1);
+    assert_eq!(grid.get(Coord { x: 2, y: 2 }), Some(&2));
+    assert_eq!(grid.get(Point2::new(5, 0)), Some(&0));
+    assert_eq!(Coord::from_vector(grid.origin).x as isize, grid.origin.x);
+
+    grid[(0, 1)] = 3;
+    grid[[1, 0]] = 4;
+    assert_eq!(grid[vector![0, 1]], 3);
+    assert_eq!(grid.get([1, 0]), Some(&4));
+    assert_eq!(grid.get((100, 100)), None);
+
+    let mut grid3 = ExpandableGrid3::new();
+    grid3.expand_to_fit_box((-1, -1, -1), [3, 3, 3], &0);
+    grid3[(1, 0, -1)] = 5;
+    assert_eq!(grid3[[1, 0, -1]], 5);
+
+    let mut compact = ExpandableGrid::<u8, i32>::new();
+    compact.expand_to_fit_point(vector![-3, 5], &0);
+    compact.expand_to_fit_box(Point2::new(4, -1), vector![2, 2], &0);
+    compact[vector![2, 2]] = 2;
+    compact.change_size(vector![5, 5], vector![1, 1], &0);
+
+    assert_eq!(compact.origin, vector![-2, 0]);
+    assert_eq!(compact[vector![2, 2]], 2);
+    assert_eq!(compact.origin_as::<(i32, i32)>(), (-2, 0));
+    assert_eq!(compact.size_as::<[usize; 2]>(), [5, 5]);
+    assert_eq!(compact.corner_as::<(i32, i32)>(), (3, 5));
+    assert_eq!(compact.get(vector![-3, 5]), None);
+
+    assert_eq!(
+        compact.bounds(),
+        GridRect::new(vector![-2, 0], vector![5, 5])
+    );
+    assert_eq!(compact.find(|&cell| cell == 2), Some((vector![2, 2], &2)));
+    assert_eq!(
+        compact.nearest_where(vector![-100, -100], |&cell| cell == 2, 200),
+        Some((vector![2, 2], &2)),
+    );
+
+    let handle = compact.handle(vector![2, 2]).unwrap();
+    compact.expand_margins(vector![-4, 0], vector![1, 1], Margins::uniform(1), &0);
+    assert_eq!(compact.origin, vector![-5, 0]);
+    assert_eq!(compact.resolve(&handle), Some(&2));
+    assert_eq!(handle.position(), vector![2i32, 2]);
+
+    let mut bytes = Vec::new();
+    compact.write_binary(&mut bytes).unwrap();
+    let read = ExpandableGrid::<u8, i32>::read_binary(&mut bytes.as_slice()).unwrap();
+    assert_eq!((read.size, read.origin), (compact.size, compact.origin));
+    assert_eq!(read.data, compact.data);
+
+    let far = ExpandableGrid::<u8>::with_size(vector![1, 1], vector![i32::MAX as isize, 0], &0);
+    bytes.clear();
+    far.write_binary(&mut bytes).unwrap();
+    assert!(matches!(
+        ExpandableGrid::<u8, i32>::read_binary(&mut bytes.as_slice()),
+        Err(binary::BinaryError::SizeOverflow),
+    ));
+}
+
+#[test]
+fn ascii_maps_round_trip() {
+    let map = "
+        #..#
+        .##
+        #...
+    ";
+    let grid =
+        ExpandableGrid::from_ascii(map.replace("        ", "").as_str(), vector![-1, 3], |c| {
+            c == '#'
+        });
+
+    assert_eq!(grid.size, vector![4, 3]);
+    assert_eq!(grid.origin, vector![-1, 3]);
+    assert!(grid[vector![-1, 3]]);
+    assert!(grid[vector![2, 3]]);
+    assert!(grid[vector![0, 4]]);
+    assert!(!grid[vector![2, 4]]);
+    assert!(grid[vector![-1, 5]]);
+
+    let ascii = grid.to_ascii(|&wall| if wall { '#' } else { '.' });
+    assert_eq!(ascii, "#..#\n.##.\n#...");
+
+    let mut column_major = ExpandableGrid::with_layout(Layout::ColumnMajor);
+    column_major.expand_to_fit_box(grid.origin, grid.size, &false);
+    column_major.blit(&grid);
+    assert_eq!(
+        column_major.to_ascii(|&wall| if wall { '#' } else { '.' }),
+        ascii
+    );
+
+    assert_eq!(
+        ExpandableGrid::from_ascii("\n  \n", vector![0, 0], |_| 0).size,
+        vector![0, 0]
+    );
+}
+
+#[test]
+fn binary_format_round_trips() {
+    let mut rng = ChaCha8Rng::seed_from_u64(10);
+
+    let mut grid = ExpandableGrid::with_size_and_layout(
+        vector![13, 7],
+        vector![-4, 9],
+        &[0u8; 3],
+        Layout::ColumnMajor,
+    );
+    for cell in grid.data.iter_mut() {
+        *cell = [rng.gen(), rng.gen(), rng.gen()];
+    }
+
+    let mut bytes = Vec::new();
+    grid.write_binary(&mut bytes).unwrap();
+    let read = ExpandableGrid::<[u8; 3]>::read_binary(&mut bytes.as_slice()).unwrap();
+
+    assert_eq!(read.size, grid.size);
+    assert_eq!(read.origin, grid.origin);
+    assert_eq!(read.layout, grid.layout);
+    assert_eq!(read.data, grid.data);
+
+    let mut rle_bytes = Vec::new();
+    grid.write_binary_rle(&mut rle_bytes).unwrap();
+    let read = ExpandableGrid::<[u8; 3]>::read_binary(&mut rle_bytes.as_slice()).unwrap();
+    assert_eq!(read.data, grid.data);
+
+    assert!(matches!(
+        ExpandableGrid3::<[u8; 3]>::read_binary(&mut bytes.as_slice()),
+        Err(binary::BinaryError::DimensionMismatch { .. }),
+    ));
+    assert!(matches!(
+        ExpandableGrid::<[u8; 3]>::read_binary(&mut &bytes[..bytes.len() - 1]),
+        Err(binary::BinaryError::Io(_)),
+    ));
+}
+
+#[test]
+fn run_length_encoding_round_trips() {
+    let mut rng = ChaCha8Rng::seed_from_u64(10);
+
+    let mut grid = ExpandableGrid::with_size(vector![64, 64], vector![-32, -32], &0u16);
+    for cell in grid.data.iter_mut() {
+        // Mostly zeroes, with occasional short runs of other values
+        if rng.gen_range(0..20) == 0 {
+            *cell = rng.gen_range(1..4);
+        }
+    }
+
+    let runs = grid.encode_runs();
+    assert!(runs.iter().all(|&(length, _)| length > 0));
+    assert!(runs.windows(2).all(|pair| pair[0].1 != pair[1].1));
+
+    let decoded =
+        ExpandableGrid::from_runs(grid.size, grid.origin, grid.layout, runs.clone()).unwrap();
+    assert_eq!(decoded.data, grid.data);
+
+    let mut too_short = runs;
+    too_short.pop();
+    assert!(ExpandableGrid::from_runs(grid.size, grid.origin, grid.layout, too_short).is_none());
+
+    let mut bytes = Vec::new();
+    grid.write_binary_rle(&mut bytes).unwrap();
+    let mut raw_bytes = Vec::new();
+    grid.write_binary(&mut raw_bytes).unwrap();
+    assert!(bytes.len() < raw_bytes.len() / 2);
+
+    // A single run claiming far more cells than fit in memory is an error rather than an abort
+    let mut huge = Vec::new();
+    ExpandableGrid::with_size(vector![1, 1], vector![0, 0], &0u8)
+        .write_binary_rle(&mut huge)
+        .unwrap();
+    huge.truncate(17);
+    huge.extend_from_slice(&(1u64 << 40).to_le_bytes());
+    huge.extend_from_slice(&(1u64 << 20).to_le_bytes());
+    huge.extend_from_slice(&[0; 16]);
+    binary::write_varint(&mut huge, 1 << 60).unwrap();
+    huge.push(5);
+    assert!(matches!(
+        ExpandableGrid::<u8>::read_binary(&mut huge.as_slice()),
+        Err(binary::BinaryError::OutOfMemory { index: 0 }),
+    ));
+}
+
+#[test]
+fn region_files_load_intersecting_tiles() {
+    let mut rng = ChaCha8Rng::seed_from_u64(10);
+
+    let mut grid = ExpandableGrid::with_size(vector![37, 21], vector![-15, -4], &0u32);
+    for cell in grid.data.iter_mut() {
+        *cell = rng.gen();
+    }
+
+    let mut file = std::io::Cursor::new(Vec::new());
+    grid.write_region(vector![8, 8], &mut file).unwrap();
+    file.set_position(0);
+
+    let mut region = RegionReader::<_, 2>::open(file).unwrap();
+    assert_eq!(region.size(), grid.size);
+    assert_eq!(region.origin(), grid.origin);
+    assert_eq!(region.tiles().count(), 5 * 4);
+
+    let read = region.read_all(&u32::MAX).unwrap();
+    assert_eq!(read.data, grid.data);
+
+    let box_origin = vector![-20, 3];
+    let box_size = vector![10, 30];
+    let read = region.read_box(box_origin, box_size, &u32::MAX).unwrap();
+    assert_eq!(
+        read.data,
+        grid.copy_box(box_origin, box_size, &u32::MAX).data
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn serde_grids_round_trip() {
+    let mut grid = ExpandableGrid3::with_size_and_layout(
+        vector![3, 2, 2],
+        vector![-1, 4, 0],
+        &0i32,
+        Layout::ColumnMajor,
+    );
+    grid[vector![1, 5, 1]] = 7;
+    grid[vector![-1, 4, 0]] = -2;
+
+    let json = serde_json::to_string(&grid).unwrap();
+    let read: ExpandableGrid3<i32> = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        (read.size, read.origin, read.layout),
+        (grid.size, grid.origin, grid.layout)
+    );
+    assert_eq!(read.data, grid.data);
+
+    // Grids with other coordinate types keep their origin
+    let grid = ExpandableGridN::<u8, 2, i16>::with_size(vector![2, 1], vector![-300i16, 2], &5);
+    let read: ExpandableGridN<u8, 2, i16> =
+        serde_json::from_str(&serde_json::to_string(&grid).unwrap()).unwrap();
+    assert_eq!((read.size, read.origin), (grid.size, grid.origin));
+    assert_eq!(read.data[..], [5, 5]);
+
+    // The number of cells must match the size
+    let wrong_length = r#"{"version":2,"data_version":0,"size":[2,2],"origin":[0,0],"layout":"RowMajor","data":[1,2,3]}"#;
+    assert!(serde_json::from_str::<ExpandableGrid<u8>>(wrong_length).is_err());
+    let overflow = format!(
+        r#"{{"version":2,"data_version":0,"size":[{},3],"origin":[0,0],"layout":"RowMajor","data":[]}}"#,
+        usize::MAX,
+    );
+    assert!(serde_json::from_str::<ExpandableGrid<u8>>(&overflow).is_err());
+    // As must the number of dimensions
+    assert!(serde_json::from_str::<ExpandableGrid<i32>>(&json).is_err());
+}
+
+#[cfg(all(feature = "ron", feature = "json"))]
+#[test]
+fn readable_grids_list_rows() {
+    #[derive(::serde::Serialize, ::serde::Deserialize)]
+    struct Level {
+        #[serde(with = "crate::readable")]
+        tiles: ExpandableGrid<u8>,
+    }
+
+    let mut grid = ExpandableGrid::with_size(vector![3, 2], vector![-1, 4], &0u8);
+    grid[vector![1, 4]] = 1;
+    grid[vector![-1, 5]] = 2;
+    let mut column_major =
+        ExpandableGrid::with_size_and_layout(grid.size, grid.origin, &0, Layout::ColumnMajor);
+    column_major.blit(&grid);
+
+    // Rows are the same whatever the layout of the grid, and are each on their own line
+    let json = grid.to_json_string().unwrap();
+    assert_eq!(
+        column_major
+            .to_json_string()
+            .unwrap()
+            .replace("ColumnMajor", "RowMajor"),
+        json
+    );
+    assert!(json.contains("\n    [0,0,1],\n    [2,0,0]\n"));
+    let ron = grid.to_ron_string().unwrap();
+    assert!(ron.contains("[0, 0, 1]") && ron.contains("[2, 0, 0]"));
+
+    for grid in [&grid, &column_major] {
+        let read = ExpandableGrid::<u8>::from_json_str(&grid.to_json_string().unwrap()).unwrap();
+        assert_eq!(
+            (read.size, read.origin, read.layout),
+            (grid.size, grid.origin, grid.layout)
+        );
+        assert_eq!(read.data, grid.data);
+
+        let read = ExpandableGrid::<u8>::from_ron_str(&grid.to_ron_string().unwrap()).unwrap();
+        assert_eq!((read.layout, &read.data), (grid.layout, &grid.data));
+    }
+
+    let level = Level {
+        tiles: column_major.clone(),
+    };
+    let read: Level = serde_json::from_str(&serde_json::to_string(&level).unwrap()).unwrap();
+    assert_eq!(read.tiles.data, column_major.data);
+
+    // Rows must all be the width of the grid
+    let ragged = json.replace("[2,0,0]", "[2,0]");
+    assert!(ExpandableGrid::<u8>::from_json_str(&ragged).is_err());
+    let missing_row = json.replace(",\n    [2,0,0]", "");
+    assert!(ExpandableGrid::<u8>::from_json_str(&missing_row).is_err());
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn grids_convert_to_images() {
+    use crate::image::GridImage;
+
+    let mut grid = ExpandableGrid::with_size(vector![3, 2], vector![-1, 4], &0u8);
+    grid[vector![-1, 4]] = 10;
+    grid[vector![1, 5]] = 20;
+
+    // The origin is the top left pixel, and `y` increases downwards
+    let image = grid.to_gray_image();
+    assert_eq!(image.image.dimensions(), (3, 2));
+    assert_eq!(image.origin, grid.origin);
+    assert_eq!(image.image.get_pixel(0, 0).0, [10]);
+    assert_eq!(image.image.get_pixel(2, 1).0, [20]);
+
+    let read = ExpandableGrid::from(image);
+    assert_eq!((read.size, read.origin), (grid.size, grid.origin));
+    assert_eq!(read.data, grid.data);
+
+    // Column major grids are read by position rather than by their data
+    let mut colors = ExpandableGrid::with_size_and_layout(
+        vector![2, 3],
+        vector![0, 0],
+        &[0u8; 4],
+        Layout::ColumnMajor,
+    );
+    colors[vector![1, 0]] = [255, 0, 0, 255];
+    let image = GridImage::from(&colors);
+    assert_eq!(image.image.get_pixel(1, 0).0, [255, 0, 0, 255]);
+    assert_eq!(image.image.get_pixel(0, 1).0, [0; 4]);
+    let read = ExpandableGrid::from_rgba_image(&image.image, vector![5, -5]);
+    assert_eq!(read.origin, vector![5, -5]);
+    assert_eq!(read[vector![6, -5]], [255, 0, 0, 255]);
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn heightmaps_load_from_png() {
+    use ::image::{ImageBuffer, ImageFormat, Luma};
+    use std::io::Cursor;
+
+    // 8 bit images
+    let mut png = Cursor::new(Vec::new());
+    ImageBuffer::from_fn(3, 2, |x, y| Luma([(x + y * 3) as u8 * 51]))
+        .write_to(&mut png, ImageFormat::Png)
+        .unwrap();
+    png.set_position(0);
+    let heights = ExpandableGrid::from_heightmap_png(png, vector![-1, 4], 10.0).unwrap();
+    assert_eq!(
+        (heights.size, heights.origin),
+        (vector![3, 2], vector![-1, 4])
+    );
+    assert_eq!(heights[vector![-1, 4]], 0.0);
+    assert_eq!(heights[vector![0, 4]], 2.0);
+    assert_eq!(heights[vector![1, 5]], 10.0);
+
+    // 16 bit images keep their precision
+    let mut png = Cursor::new(Vec::new());
+    ImageBuffer::from_fn(2, 1, |x, _| Luma([[1u16, u16::MAX][x as usize]]))
+        .write_to(&mut png, ImageFormat::Png)
+        .unwrap();
+    png.set_position(0);
+    let heights = ExpandableGrid::from_heightmap_png(png, vector![0, 0], 1.0).unwrap();
+    assert_eq!(heights[vector![0, 0]], 1.0 / u16::MAX as f32);
+    assert_eq!(heights[vector![1, 0]], 1.0);
+
+    assert!(
+        ExpandableGrid::from_heightmap_png(Cursor::new(b"not a png"), vector![0, 0], 1.0).is_err()
+    );
+}
+
+#[cfg(feature = "mint")]
+#[test]
+fn mint_vectors_index_grids() {
+    let mut grid = ExpandableGrid::with_size(
+        mint::Vector2 { x: 3usize, y: 2 },
+        mint::Point2 { x: -1isize, y: 4 },
+        &0,
+    );
+    assert_eq!((grid.size, grid.origin), (vector![3, 2], vector![-1, 4]));
+
+    grid[mint::Point2 { x: 1isize, y: 5 }] = 7;
+    assert_eq!(grid[vector![1, 5]], 7);
+    assert_eq!(grid.get(mint::Vector2 { x: 2isize, y: 5 }), None);
+
+    grid.expand_to_fit_point(mint::Vector2 { x: 3isize, y: 4 }, &1);
+    assert_eq!(grid[vector![3, 4]], 1);
+    let size: mint::Vector2<usize> = grid.size.into();
+    assert_eq!((size.x, size.y), (grid.size.x, grid.size.y));
+
+    let mut grid = ExpandableGrid3::with_size(vector![2, 2, 2], vector![0, 0, 0], &0);
+    grid[mint::Vector3 {
+        x: 1isize,
+        y: 0,
+        z: 1,
+    }] = 3;
+    assert_eq!(grid[vector![1, 0, 1]], 3);
+    let point = mint::Point3::<isize>::from_vector(vector![1, 2, 3]);
+    assert_eq!((point.x, point.y, point.z), (1, 2, 3));
+}
+
+#[cfg(feature = "glam")]
+#[test]
+fn glam_vectors_index_grids() {
+    use crate::glam::*;
+    use ::glam::{IVec2, IVec3, UVec2, UVec3};
+
+    let mut grid = ExpandableGrid::with_size(UVec2::new(3, 2), IVec2::new(-1, 4), &0);
+    assert_eq!((grid.size, grid.origin), (vector![3, 2], vector![-1, 4]));
+
+    grid[IVec2::new(1, 5)] = 7;
+    assert_eq!(grid[vector![1, 5]], 7);
+    assert_eq!(grid.get(IVec2::new(2, 5)), None);
+
+    assert_eq!(to_ivec2(grid.origin), Some(IVec2::new(-1, 4)));
+    assert_eq!(to_uvec2(grid.size), Some(UVec2::new(3, 2)));
+    assert_eq!(from_ivec2(IVec2::new(-5, 6)), vector![-5, 6]);
+    assert_eq!(from_uvec2(UVec2::new(5, 6)), vector![5, 6]);
+
+    // Coordinates which don't fit in glam's 32 bit vectors aren't converted
+    assert_eq!(to_ivec2(vector![i32::MAX as isize + 1, 0]), None);
+    assert_eq!(to_uvec2(vector![0, u32::MAX as usize + 1]), None);
+
+    let mut grid = ExpandableGrid3::with_size(UVec3::new(2, 2, 2), IVec3::new(0, -1, 0), &0);
+    grid[IVec3::new(1, 0, 1)] = 3;
+    assert_eq!(grid[vector![1, 0, 1]], 3);
+    assert_eq!(
+        to_ivec3(from_ivec3(IVec3::new(1, -2, 3))),
+        Some(IVec3::new(1, -2, 3))
+    );
+    assert_eq!(
+        to_uvec3(from_uvec3(UVec3::new(1, 2, 3))),
+        Some(UVec3::new(1, 2, 3))
+    );
+
+    // Grids with other coordinate types take glam vectors too
+    let mut grid = ExpandableGrid::<u8, i32>::with_size([2, 2], IVec2::new(-1, -1), &0);
+    grid[IVec2::new(0, 0)] = 4;
+    assert_eq!(grid.origin, vector![-1i32, -1]);
+    assert_eq!(grid.get(IVec2::new(0, 0)), Some(&4));
+    let mut grid = ExpandableGrid3::<u8, i16>::new();
+    grid.expand_to_fit_point(IVec3::new(1, 2, 3), &5);
+    assert_eq!(grid[[1i16, 2, 3]], 5);
+    assert_eq!(
+        <IVec3 as GridVector<i16, 3>>::from_vector(vector![1, 2, 3]),
+        IVec3::new(1, 2, 3)
+    );
+}
+
+#[cfg(feature = "egui")]
+#[test]
+fn grid_inspectors_draw_and_select_cells() {
+    use crate::egui::GridInspector;
+    use ::egui::{epaint::Shape, Color32, Event, Pos2, RawInput, Rect, Vec2};
+
+    let mut grid = ExpandableGrid::with_size(vector![4, 3], vector![-2, 1], &0u8);
+    grid[vector![1, 3]] = 10;
+
+    let ctx = ::egui::Context::default();
+    let mut selected = None;
+    let mut time = 0.0;
+    let mut run = |events: Vec<Event>| {
+        time += 0.1;
+        let input = RawInput {
+            events,
+            time: Some(time),
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, Vec2::new(400.0, 300.0))),
+            ..Default::default()
+        };
+        ctx.run(input, |ctx| {
+            ::egui::CentralPanel::default()
+                .frame(::egui::Frame::none())
+                .show(ctx, |ui| {
+                    ui.add(
+                        GridInspector::new(&grid, |&cell| cell as f32 / 10.0, u8::to_string)
+                            .id_source("map")
+                            .cell_size(10.0)
+                            .colors(Color32::BLACK, Color32::WHITE),
+                    );
+                    selected = GridInspector::<u8>::selected(ui, "map");
+                });
+        })
+    };
+
+    // The origin is drawn at the top left, with `y` increasing downwards
+    let output = run(Vec::new());
+    let filled = |color| {
+        output
+            .shapes
+            .iter()
+            .filter_map(|clipped| match &clipped.shape {
+                Shape::Rect(rect) if rect.fill == color => Some(rect.rect),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        filled(Color32::WHITE),
+        vec![Rect::from_min_size(
+            Pos2::new(30.0, 20.0),
+            Vec2::splat(10.0)
+        )],
+    );
+    assert_eq!(filled(Color32::BLACK).len(), 11);
+
+    // Clicking a cell selects it
+    let pos = Pos2::new(15.0, 25.0);
+    let click = |pressed| Event::PointerButton {
+        pos,
+        button: ::egui::PointerButton::Primary,
+        pressed,
+        modifiers: Default::default(),
+    };
+    run(vec![Event::PointerMoved(pos), click(true)]);
+    run(vec![click(false)]);
+    run(Vec::new());
+    assert_eq!(selected, Some(vector![-1, 3]));
+}
+
+#[cfg(feature = "python")]
+#[test]
+fn python_grids_index_and_convert_to_numpy() {
+    use pyo3::{prelude::*, types::PyDict};
+
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let module = PyModule::new_bound(py, "expandable_grid").unwrap();
+        crate::python::expandable_grid(&module).unwrap();
+        let locals = PyDict::new_bound(py);
+        locals.set_item("eg", module).unwrap();
+
+        py.run_bound(
+            r#"
+grid = eg.GridI64((3, 2), (-1, 4), 7)
+assert grid.size == (3, 2) and grid.origin == (-1, 4)
+assert grid[-1, 4] == 7 and grid.get((2, 4)) is None
+grid[1, 5] = 9
+assert grid[1, 5] == 9
+try:
+    grid[2, 4] = 1
+    raise AssertionError("wrote outside the grid")
+except IndexError:
+    pass
+grid.expand_to_fit_point((-3, 4), 0)
+assert grid.origin[0] <= -3 and grid[1, 5] == 9
+assert repr(eg.GridF64((1, 1))) == "GridF64(size=(1, 1), origin=(0, 0))"
+"#,
+            None,
+            Some(&locals),
+        )
+        .unwrap();
+
+        // The arrays are only checked where numpy is installed
+        if py.import_bound("numpy").is_err() {
+            return;
+        }
+        py.run_bound(
+            r#"
+import numpy as np
+grid = eg.GridI64((3, 2), (-1, 4), 0)
+grid[1, 5] = 9
+array = grid.to_numpy()
+assert array.shape == (2, 3) and array[1, 2] == 9
+array[0, 0] = 5
+copy = eg.GridI64.from_numpy(array, (-1, 4))
+assert copy[-1, 4] == 5 and copy[1, 5] == 9 and grid[-1, 4] == 0
+assert (eg.GridF64.from_numpy(np.ones((2, 4))).to_numpy() == 1.0).all()
+"#,
+            None,
+            Some(&locals),
+        )
+        .unwrap();
+    });
+}
+
+#[cfg(feature = "bytemuck")]
+#[test]
+fn bytes_round_trip() {
+    let mut grid = ExpandableGrid::with_size(vector![3, 2], vector![-1, 4], &0u32);
+    grid[vector![1, 5]] = 0x01020304;
+
+    let bytes = grid.as_bytes().to_vec();
+    assert_eq!(bytes.len(), 24);
+
+    let read = ExpandableGrid::<u32>::from_bytes(grid.size, grid.origin, &bytes).unwrap();
+    assert_eq!(read.data, grid.data);
+    assert!(ExpandableGrid::<u32>::from_bytes(grid.size, grid.origin, &bytes[1..]).is_none());
+
+    // Unaligned bytes are copied rather than rejected
+    let mut unaligned = vec![0];
+    unaligned.extend_from_slice(&bytes);
+    let read = ExpandableGrid::<u32>::from_bytes(grid.size, grid.origin, &unaligned[1..]).unwrap();
+    assert_eq!(read[vector![1, 5]], 0x01020304);
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn compressed_grids_round_trip() {
+    use crate::compression;
+
+    let mut grid = ExpandableGrid::with_size(vector![40, 30], vector![-5, 2], &0u16);
+    grid.fill_box(vector![0, 10], vector![20, 5], &7);
+    grid[vector![-5, 2]] = 3;
+
+    let mut bytes = Vec::new();
+    grid.write_compressed(&mut bytes).unwrap();
+    let mut raw_bytes = Vec::new();
+    grid.write_binary(&mut raw_bytes).unwrap();
+    assert!(bytes.len() < raw_bytes.len() / 4);
+
+    let read = ExpandableGrid::<u16>::read_compressed(&mut bytes.as_slice()).unwrap();
+    assert_eq!(
+        (read.size, read.origin, read.layout),
+        (grid.size, grid.origin, grid.layout)
+    );
+    assert_eq!(read.data, grid.data);
+
+    // Compressed grids are a plain LZ4 frame around the binary format
+    assert_eq!(compression::decompress(&bytes).unwrap(), raw_bytes);
+    let frame = compression::compress(&raw_bytes).unwrap();
+    assert_eq!(compression::decompress(&frame).unwrap(), raw_bytes);
+    assert_eq!(
+        compression::decompress(&compression::compress(&[]).unwrap()).unwrap(),
+        [0u8; 0]
+    );
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn corrupt_compressed_grids_are_rejected() {
+    use crate::compression;
+
+    let grid = ExpandableGrid::with_size(vector![16, 16], vector![0, 0], &1u32);
+    let mut bytes = Vec::new();
+    grid.write_compressed(&mut bytes).unwrap();
+    let mut raw_bytes = Vec::new();
+    grid.write_binary(&mut raw_bytes).unwrap();
+
+    // Not an LZ4 frame
+    assert!(matches!(
+        ExpandableGrid::<u32>::read_compressed(&mut raw_bytes.as_slice()),
+        Err(binary::BinaryError::Io(_)),
+    ));
+    assert!(compression::decompress(b"not a frame").is_err());
+
+    // Truncated frames
+    assert!(matches!(
+        ExpandableGrid::<u32>::read_compressed(&mut &bytes[..bytes.len() / 2]),
+        Err(binary::BinaryError::Io(_)),
+    ));
+    assert!(compression::decompress(&bytes[..bytes.len() / 2]).is_err());
+
+    // A valid frame which doesn't contain a grid
+    let frame = compression::compress(b"not a grid").unwrap();
+    assert!(matches!(
+        ExpandableGrid::<u32>::read_compressed(&mut frame.as_slice()),
+        Err(binary::BinaryError::InvalidMagic(_)),
+    ));
+
+    // A valid grid with the wrong cell type
+    assert!(matches!(
+        ExpandableGrid::<u16>::read_compressed(&mut bytes.as_slice()),
+        Err(binary::BinaryError::CellSizeMismatch { .. }),
+    ));
+}
+
+#[cfg(feature = "ndarray")]
+#[test]
+fn array_views_round_trip() {
+    use ::ndarray::ShapeBuilder;
+
+    for layout in [Layout::RowMajor, Layout::ColumnMajor] {
+        let mut grid =
+            ExpandableGrid::with_size_and_layout(vector![3, 2], vector![-1, 4], &0, layout);
+        grid[vector![1, 4]] = 5;
+        grid[vector![-1, 5]] = 6;
+
+        // Views are indexed with `[y, x]` relative to the origin
+        let view = grid.as_array_view();
+        assert_eq!(view.dim(), (2, 3));
+        assert_eq!(view[[0, 2]], 5);
+        assert_eq!(view[[1, 0]], 6);
+        assert_eq!(view.iter().filter(|&&cell| cell != 0).count(), 2);
+
+        grid.as_array_view_mut()[[1, 1]] = 7;
+        assert_eq!(grid[vector![0, 5]], 7);
+
+        let read = ExpandableGrid::from_array(grid.as_array_view().to_owned(), grid.origin);
+        assert_eq!((read.size, read.origin), (grid.size, grid.origin));
+        for position in grid.bounds().iter() {
+            assert_eq!(read[position], grid[position]);
+        }
+
+        // Arrays in column major order become column major grids
+        let mut array = ::ndarray::Array2::zeros((2, 3).f());
+        array.assign(&grid.as_array_view());
+        let read = ExpandableGrid::from_array(array, grid.origin);
+        assert_eq!(read.layout, Layout::ColumnMajor);
+        assert_eq!(read[vector![0, 5]], 7);
+        assert_eq!(read[vector![1, 4]], 5);
+    }
+}
+
+#[cfg(feature = "wgpu")]
+#[test]
+fn texture_rows_are_padded() {
+    let mut grid = ExpandableGrid::with_size_and_layout(
+        vector![3, 2],
+        vector![0, 0],
+        &0u32,
+        Layout::ColumnMajor,
+    );
+    grid[vector![2, 1]] = 7;
+
+    let (data, bytes_per_row) = grid.to_padded_texture_data();
+    assert_eq!(bytes_per_row, 256);
+    assert_eq!(data.len(), 512);
+    assert_eq!(&data[256 + 8..256 + 12], &7u32.to_ne_bytes());
+    assert!(data[256 + 12..].iter().all(|&byte| byte == 0));
+}
+
+#[cfg(feature = "bevy")]
+#[test]
+fn grid_components_are_reflected() {
+    use crate::bevy::GridComponent;
+    use ::bevy_ecs::{reflect::ReflectComponent, world::World};
+    use ::bevy_reflect::{std_traits::ReflectDefault, FromReflect, Reflect, TypeRegistry};
+
+    let mut registry = TypeRegistry::new();
+    registry.register::<GridComponent<u8>>();
+    let registration = registry
+        .get(std::any::TypeId::of::<GridComponent<u8>>())
+        .unwrap();
+    let default = registration.data::<ReflectDefault>().unwrap().default();
+    assert_eq!(
+        format!("{default:?}"),
+        format!("{:?}", GridComponent::<u8>::default()),
+    );
+
+    let mut world = World::new();
+    let entity = world.spawn_empty().id();
+    registration.data::<ReflectComponent>().unwrap().insert(
+        &mut world.entity_mut(entity),
+        &*default,
+        &registry,
+    );
+    let mut grid = world.get_mut::<GridComponent<u8>>(entity).unwrap();
+    GridComponent::expand_to_fit_point(&mut grid, [1, 2], &3);
+    assert!(GridComponent::set(&mut grid, [1, 2], 4));
+    assert_eq!(world.get::<GridComponent<u8>>(entity).unwrap()[[1, 2]], 4);
+
+    // Cloning through reflection and inserting a reflected grid keep its cells
+    let reflected = world
+        .get::<GridComponent<u8>>(entity)
+        .unwrap()
+        .clone_value();
+    let cloned = GridComponent::<u8>::from_reflect(&*reflected).unwrap();
+    assert_eq!((cloned.origin, cloned[[1, 2]]), (vector![1, 2], 4));
+
+    let copy = world.spawn_empty().id();
+    registration.data::<ReflectComponent>().unwrap().insert(
+        &mut world.entity_mut(copy),
+        &*reflected,
+        &registry,
+    );
+    assert_eq!(world.get::<GridComponent<u8>>(copy).unwrap()[[1, 2]], 4);
+}
+
+#[cfg(feature = "tiled")]
+#[test]
+fn tiled_maps_import() {
+    use crate::tiled::{tile_flips, TiledMap, FLIPPED_HORIZONTALLY, TILE_ID_MASK};
+
+    let tmx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="3" height="2" tilewidth="8" tileheight="8" infinite="0">
+ <!-- <tileset firstgid="99"/> -->
+ <tileset firstgid="1" name="a" tilewidth="8" tileheight="8" tilecount="4" columns="2"/>
+ <tileset firstgid='10' name="b" tilewidth="8" tileheight="8" tilecount="4" columns="2"/>
+ <layer id="1" name="ground" width="3" height="2">
+  <data encoding="csv">1,0,11,
+2147483650,0,0</data>
+ </layer>
+ <group id="2" name="group">
+  <layer id="3" name="detail" width="3" height="2">
+   <data encoding="csv">0,0,0,
+0,0,13</data>
+  </layer>
+ </group>
+</map>"#;
+    let path = std::env::temp_dir().join("expandable_grid_tiled_test.tmx");
+    std::fs::write(&path, tmx).unwrap();
+
+    let map = TiledMap::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // Tilesets keep their stored first IDs, even with a gap between them
+    assert_eq!(map.first_gids, vec![1, 10]);
+    assert_eq!(map.layers.len(), 2);
+
+    let ground = &map.layer("ground").unwrap().grid;
+    assert_eq!(ground.size, vector![3, 2]);
+    assert_eq!(ground.origin, vector![0, 0]);
+    assert_eq!(ground[vector![0, 0]], 1);
+    assert_eq!(ground[vector![1, 0]], 0);
+    assert_eq!(map.tileset_tile(ground[vector![2, 0]]), Some((1, 1)));
+
+    // Flip flags are kept in the high bits of the ID
+    let flipped = ground[vector![0, 1]];
+    assert_eq!(flipped, 2 | FLIPPED_HORIZONTALLY);
+    assert_eq!(flipped & TILE_ID_MASK, 2);
+    assert!(tile_flips(flipped).horizontal && !tile_flips(flipped).vertical);
+
+    let detail = &map.layer("detail").unwrap().grid;
+    assert_eq!(detail[vector![2, 1]], 13);
+    assert_eq!(map.tileset_tile(13), Some((1, 3)));
+    assert_eq!(map.tileset_tile(0), None);
+
+    // IDs before the first tileset belong to no tileset
+    let map = TiledMap {
+        layers: Vec::new(),
+        first_gids: vec![5],
+    };
+    assert_eq!(map.tileset_tile(3), None);
+    assert_eq!(map.tileset_tile(7), Some((0, 2)));
+}
+
+#[cfg(feature = "ffi")]
+#[test]
+fn ffi_grids_expand() {
+    use crate::ffi::*;
+
+    extern "C" fn sum_cells(_x: isize, _y: isize, value: i64, user_data: *mut std::ffi::c_void) {
+        unsafe { *(user_data as *mut i64) += value };
+    }
+
+    unsafe {
+        let grid = eg_grid_new();
+        assert!(eg_grid_expand_to_fit_point(grid, -3, 2, 0));
+        assert!(eg_grid_set(grid, -3, 2, 5));
+        assert!(!eg_grid_set(grid, 100, 2, 5));
+
+        assert!(eg_grid_expand_to_fit_box(grid, 4, 4, 2, 2, 1));
+        let mut value = 0;
+        assert!(eg_grid_get(grid, -3, 2, &mut value));
+        assert_eq!(value, 5);
+        assert!(eg_grid_get(grid, 5, 5, &mut value));
+        assert_eq!(value, 1);
+
+        let (mut width, mut height, mut x, mut y) = (0, 0, 0, 0);
+        eg_grid_size(grid, &mut width, &mut height);
+        eg_grid_origin(grid, &mut x, &mut y);
+        assert_eq!((*grid).size, vector![width, height]);
+        assert_eq!((*grid).origin, vector![x, y]);
+
+        let mut length = 0;
+        let data = eg_grid_data(grid, &mut length);
+        assert_eq!(length, width * height);
+        assert_eq!(eg_grid_layout(grid), EG_LAYOUT_ROW_MAJOR);
+        let expected: i64 = std::slice::from_raw_parts(data, length).iter().sum();
+
+        let mut sum = 0i64;
+        eg_grid_for_each(grid, sum_cells, &mut sum as *mut i64 as *mut _);
+        assert_eq!(sum, expected);
+
+        // Requests which are too large leave the grid unchanged
+        assert!(!eg_grid_expand_to_fit_box(grid, 0, 0, usize::MAX, 1, 0));
+        assert!(!eg_grid_expand_to_fit_point(grid, isize::MIN, 0, 0));
+        assert!(!eg_grid_change_size(grid, usize::MAX / 4, 4, 0, 0, 0));
+        assert!(!eg_grid_change_size(grid, 1, 1, isize::MIN, 0, 0));
+        assert_eq!((*grid).size, vector![width, height]);
+        assert_eq!((*grid).origin, vector![x, y]);
+        assert!(eg_grid_change_size(grid, 1, 1, 0, 0, 0));
+        assert_eq!((*grid).size, vector![1, 1]);
+
+        eg_grid_free(grid);
+        eg_grid_free(std::ptr::null_mut());
+
+        // Column major grids store each column contiguously
+        let grid = eg_grid_with_size_and_layout(2, 3, -1, 0, 0, EG_LAYOUT_COLUMN_MAJOR);
+        assert_eq!(eg_grid_layout(grid), EG_LAYOUT_COLUMN_MAJOR);
+        assert!(eg_grid_set(grid, 0, 1, 7));
+        let data = eg_grid_data(grid, &mut length);
+        assert_eq!(std::slice::from_raw_parts(data, length), [0, 0, 0, 0, 7, 0]);
+        eg_grid_free(grid);
+
+        let grid = eg_grid_with_layout(EG_LAYOUT_COLUMN_MAJOR);
+        assert!(eg_grid_expand_to_fit_box(grid, 0, 0, 2, 2, 3));
+        assert_eq!(eg_grid_layout(grid), EG_LAYOUT_COLUMN_MAJOR);
+        eg_grid_free(grid);
+        assert!(eg_grid_with_layout(2).is_null());
+        assert!(eg_grid_with_size_and_layout(1, 1, 0, 0, 0, 2).is_null());
+        assert!(eg_grid_with_size(usize::MAX, 2, 0, 0, 0).is_null());
+        assert!(eg_grid_with_size(1, 1, isize::MAX, 0, 0).is_null());
+    }
+}
+
+#[test]
+fn map_and_zip_map_keep_coordinates() {
+    let mut grid = ExpandableGrid3::with_size(vector![3, 2, 4], vector![-1, 0, 2], &0);
+    let mut column_major =
+        ExpandableGrid3::with_size_and_layout(grid.size, grid.origin, &0, Layout::ColumnMajor);
+    for (index, position) in util::iter_box(SVector::zeros(), grid.size).enumerate() {
+        let position = grid.origin + util::usize_vec_to_isize(position);
+        grid[position] = index as i32;
+        column_major[position] = index as i32 * 10;
+    }
+
+    let doubled = grid.map(|&cell| cell as i64 * 2);
+    assert_eq!((doubled.size, doubled.origin), (grid.size, grid.origin));
+    assert_eq!(doubled[vector![1, 1, 3]], grid[vector![1, 1, 3]] as i64 * 2);
+
+    let zipped = grid.zip_map(&column_major, |&a, &b| (a, b));
+    assert!(zipped.data.iter().all(|&(a, b)| a * 10 == b));
+
+    for index in 0..grid.data.len() {
+        assert_eq!(
+            Layout::ColumnMajor
+                .linear_index(Layout::ColumnMajor.position(index, grid.size), grid.size),
+            index,
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    {
+        assert_eq!(grid.par_map(|&cell| cell as i64 * 2).data, doubled.data);
+        assert_eq!(
+            grid.par_zip_map(&column_major, |&a, &b| (a, b)).data,
+            zipped.data
+        );
+        assert_eq!(
+            column_major.par_zip_map(&grid, |&a, &b| a - b * 10).data,
+            vec![0; grid.data.len()].into_boxed_slice(),
+        );
+    }
+}
+
+#[test]
+fn concurrent_grids_expand_and_write_from_many_threads() {
+    let grid = ConcurrentGrid::new(vector![4, 4], 0u32);
+    assert_eq!(grid.get(vector![0, 0]), None);
+
+    std::thread::scope(|scope| {
+        for thread in 0..4 {
+            let grid = &grid;
+            scope.spawn(move || {
+                for i in 0..50 {
+                    let point = vector![thread * 10 - 20, i - 25];
+                    grid.expand_to_fit_point(point);
+                    assert_eq!(grid.set(point, (thread * 100 + i) as u32), Some(0));
+                }
+            });
+        }
+    });
+
+    for thread in 0..4 {
+        for i in 0..50 {
+            let point = vector![thread * 10 - 20, i - 25];
+            assert_eq!(grid.get(point), Some((thread * 100 + i) as u32));
+        }
+    }
+    assert_eq!(grid.origin().map(|component| component % 4), vector![0, 0]);
+
+    let copy = grid.to_grid();
+    assert_eq!((copy.size, copy.origin), (grid.size(), grid.origin()));
+    assert_eq!(copy[vector![10, 24]], 349);
+
+    let round_trip = ConcurrentGrid::from_grid(&copy, vector![3, 5], 0);
+    assert_eq!(round_trip.get(vector![-20, -25]), Some(0));
+    assert_eq!(round_trip.modify(vector![10, 24], |cell| *cell), Some(349));
+}
+
+#[test]
+fn atomic_grids_accumulate_from_many_threads() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    let mut heatmap: AtomicGrid<AtomicU32> = ExpandableGrid::new();
+    heatmap.expand_to_fit_box(vector![-4, -4], vector![8, 8], &AtomicCell::new(0));
+
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            let heatmap = &heatmap;
+            scope.spawn(move || {
+                for i in 0..100 {
+                    let point = vector![i % 8 - 4, i / 25 - 4];
+                    heatmap.fetch_add(point, 1, Ordering::Relaxed).unwrap();
+                }
+            });
+        }
+    });
+
+    assert_eq!(heatmap.load(vector![-4, -4], Ordering::Acquire), Some(16));
+    assert_eq!(heatmap.load(vector![100, 0], Ordering::Relaxed), None);
+    assert_eq!(
+        heatmap.fetch_max(vector![3, 3], 7, Ordering::AcqRel),
+        Some(0)
+    );
+    assert!(heatmap.store(vector![0, 0], 2, Ordering::Release));
+
+    // Expanding keeps the values of existing cells
+    heatmap.expand_to_fit_point(vector![10, 0], &AtomicCell::new(0));
+    let values = heatmap.to_values(Ordering::Relaxed);
+    assert_eq!(values[vector![3, 3]], 7);
+    assert_eq!(values[vector![0, 0]], 2);
+    assert_eq!(values.data.iter().sum::<u32>(), 400 + 2 + 7);
+    assert_eq!(
+        AtomicGrid::<AtomicU32>::from_values(&values)
+            .to_values(Ordering::Relaxed)
+            .data,
+        values.data,
+    );
+}
+
+#[test]
+fn cow_snapshots_share_unwritten_chunks() {
+    let mut grid = CowGrid::new(vector![4, 4], 0u8);
+    grid.expand_to_fit_box(vector![-5, -5], vector![10, 10]);
+    // New chunks share the fill chunk until written to
+    assert_eq!(grid.is_shared(vector![0, 0]), Some(true));
+
+    grid[vector![1, 1]] = 1;
+    grid[vector![-5, 4]] = 2;
+    assert_eq!(grid.is_shared(vector![0, 0]), Some(false));
+
+    let snapshot = grid.snapshot();
+    assert_eq!(grid.is_shared(vector![0, 0]), Some(true));
+
+    grid[vector![1, 1]] = 3;
+    grid.expand_to_fit_point(vector![30, 0]);
+    assert_eq!(grid.is_shared(vector![0, 0]), Some(false));
+    assert_eq!(grid.is_shared(vector![-5, 4]), Some(true));
+
+    assert_eq!(snapshot[vector![1, 1]], 1);
+    assert_eq!(grid[vector![1, 1]], 3);
+    assert_eq!(snapshot.get(vector![30, 0]), None);
+    assert_eq!(grid[vector![30, 0]], 0);
+
+    let copy = snapshot.to_grid();
+    assert_eq!(
+        (copy.size, copy.origin),
+        (snapshot.size(), snapshot.origin())
+    );
+    assert_eq!(copy[vector![-5, 4]], 2);
+    assert_eq!(
+        CowGrid::from_grid(&copy, vector![3, 3], 0).to_grid()[vector![-5, 4]],
+        2
+    );
+}
+
+#[test]
+fn cell_grids_update_through_shared_references() {
+    let mut grid: CellGrid<u32> =
+        ExpandableGrid::with_size(vector![2, 2], vector![0, 0], &Default::default());
+
+    // Two closures borrowing the grid at once
+    let set = |value| assert!(grid.set_value(vector![0, 0], value));
+    let double = || grid.update(vector![0, 0], |value| value * 2).unwrap();
+    set(5);
+    double();
+
+    assert_eq!(grid.get_value(vector![0, 0]), Some(10));
+    assert_eq!(grid.replace(vector![1, 1], 3), Some(0));
+    assert!(!grid.set_value(vector![2, 0], 1));
+
+    grid.expand_to_fit_point(vector![-1, 0], &Default::default());
+    let values = grid.to_values();
+    assert_eq!(values[vector![0, 0]], 10);
+    assert_eq!(values[vector![1, 1]], 3);
+    assert_eq!(CellGrid::from_values(&values).to_values().data, values.data);
+}
+
+#[test]
+fn double_buffered_grids_expand_together() {
+    let mut grid = DoubleBufferedGrid::new(ExpandableGrid::new());
+    grid.expand_to_fit_point(vector![3, -2], &false);
+    grid.front_mut()[vector![3, -2]] = true;
+    grid.expand_to_fit_box(vector![-4, 5], vector![2, 2], &false);
+
+    for _ in 0..3 {
+        grid.step(|read, write| {
+            for (index, cell) in write.data.iter_mut().enumerate() {
+                let position =
+                    read.origin + util::usize_vec_to_isize(read.layout.position(index, read.size));
+                // Each live cell moves one cell to the left
+                *cell = read.get(position + vector![1, 0]) == Some(&true);
+            }
+        });
+        assert_eq!(
+            (grid.back().size, grid.back().origin),
+            (grid.front().size, grid.front().origin),
+        );
+    }
+    assert!(grid.front()[vector![0, -2]]);
+    assert_eq!(grid.front().data.iter().filter(|&&cell| cell).count(), 1);
+
+    grid.change_size(vector![4, 4], vector![-1, -1], &false);
+    grid.copy_front_to_back();
+    assert_eq!(grid.back().data, grid.front().data);
+    assert_eq!(grid.front().origin, grid.back().origin);
+}
+
+#[test]
+fn active_grids_only_visit_changing_cells() {
+    // Falling sand, where `y` increases downwards and the grid's lowest row is the floor
+    let mut grid = ActiveGrid::new(ExpandableGrid::with_size(
+        vector![20, 20],
+        vector![0, 0],
+        &false,
+    ));
+    grid.step(|_, _| None);
+    assert!(grid.active().is_empty());
+
+    assert!(grid.set(vector![5, 0], true));
+    assert_eq!(grid.active().len(), 9);
+
+    let mut visited = 0;
+    let mut steps = 0;
+    while !grid.active().is_empty() {
+        steps += 1;
+        grid.step(|grid, index| {
+            visited += 1;
+            let filled = |offset| grid.get(index + offset).copied();
+
+            match (
+                filled(vector![0, 0]),
+                filled(vector![0, -1]),
+                filled(vector![0, 1]),
+            ) {
+                // Fall into an empty cell below
+                (Some(true), _, Some(false)) => Some(false),
+                // Be filled from above
+                (Some(false), Some(true), _) => Some(true),
+                _ => None,
+            }
+        });
+    }
+
+    assert!(grid.grid()[vector![5, 19]]);
+    assert_eq!(grid.grid().data.iter().filter(|&&cell| cell).count(), 1);
+    assert_eq!(steps, 20);
+    assert!(visited < 20 * 20);
+}
+
+#[test]
+fn tick_scheduler_returns_due_cells_in_order() {
+    let mut grid = ExpandableGrid::with_size(vector![4, 4], vector![0, 0], &0);
+    let mut scheduler = TickScheduler::new();
+
+    assert_eq!(scheduler.schedule(vector![1, 1], 3), 3);
+    assert_eq!(scheduler.schedule(vector![2, 2], 1), 1);
+    assert_eq!(scheduler.schedule(vector![3, 3], 3), 3);
+    // Scheduling again keeps the earlier tick
+    assert_eq!(scheduler.schedule(vector![1, 1], 5), 3);
+    assert_eq!(scheduler.schedule(vector![3, 3], 2), 2);
+
+    // Expanding the grid doesn't affect the scheduled cells
+    grid.expand_to_fit_point(vector![-10, 0], &0);
+
+    assert_eq!(scheduler.advance(), vec![vector![2, 2]]);
+    assert_eq!(scheduler.advance(), vec![vector![3, 3]]);
+    scheduler.reschedule(vector![1, 1], 2);
+    scheduler.schedule(vector![-10, 0], 1);
+    assert_eq!(scheduler.advance(), vec![vector![-10, 0]]);
+    assert_eq!(scheduler.due(vector![1, 1]), Some(4));
+
+    scheduler.schedule(vector![100, 100], 1);
+    scheduler.retain_within(&grid);
+    assert_eq!(scheduler.len(), 1);
+    assert_eq!(scheduler.advance(), vec![vector![1, 1]]);
+    assert!(scheduler.is_empty());
+    assert_eq!(scheduler.cancel(vector![1, 1]), None);
+}
+
+#[test]
+fn iteration_orders_ignore_layout() {
+    let row_major = ExpandableGrid::with_size(vector![3, 2], vector![-1, 4], &0);
+    let column_major = ExpandableGrid::with_size_and_layout(
+        vector![3, 2],
+        vector![-1, 4],
+        &0,
+        Layout::ColumnMajor,
+    );
+
+    let orders = [
+        (
+            IterationOrder::RowMajor,
+            [[-1, 4], [0, 4], [1, 4], [-1, 5], [0, 5], [1, 5]],
+        ),
+        (
+            IterationOrder::ColumnMajor,
+            [[-1, 4], [-1, 5], [0, 4], [0, 5], [1, 4], [1, 5]],
+        ),
+        (
+            IterationOrder::ReverseRowMajor,
+            [[1, 5], [0, 5], [-1, 5], [1, 4], [0, 4], [-1, 4]],
+        ),
+        (
+            IterationOrder::ReverseColumnMajor,
+            [[1, 5], [1, 4], [0, 5], [0, 4], [-1, 5], [-1, 4]],
+        ),
+        (
+            IterationOrder::Morton,
+            [[-1, 4], [0, 4], [-1, 5], [0, 5], [1, 4], [1, 5]],
+        ),
+    ];
+    for (order, expected) in orders {
+        let expected: Vec<_> = expected.into_iter().map(Vector2::from).collect();
+        for grid in [&row_major, &column_major] {
+            assert_eq!(
+                grid.positions_in(order).collect::<Vec<_>>(),
+                expected,
+                "{order:?}"
+            );
+        }
+    }
+
+    // Visit each row from the highest `y`, letting cells fall towards it
+    let mut grid = ExpandableGrid::with_size(vector![1, 4], vector![0, 0], &false);
+    grid[vector![0, 0]] = true;
+    grid.apply_in(IterationOrder::ReverseRowMajor, |grid, position| {
+        let below = position + vector![0, 1];
+        if grid[position] && grid.get(below) == Some(&false) {
+            (grid[position], grid[below]) = (false, true);
+        }
+    });
+    assert!(grid[vector![0, 1]]);
+
+    let mut visited = Vec::new();
+    grid.for_each_mut_in(IterationOrder::ColumnMajor, |position, cell| {
+        *cell = true;
+        visited.push(position.y);
+    });
+    assert_eq!(visited, [0, 1, 2, 3]);
+    assert_eq!(
+        grid.iter_in(IterationOrder::Morton)
+            .filter(|(_, &cell)| cell)
+            .count(),
+        4
+    );
+}
+
+#[test]
+fn dirty_grids_coalesce_written_rects() {
+    let mut grid = DirtyGrid::new(ExpandableGrid::with_size(vector![8, 8], vector![0, 0], &0));
+    assert!(!grid.is_dirty());
+
+    // A 3x2 block written cell by cell becomes one rect
+    for y in 1..3 {
+        for x in 2..5 {
+            grid[vector![x, y]] += 1;
+        }
+    }
+    grid.fill_rect(GridRect::new(vector![6, 6], vector![5, 5]), &7);
+    grid.fill_rect(GridRect::new(vector![7, 7], vector![1, 1]), &8);
+    assert_eq!(grid[vector![7, 7]], 8);
+
+    let mut dirty = grid.take_dirty();
+    dirty.sort_by_key(|rect| rect.origin.x);
+    assert_eq!(
+        dirty,
+        vec![
+            GridRect::new(vector![2, 1], vector![3, 2]),
+            GridRect::new(vector![6, 6], vector![2, 2]),
+        ],
+    );
+    assert!(!grid.is_dirty());
+
+    let source = ExpandableGrid::with_size(vector![2, 2], vector![-1, -1], &3);
+    grid.blit(&source);
+    assert_eq!(
+        grid.take_dirty(),
+        vec![GridRect::new(vector![0, 0], vector![1, 1])]
+    );
+
+    grid.set(vector![0, 0], 1);
+    grid.expand_to_fit_point(vector![-1, 0], &0);
+    assert_eq!(
+        grid.take_dirty(),
+        vec![GridRect::new(grid.origin, grid.size)]
+    );
+}
+
+#[test]
+fn dirty_grids_record_many_writes() {
+    let mut rng = ChaCha8Rng::seed_from_u64(10);
+
+    let mut grid = DirtyGrid::new(ExpandableGrid::with_size(
+        vector![100, 100],
+        vector![-50, -50],
+        &0,
+    ));
+    for position in grid.bounds().iter() {
+        grid[position] = 1;
+    }
+    assert_eq!(grid.take_dirty(), vec![grid.bounds()]);
+
+    // Scattered writes are recorded exactly, without any overlapping rects
+    let mut written = std::collections::HashSet::new();
+    for _ in 0..10_000 {
+        let position = vector![rng.gen_range(-50..50), rng.gen_range(-50..50)];
+        grid[position] += 1;
+        written.insert(position);
+    }
+    let dirty = grid.take_dirty();
+    let mut recorded = std::collections::HashSet::new();
+    for position in dirty.iter().flat_map(|rect| rect.iter()) {
+        assert!(recorded.insert(position));
+    }
+    assert_eq!(recorded, written);
+    assert!(grid.take_dirty().is_empty());
+
+    // Runs only merge with the same run in the next row, and not across later axes
+    let mut grid = DirtyGrid::new(ExpandableGrid3::with_size(
+        vector![4, 4, 2],
+        vector![0, 0, 0],
+        &0,
+    ));
+    grid.mark_dirty(GridRect::new(vector![1, 0, 0], vector![2, 4, 2]));
+    grid.set(vector![3, 1, 0], 1);
+    let mut dirty = grid.take_dirty();
+    dirty.sort_by_key(|rect| (rect.origin.z, rect.origin.y, rect.origin.x));
+    assert_eq!(
+        dirty,
+        vec![
+            GridRect::new(vector![1, 0, 0], vector![2, 1, 1]),
+            GridRect::new(vector![1, 1, 0], vector![3, 1, 1]),
+            GridRect::new(vector![1, 2, 0], vector![2, 2, 1]),
+            GridRect::new(vector![1, 0, 1], vector![2, 4, 1]),
+        ],
+    );
+
+    // Resizing through `untracked_mut` is still noticed
+    grid.untracked_mut()
+        .expand_to_fit_point(vector![4, 0, 0], &0);
+    let dirty = grid.take_dirty();
+    assert_eq!(dirty.len(), grid.size.z);
+    assert!(dirty.iter().all(|rect| rect.size.xy() == grid.size.xy()));
+}
+
+#[test]
+fn observers_are_notified_of_changes() {
+    let events = std::cell::RefCell::new(Vec::new());
+
+    let mut grid = ObservedGrid::new(ExpandableGrid::with_size(vector![2, 2], vector![0, 0], &0));
+    let id = grid.observe(|grid: &ExpandableGrid<i32>, event: &GridEvent<i32>| {
+        let description = match *event {
+            GridEvent::Set { index, old, new } => format!("set {:?} {old} {new}", index.as_slice()),
+            GridEvent::Fill { rect, value } => format!("fill {:?} {value}", rect.size.as_slice()),
+            GridEvent::Blit { rect } => format!("blit {:?}", rect.origin.as_slice()),
+            GridEvent::Resize { old, new } => {
+                assert_eq!(grid.size, new.size);
+                format!("resize {:?} {:?}", old.size.as_slice(), new.size.as_slice())
+            }
+        };
+        events.borrow_mut().push(description);
+    });
+
+    assert_eq!(grid.set(vector![1, 1], 4), Some(0));
+    assert_eq!(grid.set(vector![2, 2], 4), None);
+    grid.modify(vector![1, 1], |cell| *cell += 1);
+    grid.fill_rect(GridRect::new(vector![-1, 0], vector![2, 5]), &9);
+    grid.blit(&ExpandableGrid::with_size(vector![1, 1], vector![1, 0], &3));
+    grid.expand_to_fit_point(vector![1, 1], &0);
+    grid.expand_to_fit_point(vector![2, 0], &0);
+    assert_eq!(grid[vector![1, 1]], 5);
+
+    assert!(grid.unobserve(id));
+    grid.set(vector![0, 0], 1);
+    drop(grid);
+
+    assert_eq!(
+        events.into_inner(),
+        [
+            "set [1, 1] 0 4",
+            "set [1, 1] 4 5",
+            "fill [1, 2] 9",
+            "blit [1, 0]",
+            "resize [2, 2] [4, 2]",
+        ],
+    );
+}
+
+#[test]
+fn grid_history_undoes_and_redoes_edits() {
+    let mut original = ExpandableGrid::with_size(vector![3, 2], vector![0, 0], &0);
+    for (i, cell) in original.data.iter_mut().enumerate() {
+        *cell = i as i32;
+    }
+    let state = |grid: &ExpandableGrid<i32>| (grid.origin, grid.size, grid.data.clone());
+
+    let mut grid = GridHistory::new(original.clone());
+
+    grid.set(vector![1, 1], 100);
+    grid.group(|grid| {
+        grid.fill_rect(GridRect::new(vector![-1, 0], vector![3, 1]), &7);
+        grid.expand_to_fit_point(vector![4, -1], &-1);
+    });
+    let edited = state(&grid);
+    grid.change_size(vector![1, 1], vector![1, 1], &0);
+    assert_eq!(grid.size, vector![1, 1]);
+
+    assert!(grid.undo());
+    assert_eq!(state(&grid), edited);
+    assert!(grid.undo());
+    assert_eq!(grid[vector![1, 1]], 100);
+    assert!(grid.undo());
+    assert_eq!(state(&grid), state(&original));
+    assert!(!grid.undo());
+
+    assert!(grid.redo() && grid.redo());
+    assert_eq!(state(&grid), edited);
+
+    // A new edit discards the undone steps
+    grid.set(vector![0, 0], 5);
+    assert!(!grid.can_redo());
+
+    grid.set_max_steps(2);
+    assert!(grid.undo() && grid.undo());
+    assert!(!grid.can_undo());
+
+    grid.set_memory_budget(Some(0));
+    grid.set(vector![0, 0], 1);
+    grid.set(vector![0, 0], 2);
+    assert!(grid.undo());
+    assert!(!grid.can_undo());
+    assert_eq!(grid[vector![0, 0]], 1);
+}
+
+#[test]
+fn grid_wrappers_take_other_coordinate_types() {
+    let rect = GridRect::<2, i16>::new([-2, 3], [4, 2]);
+    assert_eq!(rect.end(), vector![2i16, 5]);
+    assert!(rect.contains([1i16, 4]) && !rect.contains([2i16, 4]));
+    assert_eq!(
+        rect.intersection(&GridRect::new([0, 0], [10, 4])),
+        Some(GridRect::new([0, 3], [2, 1]))
+    );
+    assert_eq!(rect.iter().count(), 8);
+
+    let mut dirty = DirtyGrid::new(ExpandableGrid::<u8, i32>::with_size([4, 4], [-2, -2], &0));
+    dirty[[1i32, 1]] = 3;
+    dirty.fill_rect(GridRect::new([-5, -2], [4, 1]), &1);
+    assert_eq!(
+        dirty.take_dirty(),
+        [
+            GridRect::new([-2, -2], [1, 1]),
+            GridRect::new([1, 1], [1, 1])
+        ]
+    );
+    dirty.expand_to_fit_point([3i32, 0], &0);
+    assert_eq!(dirty.take_dirty(), [dirty.bounds()]);
+
+    let mut history = GridHistory::new(ExpandableGrid::<u8, i16>::with_size([2, 2], [0, 0], &0));
+    history.set([1i16, 1], 4);
+    history.expand_to_fit_point([-300i16, 0], &2);
+    assert_eq!(history[[-300i16, 0]], 2);
+    assert!(history.undo());
+    assert_eq!(
+        (history.origin, history.size),
+        (vector![0i16, 0], vector![2, 2])
+    );
+    assert!(history.undo());
+    assert_eq!(history[[1i16, 1]], 0);
+
+    let mut buffers = DoubleBufferedGrid::new(ExpandableGrid::<u8, i32>::new());
+    buffers.expand_to_fit_box([-3i32, 2], [2, 2], &1);
+    buffers.step(|front, back| back[[-2i32, 3]] = front[[-2i32, 3]] + 1);
+    assert_eq!(buffers.front()[[-2i32, 3]], 2);
+    assert_eq!(buffers.back().origin, vector![-3i32, 2]);
+}
+
+#[test]
+fn transactions_apply_all_or_nothing() {
+    let mut grid = ExpandableGrid::with_size(vector![2, 2], vector![0, 0], &0);
+
+    // Placing a structure over an occupied cell fails part way through
+    let place = |tx: &mut Transaction<i32>, origin: Vector2<isize>| {
+        for position in [vector![0, 0], vector![1, 0], vector![1, 1]] {
+            let index = origin + position;
+            tx.expand_to_fit_point(index, &0);
+            let cell = tx.get_mut(index).unwrap();
+            if *cell != 0 {
+                return Err(index);
+            }
+            *cell = 1;
+        }
+        Ok(())
+    };
+
+    assert_eq!(grid.transaction(|tx| place(tx, vector![0, 0])), Ok(()));
+    let placed = grid.data.clone();
+
+    assert_eq!(
+        grid.transaction(|tx| place(tx, vector![3, -1]).and_then(|()| place(tx, vector![0, 1]))),
+        Err(vector![1, 1]),
+    );
+    assert_eq!((grid.size, grid.origin), (vector![2, 2], vector![0, 0]));
+    assert_eq!(grid.data, placed);
+
+    assert_eq!(
+        grid.transaction(|tx| {
+            place(tx, vector![3, -1])?;
+            assert_eq!(tx.get(vector![4, 0]), Some(&1));
+            assert_eq!(tx.get(vector![2, 1]), Some(&0));
+            Ok::<_, Vector2<isize>>(tx.size())
+        }),
+        Ok(vector![8, 4]),
+    );
+    assert_eq!((grid.size, grid.origin), (vector![8, 4], vector![0, -2]));
+    assert_eq!(grid[vector![4, 0]], 1);
+    assert_eq!(grid[vector![1, 1]], 1);
+}
+
+#[test]
+fn edit_logs_replay_from_binary() {
+    let mut grid = ExpandableGrid::new();
+    let mut log = EditLog::new();
+
+    log.apply(
+        &mut grid,
+        Edit::ExpandToFitBox {
+            box_origin: vector![-2, 1],
+            box_size: vector![3, 2],
+            fill: 0u16,
+        },
+    );
+    log.apply(
+        &mut grid,
+        Edit::Set {
+            index: vector![0, 2],
+            value: 5,
+        },
+    );
+    log.apply(
+        &mut grid,
+        Edit::FillBox {
+            box_origin: vector![-3, 0],
+            box_size: vector![2, 2],
+            value: 7,
+        },
+    );
+    let mut saved = Vec::new();
+    log.write_binary(&mut saved).unwrap();
+    let saved_edits = log.len();
+
+    log.apply(
+        &mut grid,
+        Edit::ChangeSize {
+            new_size: vector![4, 3],
+            offset: vector![0, -1],
+            fill: 1,
+        },
+    );
+    log.apply(
+        &mut grid,
+        Edit::Blit(ExpandableGrid::with_size(vector![1, 2], vector![1, 0], &9)),
+    );
+
+    // Only the new edits are appended to the save
+    log.write_binary_edits(saved_edits, &mut saved).unwrap();
+    let loaded = EditLog::<u16>::read_binary(&mut saved.as_slice()).unwrap();
+    assert_eq!(loaded.len(), 5);
+
+    let replayed = loaded.to_grid();
+    assert_eq!(
+        (replayed.size, replayed.origin, &replayed.data),
+        (grid.size, grid.origin, &grid.data),
+    );
+    assert_eq!(grid[vector![1, 0]], 9);
+    assert_eq!(grid[vector![-2, 1]], 7);
+
+    saved.push(9);
+    assert!(matches!(
+        EditLog::<u16>::read_binary(&mut saved.as_slice()),
+        Err(binary::BinaryError::InvalidEdit { index: 5 }),
+    ));
+}
+
+#[test]
+fn diffs_find_changed_rects() {
+    let old = ExpandableGrid::with_size(vector![4, 3], vector![0, 0], &0);
+    let mut new = ExpandableGrid::with_size(vector![4, 4], vector![1, -1], &0);
+    for index in [
+        vector![1, 0],
+        vector![2, 0],
+        vector![1, 1],
+        vector![2, 1],
+        vector![4, 2],
+    ] {
+        new[index] = 1;
+    }
+
+    let diff = old.diff(&new);
+    assert_eq!(
+        diff.old_bounds(),
+        GridRect::new(vector![0, 0], vector![4, 3])
+    );
+    assert_eq!(
+        diff.new_bounds(),
+        GridRect::new(vector![1, -1], vector![4, 4])
+    );
+
+    // The row below the old grid and the column to its right are new
+    let mut rects: Vec<_> = diff
+        .changed_rects()
+        .map(|rect| {
+            (
+                rect.origin.as_slice().to_vec(),
+                rect.size.as_slice().to_vec(),
+            )
+        })
+        .collect();
+    rects.sort();
+    assert_eq!(
+        rects,
+        [
+            (vec![1, -1], vec![4, 1]),
+            (vec![1, 0], vec![2, 2]),
+            (vec![4, 0], vec![1, 3]),
+        ],
+    );
+    assert_eq!(diff.changed_cells(), 11);
+    assert_eq!(diff.patches()[1][vector![2, 1]], 1);
+
+    assert!(new.diff(&new).is_empty());
+    assert!(!old
+        .diff(&ExpandableGrid::with_size(vector![1, 1], vector![0, 0], &0))
+        .is_empty());
+}
+
+#[test]
+fn applying_diffs_synchronizes_grids() {
+    let mut rng = ChaCha8Rng::seed_from_u64(157);
+    let mut random_grid = |size, origin| {
+        let mut grid = ExpandableGrid::with_size(size, origin, &0);
+        grid.data
+            .iter_mut()
+            .for_each(|cell| *cell = rng.gen_range(0..3));
+        grid
+    };
+
+    let grids = [
+        random_grid(vector![5, 4], vector![0, 0]),
+        random_grid(vector![7, 2], vector![-2, 1]),
+        random_grid(vector![3, 6], vector![1, -3]),
+        ExpandableGrid::new(),
+        random_grid(vector![2, 2], vector![4, 4]),
+    ];
+
+    let mut copy = ExpandableGrid::new();
+    let mut previous = ExpandableGrid::new();
+    for grid in grids {
+        copy.apply_diff(&previous.diff(&grid));
+        assert_eq!(
+            (copy.size, copy.origin, &copy.data),
+            (grid.size, grid.origin, &grid.data),
+        );
+        previous = grid;
+    }
+}
+
+#[test]
+fn sync_deltas_keep_copies_in_sync() {
+    let mut grid = DirtyGrid::new(ExpandableGrid::with_size(
+        vector![6, 4],
+        vector![-3, -2],
+        &0u8,
+    ));
+    let mut copy = ExpandableGrid::new();
+    let mut sender = SyncSender::new();
+    let mut receiver = SyncReceiver::new();
+
+    // The first delta includes the whole grid
+    let dirty = grid.take_dirty();
+    let first = sender.encode_rects(&grid, dirty);
+    receiver.apply(&mut copy, &first).unwrap();
+    assert_eq!((copy.size, copy.origin), (grid.size, grid.origin));
+
+    grid.fill_rect(GridRect::new(vector![-1, -1], vector![3, 2]), &4);
+    grid.set(vector![2, 1], 9);
+    let dirty = grid.take_dirty();
+    let second = sender.encode_rects(&grid, dirty);
+
+    grid.expand_to_fit_point(vector![5, 0], &1);
+    let dirty = grid.take_dirty();
+    let third = sender.encode_rects(&grid, dirty);
+
+    // Deltas must be applied in order
+    assert!(matches!(
+        receiver.apply(&mut copy, &third),
+        Err(SyncError::OutOfOrder {
+            expected: 1,
+            found: 2
+        }),
+    ));
+    receiver.apply(&mut copy, &second).unwrap();
+    receiver.apply(&mut copy, &third).unwrap();
+    assert_eq!(
+        (copy.size, copy.origin, &copy.data),
+        (grid.size, grid.origin, &grid.data),
+    );
+
+    // Runs of equal cells keep deltas small
+    let mut previous = (*grid).clone();
+    grid.fill_rect(GridRect::new(vector![-3, -2], vector![12, 4]), &2);
+    let fourth = sender.encode_diff(&previous.diff(&grid));
+    assert!(fourth.len() < 32);
+    receiver.apply(&mut copy, &fourth).unwrap();
+    assert_eq!(copy.data, grid.data);
+
+    previous.expand_to_fit_point(vector![-10, 0], &0);
+    assert!(matches!(
+        SyncReceiver::new().apply(&mut previous, &first),
+        Err(SyncError::BoundsMismatch),
+    ));
+}
+
+#[test]
+fn malformed_sync_deltas_are_rejected() {
+    // Every value of a delta is a varint, with positions zigzag encoded
+    fn delta(values: &[u64]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for &value in values {
+            binary::write_varint(&mut bytes, value).unwrap();
+        }
+        bytes
+    }
+    let zigzag = |value: i64| (value << 1 ^ value >> 63) as u64;
+
+    let mut grid = ExpandableGrid::<u8>::new();
+    let mut receiver = SyncReceiver::with_max_cells(100);
+    let mut reject = |delta: &[u8]| receiver.apply(&mut grid, delta).unwrap_err();
+
+    // New cells which no patch covers
+    let empty = [0, 0, 0, 0, 0, 0, 0, 0, 5, 5, 0];
+    assert!(matches!(reject(&empty), SyncError::MissingCells));
+    assert!(matches!(
+        reject(&delta(&[0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 0, 0, 1, 1, 1, 3])),
+        SyncError::MissingCells,
+    ));
+
+    // Coordinates which overflow
+    let max = zigzag(isize::MAX as i64);
+    assert!(matches!(
+        reject(&delta(&[0, 0, max, 0, 0, 0, zigzag(1), 0, 0, 0, 0])),
+        SyncError::TooLarge,
+    ));
+    assert!(matches!(
+        reject(&delta(&[0, 0, max, 0, 1, 0, 0, 0, 0, 0, 0])),
+        SyncError::TooLarge,
+    ));
+
+    // More cells than the receiver allows, or a run longer than its patch
+    assert!(matches!(
+        reject(&delta(&[0, 0, 0, 0, 0, 0, 0, 0, 11, 10, 0])),
+        SyncError::TooLarge,
+    ));
+    assert!(matches!(
+        reject(&delta(&[
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            10,
+            10,
+            1,
+            0,
+            0,
+            10,
+            10,
+            1 << 40,
+            7
+        ])),
+        SyncError::Decode(binary::BinaryError::InvalidRun { index: 0 }),
+    ));
+
+    // A patch outside the new bounds
+    assert!(matches!(
+        reject(&delta(&[
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            2,
+            2,
+            1,
+            zigzag(1),
+            2,
+            2,
+            2,
+            4,
+            7
+        ])),
+        SyncError::PatchOutOfBounds,
+    ));
+
+    // An empty patch, which would otherwise be used to fill the new cells
+    assert!(matches!(
+        reject(&delta(&[
+            0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 0, 0, 0, 0, 0, 0, 1, 1, 1, 7
+        ])),
+        SyncError::EmptyPatch,
+    ));
+
+    assert_eq!(grid.size, vector![0, 0]);
+    assert_eq!(receiver.next_sequence(), 0);
+    let valid = delta(&[0, 0, 0, 0, 0, 0, 0, 0, 10, 10, 1, 0, 0, 10, 10, 100, 7]);
+    receiver.apply(&mut grid, &valid).unwrap();
+    assert_eq!((grid.size, grid[[9, 9]]), (vector![10, 10], 7));
+}
+
+#[test]
+fn temporal_grids_keep_recent_ticks() {
+    let mut grid: TemporalGrid<i32, 3> =
+        TemporalGrid::new(ExpandableGrid::with_size(vector![2, 1], vector![0, 0], &0));
+
+    for value in 1..=4 {
+        grid.tick();
+        grid.current_mut()[vector![0, 0]] += value;
+    }
+    grid.expand_to_fit_point(vector![-1, 0], &-1);
+
+    assert_eq!(
+        grid.history(vector![0, 0])
+            .unwrap()
+            .copied()
+            .collect::<Vec<_>>(),
+        [10, 6, 3],
+    );
+    assert_eq!(grid.get(vector![-1, 0], 2), Some(&-1));
+    assert_eq!(grid.get(vector![1, 0], 1), Some(&0));
+    assert!(grid.at_ticks_ago(3).is_none());
+    assert!(grid.history(vector![0, 1]).is_none());
+
+    let current = grid.into_current();
+    assert_eq!(
+        (current.size, current.origin),
+        (vector![4, 1], vector![-2, 0])
+    );
+}
+
+#[test]
+fn content_bounds_follow_writes() {
+    let mut grid = ContentGrid::new(
+        ExpandableGrid::with_size(vector![4, 4], vector![0, 0], &0),
+        0,
+    );
+    assert_eq!(grid.content_bounds(), None);
+
+    grid.set(vector![1, 2], 1);
+    grid.set(vector![2, 1], 1);
+    assert_eq!(
+        grid.content_bounds(),
+        Some(GridRect::new(vector![1, 1], vector![2, 2]))
+    );
+
+    grid.expand_to_fit_point(vector![-2, 5], &0);
+    grid.fill_rect(GridRect::new(vector![-1, 3], vector![1, 3]), &2);
+    grid.set(vector![2, 1], 0);
+    assert_eq!(
+        grid.content_bounds(),
+        Some(GridRect::new(vector![-1, 2], vector![3, 4]))
+    );
+
+    // Expanding with cells which are not empty recounts every cell
+    grid.expand_to_fit_point(vector![6, 0], &3);
+    assert_eq!(
+        grid.content_bounds(),
+        Some(GridRect::new(vector![-1, 0], vector![13, 8]))
+    );
+
+    grid.change_size(vector![3, 3], vector![3, 3], &0);
+    grid.modify(vector![-1, 5], |cell| *cell = 0);
+    grid.shrink_to_content();
+    assert_eq!((grid.size, grid.origin), (vector![1, 2], vector![-1, 3]));
+    assert_eq!(grid[vector![-1, 3]], 2);
+}
+
+#[test]
+fn memory_usage_counts_buffers_and_chunks() {
+    let grid = ExpandableGrid::with_size(vector![4, 3], vector![0, 0], &vec![0u8; 5]);
+    let usage = grid.memory_usage_with(|cell| cell.capacity());
+    assert_eq!(usage.cells, 12);
+    assert_eq!(usage.buffer_bytes, 12 * std::mem::size_of::<Vec<u8>>());
+    assert_eq!((usage.unused_bytes(), usage.heap_bytes), (0, 60));
+
+    let mut cow = CowGrid::new(vector![4, 4], 0u32);
+    cow.expand_to_fit_box(vector![0, 0], vector![8, 4]);
+    let shared = cow.memory_usage();
+    assert_eq!((shared.cells, shared.chunks), (32, 1));
+    assert_eq!((shared.used_bytes, shared.buffer_bytes), (128, 64));
+
+    cow[vector![5, 0]] = 1;
+    assert_eq!(cow.memory_usage().chunks, 2);
+
+    let concurrent = ConcurrentGrid::new(vector![2, 2], 0u16);
+    concurrent.expand_to_fit_box(vector![-1, -1], vector![2, 2]);
+    let usage = concurrent.memory_usage();
+    assert_eq!((usage.cells, usage.chunks, usage.buffer_bytes), (16, 4, 32));
+    assert!(usage.total_bytes() > usage.buffer_bytes);
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct TestChunk([[u8; 2]; 2]);
+
+impl std::ops::Index<Vector2<usize>> for TestChunk {
+    type Output = u8;
+
+    fn index(&self, index: Vector2<usize>) -> &Self::Output {
+        &self.0[index.y][index.x]
+    }
+}
+
+impl std::ops::IndexMut<Vector2<usize>> for TestChunk {
+    fn index_mut(&mut self, index: Vector2<usize>) -> &mut Self::Output {
+        &mut self.0[index.y][index.x]
+    }
+}
+
+impl Subchunk for TestChunk {
+    const SUBCHUNK_SIZE: Vector2<usize> = vector![2, 2];
+}
+
+#[test]
+fn evicting_grids_store_least_recently_used_chunks() {
+    let mut grid = EvictingGrid::new(2, TestChunk([[0; 2]; 2]), std::collections::HashMap::new());
+    grid.expand_to_fit_box(vector![-2, -2], vector![6, 4]);
+    assert_eq!(grid.chunk_grid_size(), vector![3, 2]);
+    assert_eq!(grid.resident_chunks(), 0);
+
+    *grid.get_mut(vector![0, 0]).unwrap() = 1;
+    *grid.get_mut(vector![-1, -1]).unwrap() = 2;
+    assert_eq!(grid.get(vector![1, 1]), Some(&0));
+
+    // Loading a third chunk evicts the least recently used one
+    *grid.get_mut(vector![3, 1]).unwrap() = 3;
+    assert_eq!(grid.resident_chunks(), 2);
+    assert!(!grid.is_resident(vector![-1, -1]));
+    assert!(grid.store().contains_key(&vector![-1, -1]));
+
+    assert_eq!(grid.get(vector![-1, -1]), Some(&2));
+    assert!(!grid.is_resident(vector![0, 0]));
+    assert_eq!(grid.get(vector![5, 5]), None);
+
+    grid.set_budget(1);
+    assert_eq!(grid.pooled_chunks(), 2);
+    assert_eq!(grid.get(vector![2, -2]), Some(&0));
+    assert_eq!(grid.pooled_chunks(), 2);
+    grid.shrink_pool();
+    assert_eq!(grid.pooled_chunks(), 0);
+
+    let store = grid.into_store();
+    assert_eq!(store.len(), 4);
+    assert_eq!(store[&vector![1, 0]].0, [[0, 0], [0, 3]]);
+
+    // Only resident chunks take up memory, however far apart they are
+    let far = 1 << 40;
+    let mut grid = EvictingGrid::new(2, TestChunk([[0; 2]; 2]), std::collections::HashMap::new());
+    grid.expand_to_fit_point(vector![-far, -far]);
+    grid.expand_to_fit_point(vector![far, far]);
+    assert_eq!(
+        grid.chunk_grid_size(),
+        vector![far as usize + 1, far as usize + 1]
+    );
+    *grid.get_mut(vector![-far, -far]).unwrap() = 4;
+    *grid.get_mut(vector![far, far]).unwrap() = 5;
+    assert_eq!(grid.get(vector![-far, -far]), Some(&4));
+    assert_eq!(grid.get(vector![far + 2, far]), None);
+}
+
+#[test]
+fn infinite_grids_generate_chunks_on_access() {
+    let mut grid = InfiniteGrid::new(|chunk: Vector2<isize>| {
+        let value = (chunk.x * 10 + chunk.y) as u8;
+        TestChunk([[value; 2]; 2])
+    });
+
+    assert_eq!(*grid.get(vector![5, 2]), 21);
+    assert_eq!(grid.peek(vector![-3, 0]), None);
+    *grid.get_mut(vector![-3, 0]) = 100;
+    assert_eq!(grid.peek(vector![-3, 0]), Some(&100));
+    assert_eq!(grid.peek(vector![-4, 1]), Some(&(-20i8 as u8)));
+    assert_eq!(grid.generated_chunks(), 2);
+
+    grid.generate_box(vector![-1, -1], vector![3, 3]);
+    assert_eq!(grid.generated_chunks(), 6);
+    assert!(grid.is_generated(vector![0, -1]) && !grid.is_generated(vector![1, 0]));
+    assert_eq!(*grid.get(vector![-3, 0]), 100);
+
+    // Chunks far apart don't need the chunks between them to be stored
+    let far = 1 << 40;
+    assert_eq!(
+        *grid.get(vector![far, -far]),
+        (far / 2 * 10 - far / 2) as u8
+    );
+    assert_eq!(grid.generated_chunks(), 7);
+    assert!(grid.chunks().contains_key(&vector![far / 2, -far / 2]));
+}
+
+#[derive(Default)]
+struct TrackingAllocator {
+    allocated: std::cell::Cell<usize>,
+    allocations: std::cell::Cell<usize>,
+}
+
+unsafe impl GridAlloc for TrackingAllocator {
+    fn allocate(&self, layout: std::alloc::Layout) -> Option<std::ptr::NonNull<u8>> {
+        self.allocated.set(self.allocated.get() + layout.size());
+        self.allocations.set(self.allocations.get() + 1);
+        // Safety: `layout` never has a size of 0
+        std::ptr::NonNull::new(unsafe { std::alloc::alloc(layout) })
+    }
+
+    unsafe fn deallocate(&self, pointer: std::ptr::NonNull<u8>, layout: std::alloc::Layout) {
+        self.allocated.set(self.allocated.get() - layout.size());
+        std::alloc::dealloc(pointer.as_ptr(), layout);
+    }
+}
+
+#[test]
+fn alloc_grids_use_their_allocator() {
+    let allocator = TrackingAllocator::default();
+
+    let mut grid = ExpandableGrid::new_in(&allocator);
+    grid.expand_to_fit_point(vector![2, 3], &String::new());
+    grid[vector![2, 3]] = "a".to_owned();
+    grid.expand_to_fit_box(vector![0, 0], vector![3, 5], &"b".to_owned());
+    assert_eq!(
+        allocator.allocated.get(),
+        15 * std::mem::size_of::<String>()
+    );
+
+    // Filling and copying boxes keeps the allocator
+    grid.fill_box(vector![0, 0], vector![3, 1], &"c".to_owned());
+    let copy = grid.copy_box(vector![-1, 0], vector![4, 5], &String::new());
+    assert_eq!(allocator.allocations.get(), 3);
+    assert_eq!(copy[vector![2, 0]], "c");
+    assert_eq!(copy[vector![-1, 0]], "");
+    drop(grid);
+
+    let expandable = copy.clone_in(Global);
+    assert_eq!(
+        (expandable.size, expandable.origin),
+        (vector![4, 5], vector![-1, 0])
+    );
+    assert_eq!(expandable[vector![2, 3]], "a");
+    assert_eq!(expandable[vector![0, 4]], "b");
+
+    let mut other = expandable.clone_in(&allocator);
+    other.change_size(vector![2, 2], vector![3, 3], &String::new());
+    assert_eq!(other.data.len(), 4);
+    assert_eq!(other.get(vector![2, 3]), Some(&"a".to_owned()));
+    drop((copy, other));
+    assert_eq!(allocator.allocated.get(), 0);
+}
+
+#[test]
+fn small_grids_spill_onto_the_heap() {
+    let mut grid = SmallGrid::<String, 4>::new();
+    grid.expand_to_fit_point(vector![1, 1], &String::new());
+    grid[vector![1, 1]] = "a".to_owned();
+    grid.expand_to_fit_box(vector![0, 0], vector![2, 2], &"b".to_owned());
+    assert!(grid.is_inline());
+    assert_eq!((grid.size(), grid.origin()), (vector![2, 2], vector![0, 0]));
+    assert_eq!(grid.data(), ["b", "b", "b", "a"]);
+
+    let inline = grid.clone().into_grid();
+    assert_eq!(inline[vector![1, 1]], "a");
+    assert_eq!(inline.data.len(), 4);
+
+    grid.expand_to_fit_point(vector![3, 1], &"c".to_owned());
+    assert!(!grid.is_inline());
+    assert_eq!(grid[vector![1, 1]], "a");
+    assert_eq!(grid[vector![0, 0]], "b");
+    assert_eq!(grid[vector![3, 1]], "c");
+    assert_eq!(grid.get(vector![-1, 0]), None);
+}
+
+#[test]
+fn fill_box_fills_runs_in_either_layout() {
+    for layout in [Layout::RowMajor, Layout::ColumnMajor] {
+        let mut grid =
+            ExpandableGridN::<u8, 3>::with_size_and_layout([4, 3, 2], [-1, 0, 0], &0, layout);
+        grid.fill_box([0, 1, -5], [10, 2, 10], &7);
+
+        for (index, &cell) in grid.data.iter().enumerate() {
+            let position = grid.layout.position(index, grid.size);
+            let expected = if position.x >= 1 && position.y >= 1 {
+                7
+            } else {
+                0
+            };
+            assert_eq!(cell, expected, "{layout:?} {position:?}");
+        }
+
+        grid.change_size([6, 3, 2], [-2, 0, 0], &1);
+        assert_eq!(grid[[-3, 0, 0]], 1);
+        assert_eq!(grid[[-1, 0, 0]], 0);
+        assert_eq!(grid[[2, 2, 1]], 7);
+    }
+}
+
+#[test]
+fn margins_expand_only_the_growing_sides() {
+    let mut grid = ExpandableGrid::with_margins([2, 2], [0, 0], [0, 4, 1, 0], &0);
+    assert_eq!((grid.size, grid.origin), (vector![6, 3], vector![0, -1]));
+
+    grid[vector![1, 1]] = 5;
+    grid.expand_margins([7, 0], [1, 1], [0, 8, 0, 0], &1);
+    assert_eq!((grid.size, grid.origin), (vector![16, 3], vector![0, -1]));
+    assert_eq!(grid[vector![1, 1]], 5);
+    assert_eq!(grid[vector![15, 1]], 1);
+
+    // Nothing changes when the box already fits
+    grid.expand_margins([3, 0], [1, 1], Margins::uniform(10), &1);
+    assert_eq!(grid.size, vector![16, 3]);
+
+    grid.expand_margins([0, -2], [1, 1], Margins::new([0, 2], [0, 0]), &2);
+    assert_eq!((grid.size, grid.origin), (vector![16, 6], vector![0, -4]));
+    assert_eq!(grid[vector![1, 1]], 5);
+}
+
+#[test]
+fn interned_grids_share_equal_values() {
+    let mut grid = InternedGrid::new();
+    grid.expand_to_fit_box([0, 0], [4, 4], &"grass".to_owned());
+    grid.fill_rect(GridRect::new([1, 1], [2, 2]), &"stone".to_owned());
+    grid.set([3, 3], "stone".to_owned());
+    assert_eq!(grid[[1, 1]], "stone");
+    assert_eq!(grid.get([0, 0]).map(String::as_str), Some("grass"));
+    assert_eq!(grid.interned_values(), 2);
+
+    let cells = grid.cells();
+    assert!(std::sync::Arc::ptr_eq(&cells[[1, 2]], &cells[[3, 3]]));
+
+    grid.fill_rect(GridRect::new([0, 0], [4, 4]), &"water".to_owned());
+    assert!(grid.modify([2, 2], |value| value.push_str("fall")));
+    assert_eq!(grid[[2, 2]], "waterfall");
+    grid.purge();
+    assert_eq!(grid.interned_values(), 2);
+
+    let copy = InternedGrid::from_grid(grid.to_grid());
+    assert_eq!(copy.interned_values(), 2);
+    assert_eq!(copy[[2, 2]], "waterfall");
+}
+
+#[test]
+fn layered_grids_expand_together() {
+    type Layers = (
+        ExpandableGrid<u8>,
+        ExpandableGrid<Option<char>>,
+        ExpandableGrid<f32>,
+    );
+    let mut grid = LayeredGrid::<Layers>::with_size([2, 2], [0, 0], &(0, None, 1.0));
+
+    let (height, object, _) = grid.get_mut([1, 1]).unwrap();
+    *height = 3;
+    *object = Some('t');
+    grid.expand_to_fit_point([-3, 4], &(1, None, 0.5));
+
+    let (terrain, objects, lighting) = grid.layers();
+    for layer in [terrain.size, objects.size, lighting.size] {
+        assert_eq!(layer, grid.size());
+    }
+    assert_eq!(terrain.origin, grid.origin());
+    assert_eq!(grid.get([1, 1]), Some((&3, &Some('t'), &1.0)));
+    assert_eq!(grid.get([-3, 4]), Some((&1, &None, &0.5)));
+
+    let (heights, _, light) = grid.data_mut();
+    heights.fill(9);
+    light[0] = 0.0;
+    assert_eq!(grid.get(grid.origin()), Some((&9, &None, &0.0)));
+
+    let (terrain, objects, lighting) = grid.into_layers();
+    let mismatched = (
+        terrain.clone(),
+        ExpandableGrid::with_size([1, 1], [0, 0], &'x'),
+    );
+    assert!(LayeredGrid::from_layers(mismatched).is_err());
+    assert!(LayeredGrid::from_layers((terrain, objects, lighting)).is_ok());
+}
+
+#[test]
+fn zipped_grids_are_visited_in_lockstep() {
+    let mut height = ExpandableGrid::with_size([3, 2], [-1, 0], &0);
+    let mut moisture =
+        ExpandableGrid::with_size_and_layout([3, 2], [-1, 0], &0.0, Layout::ColumnMajor);
+    let mut temperature = ExpandableGrid::with_size([3, 2], [-1, 0], &0i8);
+
+    let view = zip_view_mut((&mut height, &mut moisture, &mut temperature));
+    assert_eq!(view.len(), 6);
+    for (position, (height, moisture, temperature)) in view {
+        *height = position.x + 10 * position.y;
+        *moisture = *height as f32 / 2.0;
+        *temperature = -position.y as i8;
+    }
+
+    for position in height.positions_in(IterationOrder::RowMajor) {
+        assert_eq!(height[position], position.x + 10 * position.y);
+        assert_eq!(moisture[position], height[position] as f32 / 2.0);
+        assert_eq!(temperature[position], -position.y as i8);
+    }
+}
+
+#[test]
+fn overlays_override_the_base_until_flattened() {
+    let mut grid = OverlayGrid::new(ExpandableGrid::with_size([4, 4], [0, 0], &1));
+    assert!(grid.set([1, 1], 5));
+    assert!(!grid.set([4, 0], 5));
+    *grid.get_mut([2, 2]).unwrap() += 1;
+    assert_eq!((grid[[1, 1]], grid[[2, 2]], grid[[3, 3]]), (5, 2, 1));
+    assert_eq!(grid.base()[[1, 1]], 1);
+    assert_eq!(grid.override_count(), 2);
+
+    assert_eq!(grid.remove_override([2, 2]), Some(2));
+    assert_eq!(grid[[2, 2]], 1);
+    assert_eq!(grid.to_flattened()[[1, 1]], 5);
+
+    grid.flatten();
+    assert_eq!(grid.override_count(), 0);
+    assert_eq!(grid.base()[[1, 1]], 5);
+
+    grid.set([0, 0], 9);
+    grid.discard();
+    assert_eq!(grid.into_base()[[0, 0]], 1);
+}
+
+#[test]
+fn merging_grids_resolves_overlapping_cells() {
+    let mut west = ExpandableGrid::with_size([3, 2], [0, 0], &1);
+    let east = ExpandableGrid::with_size([3, 2], [2, 1], &10);
+    west.merge(&east, |a, b| a + b, &0);
+
+    assert!(west.index_of([4, 2]).is_some());
+    assert_eq!(west[[0, 0]], 1);
+    assert_eq!(west[[2, 1]], 11);
+    assert_eq!(west[[3, 1]], 10);
+    assert_eq!(west[[4, 2]], 10);
+    assert_eq!(west[[0, 2]], 0);
+
+    let mut empty = ExpandableGrid::new();
+    empty.merge(&east, |_, _| unreachable!(), &0);
+    assert_eq!((empty.size, empty.origin), (east.size, east.origin));
+    assert_eq!(empty.data, east.data);
+}
+
+#[test]
+fn intersections_visit_overlapping_cells() {
+    let heights = ExpandableGrid::with_size([4, 4], [0, 0], &3);
+    let mut water = ExpandableGrid::with_size_and_layout([4, 4], [2, -1], &1, Layout::ColumnMajor);
+    water[[3, 1]] = 5;
+
+    let bounds = heights.intersection_bounds(&water);
+    assert_eq!(bounds, Some(GridRect::new([2, 0], [2, 3])));
+    assert_eq!(
+        heights.intersection_bounds(&ExpandableGrid::with_size([1, 1], [9, 9], &0)),
+        None
+    );
+
+    let mut visited = Vec::new();
+    heights.intersect_with(&water, |position, height, water| {
+        visited.push((position, height + water));
+    });
+    assert_eq!(visited.len(), 6);
+    assert!(visited.contains(&(vector![3, 1], 8)));
+    assert!(visited.contains(&(vector![2, 0], 4)));
+}
+
+#[test]
+fn grouped_grids_expand_together() {
+    let mut group = GridGroup::new();
+    let terrain = group.add_grid(
+        std::sync::Arc::new(std::sync::RwLock::new(ExpandableGrid::with_size(
+            [2, 2],
+            [0, 0],
+            &1u8,
+        ))),
+        0,
+    );
+    let lighting = group.add(0.5f32);
+    assert_eq!(lighting.read().unwrap().size, vector![2, 2]);
+
+    terrain.write().unwrap()[[1, 1]] = 7;
+    group.expand_to_fit_point([-3, 1]);
+    assert_eq!(
+        (group.size(), group.origin()),
+        (vector![5, 2], vector![-3, 0])
+    );
+    for (size, origin) in [
+        (terrain.read().unwrap().size, terrain.read().unwrap().origin),
+        (
+            lighting.read().unwrap().size,
+            lighting.read().unwrap().origin,
+        ),
+    ] {
+        assert_eq!((size, origin), (group.size(), group.origin()));
+    }
+    assert_eq!(terrain.read().unwrap()[[1, 1]], 7);
+    assert_eq!(terrain.read().unwrap()[[-3, 0]], 0);
+    assert_eq!(lighting.read().unwrap()[[-3, 0]], 0.5);
+
+    drop(lighting);
+    assert_eq!(group.len(), 1);
+    group.change_size([2, 2], [3, 0]);
+    assert_eq!(terrain.read().unwrap().origin, vector![0, 0]);
+    assert_eq!(terrain.read().unwrap()[[1, 1]], 7);
+}
+
+#[test]
+fn arena_grids_share_storage() {
+    let mut arena = GridArena::new();
+    let first = arena.insert([2, 2], [0, 0], &0);
+    let mut second_grid =
+        ExpandableGrid::with_size_and_layout([2, 3], [5, 5], &1, Layout::ColumnMajor);
+    second_grid[[6, 5]] = 2;
+    let second = arena.insert_grid(second_grid);
+
+    *arena.get_cell_mut(first, [1, 1]).unwrap() = 4;
+    assert_eq!(arena.get_cell(second, [6, 5]), Some(&2));
+    assert_eq!(arena.get(second).unwrap().data, [1, 2, 1, 1, 1, 1]);
+
+    assert!(arena.expand_to_fit_point(first, [-1, 0], &3));
+    assert_eq!(arena.get_cell(first, [1, 1]), Some(&4));
+    assert_eq!(arena.get_cell(first, [-1, 0]), Some(&3));
+    let expanded = arena.to_grid(first).unwrap();
+    assert_eq!(
+        (expanded.size, expanded.origin),
+        (vector![4, 2], vector![-2, 0])
+    );
+
+    assert!(arena.remove(second));
+    assert!(!arena.contains(second));
+    assert_eq!(arena.get_cell(second, [6, 5]), None);
+    let third = arena.insert([1, 1], [0, 0], &9);
+    assert_ne!(third, second);
+    assert_eq!(arena.len(), 2);
+
+    arena.compact();
+    assert_eq!(arena.unused_cells(), 0);
+    assert_eq!(arena.get_cell(first, [1, 1]), Some(&4));
+    assert_eq!(arena.get_cell(third, [0, 0]), Some(&9));
+    let order: Vec<_> = arena.iter().map(|(handle, _)| handle).collect();
+    assert_eq!(order, [first, third]);
+}
+
+#[test]
+fn enum_grids_store_variant_indices() {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum Tile {
+        Air = 4,
+        Stone,
+        Water = 1,
+    }
+    crate::palette_enum!(Tile: u8 { Air, Stone, Water });
+
+    assert_eq!(Tile::Water.to_index(), 2);
+    assert_eq!(Tile::from_index(1), Tile::Stone);
+
+    let mut grid = EnumGrid::with_size([3, 3], [0, 0], Tile::Air);
+    grid.fill_rect(GridRect::new([0, 0], [3, 1]), Tile::Stone);
+    grid.set([1, 1], Tile::Water);
+    grid.expand_to_fit_point([0, 3], Tile::Air);
+    assert_eq!(std::mem::size_of_val(&grid.indices().data[0]), 1);
+
+    assert_eq!(grid.get([2, 0]), Some(Tile::Stone));
+    assert_eq!(grid.get([1, 1]), Some(Tile::Water));
+    assert_eq!(grid.count(Tile::Stone), 3);
+    assert!(grid.contains(Tile::Water));
+
+    let copy = EnumGrid::from_grid(&grid.to_grid());
+    assert_eq!(copy.indices().data, grid.indices().data);
+}
+
+#[test]
+fn cell_handles_survive_resizes() {
+    let mut grid = ExpandableGrid::with_size([2, 2], [0, 0], &0);
+    grid[[1, 1]] = 5;
+    let mut handle = grid.handle([1, 1]).unwrap();
+    assert_eq!(grid.handle([2, 0]), None);
+
+    grid.expand_to_fit_point([-4, -4], &0);
+    assert!(grid.is_stale(&handle));
+    assert_eq!(grid.resolve(&handle), Some(&5));
+    *grid.resolve_mut(&handle).unwrap() += 1;
+    assert_eq!(grid[handle.position()], 6);
+
+    assert!(grid.refresh(&mut handle));
+    assert!(!grid.is_stale(&handle));
+    assert_eq!(grid.resolve(&handle), Some(&6));
+
+    grid.change_size([2, 2], [0, 0], &0);
+    assert_eq!(grid.resolve(&handle), None);
+    assert!(!grid.refresh(&mut handle));
+}
+
+#[test]
+fn grids_convert_their_cells() {
+    let mut tiles =
+        ExpandableGrid::with_size_and_layout([2, 3], [-1, 0], &7u8, Layout::ColumnMajor);
+    tiles[[0, 2]] = 200;
+
+    let wide = tiles.clone().convert::<u16>();
+    assert_eq!(
+        (wide.size, wide.origin, wide.layout),
+        (tiles.size, tiles.origin, tiles.layout)
+    );
+    assert_eq!(wide[[0, 2]], 200);
+
+    let signed = tiles.clone().try_convert::<i8>();
+    assert!(signed.is_err());
+    tiles[[0, 2]] = 100;
+    assert_eq!(tiles.try_convert::<i8>().unwrap()[[0, 2]], 100);
+}
+
+#[test]
+fn cells_can_be_found_by_value() {
+    let map = ExpandableGrid::from_ascii("#..C\n.S.#\nC..#", vector![0, 0], |tile| tile);
+
+    assert_eq!(map.find(|&tile| tile == 'S'), Some((vector![1, 1], &'S')));
+    assert_eq!(map.find(|&tile| tile == 'X'), None);
+
+    let mut chests: Vec<_> = map
+        .find_all(|&tile| tile == 'C')
+        .map(|(position, _)| position)
+        .collect();
+    chests.sort_by_key(|position| (position.x, position.y));
+    assert_eq!(chests, [vector![0, 2], vector![3, 0]]);
+}
+
+#[test]
+fn cells_can_be_counted_within_boxes() {
+    for layout in [Layout::RowMajor, Layout::ColumnMajor] {
+        let mut grid = ExpandableGrid::with_size_and_layout([5, 4], [-2, -1], &false, layout);
+        for position in [[-2, -1], [0, 0], [1, 0], [2, 2], [0, 2]] {
+            grid[position] = true;
+        }
+
+        assert_eq!(grid.count_where(|&filled| filled), 5);
+        assert_eq!(grid.count_where(|&filled| !filled), 15);
+        assert_eq!(grid.count_where_box([0, 0], [10, 10], |&filled| filled), 4);
+        assert_eq!(grid.count_where_box([0, 0], [2, 1], |&filled| filled), 2);
+        assert_eq!(grid.count_where_box([9, 9], [1, 1], |&filled| filled), 0);
+    }
+}
+
+#[test]
+fn bounding_boxes_fit_matching_cells() {
+    let mut grid = ExpandableGrid3::with_size([6, 6, 6], [-3, -3, -3], &0);
+    grid[[-2, 1, 0]] = 1;
+    grid[[1, -1, 2]] = 1;
+    grid[[0, 0, 0]] = 2;
+
+    assert_eq!(
+        grid.bounding_box_of(|&cell| cell == 1),
+        Some(GridRect::new([-2, -1, 0], [4, 3, 3]))
+    );
+    assert_eq!(
+        grid.bounding_box_of(|&cell| cell == 2),
+        Some(GridRect::new([0, 0, 0], [1, 1, 1]))
+    );
+    assert_eq!(grid.bounding_box_of(|&cell| cell == 3), None);
+}
+
+#[test]
+fn extremal_cells_can_be_found() {
+    let mut heights = ExpandableGrid::with_size([4, 4], [-2, -2], &0.0f32);
+    heights[[1, -1]] = 9.5;
+    heights[[-2, 0]] = -3.0;
+    heights[[0, 1]] = -3.0;
+
+    assert_eq!(
+        heights.max_by(|a, b| a.total_cmp(b)),
+        Some((vector![1, -1], &9.5))
+    );
+    assert_eq!(
+        heights.min_by(|a, b| a.total_cmp(b)),
+        Some((vector![-2, 0], &-3.0))
+    );
+
+    let influence = heights.map(|&height| (height * 2.0) as i32);
+    assert_eq!(
+        influence
+            .min_by_key(|&value| value.abs())
+            .map(|(position, _)| position),
+        Some(vector![-2, -2])
+    );
+    assert_eq!(
+        influence.max_by_key(|&value| value),
+        Some((vector![1, -1], &19))
+    );
+    assert_eq!(ExpandableGrid::<u8>::new().max_by_key(|&value| value), None);
+}
+
+#[test]
+fn nearest_matching_cells_are_found_in_rings() {
+    for radius in 0..4 {
+        let full = (
+            SVector::repeat(-(radius as isize)),
+            SVector::repeat(2 * radius + 1),
+        );
+        let ring: Vec<SVector<isize, 3>> = util::iter_ring_within(radius, full.0, full.1).collect();
+        let expected = (2 * radius + 1).pow(3) - (2 * radius).saturating_sub(1).pow(3);
+        assert_eq!(ring.len(), expected);
+        assert!(ring
+            .iter()
+            .all(|offset| offset.abs().max() == radius as isize));
+
+        let (box_origin, box_size) = (vector![-1, 0, -5], vector![3, 10, 10]);
+        let clipped: Vec<SVector<isize, 3>> =
+            util::iter_ring_within(radius, box_origin, box_size).collect();
+        let expected: Vec<SVector<isize, 3>> = (ring.iter().copied())
+            .filter(|&offset| util::relative_position(box_origin, box_size, offset).is_some())
+            .collect();
+        assert_eq!(clipped, expected);
+    }
+
+    let mut grid = ExpandableGrid::with_size([10, 10], [0, 0], &'.');
+    grid[[7, 2]] = 'T';
+    grid[[1, 9]] = 'T';
+
+    assert_eq!(
+        grid.nearest_where([5, 5], |&tile| tile == 'T', 10),
+        Some((vector![7, 2], &'T'))
+    );
+    assert_eq!(grid.nearest_where([5, 5], |&tile| tile == 'T', 2), None);
+    assert_eq!(
+        grid.nearest_where([-20, 9], |&tile| tile == 'T', 100),
+        Some((vector![1, 9], &'T'))
+    );
+    assert_eq!(grid.nearest_where([5, 5], |&tile| tile == 'X', 100), None);
+
+    // The search starts at the bounds of the grid, so far points don't scan every empty ring
+    assert_eq!(
+        grid.nearest_where([-100_000, 9], |&tile| tile == 'T', usize::MAX),
+        Some((vector![1, 9], &'T'))
+    );
+    assert_eq!(
+        grid.nearest_where([-100_000, 9], |&tile| tile == 'T', 99_999),
+        None
+    );
+
+    // Rings are clipped to the grid, so far points in 3d don't list the cells outside it
+    let mut grid = ExpandableGrid3::with_size([4, 4, 4], [0, 0, 0], &'.');
+    grid[[3, 1, 2]] = 'T';
+    grid[[0, 3, 3]] = 'T';
+    assert_eq!(
+        grid.nearest_where([2000, 0, 0], |&tile| tile == 'T', usize::MAX),
+        Some((vector![3, 1, 2], &'T'))
+    );
+    assert_eq!(
+        grid.nearest_where([2000, 0, 0], |&tile| tile == 'X', usize::MAX),
+        None
+    );
+}
+
+#[test]
+fn k_nearest_cells_are_sorted_by_distance() {
+    let mut grid = ExpandableGrid::with_size([12, 12], [0, 0], &false);
+    for position in [[6, 11], [8, 8], [2, 3], [9, 5], [0, 0]] {
+        grid[position] = true;
+    }
+    let positions = |metric| {
+        grid.k_nearest_where([5, 5], 3, metric, |&spawn| spawn)
+            .into_iter()
+            .map(|(position, _)| position)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        positions(Metric::Euclidean),
+        [vector![2, 3], vector![9, 5], vector![8, 8]]
+    );
+    assert_eq!(
+        positions(Metric::Manhattan),
+        [vector![9, 5], vector![2, 3], vector![8, 8]]
+    );
+    assert_eq!(positions(Metric::Chebyshev).len(), 3);
+    assert_eq!(
+        grid.k_nearest_where([5, 5], 10, Metric::Euclidean, |&spawn| spawn)
+            .len(),
+        5
+    );
+    assert!(grid
+        .k_nearest_where([5, 5], 0, Metric::Euclidean, |&spawn| spawn)
+        .is_empty());
+    assert_eq!(
+        grid.k_nearest_where([100_000, -100_000], 1, Metric::Chebyshev, |&spawn| spawn),
+        [(vector![0, 0], &true)]
+    );
+
+    // Offsets from points near the ends of the coordinate space don't overflow, and cells
+    // further than `isize::MAX` away are out of reach
+    for metric in [Metric::Chebyshev, Metric::Manhattan] {
+        assert_eq!(
+            grid.k_nearest_where([isize::MAX, 5], 2, metric, |&spawn| spawn),
+            [(vector![9, 5], &true), (vector![8, 8], &true)]
+        );
+    }
+    assert_eq!(
+        grid.k_nearest_where([isize::MAX, 5], 2, Metric::Euclidean, |&spawn| spawn)
+            .len(),
+        2
+    );
+    assert_eq!(
+        grid.k_nearest_where([isize::MIN + 5, 0], 1, Metric::Euclidean, |&spawn| spawn),
+        [(vector![0, 0], &true)]
+    );
+    assert!(grid
+        .k_nearest_where([isize::MIN, isize::MIN], 1, Metric::Euclidean, |&spawn| {
+            spawn
+        })
+        .is_empty());
+    assert_eq!(
+        grid.nearest_where([isize::MIN, 0], |&spawn| spawn, usize::MAX),
+        None
+    );
+    assert_eq!(
+        util::iter_ring_within(usize::MAX, vector![isize::MAX - 1, 0], vector![5, 1])
+            .collect::<Vec<_>>(),
+        [vector![isize::MAX, 0]]
+    );
+
+    let mut grid = ExpandableGrid3::with_size([4, 4, 4], [0, 0, 0], &false);
+    for position in [[3, 0, 0], [3, 3, 3], [0, 0, 0]] {
+        grid[position] = true;
+    }
+    assert_eq!(
+        grid.k_nearest_where([2000, 0, 0], 2, Metric::Euclidean, |&spawn| spawn),
+        [(vector![3, 0, 0], &true), (vector![3, 3, 3], &true)]
+    );
+}
+
+#[test]
+fn cells_within_a_radius_form_a_disc() {
+    for layout in [Layout::RowMajor, Layout::ColumnMajor] {
+        let mut grid = ExpandableGrid::with_size_and_layout([10, 10], [0, 0], &0, layout);
+
+        assert_eq!(grid.iter_within_radius([5, 5], 2).count(), 13);
+        assert_eq!(grid.iter_within_radius([0, 0], 2).count(), 6);
+        assert_eq!(grid.iter_within_radius([-10, 0], 2).count(), 0);
+
+        for (position, cell) in grid.iter_within_radius_mut([9, 4], 1) {
+            *cell = position.y;
+        }
+        assert_eq!(grid.count_where(|&cell| cell != 0), 4);
+        assert_eq!((grid[[9, 3]], grid[[8, 4]], grid[[9, 5]]), (3, 4, 5));
+    }
+}
+
+#[test]
+fn rays_step_until_leaving_the_grid() {
+    let grid = ExpandableGrid::with_size([8, 8], [0, 0], &'.');
+
+    let knight: Vec<_> = grid
+        .iter_ray([0, 0], [1, 2], 10)
+        .map(|(position, _)| position)
+        .collect();
+    assert_eq!(knight, [vector![1, 2], vector![2, 4], vector![3, 6]]);
+
+    assert_eq!(grid.iter_ray([3, 3], [-1, 0], 2).count(), 2);
+    assert_eq!(grid.iter_ray([3, 3], [0, 1], 100).count(), 4);
+    assert_eq!(grid.iter_ray([-5, 0], [1, 0], 100).count(), 0);
+}
+
+#[test]
+fn runs_are_found_along_each_row() {
+    let map = ExpandableGrid::from_ascii("aab\nbbb\nabb", vector![1, -1], |tile| tile);
+    let mut column_major = ExpandableGrid::with_layout(Layout::ColumnMajor);
+    column_major.expand_to_fit_box(map.origin, map.size, &' ');
+    column_major.blit(&map);
+
+    for grid in [&map, &column_major] {
+        let runs: Vec<_> = grid.iter_runs().collect();
+        assert_eq!(
+            runs,
+            [
+                (vector![1, -1], 2, &'a'),
+                (vector![3, -1], 1, &'b'),
+                (vector![1, 0], 3, &'b'),
+                (vector![1, 1], 1, &'a'),
+                (vector![2, 1], 2, &'b'),
+            ]
+        );
+    }
+    assert_eq!(ExpandableGrid::<u8>::new().iter_runs().count(), 0);
+}
+
+#[test]
+fn rects_combine_and_view_grids() {
+    let a = GridRect::new([0, 0], [4, 3]);
+    let b = GridRect::new([2, -1], [4, 2]);
+    let empty = GridRect::new([9, 9], [0, 3]);
+
+    assert_eq!(a.intersection(&b), Some(GridRect::new([2, 0], [2, 1])));
+    assert_eq!(a.intersection(&empty), None);
+    assert_eq!(a.union(&b), GridRect::new([0, -1], [6, 4]));
+    assert_eq!(a.union(&empty), a);
+    assert!(a.contains([3, 2]) && !a.contains([4, 2]));
+    assert!(a.union(&b).contains_rect(&b) && !a.contains_rect(&b));
+    assert_eq!(b.inflate(1), GridRect::new([1, -2], [6, 4]));
+    assert_eq!(a.iter().count(), 12);
+    assert_eq!(b.iter().next(), Some(vector![2, -1]));
+
+    let mut grid = ExpandableGrid::new();
+    grid.expand_to_fit_rect(a, &0);
+    grid.fill_rect(b, &1);
+    assert_eq!(grid.count_where(|&cell| cell == 1), 2);
+
+    let view = grid.view(b.inflate(1));
+    assert_eq!(view.rect(), GridRect::new([1, 0], [3, 2]));
+    assert_eq!(view.get([0, 0]), None);
+    assert_eq!(view[[2, 0]], 1);
+    assert_eq!(view.iter().filter(|&(_, &cell)| cell == 1).count(), 2);
+}
+
+#[test]
+fn grids_report_their_geometry() {
+    let grid = ExpandableGrid::with_size([3, 2], [-1, 4], &0);
+
+    assert_eq!(grid.bounds(), GridRect::new([-1, 4], [3, 2]));
+    assert_eq!(grid.corner(), vector![2, 6]);
+    assert_eq!(grid.area(), 6);
+    assert!(!grid.is_empty());
+    assert!(grid.contains([1, 5]));
+    assert!(!grid.contains(grid.corner()));
+    assert!(grid.bounds().iter().all(|point| grid.contains(point)));
+
+    let empty = ExpandableGrid::with_size([3, 0], [0, 0], &0);
+    assert!(empty.is_empty() && empty.bounds().is_empty());
+    assert_eq!(empty.area(), 0);
+}
+
+#[test]
+fn segments_cover_every_touched_cell() {
+    let diagonal: Vec<_> = GridSegment::between_cells([0, 0], [2, 2]).cells().collect();
+    assert_eq!(
+        diagonal,
+        [
+            vector![0, 0],
+            vector![1, 0],
+            vector![0, 1],
+            vector![1, 1],
+            vector![2, 1],
+            vector![1, 2],
+            vector![2, 2],
+        ]
+    );
+
+    let shallow: Vec<_> = GridSegment::new([0.5, 0.5], [3.5, 1.2]).cells().collect();
+    assert_eq!(
+        shallow,
+        [
+            vector![0, 0],
+            vector![1, 0],
+            vector![2, 0],
+            vector![2, 1],
+            vector![3, 1]
+        ]
+    );
+    assert_eq!(
+        GridSegment::new([-0.5, 2.0], [-0.2, 2.9])
+            .cells()
+            .collect::<Vec<_>>(),
+        [vector![-1, 2]]
+    );
+
+    // A Bresenham line would slip between these cells
+    let mut grid = ExpandableGrid::with_size([3, 3], [0, 0], &false);
+    grid[[1, 0]] = true;
+    let segment = GridSegment::between_cells([0, 0], [2, 2]);
+    assert_eq!(
+        segment.first_blocked(&grid, |&solid| solid),
+        Some((vector![1, 0], &true))
+    );
+    assert_eq!(
+        GridSegment::between_cells([0, 2], [2, 2]).first_blocked(&grid, |&solid| solid),
+        None
+    );
+}
+
+#[test]
+fn boxes_collide_with_solid_cells() {
+    let mut grid = ExpandableGrid::with_size([6, 4], [0, 0], &false);
+    grid.fill_rect(GridRect::new([0, 0], [6, 1]), &true);
+    grid[[4, 1]] = true;
+    let solid = |&cell: &bool| cell;
+
+    let standing = Aabb::with_size([1.0, 1.0], [0.8, 1.5]);
+    assert_eq!(standing.cells(), GridRect::new([1, 1], [1, 2]));
+    assert!(!grid.collides_rect(standing, solid));
+    assert!(grid.collides_rect(standing.translated([0.0, -0.1]), solid));
+
+    let wide = Aabb::new([2.5, 0.5], [4.5, 1.5]);
+    let cells: Vec<_> = (grid.overlapping_solid_cells(wide, solid))
+        .map(|(position, _)| position)
+        .collect();
+    assert_eq!(
+        cells,
+        [vector![2, 0], vector![3, 0], vector![4, 0], vector![4, 1]]
+    );
+
+    // Cells outside the grid are never solid
+    assert!(!grid.collides_rect(Aabb::new([-3.0, -3.0], [-1.0, 5.0]), solid));
+}
+
+#[test]
+fn swept_boxes_stop_at_solid_cells() {
+    let mut grid = ExpandableGrid::with_size([8, 4], [0, 0], &false);
+    grid.fill_rect(GridRect::new([0, 0], [8, 1]), &true);
+    grid.fill_rect(GridRect::new([6, 1], [1, 3]), &true);
+    let solid = |&cell: &bool| cell;
+    let player = Aabb::with_size([1.0, 1.0], [1.0, 1.0]);
+
+    // Sliding along the floor doesn't catch on the seams between cells, until it hits the wall
+    let slide = grid.sweep_rect(player, [8.0, 0.0], solid);
+    assert_eq!(
+        slide,
+        SweepResult {
+            time: 0.5,
+            normal: vector![-1.0, 0.0],
+            rect: Aabb::with_size([5.0, 1.0], [1.0, 1.0]),
+            cell: Some(vector![6, 1]),
+        }
+    );
+
+    let fall = grid.sweep_rect(player.translated([0.0, 2.0]), [1.0, -4.0], solid);
+    assert!(fall.hit());
+    assert_eq!((fall.time, fall.normal), (0.5, vector![0.0, 1.0]));
+    assert_eq!(fall.rect, Aabb::with_size([1.5, 1.0], [1.0, 1.0]));
+
+    let free = grid.sweep_rect(player, [2.0, 1.5], solid);
+    assert!(!free.hit());
+    assert_eq!(free.rect, player.translated([2.0, 1.5]));
+}
+
+#[test]
+fn regions_update_as_walls_change() {
+    let map = ExpandableGrid::from_ascii(
+        "....#...\n.##.#...\n.#..#..#\n.#.##...",
+        vector![0, 0],
+        |tile| tile == '.',
+    );
+    let mut regions = RegionGrid::new(map, [3, 3], |&open| open);
+
+    assert_eq!(regions.region_count(), 2);
+    assert!(regions.same_region([0, 0], [2, 3]));
+    assert!(regions.same_region([5, 0], [7, 3]));
+    assert!(!regions.same_region([0, 0], [7, 3]));
+    assert!(!regions.same_region([1, 1], [1, 1]));
+    assert_eq!(regions.region([4, 0]), None);
+
+    // Opening the wall joins the regions, and closing the corridor above splits them again
+    regions.set([4, 1], true);
+    assert_eq!(regions.region_count(), 1);
+    assert!(regions.same_region([0, 0], [7, 3]));
+    regions.set([3, 0], false);
+    regions.set([3, 1], false);
+    assert_eq!(regions.region_count(), 3);
+    assert!(regions.same_region([4, 1], [7, 3]));
+    assert!(!regions.same_region([0, 0], [2, 3]));
+    assert!(!regions.same_region([2, 3], [7, 3]));
+
+    regions.expand_to_fit_point([-1, 0], &true);
+    assert!(regions.same_region([-1, 0], [0, 3]));
+
+    // Changing cells one at a time gives the same regions as building the grid from scratch
+    let mut state = 7u32;
+    for _ in 0..300 {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        let position = [(state >> 8) as isize % 9 - 1, (state >> 16) as isize % 4];
+        regions.set(position, state >> 28 < 10);
+
+        let rebuilt = RegionGrid::new(regions.grid().clone(), [3, 3], |&open| open);
+        assert_eq!(regions.region_count(), rebuilt.region_count());
+        let cells = (-1..8).flat_map(|x| (0..4).map(move |y| [x, y]));
+        for (a, b) in cells
+            .clone()
+            .flat_map(|a| cells.clone().map(move |b| (a, b)))
+        {
+            assert_eq!(regions.same_region(a, b), rebuilt.same_region(a, b));
+        }
+    }
+}
+
+#[test]
+fn influence_spreads_from_moving_sources() {
+    let mut map = InfluenceMap::with_size([10, 10], [0, 0]);
+    let tower = map.add_source(InfluenceSource::new([2, 2], 4.0, 2, Decay::Linear));
+    let unit = map.add_source(InfluenceSource::new([7, 2], 1.0, 3, Decay::Constant));
+
+    assert_eq!(map[[2, 2]], 4.0);
+    assert_eq!(map[[3, 2]], 2.0);
+    assert_eq!(map[[4, 2]], 1.0);
+    assert_eq!(map[[2, 5]], 0.0);
+    assert_eq!(map[[7, 5]], 1.0);
+
+    assert!(map.move_source(unit, [3, 2]));
+    assert_eq!(map[[3, 2]], 3.0);
+    assert_eq!(map[[7, 5]], 0.0);
+    assert_eq!(map.source(unit).unwrap().position, vector![3, 2]);
+
+    map.remove_source(tower);
+    assert_eq!(map[[2, 2]], 1.0);
+    assert!(!map.move_source(tower, [0, 0]));
+
+    let before = map.data.clone();
+    map.recompute();
+    assert_eq!(map.data, before);
+
+    map.add_source(InfluenceSource::new(
+        [0, 9],
+        8.0,
+        2,
+        Decay::Exponential(0.5),
+    ));
+    assert_eq!(map[[0, 7]], 2.0);
+    map.expand_to_fit_point([-1, 9]);
+    assert_eq!(map[[-1, 9]], 4.0);
+}
+
+#[test]
+fn automata_step_with_boundaries() {
+    let blinker = ExpandableGrid::from_ascii(".....\n.###.\n.....", vector![0, 0], |c| c == '#');
+    let life = Automaton::new(BirthSurvival::life(), Boundary::Fixed(false));
+    assert_eq!(BirthSurvival::parse("B3/S23"), Some(BirthSurvival::life()));
+    assert_eq!(BirthSurvival::parse("3/23"), None);
+
+    let vertical = life.step(&blinker);
+    let alive: Vec<_> = vertical.find_all(|&alive| alive).map(|(p, _)| p).collect();
+    assert_eq!(alive, [vector![2, 0], vector![2, 1], vector![2, 2]]);
+
+    let mut buffered = DoubleBufferedGrid::new(vertical);
+    life.step_buffered(&mut buffered);
+    assert_eq!(buffered.front().data, blinker.data);
+
+    // On a torus the blinker's ends wrap around and meet
+    let wrapped = Automaton::new(BirthSurvival::life(), Boundary::Wrap);
+    let row = ExpandableGrid::from_ascii("###", vector![0, 0], |c| c == '#');
+    assert_eq!(wrapped.step(&row).count_where(|&alive| alive), 0);
+
+    // A closure rule counting walls, where the world beyond the map is solid
+    let count = |_: &u8, neighbors: &[&u8]| neighbors.iter().map(|&&n| n).sum::<u8>();
+    let walls =
+        Automaton::new(count, Boundary::Fixed(1)).with_neighborhood(Neighborhood::VonNeumann);
+    let grid = ExpandableGrid::with_size([3, 2], [5, 5], &0u8);
+    assert_eq!(walls.step(&grid).data.as_ref(), [2, 1, 2, 2, 1, 2]);
+
+    let clamped = Automaton::new(count, Boundary::Clamp);
+    let mut grid = ExpandableGrid::with_size([2, 1], [0, 0], &0u8);
+    grid[[1, 0]] = 1;
+    assert_eq!(clamped.step(&grid).data.as_ref(), [3, 5]);
+}
+
+#[test]
+fn array_subchunks_index_rows_of_cells() {
+    type Chunk = ArraySubchunk<u8, 3, 2>;
+    assert_eq!(Chunk::SUBCHUNK_SIZE, vector![3, 2]);
+
+    let chunk = Chunk::from_fn(|position| (position.x + 10 * position.y) as u8);
+    assert_eq!(chunk.cells, [[0, 1, 2], [10, 11, 12]]);
+    assert_eq!(chunk[vector![2, 1]], 12);
+    assert_eq!(chunk.iter().nth(3), Some((vector![0, 1], &10)));
+
+    let mut grid = ExpandableGrid::with_size(vector![2, 2], vector![-1, -1], &Chunk::default());
+    *grid.get_mut_from_subchunk(vector![-1, -1]).unwrap() = 5;
+    assert_eq!(grid[vector![-1, -1]][vector![2, 1]], 5);
+    assert_eq!(grid.get_from_subchunk(vector![5, 3]), None);
+
+    let mut filled = Chunk::filled(&7);
+    filled
+        .iter_mut()
+        .for_each(|(position, cell)| *cell += position.y as u8);
+    assert_eq!(filled.into_inner(), [[7; 3], [8; 3]]);
+}
+
+#[test]
+fn setting_subchunk_cells_creates_their_chunks() {
+    type Chunk = ArraySubchunk<u8, 2, 2>;
+    let mut grid = ExpandableGrid::<Chunk>::new();
+
+    grid.set_from_subchunk(vector![3, -1], 4, Chunk::default);
+    assert_eq!(grid.get_from_subchunk(vector![3, -1]), Some(&4));
+    assert_eq!(grid[[1, -1]].cells, [[0, 0], [0, 4]]);
+
+    grid.set_from_subchunk(vector![-2, 0], 5, || Chunk::filled(&1));
+    assert_eq!(grid.get_from_subchunk(vector![-1, 1]), Some(&1));
+    assert_eq!(grid.get_from_subchunk(vector![-2, 0]), Some(&5));
+    assert_eq!(grid.get_from_subchunk(vector![3, -1]), Some(&4));
+
+    let size = grid.size;
+    grid.set_from_subchunk(vector![3, -2], 6, || unreachable!());
+    assert_eq!(grid.size, size);
+    assert_eq!(grid[[1, -1]].cells, [[0, 6], [0, 4]]);
+}
+
+#[test]
+fn subchunk_cells_are_indexed_globally() {
+    type Chunk = ArraySubchunk<u8, 2, 3>;
+    let mut grid = ExpandableGrid::with_size([2, 1], [-1, 0], &Chunk::default());
+
+    grid.cells_mut()[[-1, 2]] = 3;
+    grid.cells_mut()[vector![1, 0]] += 4;
+    assert_eq!(grid[[-1, 0]].cells[2][1], 3);
+    assert_eq!(grid[[0, 0]].cells[0][1], 4);
+
+    let cells = grid.cells();
+    assert_eq!(cells[[-1, 2]], 3);
+    assert_eq!(cells.get([1, 0]), Some(&4));
+    assert_eq!(cells.get([2, 0]), None);
+    assert_eq!(cells.get([0, -1]), None);
+}
+
+#[test]
+fn subchunk_cells_are_iterated_chunk_by_chunk() {
+    type Chunk = ArraySubchunk<u8, 2, 2>;
+    let mut grid = ExpandableGrid::with_size([2, 1], [-1, 3], &Chunk::default());
+
+    grid.for_each_subchunk_cell_mut(|position, cell| *cell = (position.x + 10 * position.y) as u8);
+    assert_eq!(grid.cells()[[-2, 7]], 68);
+
+    let cells: Vec<_> = grid.iter_subchunk_cells().collect();
+    assert_eq!(cells.len(), 8);
+    assert_eq!(
+        cells[..4],
+        [
+            (vector![-2, 6], &58),
+            (vector![-1, 6], &59),
+            (vector![-2, 7], &68),
+            (vector![-1, 7], &69),
+        ]
+    );
+    assert_eq!(cells[4], (vector![0, 6], &60));
+    assert!(cells
+        .iter()
+        .all(|&(position, &cell)| grid.cells()[position] == cell));
+}
+
+#[test]
+fn subchunk_grids_expand_to_fit_cells() {
+    type Chunk = ArraySubchunk<u8, 4, 2>;
+    let mut grid = ExpandableGrid::<Chunk>::new();
+
+    grid.expand_to_fit_cell([-1, 5], Chunk::default);
+    assert_eq!((grid.origin, grid.size), (vector![-1, 2], vector![1, 1]));
+
+    grid.expand_to_fit_cell_box([-8, 3], [12, 4], || Chunk::filled(&2));
+    assert!(grid.bounds().contains_rect(&GridRect::new([-2, 1], [3, 3])));
+    assert_eq!(grid.cells()[[-8, 3]], 2);
+    assert_eq!(grid.cells()[[-1, 5]], 0);
+
+    let bounds = grid.bounds();
+    grid.expand_to_fit_cell_box([-5, 2], [8, 5], || unreachable!());
+    grid.expand_to_fit_cell_box([100, 100], [0, 3], || unreachable!());
+    assert_eq!(grid.bounds(), bounds);
+}
+
+#[test]
+fn chunk_metadata_moves_with_its_chunk() {
+    type Entry = ChunkEntry<ArraySubchunk<u8, 2, 2>, Option<&'static str>>;
+    let mut grid = ExpandableGrid::<Entry>::new();
+
+    grid.set_from_subchunk(vector![3, 1], 7, Entry::default);
+    *grid.metadata_of_cell_mut([2, 0]).unwrap() = Some("generated");
+    assert_eq!(grid[[1, 0]].metadata, Some("generated"));
+
+    grid.expand_to_fit_cell_box([-6, -6], [1, 1], Entry::default);
+    assert_eq!(grid.metadata_of_cell([3, 1]), Some(&Some("generated")));
+    assert_eq!(grid.metadata_of_cell([-6, -6]), Some(&None));
+    assert_eq!(grid.cells()[[3, 1]], 7);
+
+    grid.change_size([1, 1], [4, 3], &Entry::default());
+    assert_eq!(grid.metadata_of_cell([3, 1]), Some(&Some("generated")));
+    assert_eq!(grid.metadata_of_cell([-6, -6]), None);
+}
+
+#[test]
+fn uniform_subchunks_are_compressed() {
+    type Chunk = CompressedSubchunk<ArraySubchunk<u8, 2, 2>>;
+    let mut grid = ExpandableGrid::with_size([3, 1], [0, 0], &Chunk::Uniform(1));
+    assert_eq!(grid.cells()[[5, 1]], 1);
+
+    grid.cells_mut()[[2, 1]] = 4;
+    assert!(!grid[[1, 0]].is_uniform());
+    assert!(grid[[2, 0]].is_uniform());
+    assert_eq!(
+        grid.iter_subchunk_cells()
+            .filter(|&(_, &cell)| cell == 4)
+            .map(|(position, _)| position)
+            .collect::<Vec<_>>(),
+        [vector![2, 1]],
+    );
+
+    assert_eq!(grid.compress_uniform_chunks(), 2);
+    grid.cells_mut()[[2, 1]] = 1;
+    assert_eq!(grid.compress_uniform_chunks(), 3);
+    assert_eq!(grid[[1, 0]], Chunk::Uniform(1));
+}
+
+#[test]
+fn resizing_empty_grids_moves_them() {
+    let mut grid = ExpandableGrid::new();
+    grid.change_size([2, 2], [3, -1], &0);
+    assert_eq!((grid.size, grid.origin), (vector![2, 2], vector![3, -1]));
+
+    grid.change_size([0, 2], [1, 1], &0);
+    grid.change_size([1, 1], [2, 0], &7);
+    assert_eq!((grid.size, grid.origin), (vector![1, 1], vector![6, 0]));
+    assert_eq!(grid[[6, 0]], 7);
+}
+
+#[cfg(feature = "proptest")]
+::proptest::proptest! {
+    #[test]
+    fn arbitrary_grids_are_consistent(grid in ::proptest::prelude::any::<ExpandableGrid<u8>>()) {
+        ::proptest::prop_assert_eq!(grid.data.len(), grid.size.product());
+        ::proptest::prop_assert!(grid.size.iter().all(|&length| length <= 16));
+        ::proptest::prop_assert!(grid.origin.iter().all(|&origin| (-16..=16).contains(&origin)));
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+#[test]
+fn quickcheck_grids_shrink() {
+    use ::quickcheck::Arbitrary;
+
+    let mut grid = ExpandableGrid::with_size(vector![4, 3], vector![-5, 7], &0u8);
+    grid[vector![-2, 9]] = 9;
+
+    let shrunk: Vec<_> = grid.shrink().collect();
+    assert!(shrunk
+        .iter()
+        .all(|shrunk| shrunk.data.len() == shrunk.size.product()));
+    // Cropping keeps the coordinates of the remaining cells
+    assert!(shrunk.iter().any(|shrunk| shrunk.size == vector![2, 3]
+        && shrunk.origin == vector![-3, 7]
+        && shrunk[vector![-2, 9]] == 9));
+    assert!(shrunk.iter().any(|shrunk| shrunk.origin == vector![0, 7]));
+    assert!(shrunk
+        .iter()
+        .any(|shrunk| shrunk.data.iter().sum::<u8>() < 9));
+}
+
+#[cfg(feature = "scripting")]
+#[test]
+fn scripts_transform_grids() {
+    use crate::scripting::{register_grid, run_script};
+
+    let mut engine = rhai::Engine::new();
+    register_grid::<i64>(&mut engine);
+
+    let mut grid = ExpandableGrid::with_size(vector![3, 3], vector![-1, -1], &1i64);
+    let script = "
+        grid.fill_box(0, 0, 5, 5, 7);
+        grid.map(|value| value * 2);
+        grid.expand_to_fit_point(4, -1, 0);
+        grid.set(4, -1, 3);
+        let total = 0;
+        for position in grid.positions() {
+            total += grid.get(position[0], position[1]);
+        }
+        total
+    ";
+    let total = run_script(&engine, script, &mut grid).unwrap();
+
+    assert_eq!(grid[vector![-1, -1]], 2);
+    assert_eq!(grid[vector![1, 1]], 14);
+    assert_eq!(grid[vector![4, -1]], 3);
+    assert_eq!(total.as_int().unwrap(), grid.data.iter().sum::<i64>());
+
+    assert!(run_script(&engine, "grid.set(0, 0, \"text\")", &mut grid).is_err());
+    assert_eq!(grid[vector![1, 1]], 14);
+
+    // Boxes past the range of coordinates are errors rather than overflowing
+    for script in [
+        "grid.fill_box(0, 9223372036854775807, 1, 1, 5)",
+        "grid.fill_box(1, 0, 9223372036854775807, 1, 5)",
+        "grid.expand_to_fit_box(9223372036854775807, 0, 2, 1, 5)",
+    ] {
+        assert!(run_script(&engine, script, &mut grid).is_err());
+    }
+    assert!(run_script(&engine, "grid.fill_box(1, 1, -3, 2, 5)", &mut grid).is_ok());
+    assert_eq!(grid[vector![1, 1]], 14);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn tile_executor_matches_serial_updates() {
+    use crate::parallel::TileExecutor;
+
+    let mut rng = ChaCha8Rng::seed_from_u64(7);
+    let mut grid = ExpandableGrid::with_size(vector![37, 23], vector![-11, 5], &0u32);
+    for cell in grid.data.iter_mut() {
+        *cell = rng.gen_range(0..10);
+    }
+
+    // Sum each cell with its neighbors, serially
+    let mut expected = grid.clone();
+    for _ in 0..3 {
+        let previous = expected.clone();
+        for y in 0..23 {
+            for x in 0..37 {
+                let cell = vector![x - 11, y + 5];
+                let sum = [[0, 0], [1, 0], [-1, 0], [0, 1], [0, -1]]
+                    .into_iter()
+                    .map(|[dx, dy]| previous.get(cell + vector![dx, dy]).copied().unwrap_or(1))
+                    .sum();
+                expected[cell] = sum;
+            }
+        }
+    }
+
+    TileExecutor::new(vector![8, 5], 1).run(&mut grid, 3, &1, |view, output| {
+        for cell in view.positions() {
+            output[cell] = [[0, 0], [1, 0], [-1, 0], [0, 1], [0, -1]]
+                .into_iter()
+                .map(|[dx, dy]| *view.get(cell + vector![dx, dy]))
+                .sum();
+        }
+    });
+
+    assert_eq!(grid.data, expected.data);
+}
+
+#[cfg(feature = "rand")]
+#[test]
+fn cells_are_sampled_randomly() {
+    let mut rng = ChaCha8Rng::seed_from_u64(3);
+    let mut grid = ExpandableGrid::with_size(vector![6, 5], vector![-3, 2], &0u32);
+    grid[vector![1, 4]] = 5;
+    grid[vector![-2, 6]] = 1;
+
+    for _ in 0..50 {
+        let (position, &cell) = grid.choose(&mut rng).unwrap();
+        assert_eq!(grid[position], cell);
+
+        let (position, _) = grid.choose_where(&mut rng, |&cell| cell > 0).unwrap();
+        assert!([vector![1, 4], vector![-2, 6]].contains(&position));
+    }
+    assert_eq!(grid.choose_where(&mut rng, |&cell| cell > 5), None);
+
+    let heavy = (0..200)
+        .filter(|_| {
+            grid.choose_weighted(&mut rng, |&cell| cell as f64)
+                .unwrap()
+                .1
+                == &5
+        })
+        .count();
+    assert!(heavy > 140, "{heavy}");
+    assert_eq!(grid.choose_weighted(&mut rng, |_| 0.0), None);
+    assert_eq!(grid.choose_weighted(&mut rng, |_| f64::INFINITY), None);
+
+    let huge = (0..200)
+        .filter(|_| {
+            let (_, &cell) = grid
+                .choose_weighted(&mut rng, |&cell| match cell {
+                    5 => f64::MAX,
+                    1 => f64::MAX / 4.0,
+                    _ => f64::NAN,
+                })
+                .unwrap();
+            assert_ne!(cell, 0);
+            cell == 5
+        })
+        .count();
+    assert!((140..190).contains(&huge), "{huge}");
+    assert_eq!(ExpandableGrid::<u8>::new().choose(&mut rng), None);
+}
+
+#[cfg(feature = "rand")]
+#[test]
+fn poisson_scatter_keeps_points_apart() {
+    let mut rng = ChaCha8Rng::seed_from_u64(7);
+    let mut grid = ExpandableGrid::with_size(vector![40, 30], vector![-10, 0], &'.');
+    grid.fill_rect(GridRect::new([5, 0], [3, 30]), &'~');
+
+    let points = grid.scatter_poisson(&mut rng, 4.0, |_, &tile| tile == '.');
+    assert!(points.len() > 30, "{}", points.len());
+    for (i, a) in points.iter().enumerate() {
+        assert_eq!(grid[*a], '.');
+        for b in &points[i + 1..] {
+            assert!((a - b).map(|length| length as f64).norm() >= 4.0);
+        }
+    }
+
+    // Both sides of the river are filled
+    assert!(points.iter().any(|point| point.x < 5));
+    assert!(points.iter().any(|point| point.x >= 8));
+
+    let every_cell = grid.scatter_poisson(&mut rng, 0.5, |position, _| position.y == 0);
+    assert_eq!(every_cell.len(), 40);
+}
+
+#[cfg(feature = "rand")]
+#[test]
+fn synthesized_grids_follow_the_example() {
+    use crate::synthesis::TileRules;
+
+    // Water is always separated from grass by sand
+    let example = ExpandableGrid::from_ascii(
+        "~~~ss,,\n~~ss,,,\n~ss,,,,\nss,,,ss\ns,,,s~~",
+        vector![0, 0],
+        |tile| tile,
+    );
+    let rules = TileRules::learn(&example);
+    assert_eq!(rules.tiles(), ['~', 's', ',']);
+    assert!(rules.allows(&'~', &'s', 0) && !rules.allows(&'~', &',', 0));
+    assert!(!rules.allows(&',', &'~', 1));
+
+    let mut rng = ChaCha8Rng::seed_from_u64(11);
+    let grid = rules.generate(&mut rng, [12, 9], [-3, 2], 20).unwrap();
+    assert_eq!(grid.bounds(), GridRect::new([-3, 2], [12, 9]));
+    for position in grid.bounds().iter() {
+        for axis in 0..2 {
+            let mut next = position;
+            next[axis] += 1;
+            if let Some(neighbor) = grid.get(next) {
+                assert!(rules.allows(&grid[position], neighbor, axis));
+            }
+        }
+    }
+
+    // A lone tile can't be placed next to anything
+    let lonely = TileRules::learn(&ExpandableGrid::with_size([1, 1], [0, 0], &0));
+    assert!(lonely.generate(&mut rng, [2, 1], [0, 0], 5).is_none());
+    assert!(lonely.generate(&mut rng, [1, 1], [0, 0], 5).is_some());
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn derived_subchunks_index_their_cells() {
+    #[derive(Clone, Default, crate::subchunk::Subchunk)]
+    #[subchunk(crate = crate)]
+    struct Rows([[u8; 3]; 2]);
+
+    #[derive(Clone, crate::subchunk::Subchunk)]
+    #[subchunk(crate = crate, width = 2, height = 3, column_major)]
+    struct Columns {
+        #[subchunk]
+        cells: Vec<u8>,
+        _generation: u32,
+    }
+
+    assert_eq!(Rows::SUBCHUNK_SIZE, Vector2::new(3, 2));
+    assert_eq!(Columns::SUBCHUNK_SIZE, Vector2::new(2, 3));
+
+    let mut rows = Rows::default();
+    rows[Vector2::new(2, 1)] = 5;
+    assert_eq!(rows.0[1][2], 5);
+
+    let mut columns = Columns {
+        cells: vec![0; 6],
+        _generation: 0,
+    };
+    columns[Vector2::new(1, 0)] = 7;
+    assert_eq!(columns.cells, [0, 0, 0, 7, 0, 0]);
+
+    let mut grid = ExpandableGrid::with_size([1, 1], [-1, 0], &rows);
+    *grid.get_mut_from_subchunk(vector![-1, 1]).unwrap() = 9;
+    assert_eq!(grid[[-1, 0]].0[1][2], 9);
+    assert_eq!(grid.get_from_subchunk(vector![0, 0]), None);
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn mmap_grid_expands_and_persists() {
+    use crate::mmap::MmapGrid;
+
+    let path = std::env::temp_dir().join("expandable_grid_mmap_test.grid");
+
+    let mut grid = MmapGrid::create(&path, vector![4, 4], vector![0, 0], &0u32).unwrap();
+    grid[vector![1, 2]] = 5;
+    grid.expand_to_fit_point(vector![-10, 7], &1);
+    grid[vector![-10, 7]] = 6;
+    grid.fill_box([2, 0], [2, 1], &7);
+    assert_eq!(grid.path(), path);
+    grid.flush().unwrap();
+    let (size, origin) = (grid.size, grid.origin);
+    drop(grid);
+
+    let mut grid = MmapGrid::<u32, 2>::open(&path).unwrap();
+    assert_eq!(grid.size, size);
+    assert_eq!(grid.origin, origin);
+    assert_eq!(grid[vector![1, 2]], 5);
+    assert_eq!(grid[vector![-10, 7]], 6);
+    assert_eq!(grid[vector![0, 0]], 0);
+    assert_eq!(grid[vector![3, 0]], 7);
+    assert_eq!(grid[vector![-1, 0]], 1);
+
+    // Shrinking keeps the cells within the new bounds, but the file can't be opened until the
+    // new size has been flushed
+    grid.change_size(vector![2, 3], vector![10, 0], &0);
+    assert_eq!(grid.data.len(), 6);
+    assert_eq!(grid[vector![1, 2]], 5);
+    assert!(MmapGrid::<u32, 2>::open(&path).is_err());
+    grid.flush().unwrap();
+    drop(grid);
+    assert_eq!(MmapGrid::<u32, 2>::open(&path).unwrap()[vector![1, 2]], 5);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn tracked_grid_saves_only_dirty_tiles() {
+    #[derive(Default)]
+    struct MemoryStore(std::collections::HashMap<Vector2<isize>, ExpandableGrid<u8>>);
+
+    impl TileStore<u8, 2> for MemoryStore {
+        fn store_tile(
+            &mut self,
+            tile: Vector2<isize>,
+            grid: &ExpandableGrid<u8>,
+        ) -> std::io::Result<()> {
+            self.0.insert(tile, grid.clone());
+            Ok(())
+        }
+
+        fn remove_tile(&mut self, tile: Vector2<isize>) -> std::io::Result<()> {
+            self.0.remove(&tile);
+            Ok(())
+        }
+    }
+
+    let grid = ExpandableGrid::with_size(vector![32, 32], vector![0, 0], &0u8);
+    let mut tracked = TrackedGrid::new(grid, vector![8, 8]);
+    let mut store = MemoryStore::default();
+
+    assert_eq!(tracked.save_dirty(&mut store).unwrap(), 16);
+    assert_eq!(tracked.save_dirty(&mut store).unwrap(), 0);
+
+    tracked[vector![3, 3]] = 1;
+    tracked[vector![4, 5]] = 2;
+    tracked[vector![31, 0]] = 3;
+    assert_eq!(tracked.save_dirty(&mut store).unwrap(), 2);
+    assert_eq!(store.0[&vector![0, 0]][vector![4, 5]], 2);
+
+    // Shrinking removes tiles, and changes the contents of the tiles on the new edge
+    tracked.change_size(vector![20, 32], vector![0, 0], &0);
+    assert_eq!(tracked.save_dirty(&mut store).unwrap(), 4);
+    assert_eq!(store.0.len(), 12);
+    assert_eq!(store.0[&vector![2, 0]].size, vector![4, 8]);
+}
+
+#[test]
+fn corrupted_data_is_detected() {
+    let mut grid = ExpandableGrid::with_size(vector![20, 20], vector![0, 0], &0u16);
+    for (i, cell) in grid.data.iter_mut().enumerate() {
+        *cell = i as u16;
+    }
+
+    let mut bytes = Vec::new();
+    grid.write_binary(&mut bytes).unwrap();
+    bytes[100] ^= 1;
+    assert!(matches!(
+        ExpandableGrid::<u16>::read_binary(&mut bytes.as_slice()),
+        Err(binary::BinaryError::ChecksumMismatch { .. }),
+    ));
+
+    let mut file = std::io::Cursor::new(Vec::new());
+    grid.write_region(vector![8, 8], &mut file).unwrap();
+    let mut bytes = file.into_inner();
+
+    // Corrupt the last tile, which is the one with the highest coordinates
+    let last = bytes.len() - 10;
+    bytes[last] ^= 1;
+
+    let mut region = RegionReader::<_, 2>::open(std::io::Cursor::new(bytes)).unwrap();
+    assert!(region.read_tile::<u16>(vector![0, 0]).is_ok());
+    match region.read_tile::<u16>(vector![2, 2]) {
+        Err(binary::BinaryError::InTile { tile, error }) => {
+            assert_eq!(tile, vec![2, 2]);
+            assert!(matches!(
+                *error,
+                binary::BinaryError::ChecksumMismatch { .. }
+            ));
+        }
+        result => panic!("corrupted tile should fail to read, got {result:?}"),
+    }
+    assert!(region.verify().is_err());
+
+    // Older versions of the formats are not read, rather than being read without checksums
+    let mut bytes = Vec::new();
+    grid.write_binary(&mut bytes).unwrap();
+    bytes[4] = 3;
+    assert!(matches!(
+        ExpandableGrid::<u16>::read_binary(&mut bytes.as_slice()),
+        Err(binary::BinaryError::UnsupportedVersion(3)),
+    ));
+
+    let mut file = std::io::Cursor::new(Vec::new());
+    grid.write_region(vector![8, 8], &mut file).unwrap();
+    let mut bytes = file.into_inner();
+    bytes[4] = 1;
+    assert!(matches!(
+        RegionReader::<_, 2>::open(std::io::Cursor::new(bytes)),
+        Err(binary::BinaryError::UnsupportedVersion(1)),
+    ));
+}
+
+#[test]
+fn migrations_upgrade_old_grids() {
+    let mut grid = ExpandableGrid::with_size(vector![5, 4], vector![-2, 1], &3u8);
+    grid[vector![0, 2]] = 200;
+
+    let mut migrations = Migrations::new(2);
+    migrations.register(0, |grid| {
+        grid.map_cells(2, |old, new| {
+            new.copy_from_slice(&(old[0] as u16).to_le_bytes())
+        });
+        Ok(())
+    });
+    migrations.register(1, |grid| {
+        grid.map_cells(2, |old, new| {
+            let value = u16::from_le_bytes([old[0], old[1]]) * 10;
+            new.copy_from_slice(&value.to_le_bytes());
+        });
+        Ok(())
+    });
+
+    let mut raw_bytes = Vec::new();
+    grid.write_binary(&mut raw_bytes).unwrap();
+    let mut rle_bytes = Vec::new();
+    grid.write_binary_rle(&mut rle_bytes).unwrap();
+
+    for bytes in [&raw_bytes, &rle_bytes] {
+        let read = ExpandableGrid::<u16>::read_binary_migrated(&mut bytes.as_slice(), &migrations)
+            .unwrap();
+        assert_eq!(read.size, grid.size);
+        assert_eq!(read.origin, grid.origin);
+        assert_eq!(read[vector![0, 2]], 2000);
+        assert_eq!(read[vector![2, 4]], 30);
+    }
+
+    // Grids already at the current version are read directly
+    let mut current = Vec::new();
+    ExpandableGrid::with_size(vector![2, 2], vector![0, 0], &7u16)
+        .write_binary_with_version(&mut current, 2)
+        .unwrap();
+    let read =
+        ExpandableGrid::<u16>::read_binary_migrated(&mut current.as_slice(), &migrations).unwrap();
+    assert!(read.data.iter().all(|&cell| cell == 7));
+
+    assert!(matches!(
+        ExpandableGrid::<u16>::read_binary_migrated(&mut current.as_slice(), &Migrations::new(1)),
+        Err(binary::BinaryError::UnsupportedDataVersion {
+            current: 1,
+            found: 2
+        }),
+    ));
+    assert!(matches!(
+        ExpandableGrid::<u16>::read_binary_migrated(&mut raw_bytes.as_slice(), &Migrations::new(1)),
+        Err(binary::BinaryError::MissingMigration { version: 0 }),
+    ));
+}
+
+#[test]
+fn directory_stores_migrate_old_tiles() {
+    let directory = std::env::temp_dir().join("expandable_grid_directory_store_test");
+    let _ = std::fs::remove_dir_all(&directory);
+
+    let mut store = DirectoryStore::new(&directory).unwrap();
+    let tile = ExpandableGrid::with_size(vector![4, 4], vector![4, 0], &3u8);
+    store.store_tile(vector![1, 0], &tile).unwrap();
+
+    let mut migrations = Migrations::new(1);
+    migrations.register(0, |grid| {
+        grid.map_cells(2, |old, new| {
+            new.copy_from_slice(&(old[0] as u16 * 10).to_le_bytes())
+        });
+        Ok(())
+    });
+
+    let read = store
+        .read_tile_migrated::<u16, 2>(vector![1, 0], &migrations)
+        .unwrap()
+        .unwrap();
+    assert_eq!((read.size, read.origin), (tile.size, tile.origin));
+    assert!(read.data.iter().all(|&cell| cell == 30));
+    assert!(store
+        .read_tile_migrated::<u16, 2>(vector![5, 5], &migrations)
+        .unwrap()
+        .is_none());
+
+    // Tiles saved with the current data version are read directly
+    let mut store = DirectoryStore::with_data_version(&directory, 1).unwrap();
+    store
+        .store_tile(
+            vector![1, 0],
+            &ExpandableGrid::with_size(vector![4, 4], vector![4, 0], &7u16),
+        )
+        .unwrap();
+    let read = store
+        .read_tile_migrated::<u16, 2>(vector![1, 0], &migrations)
+        .unwrap()
+        .unwrap();
+    assert!(read.data.iter().all(|&cell| cell == 7));
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn serialized_grids_migrate_old_cells() {
+    use crate::migration::CellMigrator;
+    use ::serde::{Deserialize, Deserializer};
+
+    /// Version 0 stored cells as `u8`, and version 1 as `u16`, which version 2 multiplies by 10.
+    struct Upgrade;
+
+    impl CellMigrator<u32> for Upgrade {
+        fn current_version(&self) -> u32 {
+            2
+        }
+
+        fn deserialize_cell<'de, De: Deserializer<'de>>(
+            &self,
+            version: u32,
+            deserializer: De,
+        ) -> Result<u32, De::Error> {
+            match version {
+                0 => u8::deserialize(deserializer).map(|cell| cell as u32 * 10),
+                _ => u16::deserialize(deserializer).map(|cell| cell as u32 * 10),
+            }
+        }
+    }
+
+    fn from_json<T>(
+        json: &str,
+        f: impl FnOnce(&mut serde_json::Deserializer<serde_json::de::StrRead>) -> serde_json::Result<T>,
+    ) -> serde_json::Result<T> {
+        f(&mut serde_json::Deserializer::from_str(json))
+    }
+
+    let mut grid = ExpandableGrid::with_size(vector![3, 2], vector![-1, 4], &4u8);
+    grid[vector![1, 5]] = 200;
+
+    // The default form
+    let json = serde_json::to_string(&grid).unwrap();
+    assert!(json.starts_with(r#"{"version":2,"data_version":0,"#));
+    let read = from_json(&json, |json| {
+        ExpandableGrid::<u32>::deserialize_migrated(json, &Upgrade)
+    })
+    .unwrap();
+    assert_eq!((read.size, read.origin), (grid.size, grid.origin));
+    assert_eq!((read[vector![1, 5]], read[vector![0, 4]]), (2000, 40));
+
+    let mut current = Vec::new();
+    ExpandableGrid::with_size(vector![1, 1], vector![0, 0], &70_000u32)
+        .serialize_with_version(2, &mut serde_json::Serializer::new(&mut current))
+        .unwrap();
+    let current = String::from_utf8(current).unwrap();
+    let read = from_json(&current, |json| {
+        ExpandableGrid::<u32>::deserialize_migrated(json, &Upgrade)
+    })
+    .unwrap();
+    assert_eq!(read.data[0], 70_000);
+    assert!(serde_json::from_str::<ExpandableGrid<u32>>(&current).is_ok());
+
+    // Fields in order, as formats which are not self describing store them, including version 1
+    // which had no data version
+    let read: ExpandableGrid<u8> =
+        serde_json::from_str(r#"[2, 0, [1, 2], [0, 0], "RowMajor", [1, 2]]"#).unwrap();
+    assert_eq!(read.data[..], [1, 2]);
+    let read = from_json(r#"[1, [1, 2], [0, 0], "ColumnMajor", [1, 2]]"#, |json| {
+        ExpandableGrid::<u32>::deserialize_migrated(json, &Upgrade)
+    })
+    .unwrap();
+    assert_eq!(
+        (read.layout, &read.data[..]),
+        (Layout::ColumnMajor, &[10, 20][..])
+    );
+
+    // The version is never optional, and the data version must be known before the cells
+    let missing = r#"{"size":[1,1],"origin":[0,0],"layout":"RowMajor","data":[1]}"#;
+    assert!(serde_json::from_str::<ExpandableGrid<u8>>(missing).is_err());
+    let late = r#"{"version":2,"data":[1],"data_version":0,"size":[1,1],"origin":[0,0],"layout":"RowMajor"}"#;
+    assert!(serde_json::from_str::<ExpandableGrid<u8>>(late).is_err());
+    let newer = r#"{"version":3,"data_version":0,"size":[1,1],"origin":[0,0],"layout":"RowMajor","data":[1]}"#;
+    assert!(serde_json::from_str::<ExpandableGrid<u8>>(newer).is_err());
+    let future = current.replace(r#""data_version":2"#, r#""data_version":3"#);
+    assert!(from_json(&future, |json| {
+        ExpandableGrid::<u32>::deserialize_migrated(json, &Upgrade)
+    })
+    .is_err());
+
+    // The run length encoded form
+    let mut json = Vec::new();
+    crate::rle::serde::serialize_with_version(
+        &grid,
+        1,
+        &mut serde_json::Serializer::new(&mut json),
+    )
+    .unwrap();
+    let json = String::from_utf8(json).unwrap();
+    assert!(json.starts_with(r#"{"version":1,"data_version":1,"#));
+    let read: ExpandableGrid<u32> = from_json(&json, |json| {
+        crate::rle::serde::deserialize_migrated(json, &Upgrade)
+    })
+    .unwrap();
+    assert_eq!((read[vector![1, 5]], read[vector![0, 4]]), (2000, 40));
+
+    // A size far larger than its runs is rejected without allocating it
+    let huge = r#"{"version":1,"data_version":0,"size":[2147483648,2147483648],"origin":[0,0],"layout":"RowMajor","runs":[]}"#;
+    let huge: Result<ExpandableGrid<u32>, _> =
+        crate::rle::serde::deserialize(&mut serde_json::Deserializer::from_str(huge));
+    assert!(huge.is_err());
+
+    // The readable form
+    let json = grid.to_json_string().unwrap();
+    assert!(json.starts_with("{\n  \"version\": 1,\n  \"data_version\": 0,\n"));
+    let read = ExpandableGrid::<u32>::from_json_str_migrated(&json, &Upgrade).unwrap();
+    assert_eq!((read[vector![1, 5]], read[vector![0, 4]]), (2000, 40));
+    let read = ExpandableGrid::<u16>::from_json_str(&json).unwrap();
+    assert_eq!(read[vector![1, 5]], 200);
+
+    #[cfg(feature = "ron")]
+    {
+        let ron = grid.to_ron_string_with_version(1).unwrap();
+        let read = ExpandableGrid::<u32>::from_ron_str_migrated(&ron, &Upgrade).unwrap();
+        assert_eq!((read[vector![1, 5]], read[vector![0, 4]]), (2000, 40));
+    }
+}
@@ -0,0 +1,265 @@
+//! Importing maps made with the Tiled map editor, through the `tiled` crate.
+//!
+//! Each tile layer of a map becomes an `ExpandableGrid<u32>` of global tile IDs, where `0` is an
+//! empty cell. As in Tiled, `y` increases downwards, so the cell at `(x, y)` of a grid is the tile
+//! at `(x, y)` in Tiled, including at negative coordinates in infinite maps. Every layer of a map
+//! is given the same origin and size, covering all of the tiles in the map, so cells at the same
+//! coordinates in different layers line up.
+//!
+//! Global tile IDs are the same as in the map file, where each tileset starts at the `firstgid`
+//! stored for it. There may be gaps between tilesets, such as after a tileset is removed, so the
+//! first IDs are read from the file with an XML reader rather than counted from the sizes of the
+//! tilesets, since the `tiled` crate doesn't keep them. The file is only read once, and the same
+//! bytes are given to the `tiled` loader. Whether a tile is flipped is stored in the highest bits
+//! of its ID as in Tiled's own format, which can be read with `tile_flips`, and masked off with
+//! `TILE_ID_MASK`.
+
+use crate::ExpandableGrid;
+use nalgebra::{vector, Vector2};
+use std::{
+    fs::File,
+    io::{self, Cursor, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tiled::{ChunkData, LayerTileData, LayerType, Map, ResourceReader, TileLayer};
+use xml::reader::{EventReader, XmlEvent};
+
+/// Set in a global tile ID when the tile is flipped horizontally.
+pub const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+/// Set in a global tile ID when the tile is flipped vertically.
+pub const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+/// Set in a global tile ID when the tile is flipped diagonally, which is applied before the other
+/// flips, so that combined with them it rotates the tile.
+pub const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+/// The bits of a global tile ID which identify the tile, without its flags.
+pub const TILE_ID_MASK: u32 = !(FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY);
+
+/// How a tile is flipped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TileFlips {
+    pub horizontal: bool,
+    pub vertical: bool,
+    pub diagonal: bool,
+}
+
+/// Returns how the tile with the global ID `gid` is flipped.
+pub fn tile_flips(gid: u32) -> TileFlips {
+    TileFlips {
+        horizontal: gid & FLIPPED_HORIZONTALLY != 0,
+        vertical: gid & FLIPPED_VERTICALLY != 0,
+        diagonal: gid & FLIPPED_DIAGONALLY != 0,
+    }
+}
+
+/// The tile layers of a Tiled map, converted to grids of global tile IDs.
+#[derive(Clone, Debug)]
+pub struct TiledMap {
+    /// The tile layers of the map, in the order they are drawn. Tile layers within groups are
+    /// included in place of the group.
+    pub layers: Vec<TiledLayer>,
+    /// The first global tile ID of each tileset of the map.
+    pub first_gids: Vec<u32>,
+}
+
+/// A tile layer of a Tiled map.
+#[derive(Clone, Debug)]
+pub struct TiledLayer {
+    pub name: String,
+    pub grid: ExpandableGrid<u32>,
+}
+
+impl TiledMap {
+    /// Loads a TMX map from `path`, along with any external TSX tilesets it uses.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, tiled::Error> {
+        let path = path.as_ref();
+        let tmx: Arc<[u8]> = std::fs::read(path)
+            .map_err(|error| tiled::Error::ResourceLoadingError {
+                path: path.to_owned(),
+                err: Box::new(error),
+            })?
+            .into();
+
+        let map = tiled::Loader::with_reader(MapReader {
+            path: path.to_owned(),
+            tmx: tmx.clone(),
+        })
+        .load_tmx_map(path)?;
+
+        let first_gids = read_first_gids(&tmx)
+            .filter(|first_gids| first_gids.len() == map.tilesets().len())
+            .ok_or_else(|| {
+                tiled::Error::MalformedAttributes("tileset has no valid firstgid".to_owned())
+            })?;
+
+        Ok(Self::from_map(&map, first_gids))
+    }
+
+    /// Converts the tile layers of `map`, where `first_gids` is the first global tile ID of each
+    /// of its tilesets.
+    fn from_map(map: &Map, first_gids: Vec<u32>) -> Self {
+        let mut tile_layers = Vec::new();
+        collect_tile_layers(map.layers(), &mut tile_layers);
+
+        // Find the box covering the tiles of every layer
+        let bounds = tile_layers
+            .iter()
+            .filter_map(|(_, layer)| layer_bounds(layer))
+            .reduce(|(a_min, a_max), (b_min, b_max)| (a_min.inf(&b_min), a_max.sup(&b_max)));
+        let (origin, size) = match bounds {
+            Some((min, max)) => (min, (max - min).map(|length| length as usize)),
+            None => (Vector2::zeros(), Vector2::zeros()),
+        };
+
+        let layers = tile_layers
+            .into_iter()
+            .map(|(name, layer)| TiledLayer {
+                name,
+                grid: layer_to_grid(&layer, origin, size, &first_gids),
+            })
+            .collect();
+
+        Self { layers, first_gids }
+    }
+
+    /// Returns the layer named `name`, if any.
+    pub fn layer(&self, name: &str) -> Option<&TiledLayer> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+
+    /// Returns the index of the tileset of the tile with the global ID `gid`, and the ID of the
+    /// tile within that tileset, or `None` if `gid` is empty or comes before the first tileset.
+    pub fn tileset_tile(&self, gid: u32) -> Option<(usize, u32)> {
+        let gid = gid & TILE_ID_MASK;
+        if gid == 0 {
+            return None;
+        }
+
+        let tileset = self
+            .first_gids
+            .partition_point(|&first_gid| first_gid <= gid)
+            .checked_sub(1)?;
+        Some((tileset, gid - self.first_gids[tileset]))
+    }
+}
+
+/// Reads the map file from the bytes already read by `TiledMap::load`, and any other files, such
+/// as external tilesets, from disk.
+struct MapReader {
+    path: PathBuf,
+    tmx: Arc<[u8]>,
+}
+
+impl ResourceReader for MapReader {
+    type Resource = Box<dyn Read>;
+    type Error = io::Error;
+
+    fn read_from(&mut self, path: &Path) -> io::Result<Self::Resource> {
+        if path == self.path {
+            Ok(Box::new(Cursor::new(self.tmx.clone())))
+        } else {
+            Ok(Box::new(File::open(path)?))
+        }
+    }
+}
+
+/// Returns the `firstgid` of each tileset of the TMX map `tmx`, in the order they appear, or
+/// `None` if the map is not valid XML, or a tileset has no valid `firstgid`.
+fn read_first_gids(tmx: &[u8]) -> Option<Vec<u32>> {
+    let mut first_gids = Vec::new();
+    let mut depth = 0;
+
+    for event in EventReader::new(tmx) {
+        match event.ok()? {
+            // Tilesets of the map are always children of its root `map` element
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                if depth == 1 && name.local_name == "tileset" {
+                    let first_gid = attributes
+                        .iter()
+                        .find(|attribute| attribute.name.local_name == "firstgid")?;
+                    first_gids.push(first_gid.value.parse().ok()?);
+                }
+                depth += 1;
+            }
+            XmlEvent::EndElement { .. } => depth -= 1,
+            _ => (),
+        }
+    }
+
+    Some(first_gids)
+}
+
+fn collect_tile_layers<'map>(
+    layers: impl Iterator<Item = tiled::Layer<'map>>,
+    tile_layers: &mut Vec<(String, TileLayer<'map>)>,
+) {
+    for layer in layers {
+        match layer.layer_type() {
+            LayerType::Tiles(tile_layer) => tile_layers.push((layer.name.clone(), tile_layer)),
+            LayerType::Group(group) => collect_tile_layers(group.layers(), tile_layers),
+            LayerType::Objects(_) | LayerType::Image(_) => (),
+        }
+    }
+}
+
+/// Returns the lowest corner of the tiles of `layer` and the corner just past the highest, or
+/// `None` if it has no tiles.
+fn layer_bounds(layer: &TileLayer) -> Option<(Vector2<isize>, Vector2<isize>)> {
+    match layer {
+        TileLayer::Finite(layer) => Some((
+            Vector2::zeros(),
+            vector![layer.width() as isize, layer.height() as isize],
+        )),
+        TileLayer::Infinite(layer) => {
+            let chunk_size = vector![ChunkData::WIDTH as isize, ChunkData::HEIGHT as isize];
+
+            layer
+                .chunks()
+                .map(|((x, y), _)| {
+                    let min = vector![x as isize, y as isize].component_mul(&chunk_size);
+                    (min, min + chunk_size)
+                })
+                .reduce(|(a_min, a_max), (b_min, b_max)| (a_min.inf(&b_min), a_max.sup(&b_max)))
+        }
+    }
+}
+
+fn layer_to_grid(
+    layer: &TileLayer,
+    origin: Vector2<isize>,
+    size: Vector2<usize>,
+    first_gids: &[u32],
+) -> ExpandableGrid<u32> {
+    let mut grid = ExpandableGrid::with_size(size, origin, &0);
+
+    for (index, cell) in grid.data.iter_mut().enumerate() {
+        let position = origin + vector![(index % size.x) as isize, (index / size.x) as isize];
+        let (x, y) = (position.x as i32, position.y as i32);
+
+        let tile = match layer {
+            TileLayer::Finite(layer) => layer.get_tile_data(x, y),
+            TileLayer::Infinite(layer) => layer.get_tile_data(x, y),
+        };
+        *cell = tile.map_or(0, |tile| gid(tile, first_gids));
+    }
+
+    grid
+}
+
+fn gid(tile: &LayerTileData, first_gids: &[u32]) -> u32 {
+    let mut gid = first_gids[tile.tileset_index()] + tile.id();
+
+    if tile.flip_h {
+        gid |= FLIPPED_HORIZONTALLY;
+    }
+    if tile.flip_v {
+        gid |= FLIPPED_VERTICALLY;
+    }
+    if tile.flip_d {
+        gid |= FLIPPED_DIAGONALLY;
+    }
+
+    gid
+}
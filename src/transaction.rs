@@ -0,0 +1,188 @@
+//! Batching edits to a grid so that they are applied all at once or not at all.
+//!
+//! `ExpandableGridN::transaction` calls a closure with a `Transaction`, which records writes
+//! and expansions without changing the grid. Reads through the transaction see its own pending
+//! edits. If the closure returns `Ok` the edits are applied to the grid, and if it returns `Err`
+//! they are discarded, leaving the grid untouched. This suits placing a structure made of many
+//! cells, where validation may fail part way through.
+
+use crate::{
+    coord::{self, Coordinate, GridVector},
+    rect::GridRect,
+    util, ExpandableGridN,
+};
+use nalgebra::SVector;
+use std::collections::HashMap;
+
+/// Edits to a grid which have not been applied yet. See the `transaction` module for details.
+#[derive(Debug)]
+pub struct Transaction<'a, T, const D: usize = 2, C = isize> {
+    grid: &'a ExpandableGridN<T, D, C>,
+    /// The bounds of the grid once the pending expansions are applied.
+    bounds: GridRect<D>,
+    expansions: Vec<Expansion<T, D>>,
+    writes: HashMap<SVector<isize, D>, T>,
+}
+
+/// A pending call to `expand_to_fit_box`.
+#[derive(Clone, Debug)]
+struct Expansion<T, const D: usize> {
+    /// The bounds of the grid after this expansion.
+    bounds: GridRect<D>,
+    box_origin: SVector<isize, D>,
+    box_size: SVector<usize, D>,
+    fill: T,
+}
+
+impl<'a, T: Clone, const D: usize, C: Coordinate> Transaction<'a, T, D, C> {
+    fn new(grid: &'a ExpandableGridN<T, D, C>) -> Self {
+        Self {
+            grid,
+            bounds: grid.bounds().to_isize(),
+            expansions: Vec::new(),
+            writes: HashMap::new(),
+        }
+    }
+
+    /// Returns the origin of the grid once the pending expansions are applied.
+    pub fn origin(&self) -> SVector<C, D> {
+        coord::from_isize_vec(self.bounds.origin)
+    }
+
+    /// Returns the size of the grid once the pending expansions are applied.
+    pub fn size(&self) -> SVector<usize, D> {
+        self.bounds.size
+    }
+
+    /// Returns the number of cells written to by this transaction.
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty() && self.expansions.is_empty()
+    }
+
+    /// Returns the cell at `index` as it will be once this transaction is applied, or `None` if
+    /// it will be out of bounds.
+    pub fn get(&self, index: impl GridVector<C, D>) -> Option<&T> {
+        let index = coord::to_isize_vec(index.to_vector());
+
+        if let Some(value) = self.writes.get(&index) {
+            return Some(value);
+        }
+        if let Some(value) = self.grid.get_isize(index) {
+            return Some(value);
+        }
+
+        // The cell was added by the first expansion which covers it
+        self.expansions
+            .iter()
+            .find(|expansion| {
+                util::relative_position(expansion.bounds.origin, expansion.bounds.size, index)
+                    .is_some()
+            })
+            .map(|expansion| &expansion.fill)
+    }
+
+    /// Returns a mutable reference to the pending value of the cell at `index`, or `None` if it
+    /// will be out of bounds.
+    pub fn get_mut(&mut self, index: impl GridVector<C, D>) -> Option<&mut T> {
+        let position = index.to_vector();
+        let index = coord::to_isize_vec(position);
+
+        if !self.writes.contains_key(&index) {
+            let value = self.get(position)?.clone();
+            self.writes.insert(index, value);
+        }
+        self.writes.get_mut(&index)
+    }
+
+    /// Sets the cell at `index` to `value` once this transaction is applied, returning `false`
+    /// if it will be out of bounds.
+    pub fn set(&mut self, index: impl GridVector<C, D>, value: T) -> bool {
+        let index = coord::to_isize_vec(index.to_vector());
+
+        if util::relative_position(self.bounds.origin, self.bounds.size, index).is_none() {
+            return false;
+        }
+        self.writes.insert(index, value);
+        true
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_point`.
+    pub fn expand_to_fit_point(&mut self, point: impl GridVector<C, D>, fill: &T) {
+        self.expand_to_fit_box(point, SVector::<usize, D>::repeat(1), fill);
+    }
+
+    /// See `ExpandableGridN::expand_to_fit_box`. The grid will expand exactly as it would if
+    /// the expansions of this transaction were made to it directly.
+    pub fn expand_to_fit_box(
+        &mut self,
+        box_origin: impl GridVector<C, D>,
+        box_size: impl GridVector<usize, D>,
+        fill: &T,
+    ) {
+        let box_origin = coord::to_isize_vec(box_origin.to_vector());
+        let box_size = box_size.to_vector();
+
+        let bounds = if self.bounds.size == SVector::<usize, D>::zeros() {
+            GridRect::new(box_origin, box_size)
+        } else if let Some((size, offset)) =
+            util::expansion_to_fit_box(self.bounds.size, self.bounds.origin, box_origin, box_size)
+        {
+            GridRect::new(self.bounds.origin + offset, size)
+        } else {
+            return;
+        };
+
+        self.bounds = bounds;
+        self.expansions.push(Expansion {
+            bounds,
+            box_origin,
+            box_size,
+            fill: fill.clone(),
+        });
+    }
+
+    /// Releases the borrow of the grid, returning the pending edits.
+    fn into_edits(self) -> Edits<T, D> {
+        Edits {
+            expansions: self.expansions,
+            writes: self.writes,
+        }
+    }
+}
+
+impl<T: Clone, const D: usize, C: Coordinate> ExpandableGridN<T, D, C> {
+    /// Calls `f` with a transaction on this grid, applying its edits if `f` returns `Ok` and
+    /// discarding them if it returns `Err`. See the `transaction` module for details.
+    pub fn transaction<R, E>(
+        &mut self,
+        f: impl FnOnce(&mut Transaction<T, D, C>) -> Result<R, E>,
+    ) -> Result<R, E> {
+        let mut transaction = Transaction::new(self);
+        let result = f(&mut transaction)?;
+
+        transaction.into_edits().apply(self);
+        Ok(result)
+    }
+}
+
+/// The pending edits of a transaction, once it no longer borrows the grid.
+struct Edits<T, const D: usize> {
+    expansions: Vec<Expansion<T, D>>,
+    writes: HashMap<SVector<isize, D>, T>,
+}
+
+impl<T: Clone, const D: usize> Edits<T, D> {
+    fn apply<C: Coordinate>(self, grid: &mut ExpandableGridN<T, D, C>) {
+        for expansion in &self.expansions {
+            let box_origin = coord::from_isize_vec::<C, D>(expansion.box_origin);
+            grid.expand_to_fit_box(box_origin, expansion.box_size, &expansion.fill);
+        }
+        for (index, value) in self.writes {
+            grid[coord::from_isize_vec::<C, D>(index)] = value;
+        }
+    }
+}
@@ -1,18 +1,359 @@
-use nalgebra::{vector, Vector2};
+use crate::Layout;
+use nalgebra::SVector;
 
-pub fn calculate_exponential_distance(distance: isize, current_size: usize) -> usize {
-    let minimum_size = current_size + distance as usize;
-    let new_size = minimum_size.max(current_size * 2);
-    new_size - current_size
+/// Returns how far a grid of size `current_size` expands on one side to fit a box `distance`
+/// cells beyond it, or `None` if the new size overflows `usize`.
+pub fn calculate_exponential_distance(distance: usize, current_size: usize) -> Option<usize> {
+    let minimum_size = current_size.checked_add(distance)?;
+    let new_size = minimum_size.max(current_size.checked_mul(2)?);
+    Some(new_size - current_size)
 }
 
-pub fn usize_vec_to_isize(vector: Vector2<usize>) -> Vector2<isize> {
-    vector![vector.x as isize, vector.y as isize]
+/// Returns the new size and the offset of the origin of a grid of size `size` at `origin` after
+/// expanding it to fit the given box, or `None` if the box is already within its bounds.
+///
+/// On each axis the grid at least doubles in size when it needs to expand, and expands further if
+/// this is not enough.
+///
+/// # Panics
+/// Panics if the corners of the box or of the expanded grid overflow `isize`.
+pub fn expansion_to_fit_box<const D: usize>(
+    size: SVector<usize, D>,
+    origin: SVector<isize, D>,
+    box_origin: SVector<isize, D>,
+    box_size: SVector<usize, D>,
+) -> Option<(SVector<usize, D>, SVector<isize, D>)> {
+    checked_expansion_to_fit_box(size, origin, box_origin, box_size)
+        .expect("expanded grid should have its corners within isize")
 }
 
-pub fn isize_vec_to_usize_saturating(vector: Vector2<isize>) -> Vector2<usize> {
-    vector![
-        if vector.x < 0 { 0 } else { vector.x as usize },
-        if vector.y < 0 { 0 } else { vector.y as usize }
-    ]
+/// Same as `expansion_to_fit_box`, but returns `None` if the corners of the box or of the
+/// expanded grid overflow `isize`, and `Some(None)` if the box is already within its bounds.
+pub fn checked_expansion_to_fit_box<const D: usize>(
+    size: SVector<usize, D>,
+    origin: SVector<isize, D>,
+    box_origin: SVector<isize, D>,
+    box_size: SVector<usize, D>,
+) -> Option<Option<(SVector<usize, D>, SVector<isize, D>)>> {
+    let area_corner = checked_add(origin, size)?;
+    let box_corner = checked_add(box_origin, box_size)?;
+
+    let mut new_size = size;
+    let mut offset = SVector::zeros();
+    let mut expanded = false;
+
+    // Expand on each axis
+    for axis in 0..D {
+        if box_origin[axis] < origin[axis] {
+            let distance = origin[axis].abs_diff(box_origin[axis]);
+            let distance = calculate_exponential_distance(distance, size[axis])?;
+            offset[axis] = -isize::try_from(distance).ok()?;
+            new_size[axis] = new_size[axis].checked_add(distance)?;
+            expanded = true;
+        }
+        if box_corner[axis] > area_corner[axis] {
+            let distance = box_corner[axis].abs_diff(area_corner[axis]);
+            let distance = calculate_exponential_distance(distance, size[axis])?;
+            new_size[axis] = new_size[axis].checked_add(distance)?;
+            expanded = true;
+        }
+    }
+
+    if !expanded {
+        return Some(None);
+    }
+    checked_add(checked_add_signed(origin, offset)?, new_size)?;
+    Some(Some((new_size, offset)))
+}
+
+/// Returns an iterator over the cells which are kept when a grid of size `old_size` changes to
+/// size `new_size`, with its origin shifted by `offset`. Each item is the index of a cell within
+/// the new data, followed by its index within the old data.
+pub fn kept_cells<const D: usize>(
+    layout: Layout,
+    old_size: SVector<usize, D>,
+    new_size: SVector<usize, D>,
+    offset: SVector<isize, D>,
+) -> impl Iterator<Item = (usize, usize)> {
+    // Calculate the offsets of size and size + origin
+    let relative_size = usize_vec_to_isize(new_size) - usize_vec_to_isize(old_size);
+    let corner_offset = offset + relative_size;
+
+    // Calculate bounds of the old size in the coordinate space of the new size
+    let start = isize_vec_to_usize_saturating(-offset);
+    let end = new_size.zip_map(
+        &isize_vec_to_usize_saturating(corner_offset),
+        |length, cut| length.saturating_sub(cut),
+    );
+
+    iter_box(start, end).map(move |position| {
+        let old_position = position.zip_map(&offset, |position, offset| {
+            (position.checked_add_signed(offset))
+                .expect("offset should never be less than -position")
+        });
+
+        (
+            layout.linear_index(position, new_size),
+            layout.linear_index(old_position, old_size),
+        )
+    })
+}
+
+/// Returns an iterator over the runs of cells which are kept when a grid of size `old_size`
+/// changes to size `new_size`, with its origin shifted by `offset`. Each run is stored
+/// contiguously within both the old and new data. Each item is the index of the first cell of a
+/// run within the new data, its index within the old data, and the length of the run.
+pub fn kept_runs<const D: usize>(
+    layout: Layout,
+    old_size: SVector<usize, D>,
+    new_size: SVector<usize, D>,
+    offset: SVector<isize, D>,
+) -> impl Iterator<Item = (usize, usize, usize)> {
+    let relative_size = usize_vec_to_isize(new_size) - usize_vec_to_isize(old_size);
+    let corner_offset = offset + relative_size;
+
+    let start = isize_vec_to_usize_saturating(-offset);
+    let end = new_size.zip_map(
+        &isize_vec_to_usize_saturating(corner_offset),
+        |length, cut| length.saturating_sub(cut),
+    );
+
+    iter_runs(layout, start, end).map(move |(position, length)| {
+        let old_position = position.zip_map(&offset, |position, offset| {
+            (position.checked_add_signed(offset))
+                .expect("offset should never be less than -position")
+        });
+
+        (
+            layout.linear_index(position, new_size),
+            layout.linear_index(old_position, old_size),
+            length,
+        )
+    })
+}
+
+/// Returns an iterator over the runs of cells of the box with its lowest corner at `box_origin`
+/// and size `box_size` within a grid of size `size`, where each run is stored contiguously in
+/// `layout`. Each item is the index of the first cell of a run, and the length of the run.
+pub fn box_runs<const D: usize>(
+    layout: Layout,
+    size: SVector<usize, D>,
+    box_origin: SVector<usize, D>,
+    box_size: SVector<usize, D>,
+) -> impl Iterator<Item = (usize, usize)> {
+    iter_runs(layout, box_origin, box_origin + box_size)
+        .map(move |(position, length)| (layout.linear_index(position, size), length))
+}
+
+/// Returns an iterator over the first position of each run of the box from `start` to `end`
+/// along the axis which varies fastest in `layout`, and the length of the runs.
+fn iter_runs<const D: usize>(
+    layout: Layout,
+    start: SVector<usize, D>,
+    end: SVector<usize, D>,
+) -> impl Iterator<Item = (SVector<usize, D>, usize)> {
+    let axis = match layout {
+        Layout::RowMajor => 0,
+        Layout::ColumnMajor => D - 1,
+    };
+    let length = end[axis].saturating_sub(start[axis]);
+
+    // Only the first cell of each run is visited
+    let mut run_end = end;
+    run_end[axis] = end[axis].min(start[axis] + 1);
+
+    iter_box(start, run_end).map(move |position| (position, length))
+}
+
+pub fn usize_vec_to_isize<const D: usize>(vector: SVector<usize, D>) -> SVector<isize, D> {
+    vector.map(|component| component as isize)
+}
+
+pub fn isize_vec_to_usize_saturating<const D: usize>(
+    vector: SVector<isize, D>,
+) -> SVector<usize, D> {
+    vector.map(|component| if component < 0 { 0 } else { component as usize })
+}
+
+/// Returns the position of `point` relative to `origin`, or `None` if it is outside of the box
+/// with its lowest corner at `origin` and size `size`.
+pub fn relative_position<const D: usize>(
+    origin: SVector<isize, D>,
+    size: SVector<usize, D>,
+    point: SVector<isize, D>,
+) -> Option<SVector<usize, D>> {
+    let relative = point - origin;
+
+    if (0..D).any(|axis| relative[axis] < 0 || relative[axis] as usize >= size[axis]) {
+        None
+    } else {
+        Some(relative.map(|component| component as usize))
+    }
+}
+
+/// Returns the number of cells within a grid of size `size`, or `None` if it overflows `usize`.
+pub fn checked_area<const D: usize>(size: SVector<usize, D>) -> Option<usize> {
+    size.iter()
+        .try_fold(1usize, |area, &length| area.checked_mul(length))
+}
+
+/// Returns the corner of the box with its lowest corner at `origin` and size `size`, or `None` if
+/// it overflows `isize`.
+pub fn checked_add<const D: usize>(
+    origin: SVector<isize, D>,
+    size: SVector<usize, D>,
+) -> Option<SVector<isize, D>> {
+    let mut corner = origin;
+    for axis in 0..D {
+        corner[axis] = origin[axis].checked_add_unsigned(size[axis])?;
+    }
+    Some(corner)
+}
+
+/// Returns `origin` shifted by `offset`, or `None` if it overflows `isize`.
+pub fn checked_add_signed<const D: usize>(
+    origin: SVector<isize, D>,
+    offset: SVector<isize, D>,
+) -> Option<SVector<isize, D>> {
+    let mut shifted = origin;
+    for axis in 0..D {
+        shifted[axis] = origin[axis].checked_add(offset[axis])?;
+    }
+    Some(shifted)
+}
+
+/// Returns the origin and size of the box where the two boxes overlap, or `None` if they do not
+/// overlap.
+pub fn intersect_boxes<const D: usize>(
+    a_origin: SVector<isize, D>,
+    a_size: SVector<usize, D>,
+    b_origin: SVector<isize, D>,
+    b_size: SVector<usize, D>,
+) -> Option<(SVector<isize, D>, SVector<usize, D>)> {
+    let origin = a_origin.sup(&b_origin);
+    let corner =
+        (a_origin + usize_vec_to_isize(a_size)).inf(&(b_origin + usize_vec_to_isize(b_size)));
+
+    (0..D)
+        .all(|axis| origin[axis] < corner[axis])
+        .then(|| (origin, (corner - origin).map(|length| length as usize)))
+}
+
+/// Iterates over every position within `start..end` on all axes, varying `x` fastest. Yields
+/// nothing if the box is empty on any axis.
+pub fn iter_box<const D: usize>(
+    start: SVector<usize, D>,
+    end: SVector<usize, D>,
+) -> impl Iterator<Item = SVector<usize, D>> {
+    let empty = (0..D).any(|axis| start[axis] >= end[axis]);
+    let mut next = (!empty).then_some(start);
+
+    std::iter::from_fn(move || {
+        let current = next?;
+
+        let mut position = current;
+        next = (0..D)
+            .find(|&axis| {
+                position[axis] += 1;
+                if position[axis] < end[axis] {
+                    true
+                } else {
+                    position[axis] = start[axis];
+                    false
+                }
+            })
+            .map(|_| position);
+
+        Some(current)
+    })
+}
+
+/// Returns an iterator over the offsets of the cells at a Chebyshev distance of exactly `radius`
+/// from the origin which are within the box with its lowest corner at `box_origin` and size
+/// `box_size`. Only the part of the ring overlapping the box is visited, so the cost depends on
+/// the size of the box rather than the radius. Radii beyond `isize::MAX` are treated as
+/// `isize::MAX`, since no further offset can be represented.
+pub fn iter_ring_within<const D: usize>(
+    radius: usize,
+    box_origin: SVector<isize, D>,
+    box_size: SVector<usize, D>,
+) -> impl Iterator<Item = SVector<isize, D>> {
+    let radius = radius.min(isize::MAX as usize);
+    let signed_radius = radius as isize;
+
+    // Clip the box to the ring's bounds per axis, since neither the ring's size nor the box's
+    // corner is guaranteed to fit in an `isize`
+    let clipped = (0..D)
+        .all(|axis| box_size[axis] > 0)
+        .then(|| {
+            let low = box_origin.map(|origin| origin.max(-signed_radius));
+            let high = box_origin.zip_map(&box_size, |origin, size| {
+                origin.saturating_add_unsigned(size - 1).min(signed_radius)
+            });
+            (low, high)
+        })
+        .filter(|(low, high)| (0..D).all(|axis| low[axis] <= high[axis]))
+        .map(|(low, high)| (low, high.zip_map(&low, |high, low| high.abs_diff(low) + 1)));
+
+    clipped.into_iter().flat_map(move |(origin, size)| {
+        let (low, high) = (origin[0], origin[0].saturating_add_unsigned(size[0] - 1));
+
+        // Visit every position of the clipped box on every axis but `x`, then only the `x`
+        // positions on the surface of the ring
+        let mut end = size;
+        end[0] = 1;
+
+        iter_box(SVector::zeros(), end).flat_map(move |position| {
+            let mut offset = origin.zip_map(&position, |origin, position| {
+                origin.saturating_add_unsigned(position)
+            });
+            let on_surface = (1..D).any(|axis| offset[axis].abs() == signed_radius);
+            let (start, stop, step) = if on_surface || radius == 0 {
+                (low, high, 1)
+            } else {
+                (-signed_radius, signed_radius, 2 * radius)
+            };
+
+            (start..=stop)
+                .step_by(step)
+                .filter(move |x| (low..=high).contains(x))
+                .map(move |x| {
+                    offset[0] = x;
+                    offset
+                })
+        })
+    })
+}
+
+/// Returns the coordinates of the chunk of size `chunk_size` containing `index`, and the position
+/// of `index` within that chunk.
+pub fn split_chunk_index<const D: usize>(
+    index: SVector<isize, D>,
+    chunk_size: SVector<usize, D>,
+) -> (SVector<isize, D>, SVector<usize, D>) {
+    let chunk_size = usize_vec_to_isize(chunk_size);
+
+    (
+        index.zip_map(&chunk_size, |index, size| index.div_euclid(size)),
+        index.zip_map(&chunk_size, |index, size| index.rem_euclid(size) as usize),
+    )
+}
+
+/// Returns the coordinates of the lowest and highest chunks of size `chunk_size` which contain
+/// cells of the box with its lowest corner at `box_origin` and size `box_size`, or `None` if the
+/// box is empty.
+pub fn chunk_range<const D: usize>(
+    box_origin: SVector<isize, D>,
+    box_size: SVector<usize, D>,
+    chunk_size: SVector<usize, D>,
+) -> Option<(SVector<isize, D>, SVector<isize, D>)> {
+    if box_size.iter().any(|&length| length == 0) {
+        return None;
+    }
+
+    let box_end = box_origin + usize_vec_to_isize(box_size);
+    Some((
+        split_chunk_index(box_origin, chunk_size).0,
+        split_chunk_index(box_end.add_scalar(-1), chunk_size).0,
+    ))
 }
@@ -0,0 +1,292 @@
+//! Helpers for uploading grids to the GPU with `wgpu`, for cells which implement `bytemuck::Pod`.
+//!
+//! A 2d grid can be uploaded as a texture with a texel for each cell, where the texel at the
+//! top left corner of the texture is the cell at the grid's origin, or as a storage buffer
+//! holding the cells in the order given by the grid's `layout`. Both can be updated in place from
+//! a box of cells which has changed, so that only the cells that changed are copied each frame.
+//!
+//! Textures and buffers do not grow with the grid, so they should be recreated whenever the
+//! size of the grid changes.
+
+use crate::{
+    coord::{self, Coordinate, GridVector},
+    util, ExpandableGrid, Layout,
+};
+use bytemuck::Pod;
+use nalgebra::{vector, Vector2};
+use std::{borrow::Cow, mem::size_of};
+use wgpu::util::DeviceExt;
+
+/// Returns the number of bytes per row of a texture `width` cells wide, padded to a multiple of
+/// `wgpu::COPY_BYTES_PER_ROW_ALIGNMENT` as is required when copying between buffers and textures.
+pub fn padded_bytes_per_row<T>(width: usize) -> u32 {
+    let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize;
+    let bytes_per_row = (width * size_of::<T>()).div_ceil(alignment) * alignment;
+
+    bytes_per_row
+        .try_into()
+        .expect("row of grid should fit in a u32")
+}
+
+impl<T: Pod, C: Coordinate> ExpandableGrid<T, C> {
+    /// Creates a 2d texture with a texel for each cell of this grid, and fills it with the cells.
+    /// `COPY_DST` is always added to `usage`.
+    ///
+    /// # Panics
+    /// Panics if the size of a texel of `format` is not the size of `T`, or if the width or height
+    /// of the grid does not fit in a `u32`. `wgpu` will also reject the texture if the grid is
+    /// empty.
+    pub fn create_texture(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: Option<&str>,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+    ) -> wgpu::Texture {
+        check_format::<T>(format);
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: extent(self.size),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: usage | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        self.write_texture(queue, &texture);
+
+        texture
+    }
+
+    /// Copies every cell of this grid to `texture`, which should have been created by
+    /// `create_texture` for a grid of the same size.
+    pub fn write_texture(&self, queue: &wgpu::Queue, texture: &wgpu::Texture) {
+        self.write_texture_box(queue, texture, self.origin, self.size);
+    }
+
+    /// Copies the cells of this grid within the box with its lowest corner at `box_origin` and
+    /// size `box_size` to the same texels of `texture`, which should have been created by
+    /// `create_texture` for a grid of the same size. Cells of the box outside the bounds of this
+    /// grid are ignored.
+    ///
+    /// # Panics
+    /// Panics if `texture` is not the size of this grid, or if the size of its texels is not the
+    /// size of `T`.
+    pub fn write_texture_box(
+        &self,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        box_origin: impl GridVector<C, 2>,
+        box_size: impl GridVector<usize, 2>,
+    ) {
+        assert_eq!(
+            texture.size(),
+            extent(self.size),
+            "texture should be the same size as the grid",
+        );
+        check_format::<T>(texture.format());
+
+        let grid_origin = coord::to_isize_vec(self.origin);
+        let Some((origin, size)) = util::intersect_boxes(
+            grid_origin,
+            self.size,
+            coord::to_isize_vec(box_origin.to_vector()),
+            box_size.to_vector(),
+        ) else {
+            return;
+        };
+
+        // `write_texture` has no alignment requirements, so the rows are not padded
+        let bytes_per_row = size.x * size_of::<T>();
+        let offset = extent(util::isize_vec_to_usize_saturating(origin - grid_origin));
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: offset.width,
+                    y: offset.height,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &self.box_rows(origin, size, bytes_per_row),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row as u32),
+                rows_per_image: Some(size.y as u32),
+            },
+            extent(size),
+        );
+    }
+
+    /// Returns the cells of this grid as rows of texels from the lowest `y` to the highest, with
+    /// each row padded with zeroes to the length returned by `padded_bytes_per_row`, along with
+    /// that length. This is the layout required to copy the grid to a texture through a staging
+    /// buffer with `CommandEncoder::copy_buffer_to_texture`.
+    pub fn to_padded_texture_data(&self) -> (Vec<u8>, u32) {
+        let bytes_per_row = padded_bytes_per_row::<T>(self.size.x);
+
+        (
+            self.box_rows(
+                coord::to_isize_vec(self.origin),
+                self.size,
+                bytes_per_row as usize,
+            ),
+            bytes_per_row,
+        )
+    }
+
+    /// Creates a storage buffer holding the cells of this grid in the order given by `layout`.
+    /// `STORAGE` and `COPY_DST` are always added to `usage`.
+    pub fn create_storage_buffer(
+        &self,
+        device: &wgpu::Device,
+        label: Option<&str>,
+        usage: wgpu::BufferUsages,
+    ) -> wgpu::Buffer {
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label,
+            contents: self.as_bytes(),
+            usage: usage | wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        })
+    }
+
+    /// Copies every cell of this grid to `buffer`, which should have been created by
+    /// `create_storage_buffer` for a grid of the same size and layout.
+    pub fn write_storage_buffer(&self, queue: &wgpu::Queue, buffer: &wgpu::Buffer) {
+        self.write_storage_buffer_box(queue, buffer, self.origin, self.size);
+    }
+
+    /// Copies the cells of this grid within the box with its lowest corner at `box_origin` and
+    /// size `box_size` to `buffer`, which should have been created by `create_storage_buffer` for
+    /// a grid of the same size and layout. Cells of the box outside the bounds of this grid are
+    /// ignored.
+    ///
+    /// Each row of the box (or column, for `Layout::ColumnMajor`) is contiguous within the
+    /// buffer, so it is copied with one write, and adjacent rows are merged into a single write.
+    /// Writes are widened to a multiple of `wgpu::COPY_BUFFER_ALIGNMENT`, so a few cells either
+    /// side of the box may be copied as well.
+    ///
+    /// # Panics
+    /// Panics if `buffer` is smaller than the cells of this grid.
+    pub fn write_storage_buffer_box(
+        &self,
+        queue: &wgpu::Queue,
+        buffer: &wgpu::Buffer,
+        box_origin: impl GridVector<C, 2>,
+        box_size: impl GridVector<usize, 2>,
+    ) {
+        let bytes = self.as_bytes();
+        assert!(
+            buffer.size() >= bytes.len() as u64,
+            "buffer should be large enough to hold the grid",
+        );
+
+        let Some((origin, size)) = util::intersect_boxes(
+            coord::to_isize_vec(self.origin),
+            self.size,
+            coord::to_isize_vec(box_origin.to_vector()),
+            box_size.to_vector(),
+        ) else {
+            return;
+        };
+
+        let (length, lines) = match self.layout {
+            Layout::RowMajor => (
+                size.x,
+                (0..size.y).map(|y| vector![0, y]).collect::<Vec<_>>(),
+            ),
+            Layout::ColumnMajor => (size.y, (0..size.x).map(|x| vector![x, 0]).collect()),
+        };
+
+        let alignment = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for line in lines {
+            let index = self
+                .index_of_isize(origin + util::usize_vec_to_isize(line))
+                .unwrap();
+            let start = index * size_of::<T>() / alignment * alignment;
+            let end = ((index + length) * size_of::<T>()).div_ceil(alignment) * alignment;
+
+            match ranges.last_mut() {
+                Some((_, last_end)) if start <= *last_end => *last_end = end,
+                _ => ranges.push((start, end)),
+            }
+        }
+
+        for (start, end) in ranges {
+            // The buffer is padded to the alignment, but the grid may not be
+            let data = if end <= bytes.len() {
+                Cow::Borrowed(&bytes[start..end])
+            } else {
+                let mut data = bytes[start..].to_vec();
+                data.resize(end - start, 0);
+                Cow::Owned(data)
+            };
+
+            queue.write_buffer(buffer, start as u64, &data);
+        }
+    }
+
+    /// Returns the cells of the box with its lowest corner at `origin` and size `size`, which
+    /// must be within the bounds of this grid, as rows of `bytes_per_row` bytes each.
+    fn box_rows(
+        &self,
+        origin: Vector2<isize>,
+        size: Vector2<usize>,
+        bytes_per_row: usize,
+    ) -> Vec<u8> {
+        let row_length = size.x * size_of::<T>();
+        let mut data = vec![0; bytes_per_row * size.y];
+
+        for (y, row) in data.chunks_exact_mut(bytes_per_row.max(1)).enumerate() {
+            let start = origin + vector![0, y as isize];
+
+            match self.layout {
+                Layout::RowMajor => {
+                    let index = self.index_of_isize(start).unwrap();
+                    row[..row_length]
+                        .copy_from_slice(bytemuck::cast_slice(&self.data[index..index + size.x]));
+                }
+                Layout::ColumnMajor => {
+                    for (x, texel) in row[..row_length]
+                        .chunks_exact_mut(size_of::<T>())
+                        .enumerate()
+                    {
+                        let cell = self.get_isize(start + vector![x as isize, 0]).unwrap();
+                        texel.copy_from_slice(bytemuck::bytes_of(cell));
+                    }
+                }
+            }
+        }
+
+        data
+    }
+}
+
+fn check_format<T>(format: wgpu::TextureFormat) {
+    assert_eq!(
+        format.block_copy_size(None),
+        Some(size_of::<T>() as u32),
+        "texels of {format:?} should be the same size as the cells of the grid",
+    );
+}
+
+fn extent(size: Vector2<usize>) -> wgpu::Extent3d {
+    wgpu::Extent3d {
+        width: size
+            .x
+            .try_into()
+            .expect("width of grid should fit in a u32"),
+        height: size
+            .y
+            .try_into()
+            .expect("height of grid should fit in a u32"),
+        depth_or_array_layers: 1,
+    }
+}
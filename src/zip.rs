@@ -0,0 +1,165 @@
+//! Iterating over several grids with the same bounds in lockstep.
+//!
+//! `zip_view_mut` takes a tuple of mutable references to grids, such as separate height,
+//! moisture, and temperature grids stored as a structure of arrays, and yields the coordinates
+//! of each cell with a tuple of mutable references to that cell of every grid. The grids may
+//! have different layouts, in which case cells are visited in the order of the first grid, and
+//! the others are reordered to match once up front.
+//!
+//! `ZipGridsMut` is implemented for tuples of 2 to 6 grids.
+
+use crate::{util, ExpandableGridN, Layout};
+use nalgebra::SVector;
+
+/// A tuple of mutable references to grids which can be iterated over in lockstep. See the `zip`
+/// module for details.
+pub trait ZipGridsMut<'a, const D: usize> {
+    /// A tuple of an `Aligned` iterator for each grid.
+    type Iters;
+
+    /// Returns the size, origin, and layout of the first grid, with an iterator over the cells
+    /// of each grid in the order of the first.
+    ///
+    /// # Panics
+    /// Panics if the grids do not all have the same size and origin.
+    fn aligned(self) -> (SVector<usize, D>, SVector<isize, D>, Layout, Self::Iters);
+}
+
+/// An iterator over the cells of a grid in the order of another grid's layout.
+#[derive(Debug)]
+pub enum Aligned<'a, T> {
+    /// The grid has the same layout, so its cells are visited in place.
+    Direct(std::slice::IterMut<'a, T>),
+    /// The grid has a different layout, so its cells were reordered.
+    Permuted(std::vec::IntoIter<&'a mut T>),
+}
+
+impl<'a, T> Aligned<'a, T> {
+    /// Returns an iterator over the cells of `grid` in the order given by `layout`.
+    fn new<const D: usize>(grid: &'a mut ExpandableGridN<T, D>, layout: Layout) -> Self {
+        if grid.layout == layout {
+            return Self::Direct(grid.data.iter_mut());
+        }
+
+        let (size, grid_layout) = (grid.size, grid.layout);
+        let mut cells: Vec<_> = grid.data.iter_mut().map(Some).collect();
+        let permuted = (0..cells.len())
+            .map(|index| {
+                let index = grid_layout.linear_index(layout.position(index, size), size);
+                cells[index].take().unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        Self::Permuted(permuted.into_iter())
+    }
+}
+
+impl<'a, T> Iterator for Aligned<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Direct(cells) => cells.next(),
+            Self::Permuted(cells) => cells.next(),
+        }
+    }
+}
+
+/// An iterator over the coordinates of every cell of several grids, with a tuple of mutable
+/// references to that cell of each grid. Created by `zip_view_mut`.
+#[derive(Debug)]
+pub struct ZipViewMut<I, const D: usize> {
+    iters: I,
+    index: usize,
+    size: SVector<usize, D>,
+    origin: SVector<isize, D>,
+    layout: Layout,
+}
+
+/// Iterates over the coordinates of every cell of `grids`, with a tuple of mutable references to
+/// that cell of each grid. See the `zip` module for details.
+///
+/// # Panics
+/// Panics if the grids do not all have the same size and origin.
+pub fn zip_view_mut<'a, Z: ZipGridsMut<'a, D>, const D: usize>(
+    grids: Z,
+) -> ZipViewMut<Z::Iters, D> {
+    let (size, origin, layout, iters) = grids.aligned();
+
+    ZipViewMut {
+        iters,
+        index: 0,
+        size,
+        origin,
+        layout,
+    }
+}
+
+impl<I, const D: usize> ZipViewMut<I, D> {
+    fn next_position(&mut self) -> SVector<isize, D> {
+        let offset = self.layout.position(self.index, self.size);
+        self.index += 1;
+
+        self.origin + util::usize_vec_to_isize(offset)
+    }
+}
+
+macro_rules! impl_zip_grids_mut {
+    ($first:ident $first_index:tt $(, $name:ident $index:tt)*) => {
+        impl<'a, $first, $($name,)* const D: usize> ZipGridsMut<'a, D>
+            for (&'a mut ExpandableGridN<$first, D>, $(&'a mut ExpandableGridN<$name, D>,)*)
+        {
+            type Iters = (Aligned<'a, $first>, $(Aligned<'a, $name>,)*);
+
+            fn aligned(self) -> (SVector<usize, D>, SVector<isize, D>, Layout, Self::Iters) {
+                let first = &self.$first_index;
+                let (size, origin, layout) = (first.size, first.origin, first.layout);
+                $(assert!(
+                    self.$index.size == size && self.$index.origin == origin,
+                    "zipped grids should have the same size and origin",
+                );)*
+
+                (
+                    size,
+                    origin,
+                    layout,
+                    (
+                        Aligned::new(self.$first_index, layout),
+                        $(Aligned::new(self.$index, layout),)*
+                    ),
+                )
+            }
+        }
+
+        impl<'a, $first, $($name,)* const D: usize> Iterator
+            for ZipViewMut<(Aligned<'a, $first>, $(Aligned<'a, $name>,)*), D>
+        {
+            type Item = (SVector<isize, D>, (&'a mut $first, $(&'a mut $name,)*));
+
+            fn next(&mut self) -> Option<Self::Item> {
+                let cells = (
+                    self.iters.$first_index.next()?,
+                    $(self.iters.$index.next()?,)*
+                );
+
+                Some((self.next_position(), cells))
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                let remaining = self.size.product() - self.index;
+                (remaining, Some(remaining))
+            }
+        }
+
+        impl<'a, $first, $($name,)* const D: usize> ExactSizeIterator
+            for ZipViewMut<(Aligned<'a, $first>, $(Aligned<'a, $name>,)*), D>
+        {
+        }
+    };
+}
+
+impl_zip_grids_mut!(A 0, B 1);
+impl_zip_grids_mut!(A 0, B 1, C 2);
+impl_zip_grids_mut!(A 0, B 1, C 2, E 3);
+impl_zip_grids_mut!(A 0, B 1, C 2, E 3, F 4);
+impl_zip_grids_mut!(A 0, B 1, C 2, E 3, F 4, G 5);